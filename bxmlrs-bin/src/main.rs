@@ -1,43 +1,114 @@
 use anyhow::Result;
+use bxmlrs::abx::Abx;
+use bxmlrs::label_icon::LabelAndIcon;
+use bxmlrs::lint::Linter;
+use bxmlrs::manifest::Component;
+use bxmlrs::network_security_config::utc_date;
 use bxmlrs::parser;
-use clap::Parser;
+use bxmlrs::permissions::RequestedPermission;
+use bxmlrs::res_config::ResConfig;
+use bxmlrs::scan::ScanLimits;
+use bxmlrs::signature::{ApkSignatures, Certificate};
+use clap::{ArgGroup, Parser};
 use path_clean::PathClean;
-use quick_xml::name::QName;
 use std::path::{Path, PathBuf};
-
-#[derive(Debug)]
-pub struct IntentFilter {
-  pub action: String,
-  pub categories: Vec<String>,
-}
-
-#[derive(Debug)]
-pub struct Component {
-  pub name: String,
-  pub intent_filters: Vec<IntentFilter>,
-}
+use std::time::SystemTime;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
+// The modes are exclusive, without one the manifest summary is printed
+#[clap(group(ArgGroup::new("mode").multiple(false)))]
 struct Args {
   #[clap(short, long = "file", value_parser)]
   file: Option<PathBuf>,
 
   #[clap(short, long = "dir", value_parser)]
   dir: Option<String>,
+
+  /// Print the public.xml of the resource table instead of the manifest summary
+  #[clap(long = "public-xml", group = "mode")]
+  public_xml: bool,
+
+  /// Compare the manifest with the one of this newer APK
+  #[clap(long = "diff", value_parser, group = "mode")]
+  diff: Option<PathBuf>,
+
+  /// Print the manifest diff as JSON
+  #[clap(long = "json", requires = "diff")]
+  json: bool,
+
+  /// Print the DEX files, native libraries, assets and resource files of the APK
+  #[clap(long = "inventory", group = "mode")]
+  inventory: bool,
+
+  /// Print the deep links and App Links the activities claim
+  #[clap(long = "deep-links", group = "mode")]
+  deep_links: bool,
+
+  /// Check the manifest for security issues
+  #[clap(long = "lint", group = "mode")]
+  lint: bool,
+
+  /// Print the network security config and the issues found in it
+  #[clap(long = "network-security-config", group = "mode")]
+  network_security_config: bool,
+
+  /// Print the signers of every signature scheme and whether the digests match the APK
+  #[clap(long = "signatures", group = "mode")]
+  signatures: bool,
+
+  /// Print the APKs, archives, DEX files and encrypted looking blobs embedded in the APK
+  #[clap(long = "scan", group = "mode")]
+  scan: bool,
+
+  /// Print a binary XML entry decoded, by path or by resource id such as 0x7f0b001c
+  #[clap(long = "xml", value_parser, group = "mode")]
+  xml: Option<String>,
+
+  /// Decode every binary XML entry (layouts, drawables, res/xml, ...) into this directory,
+  /// into a subdirectory per APK with --dir
+  #[clap(long = "xml-dir", value_parser, group = "mode")]
+  xml_dir: Option<PathBuf>,
+
+  /// Decode the values resources (strings, colors, styles, ...) into this directory, into a
+  /// subdirectory per APK with --dir
+  #[clap(long = "values-dir", value_parser, group = "mode")]
+  values_dir: Option<PathBuf>,
+}
+
+impl Args {
+  /// The flag of the selected mode, `None` for the manifest summary.
+  fn mode(&self) -> Option<&'static str> {
+    [
+      (self.public_xml, "--public-xml"),
+      (self.diff.is_some(), "--diff"),
+      (self.inventory, "--inventory"),
+      (self.deep_links, "--deep-links"),
+      (self.lint, "--lint"),
+      (self.network_security_config, "--network-security-config"),
+      (self.signatures, "--signatures"),
+      (self.scan, "--scan"),
+      (self.xml.is_some(), "--xml"),
+      (self.xml_dir.is_some(), "--xml-dir"),
+      (self.values_dir.is_some(), "--values-dir"),
+    ]
+    .into_iter()
+    .find(|(selected, _)| *selected)
+    .map(|(_, flag)| flag)
+  }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
   let args = Args::parse();
-  if let Some(dir_path) = args.dir {
+  if let Some(dir_path) = &args.dir {
     let dir = std::fs::read_dir(dir_path)?;
     for entry in dir {
       let entry = entry?;
       let file_path = entry.path().clean();
-      print_manifest(&file_path)?;
+      process_file(&args, &file_path)?;
     }
-  } else if let Some(file_path) = args.file {
-    print_manifest(&file_path)?;
+  } else if let Some(file_path) = &args.file {
+    process_file(&args, file_path)?;
   } else {
     println!("No file or directory specified.");
   }
@@ -45,177 +116,337 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
   Ok(())
 }
 
-fn print_manifest(file_path: &Path) -> Result<()> {
-  let mut parser = parser::Parser::from_file(file_path)?;
-  let manifest_bytes = parser.parse()?;
-
-  let mut xml_reader = quick_xml::reader::Reader::from_str(std::str::from_utf8(&manifest_bytes)?);
-  xml_reader.trim_text(true);
-
-  let mut activities: Vec<Component> = Vec::new();
-  let mut receivers: Vec<Component> = Vec::new();
-  let mut services: Vec<Component> = Vec::new();
-  let mut providers: Vec<String> = Vec::new();
-  let mut stack: Vec<Component> = Vec::new();
-
-  let mut permissions: Vec<String> = Vec::new();
-
-  let mut min_sdk = String::new();
-  let mut target_sdk = String::new();
-
-  let mut application_class = String::new();
-  let mut application_name = String::new();
-  let mut package_name = String::new();
-  let mut icon = String::new();
-
-  loop {
-    match xml_reader.read_event() {
-      Ok(quick_xml::events::Event::Eof) => break,
-      Ok(event) => match event {
-        quick_xml::events::Event::Start(e) => match e.name() {
-          QName(b"manifest") => {
-            if let Some(attr) = e.try_get_attribute("package")? {
-              package_name = attr.unescape_value().unwrap_or_default().into_owned();
-            }
-          }
-          QName(b"application") => {
-            if let Some(attr) = e.try_get_attribute("label")? {
-              application_name = attr.unescape_value().unwrap_or_default().into_owned();
-            }
-            if let Some(attr) = e.try_get_attribute("name")? {
-              application_class = attr.unescape_value().unwrap_or_default().into_owned();
-            }
-            if let Some(attr) = e.try_get_attribute("icon")? {
-              icon = attr.unescape_value().unwrap_or_default().into_owned();
-            }
-          }
-          QName(b"activity") | QName(b"receiver") | QName(b"service") => {
-            let activity = Component {
-              name: e
-                .try_get_attribute("name")?
-                .map(|attr| attr.unescape_value().unwrap_or_default().into_owned())
-                .unwrap_or_default(),
-              intent_filters: Vec::new(),
-            };
-            stack.push(activity);
-          }
-          QName(b"provider") => {
-            if let Some(attr) = e.try_get_attribute("name")? {
-              providers.push(attr.unescape_value().unwrap_or_default().into_owned())
-            }
-          }
-          QName(b"intent-filter") => {
-            let intent_filter = IntentFilter {
-              action: String::new(),
-              categories: Vec::new(),
-            };
-            if let Some(activity) = stack.last_mut() {
-              activity.intent_filters.push(intent_filter);
-            }
-          }
-          QName(b"uses-sdk") => {
-            if let Some(attr) = e.try_get_attribute("minSdkVersion")? {
-              min_sdk = attr.unescape_value().unwrap_or_default().into_owned();
-            }
-            if let Some(attr) = e.try_get_attribute("targetSdkVersion")? {
-              target_sdk = attr.unescape_value().unwrap_or_default().into_owned();
-            }
-          }
-          QName(b"uses-permission")
-          | QName(b"permission")
-          | QName(b"permission-tree")
-          | QName(b"permission-group") => {
-            if let Some(attr) = e.try_get_attribute("name")? {
-              permissions.push(attr.unescape_value().unwrap_or_default().into_owned());
-            }
-          }
-          QName(b"action") => {
-            if let Some(intent_filter) = stack
-              .last_mut()
-              .and_then(|component| component.intent_filters.last_mut())
-            {
-              if let Some(attr) = e.try_get_attribute("name")? {
-                intent_filter.action = attr.unescape_value().unwrap_or_default().into_owned();
-              }
-            }
-          }
-          QName(b"category") => {
-            if let Some(intent_filter) = stack
-              .last_mut()
-              .and_then(|component| component.intent_filters.last_mut())
-            {
-              if let Some(attr) = e.try_get_attribute("name")? {
-                intent_filter
-                  .categories
-                  .push(attr.unescape_value().unwrap_or_default().into_owned());
-              }
-            }
-          }
-          _ => {}
-        },
-        quick_xml::events::Event::End(e) => match e.name() {
-          QName(b"activity") => {
-            if let Some(activity) = stack.pop() {
-              activities.push(activity);
-            }
-          }
-          QName(b"receiver") => {
-            if let Some(activity) = stack.pop() {
-              receivers.push(activity);
-            }
-          }
-          QName(b"service") => {
-            if let Some(activity) = stack.pop() {
-              services.push(activity);
-            }
-          }
-          _ => {}
-        },
-        _ => {}
-      },
-      Err(e) => {
-        println!(
-          "Error at position {}: {:?}",
-          xml_reader.buffer_position(),
-          e
-        );
-      }
+fn process_file(
+  args: &Args,
+  file_path: &Path,
+) -> Result<()> {
+  // System files such as packages.xml are ABX documents rather than APKs
+  let mut magic = [0u8; 4];
+  let is_abx = std::fs::File::open(file_path)
+    .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut magic))
+    .is_ok()
+    && Abx::is_abx(&magic);
+  if is_abx {
+    // ABX documents aren't APKs, none of the modes apply to them
+    if let Some(flag) = args.mode() {
+      anyhow::bail!(
+        "{}: {} is not supported for ABX files, they can only be printed as XML",
+        file_path.display(),
+        flag
+      );
+    }
+    print_abx(file_path)
+  } else if args.public_xml {
+    print_public_xml(file_path)
+  } else if let Some(new_file_path) = &args.diff {
+    let old_manifest = parser::Parser::from_file(file_path)?.manifest()?;
+    let new_manifest = parser::Parser::from_file(new_file_path)?.manifest()?;
+    let diff = old_manifest.diff(&new_manifest);
+    match args.json {
+      true => println!("{}", diff.to_json()),
+      false => print!("{}", diff),
+    }
+    Ok(())
+  } else if args.inventory {
+    let parser = parser::Parser::from_file(file_path)?;
+    print!("{}", parser.inventory()?);
+    Ok(())
+  } else if args.deep_links {
+    let parser = parser::Parser::from_file(file_path)?;
+    print!("{}", parser.deep_links()?);
+    Ok(())
+  } else if args.lint {
+    let parser = parser::Parser::from_file(file_path)?;
+    for finding in Linter::default().run(&parser.manifest()?) {
+      println!("{}", finding);
+    }
+    Ok(())
+  } else if args.network_security_config {
+    print_network_security_config(file_path)
+  } else if args.signatures {
+    let parser = parser::Parser::from_file(file_path)?;
+    print_signatures(&parser.signatures()?);
+    Ok(())
+  } else if args.scan {
+    let parser = parser::Parser::from_file(file_path)?;
+    print!("{}", parser.scan(ScanLimits::default())?);
+    Ok(())
+  } else if let Some(xml) = &args.xml {
+    print_xml(file_path, xml)
+  } else if let Some(xml_dir) = &args.xml_dir {
+    let parser = parser::Parser::from_file(file_path)?;
+    for (path, e) in parser.write_xml(&output_dir(args, xml_dir, file_path))? {
+      eprintln!("warning: {}: {}: {}", file_path.display(), path, e);
     }
+    Ok(())
+  } else if let Some(values_dir) = &args.values_dir {
+    let parser = parser::Parser::from_file(file_path)?;
+    parser.write_values(&output_dir(args, values_dir, file_path))?;
+    Ok(())
+  } else {
+    print_manifest(file_path)
   }
+}
 
-  println!("activities");
-  for activity in activities {
-    println!("{:?}", activity);
+// With --dir every APK is written to its own subdirectory, named after the file, so the
+// files of one APK don't overwrite those of another
+fn output_dir(
+  args: &Args,
+  out_dir: &Path,
+  file_path: &Path,
+) -> PathBuf {
+  match (&args.dir, file_path.file_name()) {
+    (Some(_), Some(file_name)) => out_dir.join(file_name),
+    _ => out_dir.to_path_buf(),
   }
-  println!("receivers");
-  for activity in receivers {
-    println!("{:?}", activity);
+}
+
+fn print_abx(file_path: &Path) -> Result<()> {
+  let abx_bytes = std::fs::read(file_path)?;
+  let xml = Abx::new(&abx_bytes).parse()?;
+  println!("{}", std::str::from_utf8(&xml)?);
+  Ok(())
+}
+
+fn print_xml(
+  file_path: &Path,
+  selector: &str,
+) -> Result<()> {
+  let parser = parser::Parser::from_file(file_path)?;
+  let res_id = selector
+    .strip_prefix("0x")
+    .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+  let Some(res_id) = res_id else {
+    println!("{}", std::str::from_utf8(&parser.decode_xml(selector)?)?);
+    return Ok(());
+  };
+  for (path, xml) in parser.decode_xml_resource(res_id)? {
+    println!("<!-- {} -->", path);
+    println!("{}", std::str::from_utf8(&xml)?);
   }
-  println!("services");
-  for activity in services {
-    println!("{:?}", activity);
+  Ok(())
+}
+
+fn print_network_security_config(file_path: &Path) -> Result<()> {
+  let parser = parser::Parser::from_file(file_path)?;
+  let Some(config) = parser.network_security_config()? else {
+    println!("no network security config");
+    return Ok(());
+  };
+  print!("{}", config);
+  let target_sdk = parser.manifest()?.target_sdk();
+  if config.base_cleartext_permitted(target_sdk) {
+    println!("[warning] cleartext traffic is permitted by default");
+  }
+  for domain in config.cleartext_domains(target_sdk) {
+    println!("[warning] cleartext traffic is permitted for {}", domain);
+  }
+  if config.trusts_user_certificates(target_sdk) {
+    println!("[warning] user installed CAs are trusted");
+  }
+  for domain_config in config.expired_pin_sets(&utc_date(SystemTime::now())) {
+    let domains = domain_config
+      .domains
+      .iter()
+      .map(|domain| domain.to_string())
+      .collect::<Vec<_>>();
+    println!(
+      "[warning] the pins of {} have expired and are not enforced",
+      domains.join(", ")
+    );
+  }
+  Ok(())
+}
+
+fn print_public_xml(file_path: &Path) -> Result<()> {
+  let parser = parser::Parser::from_file(file_path)?;
+  let public_xml = parser.public_xml()?;
+  println!("{}", std::str::from_utf8(&public_xml)?);
+  Ok(())
+}
+
+fn print_manifest(file_path: &Path) -> Result<()> {
+  let parser = parser::Parser::from_file(file_path)?;
+  for diagnostic in parser.diagnostics() {
+    eprintln!("warning: {}: {}", file_path.display(), diagnostic);
+  }
+  let manifest = parser.manifest()?;
+  let application = manifest.application.clone().unwrap_or_default();
+
+  for (title, components) in [
+    ("activities", &application.activities),
+    ("receivers", &application.receivers),
+    ("services", &application.services),
+  ] {
+    println!("{}", title);
+    for component in components {
+      print_component(component);
+    }
   }
   println!("providers");
-  for provider in providers {
-    println!("{:?}", provider);
+  for provider in &application.providers {
+    println!("{:?}", provider.name);
   }
 
-  println!("min_sdk: {:?}", min_sdk);
-  println!("target_sdk: {:?}", target_sdk);
+  let attribute = |value: Option<&str>| value.unwrap_or_default().to_string();
+  println!(
+    "min_sdk: {:?}",
+    attribute(manifest.uses_sdk.min_sdk_version.as_deref())
+  );
+  println!(
+    "target_sdk: {:?}",
+    attribute(manifest.uses_sdk.target_sdk_version.as_deref())
+  );
+
+  println!("package_name: {:?}", attribute(manifest.package.as_deref()));
+  println!(
+    "application_name: {:?}",
+    attribute(application.attributes.get("label"))
+  );
+  println!(
+    "application_class: {:?}",
+    attribute(application.attributes.get("name"))
+  );
+  println!("icon: {:?}", attribute(application.attributes.get("icon")));
 
-  println!("package_name: {:?}", package_name);
-  println!("application_name: {:?}", application_name);
-  println!("application_class: {:?}", application_class);
-  println!("icon: {:?}", icon);
+  match parser.label_and_icon() {
+    Ok(label_and_icon) => print_label_and_icon(&label_and_icon),
+    Err(e) => eprintln!("warning: {}: {}", file_path.display(), e),
+  }
 
   println!("permissions");
-  for permission in permissions {
-    println!("{:?}", permission);
+  for permission in RequestedPermission::from_manifest(&manifest) {
+    match (&permission.info, permission.grant) {
+      (Some(info), Some(grant)) => println!(
+        "{:?} {} ({})",
+        permission.name,
+        info.protection_level,
+        grant.name()
+      ),
+      _ => println!("{:?}", permission.name),
+    }
+  }
+
+  println!("declared permissions");
+  for element in &manifest.root.children {
+    if !matches!(
+      element.name.as_str(),
+      "permission" | "permission-tree" | "permission-group"
+    ) {
+      continue;
+    }
+    if let Some(name) = element.attributes.get("name") {
+      println!("{:?} ({})", name, element.name);
+    }
   }
 
   Ok(())
 }
 
+fn print_label_and_icon(label_and_icon: &LabelAndIcon) {
+  println!("labels");
+  for label in &label_and_icon.labels {
+    println!(
+      "  {}: {:?}",
+      qualifier_or_default(&label.config),
+      label.label
+    );
+  }
+  println!("icons");
+  for icon in &label_and_icon.icons {
+    println!("  {}: {}", qualifier_or_default(&icon.config), icon.path);
+    let Some(adaptive) = &icon.adaptive else {
+      continue;
+    };
+    for (name, layer) in [
+      ("background", &adaptive.background),
+      ("foreground", &adaptive.foreground),
+      ("monochrome", &adaptive.monochrome),
+    ] {
+      let Some(layer) = layer else {
+        continue;
+      };
+      let values = layer
+        .values
+        .iter()
+        .map(|(config, value)| format!("{}: {}", qualifier_or_default(config), value))
+        .collect::<Vec<_>>();
+      println!("    {}: {}", name, values.join(", "));
+    }
+  }
+}
+
+fn qualifier_or_default(config: &ResConfig) -> String {
+  match config.is_default() {
+    true => "default".to_string(),
+    false => config.qualifier(),
+  }
+}
+
+fn print_component(component: &Component) {
+  println!("{:?}", component.name);
+  let exported = &component.effective_exported;
+  println!(
+    "  exported: {} ({})",
+    exported.exported,
+    exported.reasoning.join(", ")
+  );
+  for intent_filter in &component.intent_filters {
+    let uris = intent_filter
+      .uri_patterns()
+      .iter()
+      .map(|uri| uri.to_string())
+      .collect::<Vec<_>>();
+    println!(
+      "  actions: {:?}, categories: {:?}, data: {:?}",
+      intent_filter.actions, intent_filter.categories, uris
+    );
+  }
+}
+
+fn print_signatures(signatures: &ApkSignatures) {
+  println!("signatures (digests match: {})", signatures.digests_match());
+  if let Some(v1) = &signatures.v1 {
+    for signer in &v1.signers {
+      for certificate in &signer.certificates {
+        print_certificate("v1", certificate);
+      }
+    }
+  }
+  for (scheme, signers) in [
+    ("v2", &signatures.v2),
+    ("v3", &signatures.v3),
+    ("v3.1", &signatures.v31),
+  ] {
+    for signer in signers {
+      if let Some(certificate) = signer.certificate() {
+        print_certificate(scheme, certificate);
+      }
+      if let (Some(min_sdk), Some(max_sdk)) = (signer.min_sdk, signer.max_sdk) {
+        println!("  sdk: {}..={}", min_sdk, max_sdk);
+      }
+      for node in &signer.lineage {
+        println!("  lineage: {:?}", node.certificate.subject);
+      }
+    }
+  }
+  for version in signatures.stripped_schemes() {
+    println!("stripped: v{}", version);
+  }
+}
+
+fn print_certificate(
+  scheme: &str,
+  certificate: &Certificate,
+) {
+  println!(
+    "{}: {:?} sha256={}",
+    scheme,
+    certificate.subject,
+    certificate.sha256_fingerprint()
+  );
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -234,4 +465,24 @@ mod tests {
 
     Ok(())
   }
+
+  #[test]
+  fn test_abx_modes() -> Result<()> {
+    let abx =
+      bxmlrs::abx::AbxWriter::from_xml(b"<packages><package name=\"com.example\"/></packages>")?;
+    let file_path = std::env::temp_dir().join(format!("bxmlrs-{}.abx", std::process::id()));
+    std::fs::write(&file_path, abx)?;
+    let file = file_path.to_str().unwrap();
+    let plain = process_file(&Args::parse_from(["bxmlrs", "-f", file]), &file_path);
+    let lint = process_file(
+      &Args::parse_from(["bxmlrs", "-f", file, "--lint"]),
+      &file_path,
+    );
+    std::fs::remove_file(&file_path)?;
+    plain?;
+    assert!(lint.is_err_and(|e| e
+      .to_string()
+      .contains("--lint is not supported for ABX files")));
+    Ok(())
+  }
 }
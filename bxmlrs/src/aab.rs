@@ -0,0 +1,854 @@
+use crate::arsc_parser::{
+  Arsc, EntryValue, Package, StringSpan, StyledString, TypeChunk, TypeEntry, Value,
+};
+use crate::attributes;
+use crate::nom_parser::{ParseError, ResType, TableEntryFlag, TypeSpecChunkHeader, TypeSpecFlag};
+use crate::parser::{ApkArchive, Diagnostic};
+use crate::proto::{self, Field};
+use crate::res_config::ResConfig;
+use crate::values_decoder::{ATTR_MAX, ATTR_MIN, ATTR_TYPE, PLURALS};
+use crate::xml_parser::resolve_references;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, Event};
+use quick_xml::Writer;
+use std::io::Cursor;
+use std::path::Path;
+
+/// An XML file compiled by aapt2 into its protobuf `XmlNode` format, as stored in app bundles,
+/// e.g. `base/manifest/AndroidManifest.xml`.
+#[derive(Clone, Debug)]
+pub struct ProtoXml<'pxml> {
+  proto_xml: &'pxml [u8],
+}
+
+impl<'pxml> ProtoXml<'pxml> {
+  pub fn new(proto_xml: &'pxml [u8]) -> Self {
+    Self { proto_xml }
+  }
+
+  /// Decodes the document into the same XML `AndroidManifest::parse` produces for binary XML,
+  /// resolving references through `arsc` when given.
+  pub fn parse(
+    &self,
+    arsc: Option<&Arsc>,
+  ) -> Result<Vec<u8>, ParseError> {
+    let mut xml_writer = Writer::new(Cursor::new(Vec::new()));
+    let decl = BytesDecl::from_start(BytesStart::from_content(
+      "xml encoding='utf-8' version='1.1'",
+      0,
+    ));
+    xml_writer
+      .write_event(Event::Decl(decl))
+      .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+
+    let node = proto::fields(self.proto_xml)?;
+    let element = proto::field(&node, 1)
+      .ok_or_else(|| ParseError::Protobuf("XmlNode without root element".to_string()))?;
+    Self::write_element(&mut xml_writer, element.as_bytes(), arsc, 0)?;
+    Ok(xml_writer.into_inner().into_inner())
+  }
+
+  fn write_element(
+    xml_writer: &mut Writer<Cursor<Vec<u8>>>,
+    element: &[u8],
+    arsc: Option<&Arsc>,
+    depth: usize,
+  ) -> Result<(), ParseError> {
+    // Nesting is bounded by the message size, but a crafted file can still nest deep enough
+    // to exhaust the stack
+    if depth > 256 {
+      return Err(ParseError::Protobuf(
+        "XML elements nested too deep".to_string(),
+      ));
+    }
+    let element = proto::fields(element)?;
+    let elem_name = proto::field(&element, 3).map_or(String::new(), |name| name.as_string());
+    let mut xml_elem = BytesStart::new(elem_name.clone());
+    for attribute in proto::repeated(&element, 4) {
+      let attribute = proto::fields(attribute.as_bytes())?;
+      let attr_name = proto::field(&attribute, 5)
+        .map(|resource_id| resource_id.as_u32())
+        .and_then(attributes::get_attribute_name)
+        .or_else(|| proto::field(&attribute, 2).map(|name| name.as_string()));
+
+      // Only values aapt2 could compile have an item, everything else is kept as written.
+      // References of files that were compiled but not linked yet only have a name.
+      let compiled_value = match proto::field(&attribute, 6) {
+        Some(item) => match item_value(&proto::fields(item.as_bytes())?)? {
+          Some(Value::Data {
+            data_type:
+              ResType::REFERENCE
+              | ResType::ATTRIBUTE
+              | ResType::DYNAMIC_REFERENCE
+              | ResType::DYNAMIC_ATTRIBUTE,
+            data: 0,
+          }) => None,
+          value => value.and_then(|value| value.as_string()),
+        },
+        None => None,
+      };
+      let attr_value =
+        compiled_value.or_else(|| proto::field(&attribute, 3).map(|v| v.as_string()));
+      let attr_value = resolve_references(attr_value, arsc);
+
+      if let (Some(attr_name), Some(attr_value)) = (attr_name.as_deref(), attr_value.as_deref()) {
+        xml_elem.push_attribute((attr_name, attr_value));
+      }
+    }
+    xml_writer
+      .write_event(Event::Start(xml_elem))
+      .map_err(|e| ParseError::StartElement(e.to_string()))?;
+
+    // Text nodes are skipped like CDATA chunks of binary XML
+    for child in proto::repeated(&element, 5) {
+      let child = proto::fields(child.as_bytes())?;
+      if let Some(child_element) = proto::field(&child, 1) {
+        Self::write_element(xml_writer, child_element.as_bytes(), arsc, depth + 1)?;
+      }
+    }
+    xml_writer
+      .write_event(Event::End(BytesEnd::new(elem_name)))
+      .map_err(|e| ParseError::StartElement(e.to_string()))?;
+    Ok(())
+  }
+}
+
+impl Arsc<'static> {
+  /// Decodes aapt2's protobuf `ResourceTable`, e.g. `base/resources.pb` of an app bundle,
+  /// into the same model `parse` builds from `resources.arsc`.
+  pub fn from_proto(resources_pb: &[u8]) -> Result<Self, ParseError> {
+    let table = proto::fields(resources_pb)?;
+    let mut packages = Vec::new();
+    for package in proto::repeated(&table, 2) {
+      packages.push(proto_package(&proto::fields(package.as_bytes())?)?);
+    }
+    Ok(Arsc::from_packages(packages))
+  }
+}
+
+// Ids are wrapped in messages of their own, e.g. `PackageId { uint32 id = 1; }`.
+fn wrapped_id(
+  fields: &[(u32, Field)],
+  number: u32,
+) -> Result<Option<u32>, ParseError> {
+  match proto::field(fields, number) {
+    Some(id) => Ok(Some(
+      proto::field(&proto::fields(id.as_bytes())?, 1).map_or(0, |id| id.as_u32()),
+    )),
+    None => Ok(None),
+  }
+}
+
+const MAX_ENTRY_ID: u32 = 0xffff;
+
+fn proto_package(package: &[(u32, Field)]) -> Result<Package, ParseError> {
+  let mut result = Package {
+    id: wrapped_id(package, 1)?.unwrap_or(0),
+    name: proto::field(package, 2).map_or(String::new(), |name| name.as_string()),
+    type_strings: Vec::new(),
+    key_strings: Vec::new(),
+    type_spec: Vec::new(),
+    types: Vec::new(),
+    type_chunks: Vec::new(),
+    libraries: Vec::new(),
+    staged_aliases: Vec::new(),
+  };
+
+  for (type_index, typ) in proto::repeated(package, 3).enumerate() {
+    let typ = proto::fields(typ.as_bytes())?;
+    // Type ids are only missing from tables that were never linked, number them in order
+    let type_id = wrapped_id(&typ, 1)?.unwrap_or(type_index as u32 + 1);
+    let type_id = u8::try_from(type_id)
+      .map_err(|_| ParseError::Protobuf(format!("type id 0x{:x} out of range", type_id)))?;
+    if type_id == 0 {
+      continue;
+    }
+    let type_name = proto::field(&typ, 2).map_or(String::new(), |name| name.as_string());
+    if result.type_strings.len() < type_id as usize {
+      result.type_strings.resize(type_id as usize, String::new());
+    }
+    result.type_strings[type_id as usize - 1] = type_name;
+
+    let mut entry_flags: Vec<u32> = Vec::new();
+    let mut type_chunks: Vec<TypeChunk> = Vec::new();
+    for (entry_index, entry) in proto::repeated(&typ, 3).enumerate() {
+      let entry = proto::fields(entry.as_bytes())?;
+      let entry_id = wrapped_id(&entry, 1)?.unwrap_or(entry_index as u32);
+      // Entry ids are 16 bits in resource ids, larger ones would only make the vectors below huge
+      if entry_id > MAX_ENTRY_ID {
+        return Err(ParseError::Protobuf(format!(
+          "entry id 0x{:x} out of range",
+          entry_id
+        )));
+      }
+      let entry_id = entry_id as usize;
+      let key = proto::field(&entry, 2).map_or(String::new(), |name| name.as_string());
+      if !result.key_strings.contains(&key) {
+        result.key_strings.push(key.clone());
+      }
+      if entry_flags.len() <= entry_id {
+        entry_flags.resize(entry_id + 1, 0);
+      }
+
+      let mut flags = 0u16;
+      if let Some(visibility) = proto::field(&entry, 3) {
+        let visibility = proto::fields(visibility.as_bytes())?;
+        // Visibility.Level: UNKNOWN 0, PRIVATE 1, PUBLIC 2
+        if proto::field(&visibility, 1).is_some_and(|level| level.as_u32() == 2) {
+          entry_flags[entry_id] |= TypeSpecFlag::SPEC_PUBLIC;
+          flags |= TableEntryFlag::PUBLIC;
+        }
+        if proto::field(&visibility, 4).is_some_and(|staged_api| staged_api.as_bool()) {
+          entry_flags[entry_id] |= TypeSpecFlag::SPEC_PUBLIC | TypeSpecFlag::SPEC_STAGED_API;
+        }
+      }
+
+      let mut configs = Vec::new();
+      for config_value in proto::repeated(&entry, 6) {
+        let config_value = proto::fields(config_value.as_bytes())?;
+        let config = match proto::field(&config_value, 1) {
+          Some(config) => configuration(&proto::fields(config.as_bytes())?),
+          None => ResConfig::default(),
+        };
+        let Some(value) = proto::field(&config_value, 2) else {
+          continue;
+        };
+        let value = proto::fields(value.as_bytes())?;
+        let Some((entry_value, value_flags)) = entry_value(&value)? else {
+          continue;
+        };
+
+        let type_chunk = match type_chunks
+          .iter()
+          .position(|type_chunk| type_chunk.res_config() == config)
+        {
+          Some(chunk_index) => &mut type_chunks[chunk_index],
+          None => {
+            type_chunks.push(TypeChunk {
+              type_id,
+              config: config.to_config(),
+              entries: Vec::new(),
+            });
+            type_chunks.last_mut().unwrap()
+          }
+        };
+        if type_chunk.entries.len() <= entry_id {
+          type_chunk.entries.resize(entry_id + 1, None);
+        }
+        type_chunk.entries[entry_id] = Some(TypeEntry {
+          key: key.clone(),
+          flags: flags | value_flags,
+          value: entry_value,
+        });
+        configs.push(config);
+      }
+
+      // Like aapt2, an entry varies by every dimension in which any two of its configs differ
+      for (index, config) in configs.iter().enumerate() {
+        for other in &configs[index + 1..] {
+          entry_flags[entry_id] |= config.config_changes(other);
+        }
+      }
+    }
+
+    let entry_count = entry_flags.len() as u32;
+    result
+      .type_spec
+      .push((TypeSpecChunkHeader::new(type_id, entry_count), entry_flags));
+    result.type_chunks.extend(type_chunks);
+  }
+  Ok(result)
+}
+
+// The entry value and entry flags of a `Value` message, `None` for compound values without
+// a binary representation such as styleables and macros.
+fn entry_value(value: &[(u32, Field)]) -> Result<Option<(EntryValue, u16)>, ParseError> {
+  let mut flags = 0u16;
+  if proto::field(value, 3).is_some_and(|weak| weak.as_bool()) {
+    flags |= TableEntryFlag::WEAK;
+  }
+  if let Some(item) = proto::field(value, 4) {
+    let entry_value = item_value(&proto::fields(item.as_bytes())?)?.map(EntryValue::Simple);
+    return Ok(entry_value.map(|entry_value| (entry_value, flags)));
+  }
+  let Some(compound) = proto::field(value, 5) else {
+    return Ok(None);
+  };
+  let compound = proto::fields(compound.as_bytes())?;
+  let mut parent = 0;
+  let mut items = Vec::new();
+  if let Some(attr) = proto::field(&compound, 1) {
+    let attr = proto::fields(attr.as_bytes())?;
+    let format_flags = proto::field(&attr, 1).map_or(0, |flags| flags.as_u32());
+    items.push((ATTR_TYPE, int_value(ResType::INT_DEC, format_flags)));
+    // aapt2 stores the limits as INT_MIN and INT_MAX when they are not set
+    if let Some(min) = proto::field(&attr, 2).filter(|min| min.as_u32() as i32 != i32::MIN) {
+      items.push((ATTR_MIN, int_value(ResType::INT_DEC, min.as_u32())));
+    }
+    if let Some(max) = proto::field(&attr, 3).filter(|max| max.as_u32() as i32 != i32::MAX) {
+      items.push((ATTR_MAX, int_value(ResType::INT_DEC, max.as_u32())));
+    }
+    for symbol in proto::repeated(&attr, 4) {
+      let symbol = proto::fields(symbol.as_bytes())?;
+      let name = match proto::field(&symbol, 3) {
+        Some(name) => reference_id(&proto::fields(name.as_bytes())?),
+        None => 0,
+      };
+      let data_type = proto::field(&symbol, 5).map_or(ResType::INT_DEC, |typ| typ.as_u32() as u8);
+      let data = proto::field(&symbol, 4).map_or(0, |data| data.as_u32());
+      items.push((name, int_value(data_type, data)));
+    }
+  } else if let Some(style) = proto::field(&compound, 2) {
+    let style = proto::fields(style.as_bytes())?;
+    if let Some(style_parent) = proto::field(&style, 1) {
+      parent = reference_id(&proto::fields(style_parent.as_bytes())?);
+    }
+    for style_entry in proto::repeated(&style, 3) {
+      let style_entry = proto::fields(style_entry.as_bytes())?;
+      let key = match proto::field(&style_entry, 3) {
+        Some(key) => reference_id(&proto::fields(key.as_bytes())?),
+        None => 0,
+      };
+      if let Some(item) = proto::field(&style_entry, 4) {
+        if let Some(item) = item_value(&proto::fields(item.as_bytes())?)? {
+          items.push((key, item));
+        }
+      }
+    }
+  } else if let Some(array) = proto::field(&compound, 4) {
+    let array = proto::fields(array.as_bytes())?;
+    for (index, element) in proto::repeated(&array, 1).enumerate() {
+      let element = proto::fields(element.as_bytes())?;
+      if let Some(item) = proto::field(&element, 3) {
+        if let Some(item) = item_value(&proto::fields(item.as_bytes())?)? {
+          items.push((0x0200_0000 | index as u32, item));
+        }
+      }
+    }
+  } else if let Some(plural) = proto::field(&compound, 5) {
+    let plural = proto::fields(plural.as_bytes())?;
+    for plural_entry in proto::repeated(&plural, 1) {
+      let plural_entry = proto::fields(plural_entry.as_bytes())?;
+      // Plural.Arity: ZERO 0, ONE 1, TWO 2, FEW 3, MANY 4, OTHER 5
+      let quantity = match proto::field(&plural_entry, 3).map_or(0, |arity| arity.as_u32()) {
+        0 => "zero",
+        1 => "one",
+        2 => "two",
+        3 => "few",
+        4 => "many",
+        _ => "other",
+      };
+      let Some((key, _)) = PLURALS.iter().find(|(_, name)| *name == quantity) else {
+        continue;
+      };
+      if let Some(item) = proto::field(&plural_entry, 4) {
+        if let Some(item) = item_value(&proto::fields(item.as_bytes())?)? {
+          items.push((*key, item));
+        }
+      }
+    }
+  } else {
+    return Ok(None);
+  }
+  Ok(Some((
+    EntryValue::Complex { parent, items },
+    flags | TableEntryFlag::COMPLEX,
+  )))
+}
+
+fn int_value(
+  data_type: u8,
+  data: u32,
+) -> Value {
+  Value::Data { data_type, data }
+}
+
+fn reference_id(reference: &[(u32, Field)]) -> u32 {
+  proto::field(reference, 2).map_or(0, |id| id.as_u32())
+}
+
+// The value of an `Item` message, `None` for kinds unknown to this decoder.
+fn item_value(item: &[(u32, Field)]) -> Result<Option<Value>, ParseError> {
+  let Some((number, field)) = item.last() else {
+    return Ok(None);
+  };
+  let message = proto::fields(field.as_bytes())?;
+  let value = match number {
+    // Reference
+    1 => {
+      let is_attribute = proto::field(&message, 1).is_some_and(|typ| typ.as_u32() == 1);
+      let is_dynamic = match proto::field(&message, 5) {
+        Some(is_dynamic) => {
+          proto::field(&proto::fields(is_dynamic.as_bytes())?, 1).is_some_and(|v| v.as_bool())
+        }
+        None => false,
+      };
+      let data_type = match (is_attribute, is_dynamic) {
+        (false, false) => ResType::REFERENCE,
+        (true, false) => ResType::ATTRIBUTE,
+        (false, true) => ResType::DYNAMIC_REFERENCE,
+        (true, true) => ResType::DYNAMIC_ATTRIBUTE,
+      };
+      int_value(data_type, reference_id(&message))
+    }
+    // String, RawString and FileReference all keep their text in field 1
+    2 | 3 | 5 => Value::String(proto::field(&message, 1).map_or(String::new(), |v| v.as_string())),
+    4 => {
+      let mut spans = Vec::new();
+      for span in proto::repeated(&message, 2) {
+        let span = proto::fields(span.as_bytes())?;
+        spans.push(StringSpan {
+          tag: proto::field(&span, 1).map_or(String::new(), |tag| tag.as_string()),
+          first_char: proto::field(&span, 2).map_or(0, |first| first.as_u32()),
+          last_char: proto::field(&span, 3).map_or(0, |last| last.as_u32()),
+        });
+      }
+      Value::StyledString(StyledString {
+        text: proto::field(&message, 1).map_or(String::new(), |text| text.as_string()),
+        spans,
+      })
+    }
+    // Id, flattened by aapt2 as a false boolean
+    6 => int_value(ResType::INT_BOOLEAN, 0),
+    7 => match primitive_value(&message) {
+      Some(value) => value,
+      None => return Ok(None),
+    },
+    _ => return Ok(None),
+  };
+  Ok(Some(value))
+}
+
+fn primitive_value(primitive: &[(u32, Field)]) -> Option<Value> {
+  let (number, field) = primitive.last()?;
+  let data = field.as_u32();
+  let value = match number {
+    // null and @empty
+    1 => int_value(ResType::NULL, 0),
+    2 => int_value(ResType::NULL, 1),
+    3 => int_value(ResType::FLOAT, data),
+    6 => int_value(ResType::INT_DEC, data),
+    7 => int_value(ResType::INT_HEX, data),
+    8 => int_value(ResType::INT_BOOLEAN, if data != 0 { 0xFFFFFFFF } else { 0 }),
+    9 => int_value(ResType::INT_COLOR_ARGB8, data),
+    10 => int_value(ResType::INT_COLOR_RGB8, data),
+    11 => int_value(ResType::INT_COLOR_ARGB4, data),
+    12 => int_value(ResType::INT_COLOR_RGB4, data),
+    13 => int_value(ResType::DIMENSION, data),
+    14 => int_value(ResType::FRACTION, data),
+    _ => return None,
+  };
+  Some(value)
+}
+
+// Decodes a `Configuration` message. Its enums use the ResTable_config values, except for the
+// ones sharing a byte with another dimension. The grammatical gender has no field in
+// `ResConfig` and is dropped.
+pub(crate) fn configuration(config: &[(u32, Field)]) -> ResConfig {
+  let value = |number: u32| proto::field(config, number).map_or(0, |field| field.as_u32());
+  let mut res_config = ResConfig {
+    mcc: value(1) as u16,
+    mnc: value(2) as u16,
+    screen_width: value(5) as u16,
+    screen_height: value(6) as u16,
+    screen_width_dp: value(7) as u16,
+    screen_height_dp: value(8) as u16,
+    smallest_screen_width_dp: value(9) as u16,
+    orientation: value(15) as u8,
+    density: value(18) as u16,
+    touchscreen: value(19) as u8,
+    keyboard: value(21) as u8,
+    navigation: value(23) as u8,
+    sdk_version: value(24) as u16,
+    ..ResConfig::default()
+  };
+  if let Some(locale) = proto::field(config, 3) {
+    // An unrepresentable locale leaves the config locale neutral
+    let _ = res_config.set_bcp47_locale(&locale.as_string());
+  }
+  let layout_direction = [0, 0x40, 0x80];
+  let layout_long = [0, 0x20, 0x10];
+  res_config.screen_layout = (value(10) as u8 & 0x0f)
+    | layout_long.get(value(11) as usize).unwrap_or(&0)
+    | layout_direction.get(value(4) as usize).unwrap_or(&0);
+  let round = [0, 0x02, 0x01];
+  res_config.screen_layout2 = *round.get(value(12) as usize).unwrap_or(&0);
+  let wide_color_gamut = [0, 0x02, 0x01];
+  let hdr = [0, 0x08, 0x04];
+  res_config.color_mode = wide_color_gamut.get(value(13) as usize).unwrap_or(&0)
+    | hdr.get(value(14) as usize).unwrap_or(&0);
+  let night = [0, 0x20, 0x10];
+  res_config.ui_mode = (value(16) as u8 & 0x0f) | night.get(value(17) as usize).unwrap_or(&0);
+  let nav_hidden = [0, 0x04, 0x08];
+  res_config.input_flags =
+    (value(20) as u8 & 0x03) | nav_hidden.get(value(22) as usize).unwrap_or(&0);
+  res_config
+}
+
+/// A module of an app bundle, `base` or a feature module.
+pub struct AabModule {
+  pub name: String,
+  manifest_raw: Vec<u8>,
+  resources_raw: Option<Vec<u8>>,
+}
+
+impl AabModule {
+  pub fn is_base(&self) -> bool {
+    self.name == "base"
+  }
+
+  pub fn has_resource_table(&self) -> bool {
+    self.resources_raw.is_some()
+  }
+
+  /// The module's `resources.pb`, if it has one.
+  pub fn resource_table(&self) -> Result<Option<Arsc<'static>>, ParseError> {
+    self
+      .resources_raw
+      .as_deref()
+      .map(Arsc::from_proto)
+      .transpose()
+  }
+
+  /// Decodes the module manifest, resolving references through `arsc` when given.
+  pub fn parse_with(
+    &self,
+    arsc: Option<&Arsc>,
+  ) -> Result<Vec<u8>, ParseError> {
+    ProtoXml::new(&self.manifest_raw).parse(arsc)
+  }
+}
+
+/// An Android App Bundle (`.aab`), where every module keeps its manifest and resource table in
+/// aapt2's protobuf format.
+pub struct Aab {
+  modules: Vec<AabModule>,
+  diagnostics: Vec<Diagnostic>,
+}
+
+impl Aab {
+  pub fn from_file(file_path: &Path) -> Result<Self, ParseError> {
+    let aab = std::fs::read(file_path).map_err(|e| ParseError::File(e.to_string()))?;
+    Self::from_bytes(&aab)
+  }
+
+  pub fn from_bytes(aab: &[u8]) -> Result<Self, ParseError> {
+    let mut archive = ApkArchive::new(aab.to_vec())?;
+    let manifests = archive
+      .entries()
+      .iter()
+      .filter(|entry| {
+        entry
+          .name
+          .strip_suffix("/manifest/AndroidManifest.xml")
+          .is_some_and(|module| !module.is_empty() && !module.contains('/'))
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let mut modules: Vec<AabModule> = Vec::new();
+    for manifest in manifests {
+      let name = manifest
+        .name
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+      if modules.iter().any(|module| module.name == name) {
+        continue;
+      }
+      let manifest_raw = archive.read(&manifest)?;
+      let resources_raw = match archive.by_name(&format!("{}/resources.pb", name)).cloned() {
+        Some(resources) => Some(archive.read(&resources)?),
+        None => None,
+      };
+      modules.push(AabModule {
+        name,
+        manifest_raw,
+        resources_raw,
+      });
+    }
+    if modules.is_empty() {
+      return Err(ParseError::MissingEntry(
+        "base/manifest/AndroidManifest.xml".to_string(),
+      ));
+    }
+    // The base module first, feature modules by name
+    modules.sort_by(|a, b| (!a.is_base(), &a.name).cmp(&(!b.is_base(), &b.name)));
+    Ok(Self {
+      modules,
+      diagnostics: archive.diagnostics().to_vec(),
+    })
+  }
+
+  /// All modules, the base module first.
+  pub fn modules(&self) -> &[AabModule] {
+    &self.modules
+  }
+
+  pub fn base(&self) -> Option<&AabModule> {
+    self.modules.iter().find(|module| module.is_base())
+  }
+
+  pub fn module(
+    &self,
+    name: &str,
+  ) -> Option<&AabModule> {
+    self.modules.iter().find(|module| module.name == name)
+  }
+
+  /// Problems found while reading the bundle archive.
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    &self.diagnostics
+  }
+
+  /// The resource tables of all modules merged into one, starting with the base.
+  pub fn resource_table(&self) -> Result<Option<Arsc<'static>>, ParseError> {
+    let mut merged: Option<Arsc<'static>> = None;
+    for module in &self.modules {
+      let Some(table) = module.resource_table()? else {
+        continue;
+      };
+      match &mut merged {
+        Some(merged) => merged.merge(&table),
+        None => merged = Some(table),
+      }
+    }
+    Ok(merged)
+  }
+
+  /// Decodes the manifest of a module, resolving references through the merged table.
+  pub fn parse_manifest(
+    &self,
+    module: &AabModule,
+  ) -> Result<Vec<u8>, ParseError> {
+    let table = self.resource_table()?;
+    module.parse_with(table.as_ref())
+  }
+
+  /// Decodes the manifest of the base module, resolving references through the merged table.
+  pub fn parse(&self) -> Result<Vec<u8>, ParseError> {
+    let base = self
+      .base()
+      .ok_or_else(|| ParseError::MissingEntry("base/manifest/AndroidManifest.xml".to_string()))?;
+    self.parse_manifest(base)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::arsc_writer::ArscWriter;
+  use crate::proto::encode;
+  use crate::test_util::{message, zip};
+  use anyhow::{Context, Result};
+
+  fn wrapped_id(id: u32) -> Vec<u8> {
+    message(|out| encode::varint(out, 1, id as u64))
+  }
+
+  // A `ConfigValue` holding an item, in the default config or the given locale.
+  fn config_value(
+    locale: Option<&str>,
+    item: Vec<u8>,
+  ) -> Vec<u8> {
+    message(|out| {
+      if let Some(locale) = locale {
+        let config = message(|config| encode::bytes(config, 3, locale.as_bytes()));
+        encode::bytes(out, 1, &config);
+      }
+      let value = message(|value| encode::bytes(value, 4, &item));
+      encode::bytes(out, 2, &value);
+    })
+  }
+
+  fn string_item(text: &str) -> Vec<u8> {
+    message(|out| encode::bytes(out, 2, &message(|s| encode::bytes(s, 1, text.as_bytes()))))
+  }
+
+  fn resources_pb() -> Vec<u8> {
+    let app_name = message(|out| {
+      encode::bytes(out, 1, &wrapped_id(0));
+      encode::bytes(out, 2, b"app_name");
+      encode::bytes(out, 3, &message(|v| encode::varint(v, 1, 2)));
+      encode::bytes(out, 6, &config_value(None, string_item("Example")));
+      encode::bytes(
+        out,
+        6,
+        &config_value(Some("de-AT"), string_item("Beispiel")),
+      );
+    });
+    let string_type = message(|out| {
+      encode::bytes(out, 1, &wrapped_id(2));
+      encode::bytes(out, 2, b"string");
+      encode::bytes(out, 3, &app_name);
+    });
+    // @color/accent = #ff112233
+    let accent = message(|out| {
+      encode::bytes(out, 1, &wrapped_id(1));
+      encode::bytes(out, 2, b"accent");
+      let primitive = message(|p| encode::varint(p, 9, 0xff112233));
+      let item = message(|i| encode::bytes(i, 7, &primitive));
+      encode::bytes(out, 6, &config_value(None, item));
+    });
+    // <plurals name="songs"><item quantity="one">…</item><item quantity="other">…</item>
+    let songs = message(|out| {
+      encode::bytes(out, 1, &wrapped_id(0));
+      encode::bytes(out, 2, b"songs");
+      let plural = message(|p| {
+        for (arity, text) in [(1, "%d song"), (5, "%d songs")] {
+          let plural_entry = message(|e| {
+            encode::varint(e, 3, arity);
+            encode::bytes(e, 4, &string_item(text));
+          });
+          encode::bytes(p, 1, &plural_entry);
+        }
+      });
+      let compound = message(|c| encode::bytes(c, 5, &plural));
+      let value = message(|v| encode::bytes(v, 5, &compound));
+      encode::bytes(out, 6, &message(|c| encode::bytes(c, 2, &value)));
+    });
+    let color_type = message(|out| {
+      encode::bytes(out, 1, &wrapped_id(3));
+      encode::bytes(out, 2, b"color");
+      encode::bytes(out, 3, &accent);
+    });
+    let plurals_type = message(|out| {
+      encode::bytes(out, 1, &wrapped_id(4));
+      encode::bytes(out, 2, b"plurals");
+      encode::bytes(out, 3, &songs);
+    });
+    let package = message(|out| {
+      encode::bytes(out, 1, &wrapped_id(0x7f));
+      encode::bytes(out, 2, b"com.example");
+      for typ in [&string_type, &color_type, &plurals_type] {
+        encode::bytes(out, 3, typ);
+      }
+    });
+    message(|out| encode::bytes(out, 2, &package))
+  }
+
+  // <manifest package="com.example"><application android:label="@string/app_name"/>
+  fn manifest_pb() -> Vec<u8> {
+    let package = message(|out| {
+      encode::bytes(out, 2, b"package");
+      encode::bytes(out, 3, b"com.example");
+    });
+    let label = message(|out| {
+      encode::bytes(out, 1, b"http://schemas.android.com/apk/res/android");
+      encode::bytes(out, 2, b"label");
+      encode::bytes(out, 3, b"@string/app_name");
+      encode::varint(out, 5, 0x01010001);
+      let reference = message(|r| encode::varint(r, 2, 0x7f020000));
+      encode::bytes(out, 6, &message(|i| encode::bytes(i, 1, &reference)));
+    });
+    let application = message(|out| {
+      encode::bytes(out, 3, b"application");
+      encode::bytes(out, 4, &label);
+    });
+    let manifest = message(|out| {
+      encode::bytes(out, 3, b"manifest");
+      encode::bytes(out, 4, &package);
+      encode::bytes(out, 5, &message(|n| encode::bytes(n, 2, b"\n  ")));
+      encode::bytes(out, 5, &message(|n| encode::bytes(n, 1, &application)));
+    });
+    message(|out| encode::bytes(out, 1, &manifest))
+  }
+
+  #[test]
+  fn test_aab() -> Result<()> {
+    let table = Arsc::from_proto(&resources_pb())?;
+    assert_eq!(table.get_res_value(0x7f020000), Some("Example".to_string()));
+    let locales = table
+      .entry_values(0x7f020000)
+      .into_iter()
+      .map(|(config, _)| config.qualifier())
+      .collect::<Vec<_>>();
+    assert_eq!(locales, ["", "de-rAT"]);
+    assert_eq!(
+      table
+        .public_resources()
+        .first()
+        .map(|spec_entry| spec_entry.flags),
+      Some(TypeSpecFlag::SPEC_PUBLIC | 0x0004)
+    );
+    let accent = table.entry_values(0x7f030001);
+    assert_eq!(
+      accent.first().map(|(_, entry)| &entry.value),
+      Some(&EntryValue::Simple(Value::Data {
+        data_type: ResType::INT_COLOR_ARGB8,
+        data: 0xff112233,
+      }))
+    );
+    let songs = table
+      .entry_values(0x7f040000)
+      .first()
+      .map(|(_, entry)| entry.value.clone())
+      .context("no plurals")?;
+    assert_eq!(
+      songs,
+      EntryValue::Complex {
+        parent: 0,
+        items: vec![
+          (0x0100_0006, Value::String("%d song".to_string())),
+          (0x0100_0004, Value::String("%d songs".to_string())),
+        ],
+      }
+    );
+
+    // The decoded table is the same model the binary path builds
+    let arsc_bytes = ArscWriter::new(&table).write()?;
+    let mut binary_table = Arsc::new(&arsc_bytes);
+    binary_table.parse()?;
+    assert!(table.diff(&binary_table).is_empty());
+
+    let manifest = ProtoXml::new(&manifest_pb()).parse(Some(&table))?;
+    assert_eq!(
+      std::str::from_utf8(&manifest)?,
+      "<?xml encoding='utf-8' version='1.1'?><manifest package=\"com.example\">\
+       <application label=\"Example\"></application></manifest>"
+    );
+
+    let (manifest_pb, resources_pb) = (manifest_pb(), resources_pb());
+    let aab = Aab::from_bytes(&zip(&[
+      ("base/manifest/AndroidManifest.xml", &manifest_pb),
+      ("base/resources.pb", &resources_pb),
+      ("feature/manifest/AndroidManifest.xml", &manifest_pb),
+      ("BundleConfig.pb", &[]),
+    ])?)?;
+    let modules = aab
+      .modules()
+      .iter()
+      .map(|module| (module.name.as_str(), module.has_resource_table()))
+      .collect::<Vec<_>>();
+    assert_eq!(modules, [("base", true), ("feature", false)]);
+    assert_eq!(aab.parse()?, manifest);
+
+    // Ids that don't fit in a resource id are rejected rather than allocated for
+    let table_with = |type_id: u32, entry_id: u32| {
+      let entry = message(|out| {
+        encode::bytes(out, 1, &wrapped_id(entry_id));
+        encode::bytes(out, 2, b"huge");
+      });
+      let typ = message(|out| {
+        encode::bytes(out, 1, &wrapped_id(type_id));
+        encode::bytes(out, 2, b"string");
+        encode::bytes(out, 3, &entry);
+      });
+      let package = message(|out| {
+        encode::bytes(out, 1, &wrapped_id(0x7f));
+        encode::bytes(out, 3, &typ);
+      });
+      message(|out| encode::bytes(out, 2, &package))
+    };
+    assert!(Arsc::from_proto(&table_with(1, 0xffff)).is_ok());
+    assert!(matches!(
+      Arsc::from_proto(&table_with(1, 0xffff_fff0)),
+      Err(ParseError::Protobuf(_))
+    ));
+    assert!(matches!(
+      Arsc::from_proto(&table_with(0x102, 0)),
+      Err(ParseError::Protobuf(_))
+    ));
+    Ok(())
+  }
+}
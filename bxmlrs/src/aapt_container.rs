@@ -0,0 +1,354 @@
+use crate::aab::{self, ProtoXml};
+use crate::arsc_parser::Arsc;
+use crate::nom_parser::ParseError;
+use crate::proto;
+use crate::res_config::ResConfig;
+use crate::xml_parser::AndroidManifest;
+
+/// Magic of the container aapt2 writes its compiled `.flat` files in.
+pub const AAPT_MAGIC: [u8; 4] = *b"AAPT";
+
+const CONTAINER_VERSION: u32 = 1;
+const ENTRY_RES_TABLE: u32 = 0x00;
+const ENTRY_RES_FILE: u32 = 0x01;
+
+/// Format of a compiled file, from `FileReference.Type`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+  Unknown,
+  Png,
+  BinaryXml,
+  ProtoXml,
+}
+
+/// A compiled file resource such as a layout or a drawable, with its `CompiledFile` header.
+#[derive(Clone, Debug)]
+pub struct ResFile<'c> {
+  /// Name of the resource the file defines, e.g. `layout/activity_main`.
+  pub resource_name: String,
+  pub config: ResConfig,
+  pub file_type: FileType,
+  /// Path of the source file aapt2 compiled.
+  pub source_path: String,
+  /// Ids declared inline with `@+id/...`, e.g. `id/button`.
+  pub exported_symbols: Vec<String>,
+  data: &'c [u8],
+}
+
+impl<'c> ResFile<'c> {
+  pub fn data(&self) -> &'c [u8] {
+    self.data
+  }
+
+  /// Decodes a compiled XML file into the XML the binary XML path produces. Other files are
+  /// rejected.
+  pub fn parse_xml(
+    &self,
+    arsc: Option<&Arsc>,
+  ) -> Result<Vec<u8>, ParseError> {
+    match self.file_type {
+      FileType::ProtoXml => ProtoXml::new(self.data).parse(arsc),
+      FileType::BinaryXml => AndroidManifest::new(self.data).parse(arsc),
+      file_type => Err(ParseError::Protobuf(format!(
+        "{} is not compiled XML but {:?}",
+        self.resource_name, file_type
+      ))),
+    }
+  }
+}
+
+/// An entry of an AAPT container.
+#[derive(Clone, Debug)]
+pub enum ContainerEntry<'c> {
+  /// Values compiled into a `ResourceTable` proto, as for `res/values/strings.xml`.
+  ResTable(&'c [u8]),
+  ResFile(ResFile<'c>),
+  /// An entry of a type newer than this parser, kept as is.
+  Unknown {
+    entry_type: u32,
+    data: &'c [u8],
+  },
+}
+
+/// The `AAPT` container of aapt2 intermediates (`.flat` files). It holds the compiled values
+/// of a values file, or a compiled file together with its `CompiledFile` header.
+#[derive(Clone, Debug)]
+pub struct AaptContainer<'c> {
+  entries: Vec<ContainerEntry<'c>>,
+}
+
+impl<'c> AaptContainer<'c> {
+  /// True if the data starts with the container magic.
+  pub fn is_container(data: &[u8]) -> bool {
+    data.starts_with(&AAPT_MAGIC)
+  }
+
+  pub fn parse(container: &'c [u8]) -> Result<Self, ParseError> {
+    let mut input = container
+      .strip_prefix(&AAPT_MAGIC)
+      .ok_or_else(|| ParseError::Protobuf("missing AAPT container magic".to_string()))?;
+    let version = take_u32(&mut input)?;
+    if version != CONTAINER_VERSION {
+      return Err(ParseError::Protobuf(format!(
+        "unsupported AAPT container version {}",
+        version
+      )));
+    }
+    let entry_count = take_u32(&mut input)?;
+    let mut entries = Vec::new();
+    for _ in 0..entry_count {
+      let entry_type = take_u32(&mut input)?;
+      let entry_length = take_u64(&mut input)?;
+      let mut entry = take(&mut input, entry_length)?;
+      skip_padding(&mut input, entry_length);
+      match entry_type {
+        ENTRY_RES_TABLE => entries.push(ContainerEntry::ResTable(entry)),
+        ENTRY_RES_FILE => {
+          let header_size = take_u32(&mut entry)? as u64;
+          let data_size = take_u64(&mut entry)?;
+          let header = take(&mut entry, header_size)?;
+          skip_padding(&mut entry, header_size);
+          let data = take(&mut entry, data_size)?;
+          entries.push(ContainerEntry::ResFile(Self::res_file(header, data)?));
+        }
+        entry_type => entries.push(ContainerEntry::Unknown {
+          entry_type,
+          data: entry,
+        }),
+      }
+    }
+    Ok(Self { entries })
+  }
+
+  // Decodes the `CompiledFile` header of a file entry.
+  fn res_file(
+    header: &[u8],
+    data: &'c [u8],
+  ) -> Result<ResFile<'c>, ParseError> {
+    let compiled_file = proto::fields(header)?;
+    let string =
+      |number: u32| proto::field(&compiled_file, number).map_or(String::new(), |v| v.as_string());
+    let config = match proto::field(&compiled_file, 2) {
+      Some(config) => aab::configuration(&proto::fields(config.as_bytes())?),
+      None => ResConfig::default(),
+    };
+    let file_type = match proto::field(&compiled_file, 3).map_or(0, |v| v.as_u32()) {
+      1 => FileType::Png,
+      2 => FileType::BinaryXml,
+      3 => FileType::ProtoXml,
+      _ => FileType::Unknown,
+    };
+    let mut exported_symbols = Vec::new();
+    for symbol in proto::repeated(&compiled_file, 5) {
+      let symbol = proto::fields(symbol.as_bytes())?;
+      if let Some(name) = proto::field(&symbol, 1) {
+        exported_symbols.push(name.as_string());
+      }
+    }
+    Ok(ResFile {
+      resource_name: string(1),
+      config,
+      file_type,
+      source_path: string(4),
+      exported_symbols,
+      data,
+    })
+  }
+
+  pub fn entries(&self) -> &[ContainerEntry<'c>] {
+    &self.entries
+  }
+
+  /// The compiled files of the container.
+  pub fn res_files(&self) -> Vec<&ResFile<'c>> {
+    self
+      .entries
+      .iter()
+      .filter_map(|entry| match entry {
+        ContainerEntry::ResFile(res_file) => Some(res_file),
+        ContainerEntry::ResTable(_) | ContainerEntry::Unknown { .. } => None,
+      })
+      .collect()
+  }
+
+  /// The compiled values of all table entries, merged into one table. Compiled values are not
+  /// linked yet, so their type and entry ids are only numbered in order.
+  pub fn resource_table(&self) -> Result<Option<Arsc<'static>>, ParseError> {
+    let mut merged: Option<Arsc<'static>> = None;
+    for entry in &self.entries {
+      let ContainerEntry::ResTable(table) = entry else {
+        continue;
+      };
+      let table = Arsc::from_proto(table)?;
+      match &mut merged {
+        Some(merged) => merged.merge(&table),
+        None => merged = Some(table),
+      }
+    }
+    Ok(merged)
+  }
+}
+
+fn take<'a>(
+  input: &mut &'a [u8],
+  len: u64,
+) -> Result<&'a [u8], ParseError> {
+  let len = usize::try_from(len)
+    .ok()
+    .filter(|len| *len <= input.len())
+    .ok_or_else(|| ParseError::Protobuf(format!("entry of {} bytes exceeds the container", len)))?;
+  let (bytes, rest) = input.split_at(len);
+  *input = rest;
+  Ok(bytes)
+}
+
+fn take_u32(input: &mut &[u8]) -> Result<u32, ParseError> {
+  let bytes = take(input, 4)?;
+  Ok(u32::from_le_bytes(bytes.try_into().unwrap_or_default()))
+}
+
+fn take_u64(input: &mut &[u8]) -> Result<u64, ParseError> {
+  let bytes = take(input, 8)?;
+  Ok(u64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+}
+
+// Entries and the headers inside them are padded to 4 bytes.
+fn skip_padding(
+  input: &mut &[u8],
+  len: u64,
+) {
+  let padding = (len.next_multiple_of(4) - len) as usize;
+  *input = input.get(padding..).unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::proto::encode;
+  use crate::test_util::message;
+  use anyhow::{Context, Result};
+
+  fn padded(
+    out: &mut Vec<u8>,
+    bytes: &[u8],
+  ) {
+    out.extend_from_slice(bytes);
+    out.resize(out.len().next_multiple_of(4), 0);
+  }
+
+  fn container(entries: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut out = AAPT_MAGIC.to_vec();
+    out.extend_from_slice(&CONTAINER_VERSION.to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (entry_type, entry) in entries {
+      out.extend_from_slice(&entry_type.to_le_bytes());
+      out.extend_from_slice(&(entry.len() as u64).to_le_bytes());
+      padded(&mut out, entry);
+    }
+    out
+  }
+
+  fn res_file_entry(
+    header: &[u8],
+    data: &[u8],
+  ) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    padded(&mut out, header);
+    padded(&mut out, data);
+    out
+  }
+
+  #[test]
+  fn test_aapt_container() -> Result<()> {
+    // values/strings.xml: <string name="app_name">Example</string>, not linked so without ids
+    let string_value = message(|out| encode::bytes(out, 1, b"Example"));
+    let item = message(|out| encode::bytes(out, 2, &string_value));
+    let value = message(|out| encode::bytes(out, 4, &item));
+    let config_value = message(|out| encode::bytes(out, 2, &value));
+    let entry = message(|out| {
+      encode::bytes(out, 2, b"app_name");
+      encode::bytes(out, 6, &config_value);
+    });
+    let typ = message(|out| {
+      encode::bytes(out, 2, b"string");
+      encode::bytes(out, 3, &entry);
+    });
+    let package = message(|out| encode::bytes(out, 3, &typ));
+    let table = message(|out| encode::bytes(out, 2, &package));
+
+    // layout-land/main.xml: <TextView android:text="@string/app_name"/>
+    let reference = message(|out| encode::bytes(out, 3, b"string/app_name"));
+    let attribute = message(|out| {
+      encode::bytes(out, 2, b"text");
+      encode::bytes(out, 3, b"@string/app_name");
+      encode::varint(out, 5, 0x0101014f);
+      encode::bytes(out, 6, &message(|i| encode::bytes(i, 1, &reference)));
+    });
+    let element = message(|out| {
+      encode::bytes(out, 3, b"TextView");
+      encode::bytes(out, 4, &attribute);
+    });
+    let layout = message(|out| encode::bytes(out, 1, &element));
+    let land = message(|out| encode::varint(out, 15, 2));
+    let layout_header = message(|out| {
+      encode::bytes(out, 1, b"layout/main");
+      encode::bytes(out, 2, &land);
+      encode::varint(out, 3, 3);
+      encode::bytes(out, 4, b"res/layout-land/main.xml");
+      encode::bytes(out, 5, &message(|s| encode::bytes(s, 1, b"id/title")));
+    });
+    let png_header = message(|out| {
+      encode::bytes(out, 1, b"drawable/icon");
+      encode::varint(out, 3, 1);
+    });
+
+    let flat = container(&[
+      (ENTRY_RES_TABLE, table),
+      (ENTRY_RES_FILE, res_file_entry(&layout_header, &layout)),
+      (ENTRY_RES_FILE, res_file_entry(&png_header, b"\x89PNG\r\n")),
+      (0x7f, b"future".to_vec()),
+    ]);
+    assert!(AaptContainer::is_container(&flat));
+    let container = AaptContainer::parse(&flat)?;
+    assert_eq!(container.entries().len(), 4);
+    assert!(matches!(
+      container.entries()[3],
+      ContainerEntry::Unknown {
+        entry_type: 0x7f,
+        data: b"future"
+      }
+    ));
+
+    let table = container.resource_table()?.context("no table entry")?;
+    let app_name = table
+      .spec_entries()
+      .into_iter()
+      .find(|spec_entry| spec_entry.name == "app_name")
+      .context("app_name not found")?;
+    assert_eq!(app_name.type_name, "string");
+    assert_eq!(
+      table.get_res_value(app_name.id),
+      Some("Example".to_string())
+    );
+
+    let [layout_file, png_file] = container.res_files()[..] else {
+      panic!("expected two files");
+    };
+    assert_eq!(layout_file.resource_name, "layout/main");
+    assert_eq!(layout_file.config.qualifier(), "land");
+    assert_eq!(layout_file.file_type, FileType::ProtoXml);
+    assert_eq!(layout_file.source_path, "res/layout-land/main.xml");
+    assert_eq!(layout_file.exported_symbols, ["id/title"]);
+    assert_eq!(
+      std::str::from_utf8(&layout_file.parse_xml(None)?)?,
+      "<?xml encoding='utf-8' version='1.1'?><TextView text=\"@string/app_name\"></TextView>"
+    );
+    assert_eq!(png_file.file_type, FileType::Png);
+    assert_eq!(png_file.data(), b"\x89PNG\r\n");
+    assert!(png_file.parse_xml(None).is_err());
+    assert!(AaptContainer::parse(&flat[..flat.len() - 8]).is_err());
+    Ok(())
+  }
+}
@@ -0,0 +1,663 @@
+use crate::nom_parser::ParseError;
+use base64::Engine;
+use quick_xml::events::{BytesCData, BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::reader::Reader;
+use quick_xml::Writer;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// Magic of files written by Android's `BinaryXmlSerializer`.
+pub const ABX_MAGIC: [u8; 4] = *b"ABX\0";
+
+// Token layout: the high nibble holds the data type, the low nibble the XmlPullParser event.
+const START_DOCUMENT: u8 = 0;
+const END_DOCUMENT: u8 = 1;
+const START_TAG: u8 = 2;
+const END_TAG: u8 = 3;
+const TEXT: u8 = 4;
+const CDSECT: u8 = 5;
+const ENTITY_REF: u8 = 6;
+const IGNORABLE_WHITESPACE: u8 = 7;
+const PROCESSING_INSTRUCTION: u8 = 8;
+const COMMENT: u8 = 9;
+const DOCDECL: u8 = 10;
+const ATTRIBUTE: u8 = 15;
+
+const TYPE_NULL: u8 = 1 << 4;
+const TYPE_STRING: u8 = 2 << 4;
+const TYPE_STRING_INTERNED: u8 = 3 << 4;
+const TYPE_BYTES_HEX: u8 = 4 << 4;
+const TYPE_BYTES_BASE64: u8 = 5 << 4;
+const TYPE_INT: u8 = 6 << 4;
+const TYPE_INT_HEX: u8 = 7 << 4;
+const TYPE_LONG: u8 = 8 << 4;
+const TYPE_LONG_HEX: u8 = 9 << 4;
+const TYPE_FLOAT: u8 = 10 << 4;
+const TYPE_DOUBLE: u8 = 11 << 4;
+const TYPE_BOOLEAN_TRUE: u8 = 12 << 4;
+const TYPE_BOOLEAN_FALSE: u8 = 13 << 4;
+
+// Interned string references are u16, with this value announcing a new string.
+const NEW_INTERNED_STRING: u16 = 0xFFFF;
+
+/// A typed attribute value, kept in the representation it was written with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AbxValue {
+  Null,
+  String(String),
+  /// A string stored once in the interned string table and referenced afterwards.
+  InternedString(String),
+  BytesHex(Vec<u8>),
+  BytesBase64(Vec<u8>),
+  Int(i32),
+  IntHex(i32),
+  Long(i64),
+  LongHex(i64),
+  Float(f32),
+  Double(f64),
+  Boolean(bool),
+}
+
+impl AbxValue {
+  /// The attribute text `BinaryXmlPullParser` reports for the value, `None` for null values.
+  pub fn as_string(&self) -> Option<String> {
+    Some(match self {
+      AbxValue::Null => return None,
+      AbxValue::String(string) | AbxValue::InternedString(string) => string.clone(),
+      AbxValue::BytesHex(bytes) => hex::encode_upper(bytes),
+      AbxValue::BytesBase64(bytes) => base64::engine::general_purpose::STANDARD.encode(bytes),
+      AbxValue::Int(value) => value.to_string(),
+      AbxValue::IntHex(value) => format!("{:x}", value),
+      AbxValue::Long(value) => value.to_string(),
+      AbxValue::LongHex(value) => format!("{:x}", value),
+      AbxValue::Float(value) => format!("{:?}", value),
+      AbxValue::Double(value) => format!("{:?}", value),
+      AbxValue::Boolean(value) => value.to_string(),
+    })
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AbxAttribute {
+  pub name: String,
+  pub value: AbxValue,
+}
+
+/// One event of an ABX document, in the terms of XmlPullParser.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AbxEvent {
+  StartDocument,
+  EndDocument,
+  StartTag {
+    name: String,
+    attributes: Vec<AbxAttribute>,
+  },
+  EndTag {
+    name: String,
+  },
+  Text(String),
+  CdSect(String),
+  EntityRef(String),
+  IgnorableWhitespace(String),
+  ProcessingInstruction(String),
+  Comment(String),
+  DocDecl(String),
+}
+
+/// An Android Binary XML (ABX) document, the format Android 12+ uses for system files such as
+/// `packages.xml`, `appops.xml` and `settings_*.xml`. Unrelated to the AXML format of
+/// resources.
+#[derive(Clone, Debug)]
+pub struct Abx<'babx> {
+  binary_abx: &'babx [u8],
+}
+
+impl<'babx> Abx<'babx> {
+  pub fn new(binary_abx: &'babx [u8]) -> Self {
+    Self { binary_abx }
+  }
+
+  /// True if the data starts with the ABX magic.
+  pub fn is_abx(data: &[u8]) -> bool {
+    data.starts_with(&ABX_MAGIC)
+  }
+
+  /// Reads the typed events of the document.
+  pub fn events(&self) -> Result<Vec<AbxEvent>, ParseError> {
+    let input = self
+      .binary_abx
+      .strip_prefix(&ABX_MAGIC)
+      .ok_or_else(|| ParseError::Abx("missing ABX magic".to_string()))?;
+    let mut reader = AbxReader {
+      input,
+      strings: Vec::new(),
+    };
+    let mut events = Vec::new();
+    while let Some(token) = reader.next_token() {
+      let event = match token & 0x0f {
+        START_DOCUMENT => AbxEvent::StartDocument,
+        END_DOCUMENT => {
+          events.push(AbxEvent::EndDocument);
+          break;
+        }
+        START_TAG => {
+          let name = reader.read_string(token & 0xf0)?;
+          let mut attributes = Vec::new();
+          while reader
+            .peek_token()
+            .is_some_and(|next| next & 0x0f == ATTRIBUTE)
+          {
+            let attribute_token = reader.next_token().unwrap_or_default();
+            let name = reader.read_interned_utf()?;
+            let value = reader.read_value(attribute_token & 0xf0)?;
+            attributes.push(AbxAttribute { name, value });
+          }
+          AbxEvent::StartTag { name, attributes }
+        }
+        END_TAG => AbxEvent::EndTag {
+          name: reader.read_string(token & 0xf0)?,
+        },
+        TEXT => AbxEvent::Text(reader.read_string(token & 0xf0)?),
+        CDSECT => AbxEvent::CdSect(reader.read_string(token & 0xf0)?),
+        ENTITY_REF => AbxEvent::EntityRef(reader.read_string(token & 0xf0)?),
+        IGNORABLE_WHITESPACE => AbxEvent::IgnorableWhitespace(reader.read_string(token & 0xf0)?),
+        PROCESSING_INSTRUCTION => {
+          AbxEvent::ProcessingInstruction(reader.read_string(token & 0xf0)?)
+        }
+        COMMENT => AbxEvent::Comment(reader.read_string(token & 0xf0)?),
+        DOCDECL => AbxEvent::DocDecl(reader.read_string(token & 0xf0)?),
+        event => {
+          return Err(ParseError::Abx(format!("unknown event {}", event)));
+        }
+      };
+      events.push(event);
+    }
+    Ok(events)
+  }
+
+  /// Decodes the document into XML, written the same way `AndroidManifest::parse` writes
+  /// decoded binary XML.
+  pub fn parse(&self) -> Result<Vec<u8>, ParseError> {
+    let mut xml_writer = Writer::new(Cursor::new(Vec::new()));
+    let decl = BytesDecl::from_start(BytesStart::from_content(
+      "xml encoding='utf-8' version='1.1'",
+      0,
+    ));
+    xml_writer
+      .write_event(Event::Decl(decl))
+      .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+
+    for event in self.events()? {
+      let xml_event = match &event {
+        AbxEvent::StartDocument | AbxEvent::EndDocument => continue,
+        AbxEvent::StartTag { name, attributes } => {
+          let mut xml_elem = BytesStart::new(name.as_str());
+          for attribute in attributes {
+            // Null attributes are not reported by the pull parser either
+            if let Some(value) = attribute.value.as_string() {
+              xml_elem.push_attribute((attribute.name.as_str(), value.as_str()));
+            }
+          }
+          Event::Start(xml_elem)
+        }
+        AbxEvent::EndTag { name } => Event::End(BytesEnd::new(name.as_str())),
+        AbxEvent::Text(text) | AbxEvent::IgnorableWhitespace(text) => {
+          Event::Text(BytesText::new(text))
+        }
+        AbxEvent::CdSect(text) => Event::CData(BytesCData::new(text.as_str())),
+        AbxEvent::EntityRef(name) => Event::Text(BytesText::from_escaped(format!("&{};", name))),
+        AbxEvent::ProcessingInstruction(text) => Event::PI(BytesText::from_escaped(text.as_str())),
+        AbxEvent::Comment(text) => Event::Comment(BytesText::from_escaped(text.as_str())),
+        AbxEvent::DocDecl(text) => Event::DocType(BytesText::from_escaped(text.as_str())),
+      };
+      xml_writer
+        .write_event(xml_event)
+        .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+    }
+    Ok(xml_writer.into_inner().into_inner())
+  }
+}
+
+// Big endian reader over the tokens, following FastDataInput.
+struct AbxReader<'a> {
+  input: &'a [u8],
+  strings: Vec<String>,
+}
+
+impl<'a> AbxReader<'a> {
+  fn next_token(&mut self) -> Option<u8> {
+    let (&token, rest) = self.input.split_first()?;
+    self.input = rest;
+    Some(token)
+  }
+
+  fn peek_token(&self) -> Option<u8> {
+    self.input.first().copied()
+  }
+
+  fn take<const N: usize>(&mut self) -> Result<[u8; N], ParseError> {
+    let bytes = self.take_slice(N)?;
+    Ok(bytes.try_into().unwrap_or([0; N]))
+  }
+
+  fn take_slice(
+    &mut self,
+    len: usize,
+  ) -> Result<&'a [u8], ParseError> {
+    if self.input.len() < len {
+      return Err(ParseError::Abx("unexpected end of data".to_string()));
+    }
+    let (bytes, rest) = self.input.split_at(len);
+    self.input = rest;
+    Ok(bytes)
+  }
+
+  fn read_u16(&mut self) -> Result<u16, ParseError> {
+    Ok(u16::from_be_bytes(self.take()?))
+  }
+
+  // DataOutput.writeUTF: a u16 byte length followed by modified UTF-8.
+  fn read_utf(&mut self) -> Result<String, ParseError> {
+    let len = self.read_u16()? as usize;
+    let bytes = self.take_slice(len)?;
+    Ok(decode_modified_utf8(bytes))
+  }
+
+  fn read_interned_utf(&mut self) -> Result<String, ParseError> {
+    let reference = self.read_u16()?;
+    if reference == NEW_INTERNED_STRING {
+      let string = self.read_utf()?;
+      self.strings.push(string.clone());
+      return Ok(string);
+    }
+    self
+      .strings
+      .get(reference as usize)
+      .cloned()
+      .ok_or_else(|| ParseError::Abx(format!("invalid interned string {}", reference)))
+  }
+
+  // Payload of an event other than an attribute.
+  fn read_string(
+    &mut self,
+    data_type: u8,
+  ) -> Result<String, ParseError> {
+    match data_type {
+      TYPE_NULL => Ok(String::new()),
+      TYPE_STRING => self.read_utf(),
+      TYPE_STRING_INTERNED => self.read_interned_utf(),
+      data_type => Err(ParseError::Abx(format!(
+        "unexpected data type {} for an event",
+        data_type >> 4
+      ))),
+    }
+  }
+
+  fn read_value(
+    &mut self,
+    data_type: u8,
+  ) -> Result<AbxValue, ParseError> {
+    Ok(match data_type {
+      TYPE_NULL => AbxValue::Null,
+      TYPE_STRING => AbxValue::String(self.read_utf()?),
+      TYPE_STRING_INTERNED => AbxValue::InternedString(self.read_interned_utf()?),
+      TYPE_BYTES_HEX | TYPE_BYTES_BASE64 => {
+        let len = self.read_u16()? as usize;
+        let bytes = self.take_slice(len)?.to_vec();
+        if data_type == TYPE_BYTES_HEX {
+          AbxValue::BytesHex(bytes)
+        } else {
+          AbxValue::BytesBase64(bytes)
+        }
+      }
+      TYPE_INT => AbxValue::Int(i32::from_be_bytes(self.take()?)),
+      TYPE_INT_HEX => AbxValue::IntHex(i32::from_be_bytes(self.take()?)),
+      TYPE_LONG => AbxValue::Long(i64::from_be_bytes(self.take()?)),
+      TYPE_LONG_HEX => AbxValue::LongHex(i64::from_be_bytes(self.take()?)),
+      TYPE_FLOAT => AbxValue::Float(f32::from_be_bytes(self.take()?)),
+      TYPE_DOUBLE => AbxValue::Double(f64::from_be_bytes(self.take()?)),
+      TYPE_BOOLEAN_TRUE => AbxValue::Boolean(true),
+      TYPE_BOOLEAN_FALSE => AbxValue::Boolean(false),
+      data_type => {
+        return Err(ParseError::Abx(format!(
+          "unknown attribute type {}",
+          data_type >> 4
+        )))
+      }
+    })
+  }
+}
+
+// Java's modified UTF-8 encodes NUL as two bytes and characters outside the BMP as surrogate
+// pairs of three bytes each, so it is decoded into UTF-16 first.
+fn decode_modified_utf8(bytes: &[u8]) -> String {
+  let mut units = Vec::with_capacity(bytes.len());
+  let mut index = 0;
+  while index < bytes.len() {
+    let byte = bytes[index] as u16;
+    let continuation = |offset: usize| bytes.get(index + offset).map_or(0, |b| (b & 0x3f) as u16);
+    let (unit, len) = match byte {
+      0x00..=0x7f => (byte, 1),
+      0xc0..=0xdf => (((byte & 0x1f) << 6) | continuation(1), 2),
+      0xe0..=0xef => (
+        ((byte & 0x0f) << 12) | (continuation(1) << 6) | continuation(2),
+        3,
+      ),
+      _ => (0xfffd, 1),
+    };
+    units.push(unit);
+    index += len;
+  }
+  String::from_utf16_lossy(&units)
+}
+
+fn encode_modified_utf8(string: &str) -> Vec<u8> {
+  let mut bytes = Vec::with_capacity(string.len());
+  for unit in string.encode_utf16() {
+    match unit {
+      0x01..=0x7f => bytes.push(unit as u8),
+      0x00 | 0x80..=0x7ff => {
+        bytes.extend_from_slice(&[0xc0 | (unit >> 6) as u8, 0x80 | (unit & 0x3f) as u8])
+      }
+      _ => bytes.extend_from_slice(&[
+        0xe0 | (unit >> 12) as u8,
+        0x80 | ((unit >> 6) & 0x3f) as u8,
+        0x80 | (unit & 0x3f) as u8,
+      ]),
+    }
+  }
+  bytes
+}
+
+/// Serializer for ABX documents, following `BinaryXmlSerializer`.
+pub struct AbxWriter {
+  out: Vec<u8>,
+  interned: HashMap<String, u16>,
+}
+
+impl Default for AbxWriter {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl AbxWriter {
+  pub fn new() -> Self {
+    Self {
+      out: ABX_MAGIC.to_vec(),
+      interned: HashMap::new(),
+    }
+  }
+
+  /// Serializes a whole document.
+  pub fn write(events: &[AbxEvent]) -> Result<Vec<u8>, ParseError> {
+    let mut writer = Self::new();
+    for event in events {
+      writer.write_event(event)?;
+    }
+    Ok(writer.finish())
+  }
+
+  /// Converts a text XML document, e.g. the output of `Abx::parse`, into ABX. Text XML carries
+  /// no types, so every attribute is written as a string, as Android's `xml2abx` does.
+  pub fn from_xml(xml: &[u8]) -> Result<Vec<u8>, ParseError> {
+    let mut reader = Reader::from_reader(xml);
+    let mut writer = Self::new();
+    writer.write_event(&AbxEvent::StartDocument)?;
+    let mut buf = Vec::new();
+    loop {
+      let event = reader
+        .read_event_into(&mut buf)
+        .map_err(|e| ParseError::Abx(e.to_string()))?;
+      let to_string = |bytes: &[u8]| String::from_utf8_lossy(bytes).to_string();
+      let is_empty = matches!(event, Event::Empty(_));
+      match event {
+        Event::Start(e) | Event::Empty(e) => {
+          let mut attributes = Vec::new();
+          for attr in e.attributes() {
+            let attr = attr.map_err(|e| ParseError::Abx(e.to_string()))?;
+            let value = attr
+              .unescape_value()
+              .map_err(|e| ParseError::Abx(e.to_string()))?;
+            attributes.push(AbxAttribute {
+              name: to_string(attr.key.as_ref()),
+              value: AbxValue::String(value.into_owned()),
+            });
+          }
+          let name = to_string(e.name().as_ref());
+          writer.write_event(&AbxEvent::StartTag {
+            name: name.clone(),
+            attributes,
+          })?;
+          if is_empty {
+            writer.write_event(&AbxEvent::EndTag { name })?;
+          }
+        }
+        Event::End(e) => writer.write_event(&AbxEvent::EndTag {
+          name: to_string(e.name().as_ref()),
+        })?,
+        Event::Text(e) => {
+          let text = e.unescape().map_err(|e| ParseError::Abx(e.to_string()))?;
+          writer.write_event(&AbxEvent::Text(text.into_owned()))?;
+        }
+        Event::CData(e) => writer.write_event(&AbxEvent::CdSect(to_string(&e)))?,
+        Event::Comment(e) => writer.write_event(&AbxEvent::Comment(to_string(&e)))?,
+        Event::PI(e) => writer.write_event(&AbxEvent::ProcessingInstruction(to_string(&e)))?,
+        Event::DocType(e) => writer.write_event(&AbxEvent::DocDecl(to_string(&e)))?,
+        Event::Decl(_) => {}
+        Event::Eof => break,
+      }
+      buf.clear();
+    }
+    writer.write_event(&AbxEvent::EndDocument)?;
+    Ok(writer.finish())
+  }
+
+  pub fn write_event(
+    &mut self,
+    event: &AbxEvent,
+  ) -> Result<(), ParseError> {
+    match event {
+      AbxEvent::StartDocument => self.out.push(TYPE_NULL | START_DOCUMENT),
+      AbxEvent::EndDocument => self.out.push(TYPE_NULL | END_DOCUMENT),
+      AbxEvent::StartTag { name, attributes } => {
+        self.out.push(TYPE_STRING_INTERNED | START_TAG);
+        self.write_interned_utf(name)?;
+        for attribute in attributes {
+          self.write_attribute(attribute)?;
+        }
+      }
+      AbxEvent::EndTag { name } => {
+        self.out.push(TYPE_STRING_INTERNED | END_TAG);
+        self.write_interned_utf(name)?;
+      }
+      AbxEvent::Text(text) => self.write_text(TEXT, text)?,
+      AbxEvent::CdSect(text) => self.write_text(CDSECT, text)?,
+      AbxEvent::EntityRef(text) => self.write_text(ENTITY_REF, text)?,
+      AbxEvent::IgnorableWhitespace(text) => self.write_text(IGNORABLE_WHITESPACE, text)?,
+      AbxEvent::ProcessingInstruction(text) => self.write_text(PROCESSING_INSTRUCTION, text)?,
+      AbxEvent::Comment(text) => self.write_text(COMMENT, text)?,
+      AbxEvent::DocDecl(text) => self.write_text(DOCDECL, text)?,
+    }
+    Ok(())
+  }
+
+  pub fn finish(self) -> Vec<u8> {
+    self.out
+  }
+
+  fn write_text(
+    &mut self,
+    event: u8,
+    text: &str,
+  ) -> Result<(), ParseError> {
+    self.out.push(TYPE_STRING | event);
+    self.write_utf(text)
+  }
+
+  fn write_attribute(
+    &mut self,
+    attribute: &AbxAttribute,
+  ) -> Result<(), ParseError> {
+    let data_type = match &attribute.value {
+      AbxValue::Null => TYPE_NULL,
+      AbxValue::String(_) => TYPE_STRING,
+      AbxValue::InternedString(_) => TYPE_STRING_INTERNED,
+      AbxValue::BytesHex(_) => TYPE_BYTES_HEX,
+      AbxValue::BytesBase64(_) => TYPE_BYTES_BASE64,
+      AbxValue::Int(_) => TYPE_INT,
+      AbxValue::IntHex(_) => TYPE_INT_HEX,
+      AbxValue::Long(_) => TYPE_LONG,
+      AbxValue::LongHex(_) => TYPE_LONG_HEX,
+      AbxValue::Float(_) => TYPE_FLOAT,
+      AbxValue::Double(_) => TYPE_DOUBLE,
+      AbxValue::Boolean(true) => TYPE_BOOLEAN_TRUE,
+      AbxValue::Boolean(false) => TYPE_BOOLEAN_FALSE,
+    };
+    self.out.push(data_type | ATTRIBUTE);
+    self.write_interned_utf(&attribute.name)?;
+    match &attribute.value {
+      AbxValue::Null | AbxValue::Boolean(_) => {}
+      AbxValue::String(string) => self.write_utf(string)?,
+      AbxValue::InternedString(string) => self.write_interned_utf(string)?,
+      AbxValue::BytesHex(bytes) | AbxValue::BytesBase64(bytes) => {
+        let len = u16::try_from(bytes.len())
+          .map_err(|_| ParseError::Abx(format!("{} byte blob is too long", bytes.len())))?;
+        self.out.extend_from_slice(&len.to_be_bytes());
+        self.out.extend_from_slice(bytes);
+      }
+      AbxValue::Int(value) | AbxValue::IntHex(value) => {
+        self.out.extend_from_slice(&value.to_be_bytes())
+      }
+      AbxValue::Long(value) | AbxValue::LongHex(value) => {
+        self.out.extend_from_slice(&value.to_be_bytes())
+      }
+      AbxValue::Float(value) => self.out.extend_from_slice(&value.to_be_bytes()),
+      AbxValue::Double(value) => self.out.extend_from_slice(&value.to_be_bytes()),
+    }
+    Ok(())
+  }
+
+  fn write_utf(
+    &mut self,
+    string: &str,
+  ) -> Result<(), ParseError> {
+    let bytes = encode_modified_utf8(string);
+    let len = u16::try_from(bytes.len())
+      .map_err(|_| ParseError::Abx(format!("{} byte string is too long", bytes.len())))?;
+    self.out.extend_from_slice(&len.to_be_bytes());
+    self.out.extend_from_slice(&bytes);
+    Ok(())
+  }
+
+  fn write_interned_utf(
+    &mut self,
+    string: &str,
+  ) -> Result<(), ParseError> {
+    if let Some(reference) = self.interned.get(string) {
+      self.out.extend_from_slice(&reference.to_be_bytes());
+      return Ok(());
+    }
+    let reference = self.interned.len() as u16;
+    if reference == NEW_INTERNED_STRING {
+      return Err(ParseError::Abx("too many interned strings".to_string()));
+    }
+    self.interned.insert(string.to_string(), reference);
+    self
+      .out
+      .extend_from_slice(&NEW_INTERNED_STRING.to_be_bytes());
+    self.write_utf(string)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::Result;
+
+  #[test]
+  fn test_abx() -> Result<()> {
+    let events = vec![
+      AbxEvent::StartDocument,
+      AbxEvent::StartTag {
+        name: "packages".to_string(),
+        attributes: Vec::new(),
+      },
+      AbxEvent::StartTag {
+        name: "package".to_string(),
+        attributes: vec![
+          AbxAttribute {
+            name: "name".to_string(),
+            value: AbxValue::InternedString("com.example".to_string()),
+          },
+          AbxAttribute {
+            name: "version".to_string(),
+            value: AbxValue::Long(42),
+          },
+          AbxAttribute {
+            name: "flags".to_string(),
+            value: AbxValue::IntHex(0x3e),
+          },
+          AbxAttribute {
+            name: "isOrphaned".to_string(),
+            value: AbxValue::Boolean(true),
+          },
+          AbxAttribute {
+            name: "installer".to_string(),
+            value: AbxValue::Null,
+          },
+        ],
+      },
+      AbxEvent::StartTag {
+        name: "sigs".to_string(),
+        attributes: vec![
+          AbxAttribute {
+            name: "key".to_string(),
+            value: AbxValue::BytesHex(vec![0xca, 0xfe]),
+          },
+          AbxAttribute {
+            name: "cert".to_string(),
+            value: AbxValue::BytesBase64(b"cert".to_vec()),
+          },
+        ],
+      },
+      AbxEvent::EndTag {
+        name: "sigs".to_string(),
+      },
+      AbxEvent::Text("a < b \u{0} \u{1F600}".to_string()),
+      AbxEvent::EndTag {
+        name: "package".to_string(),
+      },
+      AbxEvent::EndTag {
+        name: "packages".to_string(),
+      },
+      AbxEvent::EndDocument,
+    ];
+    let abx = AbxWriter::write(&events)?;
+    assert!(Abx::is_abx(&abx));
+    // The tag names are interned, the closing tags only reference them
+    assert_eq!(
+      &abx[4..7],
+      [
+        TYPE_NULL | START_DOCUMENT,
+        TYPE_STRING_INTERNED | START_TAG,
+        0xff
+      ]
+    );
+    assert_eq!(Abx::new(&abx).events()?, events);
+
+    let xml = Abx::new(&abx).parse()?;
+    assert_eq!(
+      std::str::from_utf8(&xml)?,
+      "<?xml encoding='utf-8' version='1.1'?><packages>\
+       <package name=\"com.example\" version=\"42\" flags=\"3e\" isOrphaned=\"true\">\
+       <sigs key=\"CAFE\" cert=\"Y2VydA==\"></sigs>a &lt; b \u{0} \u{1F600}</package></packages>"
+    );
+
+    // Converting the XML back keeps the document, with every attribute as a string
+    let converted = AbxWriter::from_xml(&xml)?;
+    assert_eq!(Abx::new(&converted).parse()?, xml);
+    assert!(Abx::new(b"<xml/>").events().is_err());
+    Ok(())
+  }
+}
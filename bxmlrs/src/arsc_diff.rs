@@ -0,0 +1,427 @@
+use crate::arsc_parser::{Arsc, EntryValue, ResourceName, Value};
+use crate::nom_parser::ResType;
+use crate::res_config::ResConfig;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Differences between two resource tables, ordered by resource name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceDiff {
+  pub changes: Vec<ResourceChange>,
+}
+
+/// A single difference between two resource tables. Resources are matched by
+/// `package:type/name`, so a resource keeping its name but moving to another id is reported as
+/// `IdChanged` instead of being removed and added.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResourceChange {
+  Added {
+    name: ResourceName,
+    id: u32,
+    configs: Vec<ResConfig>,
+  },
+  Removed {
+    name: ResourceName,
+    id: u32,
+    configs: Vec<ResConfig>,
+  },
+  IdChanged {
+    name: ResourceName,
+    old_id: u32,
+    new_id: u32,
+  },
+  ValueChanged {
+    name: ResourceName,
+    config: ResConfig,
+    old: EntryValue,
+    new: EntryValue,
+  },
+  ConfigAdded {
+    name: ResourceName,
+    config: ResConfig,
+    value: EntryValue,
+  },
+  ConfigRemoved {
+    name: ResourceName,
+    config: ResConfig,
+    value: EntryValue,
+  },
+}
+
+impl ResourceChange {
+  pub fn name(&self) -> &ResourceName {
+    match self {
+      ResourceChange::Added { name, .. }
+      | ResourceChange::Removed { name, .. }
+      | ResourceChange::IdChanged { name, .. }
+      | ResourceChange::ValueChanged { name, .. }
+      | ResourceChange::ConfigAdded { name, .. }
+      | ResourceChange::ConfigRemoved { name, .. } => name,
+    }
+  }
+}
+
+impl std::fmt::Display for ResourceChange {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      ResourceChange::Added { name, id, .. } => write!(f, "+ {} (0x{:08x})", name, id),
+      ResourceChange::Removed { name, id, .. } => write!(f, "- {} (0x{:08x})", name, id),
+      ResourceChange::IdChanged {
+        name,
+        old_id,
+        new_id,
+      } => write!(f, "~ {} id 0x{:08x} -> 0x{:08x}", name, old_id, new_id),
+      ResourceChange::ValueChanged {
+        name,
+        config,
+        old,
+        new,
+      } => write!(
+        f,
+        "~ {} [{}] {} -> {}",
+        name,
+        config,
+        describe(old),
+        describe(new)
+      ),
+      ResourceChange::ConfigAdded {
+        name,
+        config,
+        value,
+      } => write!(f, "+ {} [{}] {}", name, config, describe(value)),
+      ResourceChange::ConfigRemoved {
+        name,
+        config,
+        value,
+      } => write!(f, "- {} [{}] {}", name, config, describe(value)),
+    }
+  }
+}
+
+impl std::fmt::Display for ResourceDiff {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    for change in &self.changes {
+      writeln!(f, "{}", change)?;
+    }
+    Ok(())
+  }
+}
+
+impl ResourceDiff {
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+}
+
+fn describe(value: &EntryValue) -> String {
+  match value {
+    EntryValue::Simple(value) => format!("{:?}", value.as_string().unwrap_or_default()),
+    EntryValue::Complex { items, .. } => format!("<{} items>", items.len()),
+  }
+}
+
+// A resource of one table: its id and its value in every configuration.
+struct TableResource<'t> {
+  id: u32,
+  values: BTreeMap<ResConfig, &'t EntryValue>,
+}
+
+fn table_resources<'t>(table: &'t Arsc) -> BTreeMap<ResourceName, TableResource<'t>> {
+  let mut resources: BTreeMap<ResourceName, TableResource<'t>> = BTreeMap::new();
+  // In id order, so the first of packages sharing a name always wins
+  for package_id in table.package_ids() {
+    let Some(package) = table.package(package_id) else {
+      continue;
+    };
+    for type_chunk in &package.type_chunks {
+      let Some(type_name) = package.type_name(type_chunk.type_id) else {
+        continue;
+      };
+      let config = type_chunk.res_config();
+      for (entry_id, entry) in type_chunk.entries.iter().enumerate() {
+        let Some(entry) = entry else {
+          continue;
+        };
+        let name = ResourceName {
+          package: package.name.clone(),
+          type_name: type_name.to_string(),
+          name: entry.key.clone(),
+        };
+        let id = (package_id << 24) | ((type_chunk.type_id as u32) << 16) | entry_id as u32;
+        resources
+          .entry(name)
+          .or_insert_with(|| TableResource {
+            id,
+            values: BTreeMap::new(),
+          })
+          .values
+          .entry(config.clone())
+          .or_insert(&entry.value);
+      }
+    }
+  }
+  resources
+}
+
+// Ids of referenced resources shift between builds as well, so references are compared by name
+// when the table can resolve them.
+#[derive(PartialEq, Eq)]
+enum Comparable<'t> {
+  Reference(u8, Result<ResourceName, u32>),
+  Value(&'t Value),
+}
+
+fn comparable_reference(
+  table: &Arsc,
+  data_type: u8,
+  id: u32,
+) -> Comparable<'static> {
+  Comparable::Reference(data_type, table.resource_name(id).ok_or(id))
+}
+
+fn comparable<'t>(
+  table: &Arsc,
+  value: &'t Value,
+) -> Comparable<'t> {
+  match value {
+    Value::Data { data_type, data }
+      if matches!(
+        *data_type,
+        ResType::REFERENCE
+          | ResType::ATTRIBUTE
+          | ResType::DYNAMIC_REFERENCE
+          | ResType::DYNAMIC_ATTRIBUTE
+      ) =>
+    {
+      comparable_reference(table, *data_type, *data)
+    }
+    _ => Comparable::Value(value),
+  }
+}
+
+fn same_value(
+  table: &Arsc,
+  value: &EntryValue,
+  other_table: &Arsc,
+  other_value: &EntryValue,
+) -> bool {
+  match (value, other_value) {
+    (EntryValue::Simple(value), EntryValue::Simple(other_value)) => {
+      comparable(table, value) == comparable(other_table, other_value)
+    }
+    (
+      EntryValue::Complex { parent, items },
+      EntryValue::Complex {
+        parent: other_parent,
+        items: other_items,
+      },
+    ) => {
+      // A parent of 0 means no parent, which never resolves to a name
+      comparable_reference(table, ResType::REFERENCE, *parent)
+        == comparable_reference(other_table, ResType::REFERENCE, *other_parent)
+        && items.len() == other_items.len()
+        && items
+          .iter()
+          .zip(other_items)
+          .all(|((key, value), (other_key, other_value))| {
+            comparable_reference(table, ResType::ATTRIBUTE, *key)
+              == comparable_reference(other_table, ResType::ATTRIBUTE, *other_key)
+              && comparable(table, value) == comparable(other_table, other_value)
+          })
+    }
+    _ => false,
+  }
+}
+
+impl<'barsc> Arsc<'barsc> {
+  /// Compares this table (the old version) with `other` (the new version).
+  pub fn diff(
+    &self,
+    other: &Arsc,
+  ) -> ResourceDiff {
+    let old_resources = table_resources(self);
+    let new_resources = table_resources(other);
+    let names = old_resources
+      .keys()
+      .chain(new_resources.keys())
+      .collect::<BTreeSet<_>>();
+
+    let mut changes = Vec::new();
+    for name in names {
+      let (old, new) = match (old_resources.get(name), new_resources.get(name)) {
+        (Some(old), Some(new)) => (old, new),
+        (Some(old), None) => {
+          changes.push(ResourceChange::Removed {
+            name: name.clone(),
+            id: old.id,
+            configs: old.values.keys().cloned().collect(),
+          });
+          continue;
+        }
+        (None, Some(new)) => {
+          changes.push(ResourceChange::Added {
+            name: name.clone(),
+            id: new.id,
+            configs: new.values.keys().cloned().collect(),
+          });
+          continue;
+        }
+        (None, None) => continue,
+      };
+
+      if old.id != new.id {
+        changes.push(ResourceChange::IdChanged {
+          name: name.clone(),
+          old_id: old.id,
+          new_id: new.id,
+        });
+      }
+      let configs = old
+        .values
+        .keys()
+        .chain(new.values.keys())
+        .collect::<BTreeSet<_>>();
+      for config in configs {
+        match (old.values.get(config), new.values.get(config)) {
+          (Some(old_value), Some(new_value)) => {
+            if !same_value(self, old_value, other, new_value) {
+              changes.push(ResourceChange::ValueChanged {
+                name: name.clone(),
+                config: config.clone(),
+                old: (*old_value).clone(),
+                new: (*new_value).clone(),
+              });
+            }
+          }
+          (Some(old_value), None) => changes.push(ResourceChange::ConfigRemoved {
+            name: name.clone(),
+            config: config.clone(),
+            value: (*old_value).clone(),
+          }),
+          (None, Some(new_value)) => changes.push(ResourceChange::ConfigAdded {
+            name: name.clone(),
+            config: config.clone(),
+            value: (*new_value).clone(),
+          }),
+          (None, None) => {}
+        }
+      }
+    }
+    ResourceDiff { changes }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::{Context, Result};
+
+  #[test]
+  fn test_resource_diff() -> Result<()> {
+    let arsc_path = std::path::Path::new(
+      "../data/arsc/ab4cb5175fc0827860b9c3361d8bf2aec99a71f63b5a7666ae2403b0030ac56f.arsc",
+    );
+    let arsc_bytes: Vec<u8> = std::fs::read(arsc_path)?;
+    let mut old = Arsc::new(arsc_bytes.as_slice());
+    old.parse()?;
+    assert!(old.diff(&old).is_empty());
+
+    let strings = old
+      .spec_entries()
+      .into_iter()
+      .filter(|spec_entry| spec_entry.type_name == "string")
+      .map(|spec_entry| spec_entry.id)
+      .collect::<Vec<_>>();
+    let [changed, removed, swapped_a, swapped_b, ..] = strings[..] else {
+      panic!("not enough strings");
+    };
+    let name = |id| old.resource_name(id).context("unnamed resource");
+
+    let mut new = old.clone();
+    new.set_string(changed, "Changed")?;
+    new.add_locale_variant(changed, "de", "Geändert")?;
+    new.remove_entry(removed)?;
+    // Swapping two entries reassigns both ids without changing any value
+    let package = new.packages_mut().get_mut(&(swapped_a >> 24)).unwrap();
+    for type_chunk in &mut package.type_chunks {
+      let (a, b) = ((swapped_a & 0xFFFF) as usize, (swapped_b & 0xFFFF) as usize);
+      if type_chunk.type_id as u32 == (swapped_a >> 16) & 0xFF && type_chunk.entries.len() > b {
+        type_chunk.entries.swap(a, b);
+      }
+    }
+
+    let diff = old.diff(&new);
+    let de = diff
+      .changes
+      .iter()
+      .find_map(|change| match change {
+        ResourceChange::ConfigAdded { config, .. } => Some(config.clone()),
+        _ => None,
+      })
+      .context("locale variant not reported")?;
+    assert_eq!(de.qualifier(), "de");
+
+    let mut expected = vec![
+      ResourceChange::ValueChanged {
+        name: name(changed)?,
+        config: ResConfig::default(),
+        old: old
+          .entry_values(changed)
+          .into_iter()
+          .find(|(config, _)| config.is_default())
+          .context("no default value")?
+          .1
+          .value
+          .clone(),
+        new: EntryValue::Simple(Value::String("Changed".to_string())),
+      },
+      ResourceChange::ConfigAdded {
+        name: name(changed)?,
+        config: de,
+        value: EntryValue::Simple(Value::String("Geändert".to_string())),
+      },
+      ResourceChange::Removed {
+        name: name(removed)?,
+        id: removed,
+        configs: old
+          .entry_values(removed)
+          .into_iter()
+          .map(|(config, _)| config)
+          .collect::<BTreeSet<_>>()
+          .into_iter()
+          .collect(),
+      },
+      ResourceChange::IdChanged {
+        name: name(swapped_a)?,
+        old_id: swapped_a,
+        new_id: swapped_b,
+      },
+      ResourceChange::IdChanged {
+        name: name(swapped_b)?,
+        old_id: swapped_b,
+        new_id: swapped_a,
+      },
+    ];
+    expected.sort_by(|a, b| a.name().cmp(b.name()));
+    let mut changes = diff.changes.clone();
+    changes.sort_by(|a, b| a.name().cmp(b.name()));
+    assert_eq!(changes, expected);
+
+    // Of two packages with the same name, the one with the lower id is compared
+    let mut shared_name = old.clone();
+    let mut feature = shared_name
+      .package(changed >> 24)
+      .context("package missing")?
+      .clone();
+    feature.id = 0x80;
+    shared_name.packages_mut().insert(0x80, feature);
+    shared_name.set_string(0x80000000 | (changed & 0xffffff), "Feature")?;
+    assert!(old.diff(&shared_name).is_empty());
+    Ok(())
+  }
+}
@@ -1,27 +1,48 @@
 #![allow(dead_code)]
 
 use crate::nom_parser::{
-  parser, ChunkHeader, ChunkType, PackageChunkHeader, ResValue, TableEntryFlag, TableMap,
-  TableMapEntry, TypeChunkHeader,
+  parser, ChunkHeader, ChunkType, LibraryChunk, PackageChunkHeader, ResType, ResValue,
+  StagedAliasChunk, TableEntryFlag, TableMap, TableMapEntry, TypeChunkConfig, TypeChunkFlags,
+  TypeChunkHeader, TypeSpecFlag, CONFIG_CHANGES,
 };
 use crate::nom_parser::{ParseError, TypeSpecChunkHeader};
+use crate::parser::Diagnostic;
+use crate::res_config::{self, ResConfig};
 use nom::multi::count;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, Event};
+use quick_xml::Writer;
 use std::collections::HashMap;
+use std::io::Cursor;
 
+/// The parsed model of a `resources.arsc` resource table.
 #[derive(Clone, Debug)]
 pub struct Arsc<'barsc> {
   binary_arsc: &'barsc [u8],
   strings: Vec<String>,
+  // style spans of the strings at the same index in `strings`
+  styles: Vec<Vec<StringSpan>>,
+  // package count declared by the table header
+  package_count: u32,
   packages: HashMap<u32, Package>,
+  diagnostics: Vec<Diagnostic>,
 }
 
+/// `Arsc` under the name used by the table level APIs such as `ResourceTable::diff`.
+pub type ResourceTable<'barsc> = Arsc<'barsc>;
+
 // contains resource entry values
 // can contain multiple values or a single value
 type ResEntry = Vec<Option<String>>;
 type TypeId = u32;
 
+// ResTable_config::CONFIG_LOCALE, set in the spec flags of entries that vary by locale
+const CONFIG_LOCALE: u32 = 0x0004;
+
 #[derive(Clone, Debug)]
 pub struct Package {
+  /// Package id, 0x7f for apps, 0x01 for the framework and 0x00 for shared libraries whose id
+  /// is only assigned at runtime.
+  pub id: u32,
   pub name: String,
   pub type_strings: Vec<String>,
   pub key_strings: Vec<String>,
@@ -36,6 +57,211 @@ pub struct Package {
   /// The `TypeId` is a unique identifier for each resource type in a package, which allows for efficient
   /// retrieval of resources based on their types.
   pub types: Vec<(TypeId, Vec<ResEntry>)>,
+  /// Every TABLE_TYPE chunk of the package in file order, one per type and configuration.
+  pub type_chunks: Vec<TypeChunk>,
+  /// Shared libraries referenced by the package, from its TABLE_LIBRARY chunks.
+  pub libraries: Vec<SharedLibrary>,
+  /// Staged resource ids and the ids they were finalized to, from TABLE_STAGED_ALIAS chunks.
+  pub staged_aliases: Vec<StagedAlias>,
+}
+
+/// Entry of the dynamic reference table: the package id a shared library was compiled against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharedLibrary {
+  pub package_id: u32,
+  pub package_name: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StagedAlias {
+  pub staged_id: u32,
+  pub finalized_id: u32,
+}
+
+/// Entries of a single TABLE_TYPE chunk, indexed by entry id.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeChunk {
+  pub type_id: u8,
+  pub config: TypeChunkConfig,
+  pub entries: Vec<Option<TypeEntry>>,
+}
+
+impl TypeChunk {
+  pub fn res_config(&self) -> ResConfig {
+    ResConfig::parse(&self.config)
+  }
+
+  fn res_entries(&self) -> Vec<ResEntry> {
+    self
+      .entries
+      .iter()
+      .map(|entry| match entry {
+        Some(entry) => entry.value.as_strings(),
+        None => Vec::new(),
+      })
+      .collect()
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeEntry {
+  // Name of the entry, resolved through the package key strings.
+  pub key: String,
+  pub flags: u16,
+  pub value: EntryValue,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntryValue {
+  Simple(Value),
+  /// Bags such as styles, arrays, plurals and attrs: the parent resource id (0 if none)
+  /// followed by (name, value) pairs.
+  Complex {
+    parent: u32,
+    items: Vec<(u32, Value)>,
+  },
+}
+
+impl EntryValue {
+  // The value of a simple entry, or the item values of a bag.
+  fn values(&self) -> Vec<&Value> {
+    match self {
+      EntryValue::Simple(value) => vec![value],
+      EntryValue::Complex { items, .. } => items.iter().map(|(_, value)| value).collect(),
+    }
+  }
+
+  // Values as stored in `Package::types`.
+  fn as_strings(&self) -> ResEntry {
+    match self {
+      EntryValue::Simple(value) => vec![value.as_string()],
+      EntryValue::Complex { items, .. } => {
+        items.iter().map(|(_, value)| value.as_string()).collect()
+      }
+    }
+  }
+}
+
+/// A typed resource value. Strings are resolved through the table's global string pool.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+  String(String),
+  /// A string with style spans, such as `<b>Bold</b> text`.
+  StyledString(StyledString),
+  /// Any other value, kept as the raw `Res_value` data type and data.
+  Data {
+    data_type: u8,
+    data: u32,
+  },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StyledString {
+  pub text: String,
+  pub spans: Vec<StringSpan>,
+}
+
+/// A style applied to a range of a string. The tag holds the markup, e.g. `b` or
+/// `font;color=#ff0000`, and the range is inclusive and counted in UTF-16 units.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StringSpan {
+  pub tag: String,
+  pub first_char: u32,
+  pub last_char: u32,
+}
+
+impl Value {
+  pub(crate) fn from_res_value(
+    value: &ResValue,
+    strings: &[String],
+    styles: &[Vec<StringSpan>],
+  ) -> Self {
+    match (value.data_type, strings.get(value.data as usize)) {
+      (ResType::STRING, Some(string)) => match styles.get(value.data as usize) {
+        Some(spans) if !spans.is_empty() => Value::StyledString(StyledString {
+          text: string.clone(),
+          spans: spans.clone(),
+        }),
+        _ => Value::String(string.clone()),
+      },
+      _ => Value::Data {
+        data_type: value.data_type,
+        data: value.data,
+      },
+    }
+  }
+
+  pub(crate) fn as_res_value(&self) -> Option<ResValue> {
+    match self {
+      Value::String(_) | Value::StyledString(_) => None,
+      Value::Data { data_type, data } => Some(ResValue {
+        size: 8,
+        res0: 0,
+        data_type: *data_type,
+        data: *data,
+      }),
+    }
+  }
+
+  /// Same representation `AndroidManifest` uses for attribute values.
+  pub fn as_string(&self) -> Option<String> {
+    match self {
+      Value::String(string) => Some(string.clone()),
+      Value::StyledString(styled_string) => Some(styled_string.text.clone()),
+      Value::Data { .. } => self.as_res_value()?.as_string(&[]),
+    }
+  }
+}
+
+/// Fully qualified name of a resource, displayed as `package:type/name`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResourceName {
+  pub package: String,
+  pub type_name: String,
+  pub name: String,
+}
+
+impl std::fmt::Display for ResourceName {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    write!(f, "{}:{}/{}", self.package, self.type_name, self.name)
+  }
+}
+
+/// A resource entry as declared by its TABLE_SPEC chunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecEntry {
+  pub id: u32,
+  pub type_name: String,
+  pub name: String,
+  pub flags: u32,
+}
+
+impl SpecEntry {
+  pub fn is_public(&self) -> bool {
+    self.flags & TypeSpecFlag::SPEC_PUBLIC != 0
+  }
+
+  /// Staged resources are public, but their id may still change in a future build.
+  pub fn is_staged_api(&self) -> bool {
+    self.flags & TypeSpecFlag::SPEC_STAGED_API != 0
+  }
+
+  /// Mask of the configuration dimensions this entry has alternative values for.
+  pub fn config_changes(&self) -> u32 {
+    self.flags & TypeSpecFlag::CONFIG_MASK
+  }
+
+  /// Names of the configuration dimensions set in `config_changes`, e.g. `["locale", "density"]`.
+  pub fn config_change_names(&self) -> Vec<&'static str> {
+    CONFIG_CHANGES
+      .iter()
+      .filter(|(flag, _)| self.flags & flag != 0)
+      .map(|(_, name)| *name)
+      .collect()
+  }
 }
 
 impl<'barsc> Arsc<'barsc> {
@@ -43,8 +269,45 @@ impl<'barsc> Arsc<'barsc> {
     Self {
       binary_arsc,
       strings: Vec::new(),
+      styles: Vec::new(),
+      package_count: 0,
       packages: HashMap::new(),
+      diagnostics: Vec::new(),
+    }
+  }
+
+  /// Builds a table from packages decoded from another format, such as the protobuf tables
+  /// of app bundles. Packages sharing an id are merged.
+  pub(crate) fn from_packages(packages: Vec<Package>) -> Arsc<'static> {
+    let mut arsc = Arsc::new(&[]);
+    arsc.package_count = packages.len() as u32;
+    for mut package in packages {
+      package.refresh_types();
+      for type_chunk in &package.type_chunks {
+        for entry in type_chunk.entries.iter().flatten() {
+          for value in entry.value.values() {
+            let (text, spans) = match value {
+              Value::String(text) => (text, Vec::new()),
+              Value::StyledString(styled_string) => {
+                (&styled_string.text, styled_string.spans.clone())
+              }
+              Value::Data { .. } => continue,
+            };
+            if !arsc.strings.contains(text) {
+              arsc.strings.push(text.clone());
+              arsc.styles.push(spans);
+            }
+          }
+        }
+      }
+      match arsc.packages.get_mut(&package.id) {
+        Some(existing) => existing.merge(package),
+        None => {
+          arsc.packages.insert(package.id, package);
+        }
+      }
     }
+    arsc
   }
 
   pub fn parse(&mut self) -> Result<Vec<u8>, ParseError> {
@@ -55,171 +318,415 @@ impl<'barsc> Arsc<'barsc> {
     // Android doesn't care about the chunk type
     // chunk_header.typ != ChunkType::Table
 
+    // Like LoadedArsc, the declared package count is only informational, every TABLE_PACKAGE
+    // chunk found is loaded
+    self.package_count = arsc_table_header.package_count;
+
     // table header size: 8 + 4 => 12
-    let mut chunk_start_offset: usize = 12;
-    let _package_count = arsc_table_header.package_count;
-    let mut input: &[u8];
-    while chunk_start_offset < self.binary_arsc.len() {
-      input = &self.binary_arsc[chunk_start_offset..];
-
-      let (_, chunk_header) =
-        ChunkHeader::parse(input).map_err(|e| ParseError::ChunkHeader(e.to_string()))?;
-      // println!("chunk header: {}", chunk_header);
+    let chunks = Self::chunks(self.binary_arsc, 12)?;
+
+    // The first string pool is the global value pool shared by all packages, it has to be
+    // known before any package is parsed
+    if let Some((chunk_start_offset, _)) = chunks
+      .iter()
+      .find(|(_, chunk_header)| chunk_header.typ == ChunkType::STRING_POOL)
+    {
+      let string_chunk = &self.binary_arsc[*chunk_start_offset..];
+      self.strings =
+        parser::string_table(string_chunk).map_err(|e| ParseError::StringPool(e.to_string()))?;
+      self.styles = parser::string_styles(string_chunk)?
+        .into_iter()
+        .map(|spans| {
+          spans
+            .into_iter()
+            .map(|span| StringSpan {
+              tag: self
+                .strings
+                .get(span.name as usize)
+                .cloned()
+                .unwrap_or_default(),
+              first_char: span.first_char,
+              last_char: span.last_char,
+            })
+            .collect()
+        })
+        .collect();
+    }
 
+    for (chunk_start_offset, chunk_header) in chunks {
       match chunk_header.typ {
-        ChunkType::STRING_POOL => {
-          let string_chunk = &self.binary_arsc[chunk_start_offset..];
-          self.strings = parser::string_table(string_chunk)
-            .map_err(|e| ParseError::StringPool(e.to_string()))?;
-        }
+        ChunkType::STRING_POOL => {}
         ChunkType::TABLE_PACKAGE => {
-          let (_, package_chunk) = PackageChunkHeader::parse(input)
-            .map_err(|e| ParseError::PackageHeader(e.to_string()))?;
-          // println!("package chunk: {:?}", package_chunk);
-
-          //  The typeStrings field specifies the offset from the start of the Package chunk
-          let types_chunk =
-            &self.binary_arsc[chunk_start_offset + package_chunk.type_strings as usize..];
-          let (_, types_chunk_header) =
-            ChunkHeader::parse(types_chunk).map_err(|e| ParseError::TypeStrings(e.to_string()))?;
-          let type_strings = parser::string_table(types_chunk)
-            .map_err(|e: ParseError| ParseError::TypeStrings(e.to_string()))?;
-
-          let key_chunk =
-            &self.binary_arsc[chunk_start_offset + package_chunk.key_strings as usize..];
-          let (_, key_chunk_header) =
-            ChunkHeader::parse(key_chunk).map_err(|e| ParseError::KeyStrings(e.to_string()))?;
-          let key_strings = parser::string_table(key_chunk)
-            .map_err(|e: ParseError| ParseError::KeyStrings(e.to_string()))?;
-
-          let type_buffer_idx = chunk_start_offset
-            + types_chunk_header.chunk_size as usize
-            + key_chunk_header.chunk_size as usize
-            + package_chunk.header.header_size as usize;
-          let mut type_buffer = &self.binary_arsc[type_buffer_idx..];
-
-          // todo: move to while loop and check for end
-          let mut type_spec = Vec::new();
-          let mut types = Vec::new();
-          loop {
-            let (_, chunk_header) = ChunkHeader::parse(type_buffer)
-              .map_err(|e| ParseError::ChunkHeader(e.to_string()))?;
-            // println!("chunk header: {}", chunk_header);
-            match chunk_header.typ {
-              ChunkType::TABLE_SPEC => {
-                let (_, type_spec_header) = TypeSpecChunkHeader::parse(type_buffer)
-                  .map_err(|e| ParseError::TypeSpecHeader(e.to_string()))?;
-                type_spec.push(type_spec_header);
-              }
-              ChunkType::TABLE_TYPE => {
-                /*
-                 * The TABLE_TYPE chunk is where the actual resource entries are stored.
-                 * Each entry corresponds to a specific resource in the application.
-                 * The code checks if the entry is a complex entry or a simple entry, and parses it accordingly.
-                 * Complex entries hold a set of name/value mappings, while simple entries hold a single value.
-                 * The parsed entries are stored in the ResEntry vector.
-                 */
-                let (type_buffer_next, type_chunk_header) = TypeChunkHeader::parse(type_buffer)
-                  .map_err(|e| ParseError::TypeChunkHeader(e.to_string()))?;
-                // println!("type chunk header: {:?}", type_chunk_header);
-                // The type identifier this chunk refers to.  Type IDs start at 1.
-                if type_chunk_header.id == 0 {
-                  // 0 is invalid
-                  println!("invalid type id: {}", type_chunk_header.id);
-                  continue;
-                }
-
-                let (_, entries) =
-                  parser::take_u32s(type_buffer_next, type_chunk_header.entry_count as usize)
-                    .map_err(|e| ParseError::TypeChunkEntries(e.to_string()))?;
-
-                let map_buffer = &type_buffer[type_chunk_header.entries_start as usize..];
-                // println!("map buffer: {:?}", &map_buffer[..16]);
-
-                let mut res_entries = Vec::new();
-                for entry in entries {
-                  let mut current_entries = Vec::new();
-                  if entry == 0xFFFFFFFF || entry > map_buffer.len() as u32 {
-                    // no value for the resource, push empty entry
-                    res_entries.push(current_entries);
-                    continue;
-                  }
-
-                  let buffer = &map_buffer[entry as usize..];
-                  let (buffer_next, table_entry) = crate::nom_parser::TableEntry::parse(buffer)
-                    .map_err(|e| ParseError::TableEntry(e.to_string()))?;
-                  if table_entry.size == 0 {
-                    res_entries.push(current_entries);
-                    continue;
-                  }
-
-                  // TODO: other flags
-                  if table_entry.flags == TableEntryFlag::COMPLEX {
-                    // If set, this is a complex entry, holding a set of name/value
-                    // mappings.  It is followed by an array of ResTable_map structures.
-                    let (buffer_next, map_entry) = TableMapEntry::parse(buffer_next)
-                      .map_err(|e| ParseError::TableEntry(e.to_string()))?;
-
-                    let (_, entry_maps) =
-                      count(TableMap::parse, map_entry.count as usize)(buffer_next)
-                        .map_err(|e| ParseError::TableEntry(e.to_string()))?;
-
-                    entry_maps.iter().for_each(|entry| {
-                      current_entries.push(entry.value.as_string(&self.strings));
-                    });
-                    res_entries.push(current_entries);
-                  } else {
-                    let mut current_entry = Vec::new();
-                    let (_, value_entry) = ResValue::parse(buffer_next)
-                      .map_err(|e| ParseError::TableEntry(e.to_string()))?;
-                    current_entry.push(value_entry.as_string(&self.strings));
-                    res_entries.push(current_entry);
-                  }
-                }
-                types.push((type_chunk_header.id.into(), res_entries));
-              }
+          let package_chunk = &self.binary_arsc
+            [chunk_start_offset..chunk_start_offset + chunk_header.chunk_size as usize];
+          let mut diagnostics = Vec::new();
+          let package = self.parse_package(package_chunk, &mut diagnostics)?;
+          self.diagnostics.extend(diagnostics);
+          // Packages sharing an id, e.g. feature splits, are merged into one
+          match self.packages.get_mut(&package.id) {
+            Some(existing) => existing.merge(package),
+            None => {
+              self.packages.insert(package.id, package);
+            }
+          }
+        }
+        // Like LoadedArsc, unknown chunks are skipped
+        chunk_type => self
+          .diagnostics
+          .push(Diagnostic::UnknownChunk { chunk_type }),
+      }
+    }
 
-              // TODO: Do we need these?
-              ChunkType::TABLE_LIBRARY => {
-                unimplemented!("Unimplemented table chunk type: {}", chunk_header.typ);
-              }
-              ChunkType::TABLE_OVERLAYABLE => {
-                unimplemented!("Unimplemented table chunk type: {}", chunk_header.typ);
-              }
-              ChunkType::TABLE_STAGED_ALIAS => {
-                unimplemented!("Unimplemented table chunk type: {}", chunk_header.typ);
-              }
-              _ => {
-                println!("Unknown table chunk type: {}", chunk_header.typ);
+    Ok(self.binary_arsc.to_vec())
+  }
+
+  // Headers and offsets of the chunks following each other in `input` from `start`. Chunks
+  // running past the end of the input are cut off.
+  fn chunks(
+    input: &[u8],
+    start: usize,
+  ) -> Result<Vec<(usize, ChunkHeader)>, ParseError> {
+    let mut chunks = Vec::new();
+    let mut chunk_start_offset = start;
+    while chunk_start_offset + 8 <= input.len() {
+      let (_, mut chunk_header) = ChunkHeader::parse(&input[chunk_start_offset..])
+        .map_err(|e| ParseError::ChunkHeader(e.to_string()))?;
+      if chunk_header.chunk_size < 8 {
+        // A chunk can't be smaller than its header, nothing after it can be trusted
+        return Err(ParseError::ChunkHeader(format!(
+          "invalid chunk size {} at offset {}",
+          chunk_header.chunk_size, chunk_start_offset
+        )));
+      }
+      let remaining = (input.len() - chunk_start_offset) as u32;
+      chunk_header.chunk_size = chunk_header.chunk_size.min(remaining);
+      let chunk_size = chunk_header.chunk_size as usize;
+      chunks.push((chunk_start_offset, chunk_header));
+      chunk_start_offset += chunk_size;
+    }
+    Ok(chunks)
+  }
+
+  fn parse_package(
+    &self,
+    package_buffer: &[u8],
+    diagnostics: &mut Vec<Diagnostic>,
+  ) -> Result<Package, ParseError> {
+    let (_, package_chunk) = PackageChunkHeader::parse(package_buffer)
+      .map_err(|e| ParseError::PackageHeader(e.to_string()))?;
+    // println!("package chunk: {:?}", package_chunk);
+
+    //  The typeStrings field specifies the offset from the start of the Package chunk
+    let types_chunk = package_buffer
+      .get(package_chunk.type_strings as usize..)
+      .ok_or_else(|| ParseError::TypeStrings("offset out of bounds".to_string()))?;
+    let mut type_strings = parser::string_table(types_chunk)
+      .map_err(|e: ParseError| ParseError::TypeStrings(e.to_string()))?;
+    // Type strings are stored by type id, so the ids below the offset are left unnamed
+    type_strings.splice(
+      0..0,
+      std::iter::repeat_n(String::new(), package_chunk.type_id_offset as usize),
+    );
+
+    let key_chunk = package_buffer
+      .get(package_chunk.key_strings as usize..)
+      .ok_or_else(|| ParseError::KeyStrings("offset out of bounds".to_string()))?;
+    let key_strings = parser::string_table(key_chunk)
+      .map_err(|e: ParseError| ParseError::KeyStrings(e.to_string()))?;
+
+    let mut package = Package {
+      id: package_chunk.id,
+      name: package_chunk.name,
+      type_strings,
+      key_strings,
+      type_spec: Vec::new(),
+      types: Vec::new(),
+      type_chunks: Vec::new(),
+      libraries: Vec::new(),
+      staged_aliases: Vec::new(),
+    };
+
+    // The type and key string pools are chunks of the package as well and are skipped
+    let chunks = Self::chunks(package_buffer, package_chunk.header.header_size as usize)?;
+    for (chunk_start_offset, chunk_header) in chunks {
+      let type_buffer =
+        &package_buffer[chunk_start_offset..chunk_start_offset + chunk_header.chunk_size as usize];
+      // println!("chunk header: {}", chunk_header);
+      match chunk_header.typ {
+        ChunkType::STRING_POOL => {}
+        ChunkType::TABLE_SPEC => {
+          let (_, (type_spec_header, entry_flags)) = TypeSpecChunkHeader::parse(type_buffer)
+            .map_err(|e| ParseError::TypeSpecHeader(e.to_string()))?;
+          package.add_type_spec(type_spec_header, entry_flags);
+        }
+        ChunkType::TABLE_TYPE => {
+          /*
+           * The TABLE_TYPE chunk is where the actual resource entries are stored.
+           * Each entry corresponds to a specific resource in the application.
+           * The code checks if the entry is a complex entry or a simple entry, and parses it accordingly.
+           * Complex entries hold a set of name/value mappings, while simple entries hold a single value.
+           * The parsed entries are stored in the ResEntry vector.
+           */
+          let (type_buffer_next, type_chunk_header) = TypeChunkHeader::parse(type_buffer)
+            .map_err(|e| ParseError::TypeChunkHeader(e.to_string()))?;
+          // println!("type chunk header: {:?}", type_chunk_header);
+          // The type identifier this chunk refers to.  Type IDs start at 1.
+          if type_chunk_header.id == 0 {
+            // 0 is invalid, LoadedArsc refuses the table
+            return Err(ParseError::TypeChunkHeader("invalid type id 0".to_string()));
+          }
+
+          let entries = Self::entry_offsets(&type_chunk_header, type_buffer_next)?;
+          let map_buffer = type_buffer
+            .get(type_chunk_header.entries_start as usize..)
+            .unwrap_or_default();
+          // println!("map buffer: {:?}", &map_buffer[..16]);
+
+          let mut chunk_entries = Vec::new();
+          for entry in entries {
+            let chunk_entry = match entry {
+              Some(entry) if (entry as usize) < map_buffer.len() => {
+                self.parse_entry(&map_buffer[entry as usize..], &package.key_strings)?
               }
-            }
-            if chunk_header.chunk_size as usize >= type_buffer.len() {
-              // println!("Reached end of type buffer");
-              break;
-            }
-            type_buffer = &type_buffer[chunk_header.chunk_size as usize..];
+              // no value for the resource, push empty entry
+              _ => None,
+            };
+            chunk_entries.push(chunk_entry);
           }
 
-          self.packages.insert(
-            package_chunk.id,
-            Package {
-              name: package_chunk.name,
-              type_strings,
-              key_strings,
-              type_spec,
-              types,
-            },
-          );
+          package.type_chunks.push(TypeChunk {
+            type_id: type_chunk_header.id,
+            config: type_chunk_header.config,
+            entries: chunk_entries,
+          });
         }
-        _ => {
-          println!("Unknown chunk type: {}", chunk_header.typ);
-          println!("Skipping chunk...");
+        ChunkType::TABLE_LIBRARY => {
+          let (_, library_chunk) = LibraryChunk::parse(type_buffer)
+            .map_err(|e| ParseError::TableLibrary(e.to_string()))?;
+          for (package_id, package_name) in library_chunk.entries {
+            package.add_library(SharedLibrary {
+              package_id,
+              package_name,
+            });
+          }
+        }
+        ChunkType::TABLE_STAGED_ALIAS => {
+          let (_, staged_alias_chunk) = StagedAliasChunk::parse(type_buffer)
+            .map_err(|e| ParseError::TableStagedAlias(e.to_string()))?;
+          package
+            .staged_aliases
+            .extend(
+              staged_alias_chunk
+                .entries
+                .into_iter()
+                .map(|(staged_id, finalized_id)| StagedAlias {
+                  staged_id,
+                  finalized_id,
+                }),
+            );
         }
+        // Overlayable policies only matter to runtime resource overlays
+        ChunkType::TABLE_OVERLAYABLE => {}
+        chunk_type => diagnostics.push(Diagnostic::UnknownChunk { chunk_type }),
       }
-      chunk_start_offset += chunk_header.chunk_size as usize;
     }
 
-    Ok(self.binary_arsc.to_vec())
+    package.refresh_types();
+    Ok(package)
+  }
+
+  // Offsets of each entry relative to `entries_start`, indexed by entry id.
+  fn entry_offsets(
+    type_chunk_header: &TypeChunkHeader,
+    input: &[u8],
+  ) -> Result<Vec<Option<u32>>, ParseError> {
+    let entry_count = type_chunk_header.entry_count as usize;
+    if type_chunk_header.flags & TypeChunkFlags::SPARSE != 0 {
+      // Sparse entries are (entry id, offset / 4) pairs of u16
+      let (_, pairs) = parser::take_u16s(input, entry_count * 2)
+        .map_err(|e| ParseError::TypeChunkEntries(e.to_string()))?;
+      let mut offsets = Vec::new();
+      for pair in pairs.chunks_exact(2) {
+        let entry_id = pair[0] as usize;
+        if offsets.len() <= entry_id {
+          offsets.resize(entry_id + 1, None);
+        }
+        offsets[entry_id] = Some(pair[1] as u32 * 4);
+      }
+      Ok(offsets)
+    } else if type_chunk_header.flags & TypeChunkFlags::OFFSET16 != 0 {
+      let (_, offsets) = parser::take_u16s(input, entry_count)
+        .map_err(|e| ParseError::TypeChunkEntries(e.to_string()))?;
+      Ok(
+        offsets
+          .into_iter()
+          .map(|offset| (offset != 0xFFFF).then_some(offset as u32 * 4))
+          .collect(),
+      )
+    } else {
+      let (_, offsets) = parser::take_u32s(input, entry_count)
+        .map_err(|e| ParseError::TypeChunkEntries(e.to_string()))?;
+      Ok(
+        offsets
+          .into_iter()
+          .map(|offset| (offset != 0xFFFFFFFF).then_some(offset))
+          .collect(),
+      )
+    }
+  }
+
+  /*
+   * The code checks if the entry is a complex entry or a simple entry, and parses it accordingly.
+   * Complex entries hold a set of name/value mappings, while simple entries hold a single value.
+   * Compact entries store the key index in the size field and the value type in the flags.
+   */
+  fn parse_entry(
+    &self,
+    buffer: &[u8],
+    key_strings: &[String],
+  ) -> Result<Option<TypeEntry>, ParseError> {
+    let (buffer_next, table_entry) = crate::nom_parser::TableEntry::parse(buffer)
+      .map_err(|e| ParseError::TableEntry(e.to_string()))?;
+
+    let (key_index, value) = if table_entry.flags & TableEntryFlag::COMPACT != 0 {
+      let value = ResValue {
+        size: 8,
+        res0: 0,
+        data_type: (table_entry.flags >> 8) as u8,
+        data: table_entry.string_index,
+      };
+      (
+        table_entry.size as u32,
+        EntryValue::Simple(Value::from_res_value(&value, &self.strings, &self.styles)),
+      )
+    } else if table_entry.size == 0 {
+      return Ok(None);
+    } else if table_entry.flags & TableEntryFlag::COMPLEX != 0 {
+      // PUBLIC and WEAK may be set alongside COMPLEX, so only test the COMPLEX bit.
+      // If set, this is a complex entry, holding a set of name/value
+      // mappings.  It is followed by an array of ResTable_map structures.
+      let (buffer_next, map_entry) =
+        TableMapEntry::parse(buffer_next).map_err(|e| ParseError::TableEntry(e.to_string()))?;
+
+      let (_, entry_maps) = count(TableMap::parse, map_entry.count as usize)(buffer_next)
+        .map_err(|e| ParseError::TableEntry(e.to_string()))?;
+
+      let items = entry_maps
+        .iter()
+        .map(|entry| {
+          (
+            entry.name,
+            Value::from_res_value(&entry.value, &self.strings, &self.styles),
+          )
+        })
+        .collect();
+      (
+        table_entry.string_index,
+        EntryValue::Complex {
+          parent: map_entry.parent,
+          items,
+        },
+      )
+    } else {
+      let (_, value_entry) =
+        ResValue::parse(buffer_next).map_err(|e| ParseError::TableEntry(e.to_string()))?;
+      (
+        table_entry.string_index,
+        EntryValue::Simple(Value::from_res_value(
+          &value_entry,
+          &self.strings,
+          &self.styles,
+        )),
+      )
+    };
+
+    Ok(Some(TypeEntry {
+      key: key_strings
+        .get(key_index as usize)
+        .cloned()
+        .unwrap_or_default(),
+      flags: table_entry.flags,
+      value,
+    }))
+  }
+
+  /// Chunks that were skipped while parsing the table.
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    &self.diagnostics
+  }
+
+  /// Global value string pool of the table.
+  pub fn strings(&self) -> &[String] {
+    &self.strings
+  }
+
+  /// Number of packages declared by the table header. Packages sharing an id are merged, so
+  /// this can differ from the number of packages in `packages()`.
+  pub fn package_count(&self) -> u32 {
+    self.package_count
+  }
+
+  /// Package ids of the table in ascending order.
+  pub fn package_ids(&self) -> Vec<u32> {
+    let mut package_ids = self.packages.keys().copied().collect::<Vec<_>>();
+    package_ids.sort_unstable();
+    package_ids
+  }
+
+  pub fn package(
+    &self,
+    package_id: u32,
+  ) -> Option<&Package> {
+    self.packages.get(&package_id)
+  }
+
+  pub fn package_by_name(
+    &self,
+    name: &str,
+  ) -> Option<&Package> {
+    self.packages.values().find(|package| package.name == name)
+  }
+
+  /// Merges the packages of another table, e.g. the table of a split APK, into this one.
+  pub fn merge(
+    &mut self,
+    other: &Arsc,
+  ) {
+    for package_id in other.package_ids() {
+      let package = other.packages[&package_id].clone();
+      match self.packages.get_mut(&package_id) {
+        Some(existing) => existing.merge(package),
+        None => {
+          self.packages.insert(package_id, package);
+        }
+      }
+    }
+  }
+
+  /// Packages of the table, keyed by package id.
+  pub fn packages(&self) -> &HashMap<u32, Package> {
+    &self.packages
+  }
+
+  /// Mutable access to the packages, for tests building tables the edit operations can't.
+  #[cfg(test)]
+  pub(crate) fn packages_mut(&mut self) -> &mut HashMap<u32, Package> {
+    &mut self.packages
+  }
+
+  pub fn resource_name(
+    &self,
+    res_id: u32,
+  ) -> Option<ResourceName> {
+    let package = self.packages.get(&(res_id >> 24))?;
+    let type_id = ((res_id >> 16) & 0xFF) as u8;
+    Some(ResourceName {
+      package: package.name.clone(),
+      type_name: package.type_name(type_id)?.to_string(),
+      name: package
+        .entry_name(type_id, (res_id & 0xFFFF) as usize)?
+        .to_string(),
+    })
   }
 
   pub fn get_res_value(
@@ -249,20 +756,461 @@ impl<'barsc> Arsc<'barsc> {
         .next()
         .cloned();
 
-      // if first_element == None {
-      //     println!("entry: {:?}", entry);
-      // }
-
-      first_element
+      first_element.map(|value| self.resolve_dynamic_value(Some(package_id), value))
     } else {
       None
     }
   }
+
+  /// The runtime id of a DYNAMIC_REFERENCE to `res_id` made from the package `referrer`. Like
+  /// `DynamicRefTable::lookupResourceId`, the package id the reference was compiled against is
+  /// mapped through the shared libraries of the referrer, 0x00 being the referrer itself.
+  pub fn dynamic_reference_id(
+    &self,
+    referrer: u32,
+    res_id: u32,
+  ) -> Option<u32> {
+    let build_id = res_id >> 24;
+    let runtime_id = match build_id {
+      // The framework and app package ids are absolute
+      0x01 | 0x7f => build_id,
+      0x00 => referrer,
+      _ => {
+        let library = self
+          .packages
+          .get(&referrer)?
+          .libraries
+          .iter()
+          .find(|library| library.package_id == build_id)?;
+        self.package_by_name(&library.package_name)?.id
+      }
+    };
+    Some(runtime_id << 24 | res_id & 0x00ffffff)
+  }
+
+  // Turns a `@dyn/0x...` value of the package `referrer`, the main package of the table when
+  // none, into the `@res/0x...` reference it resolves to.
+  pub(crate) fn resolve_dynamic_value(
+    &self,
+    referrer: Option<u32>,
+    value: String,
+  ) -> String {
+    let referrer = referrer.or_else(|| {
+      let package_ids = self.package_ids();
+      package_ids
+        .iter()
+        .copied()
+        .find(|package_id| *package_id != 0x01)
+        .or(package_ids.first().copied())
+    });
+    let res_id = value
+      .strip_prefix("@dyn/0x")
+      .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+      .zip(referrer)
+      .and_then(|(res_id, referrer)| self.dynamic_reference_id(referrer, res_id));
+    match res_id {
+      Some(res_id) => format!("@res/0x{:x}", res_id),
+      None => value,
+    }
+  }
+
+  /// Lists every entry declared by the TABLE_SPEC chunks of all packages, together with its
+  /// spec flags.
+  pub fn spec_entries(&self) -> Vec<SpecEntry> {
+    self
+      .package_ids()
+      .into_iter()
+      .flat_map(|package_id| self.packages[&package_id].spec_entries())
+      .collect()
+  }
+
+  /// Resources flagged with SPEC_PUBLIC, which other packages are allowed to reference.
+  pub fn public_resources(&self) -> Vec<SpecEntry> {
+    self
+      .spec_entries()
+      .into_iter()
+      .filter(SpecEntry::is_public)
+      .collect()
+  }
+
+  /// Resources flagged with SPEC_STAGED_API, public but without a finalized id yet.
+  pub fn staged_public_resources(&self) -> Vec<SpecEntry> {
+    self
+      .spec_entries()
+      .into_iter()
+      .filter(SpecEntry::is_staged_api)
+      .collect()
+  }
+
+  /// Builds an aapt compatible `public.xml` assigning the current id to every named entry,
+  /// so the resources can be rebuilt against the same id layout.
+  pub fn public_xml(&self) -> Result<Vec<u8>, ParseError> {
+    let mut xml_writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 4);
+    let decl = BytesDecl::new("1.0", Some("utf-8"), None);
+    xml_writer
+      .write_event(Event::Decl(decl))
+      .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+    xml_writer
+      .write_event(Event::Start(BytesStart::new("resources")))
+      .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+
+    let mut spec_entries = self.spec_entries();
+    spec_entries.sort_by_key(|spec_entry| spec_entry.id);
+    for spec_entry in spec_entries {
+      let mut public_elem = BytesStart::new("public");
+      public_elem.push_attribute(("type", spec_entry.type_name.as_str()));
+      public_elem.push_attribute(("name", spec_entry.name.as_str()));
+      public_elem.push_attribute(("id", format!("0x{:08x}", spec_entry.id).as_str()));
+      xml_writer
+        .write_event(Event::Empty(public_elem))
+        .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+    }
+
+    xml_writer
+      .write_event(Event::End(BytesEnd::new("resources")))
+      .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+
+    Ok(xml_writer.into_inner().into_inner())
+  }
+
+  /// Every value defined for a resource, one per configuration, in file order.
+  pub fn entry_values(
+    &self,
+    res_id: u32,
+  ) -> Vec<(ResConfig, &TypeEntry)> {
+    let Some(package) = self.packages.get(&(res_id >> 24)) else {
+      return Vec::new();
+    };
+    let type_id = ((res_id >> 16) & 0xFF) as u8;
+    package
+      .type_chunks
+      .iter()
+      .filter(|type_chunk| type_chunk.type_id == type_id)
+      .filter_map(|type_chunk| {
+        let entry = type_chunk
+          .entries
+          .get((res_id & 0xFFFF) as usize)?
+          .as_ref()?;
+        Some((type_chunk.res_config(), entry))
+      })
+      .collect()
+  }
+
+  /// Replaces the value of a resource in an existing configuration.
+  ///
+  /// The global string pool is rebuilt by the writer, `strings()` keeps returning the pool of
+  /// the parsed table.
+  pub fn set_value(
+    &mut self,
+    res_id: u32,
+    config: &ResConfig,
+    value: EntryValue,
+  ) -> Result<(), ParseError> {
+    let (package, type_id, entry_id) = self.locate_mut(res_id)?;
+    let entry = package
+      .type_chunks
+      .iter_mut()
+      .filter(|type_chunk| type_chunk.type_id == type_id && type_chunk.res_config() == *config)
+      .find_map(|type_chunk| type_chunk.entries.get_mut(entry_id)?.as_mut())
+      .ok_or_else(|| ParseError::ResourceNotFound(format!("0x{:08x} ({})", res_id, config)))?;
+    entry.value = value;
+    package.refresh_types();
+    Ok(())
+  }
+
+  /// Changes the default value of a string resource.
+  pub fn set_string(
+    &mut self,
+    res_id: u32,
+    value: &str,
+  ) -> Result<(), ParseError> {
+    self.set_value(
+      res_id,
+      &ResConfig::default(),
+      EntryValue::Simple(Value::String(value.to_string())),
+    )
+  }
+
+  /// Adds or replaces the value of a string resource for a locale such as `de`, `pt-rBR` or
+  /// `b+tlh+QO`, creating the TABLE_TYPE chunk of that locale if needed.
+  pub fn add_locale_variant(
+    &mut self,
+    res_id: u32,
+    locale: &str,
+    value: &str,
+  ) -> Result<(), ParseError> {
+    let (language, country) = res_config::pack_locale(locale)
+      .ok_or_else(|| ParseError::InvalidLocale(locale.to_string()))?;
+    let (package, type_id, entry_id) = self.locate_mut(res_id)?;
+    let key = package
+      .entry_name(type_id, entry_id)
+      .ok_or_else(|| ParseError::ResourceNotFound(format!("0x{:08x}", res_id)))?
+      .to_string();
+
+    let last_chunk = package
+      .type_chunks
+      .iter()
+      .rposition(|type_chunk| type_chunk.type_id == type_id)
+      .ok_or_else(|| ParseError::ResourceNotFound(format!("0x{:08x}", res_id)))?;
+    // The default config with only the locale set, whatever the size of the type's configs
+    let res_config = ResConfig {
+      language,
+      country,
+      ..ResConfig::default()
+    };
+    let config = res_config.to_config();
+
+    let chunk_index =
+      match package.type_chunks.iter().position(|type_chunk| {
+        type_chunk.type_id == type_id && type_chunk.res_config() == res_config
+      }) {
+        Some(chunk_index) => chunk_index,
+        None => {
+          package.type_chunks.insert(
+            last_chunk + 1,
+            TypeChunk {
+              type_id,
+              config,
+              entries: Vec::new(),
+            },
+          );
+          last_chunk + 1
+        }
+      };
+    let type_chunk = &mut package.type_chunks[chunk_index];
+    if type_chunk.entries.len() <= entry_id {
+      type_chunk.entries.resize(entry_id + 1, None);
+    }
+    let flags = type_chunk
+      .entries
+      .iter()
+      .flatten()
+      .next()
+      .map_or(0, |entry| entry.flags & TableEntryFlag::PUBLIC);
+    type_chunk.entries[entry_id] = Some(TypeEntry {
+      key,
+      flags,
+      value: EntryValue::Simple(Value::String(value.to_string())),
+    });
+
+    // The entry now varies by locale
+    if let Some((_, entry_flags)) = package
+      .type_spec
+      .iter_mut()
+      .find(|(type_spec_header, _)| type_spec_header.type_id == type_id)
+    {
+      if entry_flags.len() <= entry_id {
+        entry_flags.resize(entry_id + 1, 0);
+      }
+      entry_flags[entry_id] |= CONFIG_LOCALE;
+    }
+    package.refresh_types();
+    Ok(())
+  }
+
+  /// Removes a resource from every configuration. Its id stays reserved.
+  pub fn remove_entry(
+    &mut self,
+    res_id: u32,
+  ) -> Result<(), ParseError> {
+    let (package, type_id, entry_id) = self.locate_mut(res_id)?;
+    let mut removed = false;
+    for type_chunk in package
+      .type_chunks
+      .iter_mut()
+      .filter(|type_chunk| type_chunk.type_id == type_id)
+    {
+      if let Some(entry) = type_chunk.entries.get_mut(entry_id) {
+        removed |= entry.take().is_some();
+      }
+    }
+    if !removed {
+      return Err(ParseError::ResourceNotFound(format!("0x{:08x}", res_id)));
+    }
+    package.refresh_types();
+    Ok(())
+  }
+
+  fn locate_mut(
+    &mut self,
+    res_id: u32,
+  ) -> Result<(&mut Package, u8, usize), ParseError> {
+    let package = self
+      .packages
+      .get_mut(&(res_id >> 24))
+      .ok_or_else(|| ParseError::ResourceNotFound(format!("0x{:08x}", res_id)))?;
+    Ok((
+      package,
+      ((res_id >> 16) & 0xFF) as u8,
+      (res_id & 0xFFFF) as usize,
+    ))
+  }
+}
+
+impl Package {
+  /// Shared libraries are built with package id 0x00, which is assigned when loading.
+  pub fn is_shared_library(&self) -> bool {
+    self.id == 0
+  }
+
+  /// Lists every entry declared by the TABLE_SPEC chunks, together with its spec flags.
+  /// Entries are named after the first configuration that defines them.
+  pub fn spec_entries(&self) -> Vec<SpecEntry> {
+    let mut spec_entries = Vec::new();
+    for (type_spec_header, entry_flags) in &self.type_spec {
+      let type_id = type_spec_header.type_id;
+      let Some(type_name) = self.type_name(type_id) else {
+        continue;
+      };
+      for (entry_id, flags) in entry_flags.iter().enumerate() {
+        let Some(name) = self.entry_name(type_id, entry_id) else {
+          continue;
+        };
+        spec_entries.push(SpecEntry {
+          id: (self.id << 24) | ((type_id as u32) << 16) | entry_id as u32,
+          type_name: type_name.to_string(),
+          name: name.to_string(),
+          flags: *flags,
+        });
+      }
+    }
+    spec_entries
+  }
+
+  /// Resources of this package flagged with SPEC_PUBLIC.
+  pub fn public_resources(&self) -> Vec<SpecEntry> {
+    self
+      .spec_entries()
+      .into_iter()
+      .filter(SpecEntry::is_public)
+      .collect()
+  }
+
+  /// Resource id of an entry of this package by type and name, in any configuration.
+  pub fn resource_id(
+    &self,
+    type_name: &str,
+    name: &str,
+  ) -> Option<u32> {
+    self
+      .type_chunks
+      .iter()
+      .filter(|type_chunk| self.type_name(type_chunk.type_id) == Some(type_name))
+      .find_map(|type_chunk| {
+        let entry_id = type_chunk
+          .entries
+          .iter()
+          .position(|entry| entry.as_ref().is_some_and(|entry| entry.key == name))?;
+        Some((self.id << 24) | ((type_chunk.type_id as u32) << 16) | entry_id as u32)
+      })
+  }
+
+  /// Merges another package with the same id. Types defined by both are combined: their spec
+  /// flags are joined and the type chunks of `other` are added after the existing ones.
+  pub fn merge(
+    &mut self,
+    other: Package,
+  ) {
+    for (index, type_name) in other.type_strings.into_iter().enumerate() {
+      match self.type_strings.get_mut(index) {
+        Some(existing) if existing.is_empty() => *existing = type_name,
+        Some(_) => {}
+        None => self.type_strings.push(type_name),
+      }
+    }
+    for key in other.key_strings {
+      if !self.key_strings.contains(&key) {
+        self.key_strings.push(key);
+      }
+    }
+    for (type_spec_header, entry_flags) in other.type_spec {
+      self.add_type_spec(type_spec_header, entry_flags);
+    }
+    self.type_chunks.extend(other.type_chunks);
+    for library in other.libraries {
+      self.add_library(library);
+    }
+    for staged_alias in other.staged_aliases {
+      if !self.staged_aliases.contains(&staged_alias) {
+        self.staged_aliases.push(staged_alias);
+      }
+    }
+    self.refresh_types();
+  }
+
+  // A type can be declared by several TABLE_SPEC chunks, their flags are combined
+  fn add_type_spec(
+    &mut self,
+    type_spec_header: TypeSpecChunkHeader,
+    entry_flags: Vec<u32>,
+  ) {
+    match self
+      .type_spec
+      .iter_mut()
+      .find(|(existing, _)| existing.type_id == type_spec_header.type_id)
+    {
+      Some((_, existing_flags)) => {
+        if existing_flags.len() < entry_flags.len() {
+          existing_flags.resize(entry_flags.len(), 0);
+        }
+        for (existing, flags) in existing_flags.iter_mut().zip(entry_flags) {
+          *existing |= flags;
+        }
+      }
+      None => self.type_spec.push((type_spec_header, entry_flags)),
+    }
+  }
+
+  fn add_library(
+    &mut self,
+    library: SharedLibrary,
+  ) {
+    if !self.libraries.contains(&library) {
+      self.libraries.push(library);
+    }
+  }
+
+  // Keeps the string view in `types` in sync with edited type chunks
+  fn refresh_types(&mut self) {
+    self.types = self
+      .type_chunks
+      .iter()
+      .map(|type_chunk| (type_chunk.type_id.into(), type_chunk.res_entries()))
+      .collect();
+  }
+
+  /// Type ids start at 1 and name the type string at `type_id - 1`.
+  pub fn type_name(
+    &self,
+    type_id: u8,
+  ) -> Option<&str> {
+    self
+      .type_strings
+      .get((type_id as usize).checked_sub(1)?)
+      .filter(|type_name| !type_name.is_empty())
+      .map(String::as_str)
+  }
+
+  /// Name of an entry, taken from the first configuration that defines it.
+  pub fn entry_name(
+    &self,
+    type_id: u8,
+    entry_id: usize,
+  ) -> Option<&str> {
+    self
+      .type_chunks
+      .iter()
+      .filter(|type_chunk| type_chunk.type_id == type_id)
+      .find_map(|type_chunk| type_chunk.entries.get(entry_id)?.as_ref())
+      .map(|entry| entry.key.as_str())
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::nom_parser::ResType;
+  use crate::xml_parser::resolve_references;
   use anyhow::{Context, Result};
 
   #[test]
@@ -305,13 +1253,177 @@ mod tests {
     Ok(())
   }
 
+  #[test]
+  fn test_public_resources() -> Result<()> {
+    let arsc_path = std::path::Path::new(
+      "../data/arsc/62edc01a5ea294a88439822e66f43b23c39f3a5cb93e60a221ae87d22226fb58.arsc",
+    );
+    let arsc_bytes: Vec<u8> = std::fs::read(arsc_path)?;
+    let mut parser = Arsc::new(arsc_bytes.as_slice());
+    parser
+      .parse()
+      .context(format!("Failed to parse arsc: {}", arsc_path.display()))?;
+
+    let spec_entries = parser.spec_entries();
+    let public_resources = parser.public_resources();
+    assert!(!public_resources.is_empty());
+    assert!(public_resources.len() < spec_entries.len());
+    assert!(parser.staged_public_resources().is_empty());
+
+    let public_xml = String::from_utf8(parser.public_xml()?)?;
+    let first = &spec_entries[0];
+    assert!(public_xml.contains(&format!(
+      "<public type=\"{}\" name=\"{}\" id=\"0x{:08x}\"/>",
+      first.type_name, first.name, first.id
+    )));
+    assert_eq!(public_xml.matches("<public ").count(), spec_entries.len());
+    Ok(())
+  }
+
+  #[test]
+  fn test_multi_package() -> Result<()> {
+    let arsc_path = std::path::Path::new(
+      "../data/arsc/ab4cb5175fc0827860b9c3361d8bf2aec99a71f63b5a7666ae2403b0030ac56f.arsc",
+    );
+    let arsc_bytes: Vec<u8> = std::fs::read(arsc_path)?;
+    let mut parser = Arsc::new(arsc_bytes.as_slice());
+    parser.parse()?;
+    let app = parser.package(0x7f).context("app package missing")?.clone();
+    assert_eq!(parser.package_count(), 1);
+
+    // A feature package with its own id, referencing a shared library
+    let mut feature = app.clone();
+    feature.id = 0x80;
+    feature.name = "com.example.feature".to_string();
+    feature.libraries.push(SharedLibrary {
+      package_id: 0x02,
+      package_name: "com.example.lib".to_string(),
+    });
+    feature.staged_aliases.push(StagedAlias {
+      staged_id: 0x01ff0000,
+      finalized_id: 0x01010000,
+    });
+    let mut two_packages = parser.clone();
+    two_packages.packages_mut().insert(0x80, feature.clone());
+
+    let written = crate::arsc_writer::ArscWriter::new(&two_packages).write()?;
+    let mut reparsed = Arsc::new(written.as_slice());
+    reparsed.parse()?;
+    assert_eq!(reparsed.package_count(), 2);
+    assert_eq!(reparsed.package_ids(), vec![0x7f, 0x80]);
+    let reparsed_feature = reparsed
+      .package_by_name("com.example.feature")
+      .context("feature package missing")?;
+    assert_eq!(reparsed_feature.libraries, feature.libraries);
+    assert_eq!(reparsed_feature.staged_aliases, feature.staged_aliases);
+    assert_eq!(
+      reparsed_feature.spec_entries().len(),
+      app.spec_entries().len()
+    );
+    assert_eq!(reparsed.spec_entries().len(), 2 * app.spec_entries().len());
+    let first = &app.spec_entries()[0];
+    assert_eq!(
+      reparsed_feature.resource_id(&first.type_name, &first.name),
+      Some(first.id & 0x00ffffff | 0x80000000)
+    );
+
+    // Two package chunks sharing an id, like instant app feature splits, are merged
+    let (package_offset, _) = Arsc::chunks(&written, 12)?
+      .into_iter()
+      .filter(|(_, chunk_header)| chunk_header.typ == ChunkType::TABLE_PACKAGE)
+      .nth(1)
+      .context("second package missing")?;
+    let mut shared_id = written.clone();
+    shared_id[package_offset + 8..package_offset + 12].copy_from_slice(&0x7fu32.to_le_bytes());
+    let mut merged = Arsc::new(shared_id.as_slice());
+    merged.parse()?;
+    assert_eq!(merged.package_count(), 2);
+    assert_eq!(merged.package_ids(), vec![0x7f]);
+    let merged_package = merged.package(0x7f).context("merged package missing")?;
+    assert_eq!(merged_package.type_chunks.len(), 2 * app.type_chunks.len());
+    assert_eq!(
+      merged_package.spec_entries().len(),
+      app.spec_entries().len()
+    );
+    assert_eq!(merged_package.libraries, feature.libraries);
+    assert!(merged.diagnostics().is_empty());
+
+    // Unknown chunks are skipped, chunks smaller than their header end the table
+    let mut unknown_chunk = written.clone();
+    unknown_chunk.extend_from_slice(&[0x99, 0x02, 8, 0, 8, 0, 0, 0]);
+    let mut unknown = Arsc::new(unknown_chunk.as_slice());
+    unknown.parse()?;
+    assert_eq!(
+      unknown.diagnostics(),
+      [Diagnostic::UnknownChunk { chunk_type: 0x0299 }]
+    );
+    let mut invalid_size = written.clone();
+    invalid_size.extend_from_slice(&[0x99, 0x02, 8, 0, 4, 0, 0, 0]);
+    assert!(matches!(
+      Arsc::new(invalid_size.as_slice()).parse(),
+      Err(ParseError::ChunkHeader(_))
+    ));
+
+    // A string of the feature referencing the library it was compiled against as package 0x02,
+    // which is loaded as 0x03
+    let string = app
+      .spec_entries()
+      .into_iter()
+      .find(|spec_entry| spec_entry.type_name == "string")
+      .context("no string")?
+      .id
+      & 0x00ffffff;
+    let mut library = app.clone();
+    library.id = 0x03;
+    library.name = "com.example.lib".to_string();
+    let mut referencing = feature.clone();
+    for type_chunk in &mut referencing.type_chunks {
+      let entry = type_chunk
+        .entries
+        .get_mut((string & 0xffff) as usize)
+        .and_then(Option::as_mut);
+      if let (true, Some(entry)) = (type_chunk.type_id as u32 == string >> 16, entry) {
+        entry.value = EntryValue::Simple(Value::Data {
+          data_type: ResType::DYNAMIC_REFERENCE,
+          data: 0x02000000 | string,
+        });
+      }
+    }
+    let mut with_library = parser.clone();
+    with_library.packages_mut().insert(0x80, referencing);
+    with_library.packages_mut().insert(0x03, library);
+    let written = crate::arsc_writer::ArscWriter::new(&with_library).write()?;
+    let mut reparsed = Arsc::new(written.as_slice());
+    reparsed.parse()?;
+    assert_eq!(
+      reparsed.get_res_value(0x80000000 | string),
+      Some(format!("@res/0x{:x}", 0x03000000 | string))
+    );
+    assert_eq!(
+      resolve_references(
+        Some(format!("@res/0x{:x}", 0x80000000 | string)),
+        Some(&reparsed)
+      ),
+      reparsed.get_res_value(0x03000000 | string)
+    );
+    assert_eq!(
+      reparsed.dynamic_reference_id(0x80, string),
+      Some(0x80000000 | string)
+    );
+    assert_eq!(
+      reparsed.dynamic_reference_id(0x80, 0x05000000 | string),
+      None
+    );
+    Ok(())
+  }
+
   #[test]
   fn test_arsc_parser_all() -> Result<()> {
     let dir_path = std::path::Path::new("../data/arsc");
     let files = dir_path.read_dir()?;
     for file in files {
       let file = file?.path().canonicalize()?;
-      if file.extension().map_or(false, |ext| ext != "arsc") {
+      if file.extension().is_some_and(|ext| ext != "arsc") {
         continue;
       }
       println!("\n---------- TESTING {:?} ----------", file);
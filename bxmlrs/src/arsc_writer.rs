@@ -0,0 +1,642 @@
+use crate::arsc_parser::{Arsc, EntryValue, Package, StringSpan, TypeChunk, Value};
+use crate::nom_parser::{ChunkType, ParseError, ResType, StringPoolSpan, TableEntryFlag};
+use std::collections::HashMap;
+
+// Sizes of the fixed chunk headers we write.
+const TABLE_HEADER_SIZE: u16 = 12;
+const STRING_POOL_HEADER_SIZE: u16 = 28;
+const PACKAGE_HEADER_SIZE: u16 = 288;
+const TYPE_SPEC_HEADER_SIZE: u16 = 16;
+// Type chunk header without the trailing config structure.
+const TYPE_HEADER_SIZE: u16 = 20;
+const LIBRARY_HEADER_SIZE: u16 = 12;
+const STAGED_ALIAS_HEADER_SIZE: u16 = 12;
+
+const STRING_POOL_UTF8_FLAG: u32 = 0x100;
+// UTF-8 pools store lengths in at most 15 bits.
+const MAX_UTF8_LENGTH: usize = 0x7fff;
+
+/// Serializes a resource table back into the binary `resources.arsc` format.
+///
+/// String pools are rebuilt from the values in the table, so the output is equivalent to the
+/// parsed input but usually not byte identical: strings are deduplicated, sparse and compact
+/// encodings are expanded, and unused strings are dropped.
+pub struct ArscWriter<'a, 'barsc> {
+  arsc: &'a Arsc<'barsc>,
+}
+
+impl<'a, 'barsc> ArscWriter<'a, 'barsc> {
+  pub fn new(arsc: &'a Arsc<'barsc>) -> Self {
+    Self { arsc }
+  }
+
+  pub fn write(&self) -> Result<Vec<u8>, ParseError> {
+    let mut package_ids = self.arsc.packages().keys().copied().collect::<Vec<_>>();
+    package_ids.sort_unstable();
+    let packages = package_ids
+      .iter()
+      .map(|package_id| (*package_id, &self.arsc.packages()[package_id]))
+      .collect::<Vec<_>>();
+
+    let value_strings = ValueStrings::collect(packages.iter().map(|(_, package)| *package));
+
+    let mut out = Vec::new();
+    let table_start = begin_chunk(&mut out, ChunkType::TABLE, TABLE_HEADER_SIZE);
+    push_u32(&mut out, packages.len() as u32);
+    write_string_pool(&mut out, &value_strings.strings, &value_strings.styles)?;
+    for (package_id, package) in packages {
+      self.write_package(&mut out, package_id, package, &value_strings)?;
+    }
+    end_chunk(&mut out, table_start);
+
+    Ok(out)
+  }
+
+  fn write_package(
+    &self,
+    out: &mut Vec<u8>,
+    package_id: u32,
+    package: &Package,
+    value_strings: &ValueStrings,
+  ) -> Result<(), ParseError> {
+    // Keep the original key order so unchanged entries keep their key index
+    let mut key_strings = package.key_strings.clone();
+    let mut key_indices: HashMap<String, u32> = HashMap::new();
+    for (index, key) in key_strings.iter().enumerate() {
+      key_indices.entry(key.clone()).or_insert(index as u32);
+    }
+    for entry in package
+      .type_chunks
+      .iter()
+      .flat_map(|type_chunk| type_chunk.entries.iter().flatten())
+    {
+      if !key_indices.contains_key(&entry.key) {
+        key_indices.insert(entry.key.clone(), key_strings.len() as u32);
+        key_strings.push(entry.key.clone());
+      }
+    }
+
+    let package_start = begin_chunk(out, ChunkType::TABLE_PACKAGE, PACKAGE_HEADER_SIZE);
+    push_u32(out, package_id);
+    push_utf16_name(out, &package.name);
+
+    let mut type_pool = Vec::new();
+    write_string_pool(&mut type_pool, &package.type_strings, &[])?;
+    push_u32(out, PACKAGE_HEADER_SIZE as u32);
+    push_u32(out, package.type_strings.len() as u32);
+    push_u32(out, PACKAGE_HEADER_SIZE as u32 + type_pool.len() as u32);
+    push_u32(out, key_strings.len() as u32);
+    // typeIdOffset
+    push_u32(out, 0);
+    out.extend_from_slice(&type_pool);
+    write_string_pool(out, &key_strings, &[])?;
+
+    // Each TABLE_SPEC chunk is followed by the TABLE_TYPE chunks of the same type
+    let mut written_types = Vec::new();
+    for (type_spec_header, entry_flags) in &package.type_spec {
+      let type_id = type_spec_header.type_id;
+      if written_types.contains(&type_id) {
+        continue;
+      }
+      written_types.push(type_id);
+
+      let type_chunks = package
+        .type_chunks
+        .iter()
+        .filter(|type_chunk| type_chunk.type_id == type_id)
+        .collect::<Vec<_>>();
+      let mut entry_flags = entry_flags.clone();
+      let entry_count = type_chunks
+        .iter()
+        .map(|type_chunk| type_chunk.entries.len())
+        .max()
+        .unwrap_or(0);
+      if entry_flags.len() < entry_count {
+        entry_flags.resize(entry_count, 0);
+      }
+
+      let spec_start = begin_chunk(out, ChunkType::TABLE_SPEC, TYPE_SPEC_HEADER_SIZE);
+      out.push(type_id);
+      out.push(0);
+      push_u16(out, type_chunks.len() as u16);
+      push_u32(out, entry_flags.len() as u32);
+      entry_flags.iter().for_each(|flags| push_u32(out, *flags));
+      end_chunk(out, spec_start);
+
+      for type_chunk in type_chunks {
+        write_type_chunk(out, type_chunk, &key_indices, value_strings)?;
+      }
+    }
+    // Types without a TABLE_SPEC chunk are kept as well
+    for type_chunk in &package.type_chunks {
+      if !written_types.contains(&type_chunk.type_id) {
+        write_type_chunk(out, type_chunk, &key_indices, value_strings)?;
+      }
+    }
+
+    if !package.libraries.is_empty() {
+      let library_start = begin_chunk(out, ChunkType::TABLE_LIBRARY, LIBRARY_HEADER_SIZE);
+      push_u32(out, package.libraries.len() as u32);
+      for library in &package.libraries {
+        push_u32(out, library.package_id);
+        push_utf16_name(out, &library.package_name);
+      }
+      end_chunk(out, library_start);
+    }
+    if !package.staged_aliases.is_empty() {
+      let alias_start = begin_chunk(out, ChunkType::TABLE_STAGED_ALIAS, STAGED_ALIAS_HEADER_SIZE);
+      push_u32(out, package.staged_aliases.len() as u32);
+      for staged_alias in &package.staged_aliases {
+        push_u32(out, staged_alias.staged_id);
+        push_u32(out, staged_alias.finalized_id);
+      }
+      end_chunk(out, alias_start);
+    }
+
+    end_chunk(out, package_start);
+    Ok(())
+  }
+}
+
+fn write_type_chunk(
+  out: &mut Vec<u8>,
+  type_chunk: &TypeChunk,
+  key_indices: &HashMap<String, u32>,
+  value_strings: &ValueStrings,
+) -> Result<(), ParseError> {
+  let config_size = type_chunk.config.data.len() as u32 + 4;
+  let header_size = TYPE_HEADER_SIZE + config_size as u16;
+  let entry_count = type_chunk.entries.len() as u32;
+
+  let mut offsets = Vec::new();
+  let mut entries = Vec::new();
+  for entry in &type_chunk.entries {
+    let Some(entry) = entry else {
+      offsets.push(0xFFFFFFFF);
+      continue;
+    };
+    offsets.push(entries.len() as u32);
+    let key = key_indices[&entry.key];
+    // Compact entries are always written in the regular layout, whose flags don't hold the
+    // data type compact ones keep in their high byte
+    let flags = entry.flags & (TableEntryFlag::PUBLIC | TableEntryFlag::WEAK);
+    match &entry.value {
+      EntryValue::Simple(value) => {
+        push_u16(&mut entries, 8);
+        push_u16(&mut entries, flags & !TableEntryFlag::COMPLEX);
+        push_u32(&mut entries, key);
+        push_value(&mut entries, value, value_strings)?;
+      }
+      EntryValue::Complex { parent, items } => {
+        push_u16(&mut entries, 16);
+        push_u16(&mut entries, flags | TableEntryFlag::COMPLEX);
+        push_u32(&mut entries, key);
+        push_u32(&mut entries, *parent);
+        push_u32(&mut entries, items.len() as u32);
+        for (name, value) in items {
+          push_u32(&mut entries, *name);
+          push_value(&mut entries, value, value_strings)?;
+        }
+      }
+    }
+  }
+
+  let chunk_start = begin_chunk(out, ChunkType::TABLE_TYPE, header_size);
+  out.push(type_chunk.type_id);
+  // flags: neither sparse nor 16 bit offsets
+  out.push(0);
+  push_u16(out, 0);
+  push_u32(out, entry_count);
+  push_u32(out, header_size as u32 + entry_count * 4);
+  push_u32(out, config_size);
+  out.extend_from_slice(&type_chunk.config.data);
+  offsets.iter().for_each(|offset| push_u32(out, *offset));
+  out.extend_from_slice(&entries);
+  end_chunk(out, chunk_start);
+  Ok(())
+}
+
+fn push_value(
+  out: &mut Vec<u8>,
+  value: &Value,
+  value_strings: &ValueStrings,
+) -> Result<(), ParseError> {
+  let (data_type, data) = match value {
+    Value::String(string) => (ResType::STRING, value_strings.plain[string]),
+    Value::StyledString(styled_string) => (
+      ResType::STRING,
+      value_strings.styled[&(styled_string.text.clone(), styled_string.spans.clone())],
+    ),
+    Value::Data { data_type, data } => (*data_type, *data),
+  };
+  push_u16(out, 8);
+  out.push(0);
+  out.push(data_type);
+  push_u32(out, data);
+  Ok(())
+}
+
+// The global value string pool. Styled strings have to come first, since a style applies to
+// the string with the same index.
+#[derive(Default)]
+struct ValueStrings {
+  strings: Vec<String>,
+  styles: Vec<Vec<StringPoolSpan>>,
+  plain: HashMap<String, u32>,
+  styled: HashMap<(String, Vec<StringSpan>), u32>,
+}
+
+impl ValueStrings {
+  fn collect<'p>(packages: impl Iterator<Item = &'p Package>) -> Self {
+    let mut values = Vec::new();
+    for package in packages {
+      for entry in package
+        .type_chunks
+        .iter()
+        .flat_map(|type_chunk| type_chunk.entries.iter().flatten())
+      {
+        match &entry.value {
+          EntryValue::Simple(value) => values.push(value),
+          EntryValue::Complex { items, .. } => values.extend(items.iter().map(|(_, value)| value)),
+        }
+      }
+    }
+
+    let mut value_strings = Self::default();
+    let mut styled_spans = Vec::new();
+    for value in &values {
+      if let Value::StyledString(styled_string) = value {
+        let key = (styled_string.text.clone(), styled_string.spans.clone());
+        if !value_strings.styled.contains_key(&key) {
+          value_strings
+            .styled
+            .insert(key, value_strings.strings.len() as u32);
+          value_strings.strings.push(styled_string.text.clone());
+          styled_spans.push(styled_string.spans.clone());
+        }
+      }
+    }
+    for value in &values {
+      if let Value::String(string) = value {
+        value_strings.intern(string);
+      }
+    }
+    // Span tags are plain strings of the same pool
+    for spans in styled_spans {
+      let spans = spans
+        .iter()
+        .map(|span| StringPoolSpan {
+          name: value_strings.intern(&span.tag),
+          first_char: span.first_char,
+          last_char: span.last_char,
+        })
+        .collect();
+      value_strings.styles.push(spans);
+    }
+    value_strings
+  }
+
+  fn intern(
+    &mut self,
+    string: &str,
+  ) -> u32 {
+    if let Some(index) = self.plain.get(string) {
+      return *index;
+    }
+    let index = self.strings.len() as u32;
+    self.plain.insert(string.to_string(), index);
+    self.strings.push(string.to_string());
+    index
+  }
+}
+
+pub(crate) fn write_string_pool(
+  out: &mut Vec<u8>,
+  strings: &[String],
+  styles: &[Vec<StringPoolSpan>],
+) -> Result<(), ParseError> {
+  // Fall back to UTF-16 when a string is too long for the UTF-8 length encoding
+  let utf8 = strings.iter().all(|string| string.len() <= MAX_UTF8_LENGTH);
+
+  let mut offsets = Vec::new();
+  let mut string_data = Vec::new();
+  for string in strings {
+    offsets.push(string_data.len() as u32);
+    if utf8 {
+      push_utf8_length(&mut string_data, string.encode_utf16().count());
+      push_utf8_length(&mut string_data, string.len());
+      string_data.extend_from_slice(string.as_bytes());
+      string_data.push(0);
+    } else {
+      let units = string.encode_utf16().collect::<Vec<_>>();
+      if units.len() > 0x7fff {
+        if units.len() > 0x7fff_ffff {
+          return Err(ParseError::WriteTable(format!(
+            "string of {} characters is too long",
+            units.len()
+          )));
+        }
+        push_u16(&mut string_data, 0x8000 | (units.len() >> 16) as u16);
+      }
+      push_u16(&mut string_data, units.len() as u16);
+      units
+        .iter()
+        .for_each(|unit| push_u16(&mut string_data, *unit));
+      push_u16(&mut string_data, 0);
+    }
+  }
+  pad(&mut string_data);
+
+  let mut style_offsets = Vec::new();
+  let mut style_data = Vec::new();
+  for spans in styles {
+    style_offsets.push(style_data.len() as u32);
+    for span in spans {
+      push_u32(&mut style_data, span.name);
+      push_u32(&mut style_data, span.first_char);
+      push_u32(&mut style_data, span.last_char);
+    }
+    push_u32(&mut style_data, StringPoolSpan::END);
+  }
+  if !styles.is_empty() {
+    // aapt terminates the style table with two extra END markers
+    push_u32(&mut style_data, StringPoolSpan::END);
+    push_u32(&mut style_data, StringPoolSpan::END);
+  }
+
+  let strings_start =
+    STRING_POOL_HEADER_SIZE as u32 + (offsets.len() + style_offsets.len()) as u32 * 4;
+  let styles_start = if styles.is_empty() {
+    0
+  } else {
+    strings_start + string_data.len() as u32
+  };
+
+  let pool_start = begin_chunk(out, ChunkType::STRING_POOL, STRING_POOL_HEADER_SIZE);
+  push_u32(out, strings.len() as u32);
+  push_u32(out, styles.len() as u32);
+  push_u32(out, if utf8 { STRING_POOL_UTF8_FLAG } else { 0 });
+  push_u32(out, strings_start);
+  push_u32(out, styles_start);
+  offsets.iter().for_each(|offset| push_u32(out, *offset));
+  style_offsets
+    .iter()
+    .for_each(|offset| push_u32(out, *offset));
+  out.extend_from_slice(&string_data);
+  out.extend_from_slice(&style_data);
+  end_chunk(out, pool_start);
+  Ok(())
+}
+
+fn push_utf8_length(
+  out: &mut Vec<u8>,
+  len: usize,
+) {
+  if len > 0x7f {
+    out.push(0x80 | (len >> 8) as u8);
+  }
+  out.push(len as u8);
+}
+
+// Writes a chunk header with a placeholder size and returns the chunk start.
+pub(crate) fn begin_chunk(
+  out: &mut Vec<u8>,
+  typ: u16,
+  header_size: u16,
+) -> usize {
+  let start = out.len();
+  push_u16(out, typ);
+  push_u16(out, header_size);
+  push_u32(out, 0);
+  start
+}
+
+pub(crate) fn end_chunk(
+  out: &mut [u8],
+  start: usize,
+) {
+  let size = (out.len() - start) as u32;
+  out[start + 4..start + 8].copy_from_slice(&size.to_le_bytes());
+}
+
+// Package names are stored as a null terminated UTF-16 array of 128 characters.
+fn push_utf16_name(
+  out: &mut Vec<u8>,
+  name: &str,
+) {
+  let mut name = name.encode_utf16().take(127).collect::<Vec<_>>();
+  name.resize(128, 0);
+  name.iter().for_each(|c| push_u16(out, *c));
+}
+
+fn pad(out: &mut Vec<u8>) {
+  out.resize(out.len().next_multiple_of(4), 0);
+}
+
+pub(crate) fn push_u16(
+  out: &mut Vec<u8>,
+  value: u16,
+) {
+  out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn push_u32(
+  out: &mut Vec<u8>,
+  value: u32,
+) {
+  out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::res_config::ResConfig;
+  use anyhow::{Context, Result};
+
+  fn assert_equivalent(
+    left: &Arsc,
+    right: &Arsc,
+  ) {
+    assert_eq!(left.packages().len(), right.packages().len());
+    for (package_id, package) in left.packages() {
+      let other = &right.packages()[package_id];
+      assert_eq!(package.name, other.name);
+      assert_eq!(package.type_strings, other.type_strings);
+      assert_eq!(package.type_chunks, other.type_chunks);
+      assert_eq!(package.types, other.types);
+      assert_eq!(package.libraries, other.libraries);
+      assert_eq!(package.staged_aliases, other.staged_aliases);
+      let spec_flags = |package: &Package| {
+        package
+          .type_spec
+          .iter()
+          .map(|(header, flags)| (header.type_id, flags.clone()))
+          .collect::<Vec<_>>()
+      };
+      assert_eq!(spec_flags(package), spec_flags(other));
+    }
+  }
+
+  #[test]
+  fn test_arsc_round_trip_all() -> Result<()> {
+    let dir_path = std::path::Path::new("../data/arsc");
+    for file in dir_path.read_dir()? {
+      let file = file?.path().canonicalize()?;
+      if file.extension().is_some_and(|ext| ext != "arsc") {
+        continue;
+      }
+      let file_bytes: Vec<u8> = std::fs::read(&file)?;
+      let mut parser = Arsc::new(file_bytes.as_slice());
+      parser
+        .parse()
+        .context(format!("Failed to parse arsc: {}", file.display()))?;
+
+      let written = ArscWriter::new(&parser).write()?;
+      let mut reparsed = Arsc::new(written.as_slice());
+      reparsed
+        .parse()
+        .context(format!("Failed to parse written arsc: {}", file.display()))?;
+      assert_equivalent(&parser, &reparsed);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn test_compact_entries() -> Result<()> {
+    let arsc_bytes = std::fs::read(
+      "../data/arsc/ab4cb5175fc0827860b9c3361d8bf2aec99a71f63b5a7666ae2403b0030ac56f.arsc",
+    )?;
+    let mut parser = Arsc::new(&arsc_bytes);
+    parser.parse()?;
+    let written = ArscWriter::new(&parser).write()?;
+    let mut regular = Arsc::new(&written);
+    regular.parse()?;
+
+    // Rewrite the simple entries holding data in place the way aapt2 --enable-compact-entries
+    // writes them: the key index in the size, the data type in the high byte of the flags
+    let mut compact_entries = HashMap::new();
+    for package in regular.packages().values() {
+      let entries = package
+        .type_chunks
+        .iter()
+        .flat_map(|type_chunk| type_chunk.entries.iter().flatten());
+      for entry in entries {
+        let EntryValue::Simple(Value::Data { data_type, data }) = entry.value else {
+          continue;
+        };
+        let key = package
+          .key_strings
+          .iter()
+          .position(|key| *key == entry.key)
+          .context("key missing")? as u32;
+        let mut regular_entry = Vec::new();
+        push_u16(&mut regular_entry, 8);
+        push_u16(&mut regular_entry, entry.flags);
+        push_u32(&mut regular_entry, key);
+        push_u16(&mut regular_entry, 8);
+        regular_entry.extend_from_slice(&[0, data_type]);
+        push_u32(&mut regular_entry, data);
+        let mut compact_entry = Vec::new();
+        push_u16(&mut compact_entry, key as u16);
+        push_u16(
+          &mut compact_entry,
+          (data_type as u16) << 8 | TableEntryFlag::COMPACT | entry.flags,
+        );
+        push_u32(&mut compact_entry, data);
+        compact_entry.resize(regular_entry.len(), 0);
+        compact_entries.insert(regular_entry, compact_entry);
+      }
+    }
+    // Entries are 4 byte aligned
+    let mut compact = written.clone();
+    let mut compacted = 0;
+    let mut offset = 0;
+    while offset + 16 <= compact.len() {
+      match compact_entries.get(&compact[offset..offset + 16]) {
+        Some(compact_entry) => {
+          compact[offset..offset + 16].copy_from_slice(compact_entry);
+          compacted += 1;
+          offset += 16;
+        }
+        None => offset += 4,
+      }
+    }
+    assert!(compacted > 0);
+
+    let mut compact_table = Arsc::new(&compact);
+    compact_table.parse()?;
+    let rewritten = ArscWriter::new(&compact_table).write()?;
+    let mut reparsed = Arsc::new(&rewritten);
+    reparsed.parse()?;
+    assert_equivalent(&regular, &reparsed);
+    Ok(())
+  }
+
+  #[test]
+  fn test_arsc_edits() -> Result<()> {
+    let arsc_path = std::path::Path::new(
+      "../data/arsc/ab4cb5175fc0827860b9c3361d8bf2aec99a71f63b5a7666ae2403b0030ac56f.arsc",
+    );
+    let arsc_bytes: Vec<u8> = std::fs::read(arsc_path)?;
+    let mut parser = Arsc::new(arsc_bytes.as_slice());
+    parser.parse()?;
+
+    let app_name = parser
+      .spec_entries()
+      .into_iter()
+      .find(|spec_entry| spec_entry.type_name == "string" && spec_entry.name == "app_name")
+      .context("app_name not found")?
+      .id;
+    let removed = parser
+      .spec_entries()
+      .into_iter()
+      .find(|spec_entry| spec_entry.type_name == "string" && spec_entry.id != app_name)
+      .context("no other string")?
+      .id;
+
+    parser.set_string(app_name, "Renamed app")?;
+    parser.add_locale_variant(app_name, "tlh-rQO", "Klingon app")?;
+    parser.remove_entry(removed)?;
+
+    let written = ArscWriter::new(&parser).write()?;
+    let mut reparsed = Arsc::new(written.as_slice());
+    reparsed.parse()?;
+    assert_equivalent(&parser, &reparsed);
+
+    assert_eq!(
+      reparsed.get_res_value(app_name),
+      Some("Renamed app".to_string())
+    );
+    let klingon = reparsed
+      .entry_values(app_name)
+      .into_iter()
+      .find(|(config, _)| config.language() == Some("tlh".to_string()))
+      .context("locale variant missing")?;
+    assert_eq!(klingon.0.qualifier(), "b+tlh+QO");
+    assert_eq!(
+      klingon.1.value,
+      EntryValue::Simple(Value::String("Klingon app".to_string()))
+    );
+    assert!(reparsed.entry_values(removed).is_empty());
+
+    // Configs written by old tools can be shorter than the locale fields
+    let mut short_configs = parser.clone();
+    for package in short_configs.packages_mut().values_mut() {
+      for type_chunk in &mut package.type_chunks {
+        type_chunk.config.data.truncate(4);
+        type_chunk.config.structure_size = 8;
+      }
+    }
+    short_configs.add_locale_variant(app_name, "de", "Beispiel")?;
+    let german = short_configs
+      .entry_values(app_name)
+      .into_iter()
+      .find(|(config, _)| config.qualifier() == "de");
+    assert!(german.is_some());
+    assert!(reparsed
+      .entry_values(app_name)
+      .iter()
+      .any(|(config, _)| *config == ResConfig::default()));
+    Ok(())
+  }
+}
@@ -0,0 +1,362 @@
+use crate::arsc_parser::Arsc;
+use crate::manifest::Manifest;
+use crate::nom_parser::ParseError;
+use crate::parser::{ApkArchive, Diagnostic, Parser};
+use std::path::{Path, PathBuf};
+
+/// One APK of a bundle, with the split attributes of its manifest.
+pub struct Split {
+  /// Entry name inside the container, or the file path for split sets.
+  pub path: String,
+  pub package: Option<String>,
+  /// Name of the split, `None` for the base APK.
+  pub split: Option<String>,
+  pub is_feature_split: bool,
+  /// The split this configuration split belongs to.
+  pub config_for_split: Option<String>,
+  parser: Parser,
+}
+
+impl Split {
+  fn new(
+    path: String,
+    parser: Parser,
+  ) -> Result<Self, ParseError> {
+    // Split attributes are plain strings, no resource table is needed to read them
+    let manifest = Manifest::from_xml(&parser.parse_with(None)?)?;
+    let attributes = &manifest.attributes;
+    Ok(Self {
+      path,
+      package: manifest.package.clone(),
+      split: manifest.split.clone(),
+      is_feature_split: attributes.get_bool("isFeatureSplit").unwrap_or(false),
+      config_for_split: attributes.get("configForSplit").map(str::to_string),
+      parser,
+    })
+  }
+
+  pub fn is_base(&self) -> bool {
+    self.split.is_none()
+  }
+
+  /// Configuration splits hold the resources of a density, ABI or language.
+  pub fn is_config_split(&self) -> bool {
+    self.config_for_split.is_some()
+      || self
+        .split
+        .as_deref()
+        .is_some_and(|name| name.starts_with("config."))
+  }
+
+  pub fn parser(&self) -> &Parser {
+    &self.parser
+  }
+}
+
+/// An app made of a base APK and its splits, read from an `.apks`, `.xapk` or `.apkm`
+/// container or from a set of split APKs.
+pub struct Bundle {
+  splits: Vec<Split>,
+  diagnostics: Vec<Diagnostic>,
+}
+
+impl Bundle {
+  /// Opens a container holding the split APKs. A plain APK is read as a bundle of one.
+  pub fn from_file(file_path: &Path) -> Result<Self, ParseError> {
+    let container = std::fs::read(file_path).map_err(|e| ParseError::File(e.to_string()))?;
+    Self::from_bytes(&container)
+  }
+
+  pub fn from_bytes(container: &[u8]) -> Result<Self, ParseError> {
+    let mut archive = ApkArchive::new(container.to_vec())?;
+    if archive.by_name("AndroidManifest.xml").is_some() {
+      let split = Split::new(String::new(), Parser::from_apk_archive(archive)?)?;
+      return Ok(Self::from_parsed_splits(vec![split], Vec::new()));
+    }
+
+    // .xapk and .apkm keep the splits at the top level next to their metadata and OBB files.
+    // bundletool's .apks keep them in splits/, with the standalone APKs for devices without
+    // split support in standalones/ and universal.apk, which would duplicate the base.
+    let is_apks = archive.entries().iter().any(|entry| {
+      entry.name == "toc.pb"
+        || entry.name.starts_with("splits/")
+        || entry.name.starts_with("standalones/")
+    });
+    let mut diagnostics = Vec::new();
+    let mut apk_entries = Vec::new();
+    for entry in archive.entries() {
+      if !entry.name.to_ascii_lowercase().ends_with(".apk") {
+        continue;
+      }
+      if is_apks && !entry.name.starts_with("splits/") {
+        diagnostics.push(Diagnostic::StandaloneApk {
+          entry: entry.name.clone(),
+        });
+        continue;
+      }
+      apk_entries.push(entry.clone());
+    }
+    let mut apks = Vec::new();
+    for entry in apk_entries {
+      if apks.iter().any(|(path, _)| *path == entry.name) {
+        continue;
+      }
+      let apk = archive.read(&entry)?;
+      apks.push((entry.name, apk));
+    }
+    let mut bundle = Self::from_splits(apks)?;
+    diagnostics.splice(0..0, archive.diagnostics().iter().cloned());
+    bundle.diagnostics.splice(0..0, diagnostics);
+    Ok(bundle)
+  }
+
+  /// Reads a set of split APKs, e.g. the output of `adb shell pm path`.
+  pub fn from_split_files(file_paths: &[PathBuf]) -> Result<Self, ParseError> {
+    let mut apks = Vec::new();
+    for file_path in file_paths {
+      let apk = std::fs::read(file_path).map_err(|e| ParseError::File(e.to_string()))?;
+      apks.push((file_path.display().to_string(), apk));
+    }
+    Self::from_splits(apks)
+  }
+
+  /// Reads split APKs that are already in memory, given as (path, APK) pairs.
+  pub fn from_splits(apks: Vec<(String, Vec<u8>)>) -> Result<Self, ParseError> {
+    let mut splits = Vec::new();
+    for (path, apk) in apks {
+      let split = Parser::from_bytes(&apk)
+        .and_then(|parser| Split::new(path.clone(), parser))
+        .map_err(|e| ParseError::Split(format!("{}: {}", path, e)))?;
+      splits.push(split);
+    }
+    Ok(Self::from_parsed_splits(splits, Vec::new()))
+  }
+
+  // Orders the splits with the base first and the others by name.
+  fn from_parsed_splits(
+    mut splits: Vec<Split>,
+    mut diagnostics: Vec<Diagnostic>,
+  ) -> Self {
+    splits.sort_by(|a, b| (&a.split, &a.path).cmp(&(&b.split, &b.path)));
+    if !splits.iter().any(Split::is_base) {
+      diagnostics.push(Diagnostic::MissingBaseApk);
+    }
+    Self {
+      splits,
+      diagnostics,
+    }
+  }
+
+  /// All splits, the base APK first.
+  pub fn splits(&self) -> &[Split] {
+    &self.splits
+  }
+
+  pub fn base(&self) -> Option<&Split> {
+    self.splits.iter().find(|split| split.is_base())
+  }
+
+  pub fn split(
+    &self,
+    name: &str,
+  ) -> Option<&Split> {
+    self
+      .splits
+      .iter()
+      .find(|split| split.split.as_deref() == Some(name))
+  }
+
+  /// Problems of the container itself, each split reports its own through its parser.
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    &self.diagnostics
+  }
+
+  /// The resource tables of all splits merged into one, starting with the base.
+  pub fn resource_table(&self) -> Result<Option<Arsc<'_>>, ParseError> {
+    let mut merged: Option<Arsc> = None;
+    for split in &self.splits {
+      let Some(table) = split.parser.resource_table()? else {
+        continue;
+      };
+      match &mut merged {
+        Some(merged) => merged.merge(&table),
+        None => merged = Some(table),
+      }
+    }
+    Ok(merged)
+  }
+
+  /// Decodes the manifest of a split, resolving references through the merged table.
+  pub fn parse_manifest(
+    &self,
+    split: &Split,
+  ) -> Result<Vec<u8>, ParseError> {
+    let table = self.resource_table()?;
+    split.parser.parse_with(table.as_ref())
+  }
+
+  /// Decodes the manifest of the base APK, resolving references through the merged table.
+  pub fn parse(&self) -> Result<Vec<u8>, ParseError> {
+    let base = self
+      .base()
+      .ok_or_else(|| ParseError::MissingEntry("base APK".to_string()))?;
+    self.parse_manifest(base)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::arsc_writer::ArscWriter;
+  use crate::nom_parser::ResType;
+  use crate::test_util::{binary_xml, zip, AttributeValue, XmlElement};
+  use anyhow::{Context, Result};
+
+  #[test]
+  fn test_bundle() -> Result<()> {
+    let arsc_bytes = std::fs::read(
+      "../data/arsc/ab4cb5175fc0827860b9c3361d8bf2aec99a71f63b5a7666ae2403b0030ac56f.arsc",
+    )?;
+    let mut base_table = Arsc::new(&arsc_bytes);
+    base_table.parse()?;
+    let app_name = base_table
+      .spec_entries()
+      .into_iter()
+      .find(|spec_entry| spec_entry.type_name == "string" && spec_entry.name == "app_name")
+      .context("app_name not found")?
+      .id;
+
+    // The feature split brings its own package, which the base manifest references
+    let mut feature_table = base_table.clone();
+    let mut feature_package = feature_table.packages_mut().remove(&0x7f).unwrap();
+    feature_package.id = 0x80;
+    feature_package.name = "com.example.feature".to_string();
+    feature_table.packages_mut().insert(0x80, feature_package);
+    feature_table.set_string(0x80000000 | (app_name & 0xffffff), "Feature label")?;
+    let feature_arsc = ArscWriter::new(&feature_table).write()?;
+
+    // The configuration split only holds the German strings
+    let mut config_table = base_table.clone();
+    config_table.add_locale_variant(app_name, "de", "Beispiel")?;
+    let config_package = config_table.packages_mut().get_mut(&0x7f).unwrap();
+    config_package
+      .type_chunks
+      .retain(|type_chunk| type_chunk.res_config().language() == Some("de".to_string()));
+    let config_arsc = ArscWriter::new(&config_table).write()?;
+
+    let base_apk = zip(&[
+      (
+        "AndroidManifest.xml",
+        &binary_xml(
+          &XmlElement::new("manifest")
+            .attribute("package", None, AttributeValue::String("com.example"))
+            .attribute(
+              "label",
+              None,
+              AttributeValue::Data(ResType::REFERENCE, 0x80000000 | (app_name & 0xffffff)),
+            ),
+        )?,
+      ),
+      ("resources.arsc", &arsc_bytes),
+    ])?;
+    let feature_apk = zip(&[
+      (
+        "AndroidManifest.xml",
+        &binary_xml(
+          &XmlElement::new("manifest")
+            .attribute("package", None, AttributeValue::String("com.example"))
+            .attribute("split", None, AttributeValue::String("feature"))
+            .attribute(
+              "isFeatureSplit",
+              None,
+              AttributeValue::Data(ResType::INT_BOOLEAN, 0xFFFFFFFF),
+            ),
+        )?,
+      ),
+      ("resources.arsc", &feature_arsc),
+    ])?;
+    let config_apk = zip(&[
+      (
+        "AndroidManifest.xml",
+        &binary_xml(
+          &XmlElement::new("manifest")
+            .attribute("package", None, AttributeValue::String("com.example"))
+            .attribute("split", None, AttributeValue::String("config.de"))
+            .attribute("configForSplit", None, AttributeValue::String("")),
+        )?,
+      ),
+      ("resources.arsc", &config_arsc),
+    ])?;
+    let apks = zip(&[
+      ("toc.pb", b"\x0a\x00"),
+      ("splits/feature-master.apk", &feature_apk),
+      ("splits/base-de.apk", &config_apk),
+      ("splits/base-master.apk", &base_apk),
+      ("standalones/standalone-x86.apk", &base_apk),
+      ("universal.apk", &base_apk),
+    ])?;
+
+    let bundle = Bundle::from_bytes(&apks)?;
+    assert_eq!(
+      bundle.diagnostics(),
+      [
+        Diagnostic::StandaloneApk {
+          entry: "standalones/standalone-x86.apk".to_string()
+        },
+        Diagnostic::StandaloneApk {
+          entry: "universal.apk".to_string()
+        },
+      ]
+    );
+    let split_names = bundle
+      .splits()
+      .iter()
+      .map(|split| (split.path.as_str(), split.split.as_deref()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      split_names,
+      [
+        ("splits/base-master.apk", None),
+        ("splits/base-de.apk", Some("config.de")),
+        ("splits/feature-master.apk", Some("feature")),
+      ]
+    );
+    let base = bundle.base().context("base missing")?;
+    assert_eq!(base.package.as_deref(), Some("com.example"));
+    let feature = bundle.split("feature").context("feature missing")?;
+    assert!(feature.is_feature_split && !feature.is_config_split());
+    let config = bundle.split("config.de").context("config split missing")?;
+    assert!(config.is_config_split() && !config.is_feature_split);
+    assert_eq!(config.config_for_split.as_deref(), Some(""));
+
+    // The base manifest resolves into the feature split
+    let manifest = String::from_utf8(bundle.parse()?)?;
+    assert!(manifest.contains("label=\"Feature label\""));
+    assert!(!String::from_utf8(base.parser().parse_with(None)?)?.contains("Feature label"));
+
+    let table = bundle.resource_table()?.context("no table")?;
+    assert_eq!(table.package_ids(), vec![0x7f, 0x80]);
+    assert!(table
+      .entry_values(app_name)
+      .iter()
+      .any(|(config, _)| config.qualifier() == "de"));
+
+    // Without splits/ the APKs of an .xapk sit at the top level
+    let xapk = zip(&[
+      ("manifest.json", b"{}"),
+      ("com.example.apk", &base_apk),
+      ("config.de.apk", &config_apk),
+    ])?;
+    let bundle = Bundle::from_bytes(&xapk)?;
+    assert!(bundle.diagnostics().is_empty());
+    assert_eq!(bundle.splits().len(), 2);
+
+    // A set of split files needs no container
+    let bundle = Bundle::from_splits(vec![
+      ("feature.apk".to_string(), feature_apk),
+      ("config.de.apk".to_string(), config_apk),
+    ])?;
+    assert_eq!(bundle.diagnostics(), [Diagnostic::MissingBaseApk]);
+    Ok(())
+  }
+}
@@ -0,0 +1,250 @@
+use crate::arsc_parser::Arsc;
+use crate::manifest::{Component, ComponentKind, IntentData, IntentFilter, Manifest, UriPattern};
+use crate::xml_parser::resolve_references;
+
+const ACTION_VIEW: &str = "android.intent.action.VIEW";
+const CATEGORY_BROWSABLE: &str = "android.intent.category.BROWSABLE";
+const CATEGORY_DEFAULT: &str = "android.intent.category.DEFAULT";
+
+/// A URI a browsable activity filter claims.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeepLink {
+  /// Class name of the activity or activity alias.
+  pub component: String,
+  pub kind: ComponentKind,
+  pub uri: UriPattern,
+  /// The filter has `autoVerify="true"`.
+  pub auto_verify: bool,
+  /// The system verifies the host against its `assetlinks.json`: `autoVerify` on a `VIEW`
+  /// filter with the `BROWSABLE` and `DEFAULT` categories, for an `http` or `https` URI with a
+  /// host.
+  pub app_link: bool,
+  /// Whether other apps can start the component at all.
+  pub exported: bool,
+}
+
+impl DeepLink {
+  /// Why `autoVerify` doesn't make the link an App Link, if it is set.
+  pub fn note(&self) -> Option<String> {
+    if !self.auto_verify || self.app_link {
+      return None;
+    }
+    let note = if !matches!(self.uri.scheme.as_str(), "http" | "https") {
+      format!("autoVerify ignored for the {} scheme", self.uri.scheme)
+    } else if self.uri.authority.is_none() {
+      "autoVerify ignored without a host".to_string()
+    } else {
+      "autoVerify ignored without the VIEW action and the DEFAULT category".to_string()
+    };
+    Some(note)
+  }
+}
+
+/// Every deep link of the manifest.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeepLinks {
+  pub links: Vec<DeepLink>,
+}
+
+impl DeepLinks {
+  /// Collects the URIs of the activity and activity alias filters with the `BROWSABLE`
+  /// category. References left in the filter data, e.g. a host from `@string/host`, are
+  /// resolved through `arsc`.
+  pub fn from_manifest(
+    manifest: &Manifest,
+    arsc: Option<&Arsc>,
+  ) -> Self {
+    let mut links = Vec::new();
+    let activities = manifest.components().filter(|component| {
+      matches!(
+        component.kind,
+        ComponentKind::Activity | ComponentKind::ActivityAlias
+      )
+    });
+    for component in activities {
+      for filter in &component.intent_filters {
+        if filter.has_category(CATEGORY_BROWSABLE) {
+          links.extend(filter_links(component, &resolve_data(filter, arsc)));
+        }
+      }
+    }
+    Self { links }
+  }
+
+  /// Hosts the system verifies as App Links, in the order of the manifest.
+  pub fn app_link_hosts(&self) -> Vec<&str> {
+    let mut hosts = Vec::new();
+    for link in self.links.iter().filter(|link| link.app_link) {
+      if let Some(authority) = &link.uri.authority {
+        if !hosts.contains(&authority.host.as_str()) {
+          hosts.push(authority.host.as_str());
+        }
+      }
+    }
+    hosts
+  }
+}
+
+impl std::fmt::Display for DeepLinks {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    let mut component = None;
+    for link in &self.links {
+      if component != Some(&link.component) {
+        let exported = if link.exported { "" } else { " (not exported)" };
+        writeln!(f, "{}{}", link.component, exported)?;
+        component = Some(&link.component);
+      }
+      write!(f, "  {}", link.uri)?;
+      if link.app_link {
+        write!(f, " (App Link)")?;
+      }
+      if let Some(note) = link.note() {
+        write!(f, " ({})", note)?;
+      }
+      writeln!(f)?;
+    }
+    let hosts = self.app_link_hosts();
+    if !hosts.is_empty() {
+      writeln!(f, "App Link hosts")?;
+      for host in hosts {
+        writeln!(f, "  {}", host)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+fn resolve_data(
+  filter: &IntentFilter,
+  arsc: Option<&Arsc>,
+) -> IntentFilter {
+  let resolve = |value: &Option<String>| resolve_references(value.clone(), arsc);
+  let mut filter = filter.clone();
+  filter.data = filter
+    .data
+    .iter()
+    .map(|data| IntentData {
+      scheme: resolve(&data.scheme),
+      host: resolve(&data.host),
+      port: resolve(&data.port),
+      path: resolve(&data.path),
+      path_prefix: resolve(&data.path_prefix),
+      path_pattern: resolve(&data.path_pattern),
+      path_advanced_pattern: resolve(&data.path_advanced_pattern),
+      path_suffix: resolve(&data.path_suffix),
+      mime_type: resolve(&data.mime_type),
+    })
+    .collect();
+  filter
+}
+
+fn filter_links(
+  component: &Component,
+  filter: &IntentFilter,
+) -> Vec<DeepLink> {
+  let verifiable =
+    filter.auto_verify && filter.has_action(ACTION_VIEW) && filter.has_category(CATEGORY_DEFAULT);
+  filter
+    .uri_patterns()
+    .into_iter()
+    .map(|uri| DeepLink {
+      component: component.name.clone(),
+      kind: component.kind,
+      app_link: verifiable
+        && matches!(uri.scheme.as_str(), "http" | "https")
+        && uri.authority.is_some(),
+      auto_verify: filter.auto_verify,
+      exported: component.effective_exported.exported,
+      uri,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::{Context, Result};
+
+  #[test]
+  fn test_deep_links() -> Result<()> {
+    let arsc_bytes = std::fs::read(
+      "../data/arsc/ab4cb5175fc0827860b9c3361d8bf2aec99a71f63b5a7666ae2403b0030ac56f.arsc",
+    )?;
+    let mut arsc = Arsc::new(&arsc_bytes);
+    arsc.parse()?;
+    let app_name = arsc
+      .spec_entries()
+      .into_iter()
+      .find(|spec_entry| spec_entry.type_name == "string" && spec_entry.name == "app_name")
+      .context("app_name not found")?
+      .id;
+    arsc.set_string(app_name, "example.com")?;
+
+    let xml = format!(
+      r#"<manifest package="com.example"><application>
+<activity name=".Links" exported="true">
+  <intent-filter autoVerify="true">
+    <action name="android.intent.action.VIEW"></action>
+    <category name="android.intent.category.DEFAULT"></category>
+    <category name="android.intent.category.BROWSABLE"></category>
+    <data scheme="https"></data>
+    <data scheme="example"></data>
+    <data host="@res/0x{:08x}" pathPrefix="/item"></data>
+  </intent-filter>
+  <intent-filter>
+    <action name="android.intent.action.MAIN"></action>
+    <category name="android.intent.category.LAUNCHER"></category>
+  </intent-filter>
+</activity>
+<activity name=".Private" exported="false">
+  <intent-filter>
+    <category name="android.intent.category.BROWSABLE"></category>
+    <data scheme="private"></data>
+  </intent-filter>
+</activity>
+</application></manifest>"#,
+      app_name
+    );
+    let manifest = Manifest::from_xml(xml.as_bytes())?;
+    let deep_links = DeepLinks::from_manifest(&manifest, Some(&arsc));
+    let uris = deep_links
+      .links
+      .iter()
+      .map(|link| (link.uri.to_string(), link.app_link, link.exported))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      uris,
+      [
+        ("https://example.com/item*".to_string(), true, true),
+        ("example://example.com/item*".to_string(), false, true),
+        ("private:".to_string(), false, false),
+      ]
+    );
+    assert_eq!(deep_links.app_link_hosts(), ["example.com"]);
+    assert_eq!(
+      deep_links.links[1].note().as_deref(),
+      Some("autoVerify ignored for the example scheme")
+    );
+    assert_eq!(
+      deep_links.to_string(),
+      "com.example.Links
+  https://example.com/item* (App Link)
+  example://example.com/item* (autoVerify ignored for the example scheme)
+com.example.Private (not exported)
+  private:
+App Link hosts
+  example.com
+"
+    );
+
+    let unresolved = DeepLinks::from_manifest(&manifest, None);
+    assert_eq!(
+      unresolved.links[0].uri.to_string(),
+      format!("https://@res/0x{:08x}/item*", app_name)
+    );
+    Ok(())
+  }
+}
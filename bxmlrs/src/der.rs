@@ -0,0 +1,212 @@
+// Minimal DER reader for the X.509 certificates and PKCS#7 blocks of APK signatures.
+
+use crate::nom_parser::ParseError;
+
+pub(crate) const TAG_INTEGER: u8 = 0x02;
+pub(crate) const TAG_BIT_STRING: u8 = 0x03;
+pub(crate) const TAG_OID: u8 = 0x06;
+pub(crate) const TAG_UTF8_STRING: u8 = 0x0c;
+pub(crate) const TAG_PRINTABLE_STRING: u8 = 0x13;
+pub(crate) const TAG_T61_STRING: u8 = 0x14;
+pub(crate) const TAG_IA5_STRING: u8 = 0x16;
+pub(crate) const TAG_UTC_TIME: u8 = 0x17;
+pub(crate) const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const TAG_UNIVERSAL_STRING: u8 = 0x1c;
+pub(crate) const TAG_BMP_STRING: u8 = 0x1e;
+pub(crate) const TAG_SEQUENCE: u8 = 0x30;
+pub(crate) const TAG_SET: u8 = 0x31;
+// [0], constructed
+pub(crate) const TAG_CONTEXT_0: u8 = 0xa0;
+
+/// A DER element: its tag, its content and the whole encoding including the header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Der<'a> {
+  pub(crate) tag: u8,
+  pub(crate) content: &'a [u8],
+  pub(crate) raw: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+  /// Reads the element at the start of `input` and returns it with the remaining input.
+  pub(crate) fn parse(input: &'a [u8]) -> Result<(Self, &'a [u8]), ParseError> {
+    let der_error = |message: &str| ParseError::Signature(format!("DER: {}", message));
+    let (&tag, rest) = input
+      .split_first()
+      .ok_or_else(|| der_error("unexpected end of data"))?;
+    if tag & 0x1f == 0x1f {
+      return Err(der_error("multi-byte tags are not supported"));
+    }
+    let (&first, mut rest) = rest
+      .split_first()
+      .ok_or_else(|| der_error("missing length"))?;
+    let len = if first & 0x80 == 0 {
+      first as usize
+    } else {
+      let count = (first & 0x7f) as usize;
+      if count == 0 || count > 4 || rest.len() < count {
+        return Err(der_error("unsupported length"));
+      }
+      let len = rest[..count]
+        .iter()
+        .fold(0usize, |len, byte| (len << 8) | *byte as usize);
+      rest = &rest[count..];
+      len
+    };
+    if rest.len() < len {
+      return Err(der_error("element exceeds its parent"));
+    }
+    let header_len = input.len() - rest.len();
+    let element = Self {
+      tag,
+      content: &rest[..len],
+      raw: &input[..header_len + len],
+    };
+    Ok((element, &rest[len..]))
+  }
+
+  /// Reads an element that has to be the whole input.
+  pub(crate) fn parse_all(input: &'a [u8]) -> Result<Self, ParseError> {
+    let (element, rest) = Self::parse(input)?;
+    if !rest.is_empty() {
+      return Err(ParseError::Signature(format!(
+        "DER: {} trailing bytes",
+        rest.len()
+      )));
+    }
+    Ok(element)
+  }
+
+  pub(crate) fn expect(
+    self,
+    tag: u8,
+  ) -> Result<Self, ParseError> {
+    if self.tag != tag {
+      return Err(ParseError::Signature(format!(
+        "DER: expected tag 0x{:02x}, found 0x{:02x}",
+        tag, self.tag
+      )));
+    }
+    Ok(self)
+  }
+
+  /// The elements of a constructed element such as a SEQUENCE or SET.
+  pub(crate) fn children(&self) -> Result<Vec<Der<'a>>, ParseError> {
+    let mut children = Vec::new();
+    let mut input = self.content;
+    while !input.is_empty() {
+      let (child, rest) = Self::parse(input)?;
+      children.push(child);
+      input = rest;
+    }
+    Ok(children)
+  }
+
+  /// Dotted form of an OBJECT IDENTIFIER, e.g. `2.5.4.3`.
+  pub(crate) fn oid(&self) -> String {
+    let mut arcs: Vec<u64> = Vec::new();
+    let mut value = 0u64;
+    for byte in self.content {
+      value = (value << 7) | (byte & 0x7f) as u64;
+      if byte & 0x80 == 0 {
+        if arcs.is_empty() {
+          let first = (value / 40).min(2);
+          arcs.push(first);
+          arcs.push(value - first * 40);
+        } else {
+          arcs.push(value);
+        }
+        value = 0;
+      }
+    }
+    arcs
+      .iter()
+      .map(|arc| arc.to_string())
+      .collect::<Vec<_>>()
+      .join(".")
+  }
+
+  /// Text of the string types used in names.
+  pub(crate) fn string(&self) -> String {
+    match self.tag {
+      TAG_BMP_STRING => {
+        let units = self
+          .content
+          .chunks_exact(2)
+          .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+          .collect::<Vec<_>>();
+        String::from_utf16_lossy(&units)
+      }
+      TAG_UNIVERSAL_STRING => self
+        .content
+        .chunks_exact(4)
+        .filter_map(|c| char::from_u32(u32::from_be_bytes([c[0], c[1], c[2], c[3]])))
+        .collect(),
+      TAG_UTF8_STRING | TAG_PRINTABLE_STRING | TAG_IA5_STRING | TAG_T61_STRING => {
+        String::from_utf8_lossy(self.content).to_string()
+      }
+      _ => hex::encode(self.content),
+    }
+  }
+
+  /// `YYYY-MM-DD HH:MM:SS UTC` of an UTCTime or GeneralizedTime.
+  pub(crate) fn time(&self) -> String {
+    let text = String::from_utf8_lossy(self.content);
+    let text = text.trim_end_matches('Z');
+    let full = match self.tag {
+      // Two digit years below 50 are in the 21st century
+      TAG_UTC_TIME => {
+        let year = text.get(..2).and_then(|year| year.parse::<u32>().ok());
+        let century = if year.unwrap_or(0) < 50 { "20" } else { "19" };
+        format!("{}{}", century, text)
+      }
+      _ => text.to_string(),
+    };
+    // Crafted certificates can hold anything, only digits are sliced
+    if full.len() < 14 || !full.is_ascii() {
+      return full;
+    }
+    format!(
+      "{}-{}-{} {}:{}:{} UTC",
+      &full[0..4],
+      &full[4..6],
+      &full[6..8],
+      &full[8..10],
+      &full[10..12],
+      &full[12..14]
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_der() {
+    let time = |tag, content: &[u8]| {
+      Der {
+        tag,
+        content,
+        raw: &[],
+      }
+      .time()
+    };
+    assert_eq!(
+      time(TAG_UTC_TIME, b"240131235959Z"),
+      "2024-01-31 23:59:59 UTC"
+    );
+    assert_eq!(
+      time(TAG_GENERALIZED_TIME, b"19991231120000Z"),
+      "1999-12-31 12:00:00 UTC"
+    );
+    // Multi-byte characters must not be sliced
+    let text = "20é4013123595".as_bytes();
+    assert_eq!(time(TAG_GENERALIZED_TIME, text), "20é4013123595");
+
+    let sequence = [TAG_SEQUENCE, 3, TAG_INTEGER, 1, 5];
+    let element = Der::parse_all(&sequence).unwrap();
+    assert_eq!(element.children().unwrap()[0].content, [5]);
+    assert!(Der::parse_all(&[TAG_SEQUENCE, 3, TAG_INTEGER, 1, 5, 0]).is_err());
+    assert!(Der::parse_all(&[TAG_SEQUENCE, 4, TAG_INTEGER, 1, 5]).is_err());
+  }
+}
@@ -0,0 +1,259 @@
+use crate::manifest::{Attributes, Component, ComponentKind};
+
+// API levels whose targets changed the implicit exported state
+const JELLY_BEAN_MR1: u32 = 17;
+const S: u32 = 31;
+
+/// What decided whether a component is exported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportedReason {
+  /// Not worked out yet, as for a component that isn't part of a parsed manifest.
+  #[default]
+  Undetermined,
+  /// The `exported` attribute.
+  Explicit,
+  /// No `exported` attribute, intent filters make the component exported.
+  IntentFilters,
+  /// No `exported` attribute and no intent filters.
+  NoIntentFilters,
+  /// Providers of apps targeting API 16 or lower are exported by default.
+  LegacyProvider,
+  /// Providers of apps targeting API 17 or higher are private by default.
+  Provider,
+  /// Apps targeting API 31 or higher have to set `exported` on components with intent
+  /// filters, Android refuses to install the APK otherwise.
+  MissingExported,
+  /// The `exported` attribute is a reference that couldn't be resolved, exported is assumed.
+  Unresolved,
+  /// The component or the application has `enabled="false"`.
+  Disabled,
+}
+
+/// Whether other apps can reach a component, and why.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExportedState {
+  pub exported: bool,
+  pub reason: ExportedReason,
+  /// Permission callers need, from the component or else the application. Providers also
+  /// have `read_permission` and `write_permission`.
+  pub permission: Option<String>,
+  pub read_permission: Option<String>,
+  pub write_permission: Option<String>,
+  /// The decision step by step, e.g. `no exported attribute`, `has 2 intent filters`.
+  pub reasoning: Vec<String>,
+}
+
+impl ExportedState {
+  /// Exported without any permission guarding it.
+  pub fn is_unprotected(&self) -> bool {
+    self.exported
+      && self.permission.is_none()
+      && (self.read_permission.is_none() || self.write_permission.is_none())
+  }
+}
+
+/// Works out whether a component is exported the way `PackageParser` does for an app with the
+/// given target SDK. `target` is the activity an activity-alias points to.
+pub fn effective_exported(
+  component: &Component,
+  target: Option<&Component>,
+  application: &Attributes,
+  target_sdk: u32,
+) -> ExportedState {
+  let mut state = ExportedState::default();
+  let filters = component.intent_filters.len();
+  match component.attributes.get("exported") {
+    Some(exported) => match component.attributes.get_bool("exported") {
+      Some(exported) => {
+        state.exported = exported;
+        state.reason = ExportedReason::Explicit;
+        state.reasoning.push(format!("exported=\"{}\"", exported));
+      }
+      None => {
+        state.exported = true;
+        state.reason = ExportedReason::Unresolved;
+        state
+          .reasoning
+          .push(format!("exported=\"{}\" is not a boolean", exported));
+      }
+    },
+    None => {
+      state.reasoning.push("no exported attribute".to_string());
+      if component.kind == ComponentKind::Provider {
+        state.exported = target_sdk < JELLY_BEAN_MR1;
+        state.reason = if state.exported {
+          ExportedReason::LegacyProvider
+        } else {
+          ExportedReason::Provider
+        };
+        state.reasoning.push(format!(
+          "provider of an app targeting API {}, providers are only exported by default below API {}",
+          target_sdk, JELLY_BEAN_MR1
+        ));
+      } else if filters > 0 && target_sdk >= S {
+        state.exported = false;
+        state.reason = ExportedReason::MissingExported;
+        state.reasoning.push(format!(
+          "has {} intent filters and targets API {}, the APK can't be installed",
+          filters, target_sdk
+        ));
+      } else if filters > 0 {
+        state.exported = true;
+        state.reason = ExportedReason::IntentFilters;
+        state
+          .reasoning
+          .push(format!("has {} intent filters", filters));
+      } else {
+        state.exported = false;
+        state.reason = ExportedReason::NoIntentFilters;
+        state.reasoning.push("has no intent filters".to_string());
+      }
+    }
+  }
+
+  let disabled = [
+    ("component", &component.attributes),
+    ("application", application),
+  ]
+  .into_iter()
+  .find(|(_, attributes)| attributes.get_bool("enabled") == Some(false));
+  if let Some((owner, _)) = disabled {
+    if state.exported {
+      state.exported = false;
+      state.reason = ExportedReason::Disabled;
+    }
+    state
+      .reasoning
+      .push(format!("{} has enabled=\"false\"", owner));
+  }
+
+  let attribute = |name| component.attributes.get(name).map(str::to_string);
+  // An alias has the permission of its target unless it sets its own
+  state.permission = attribute("permission")
+    .or_else(|| target.and_then(|target| target.attributes.get("permission").map(str::to_string)))
+    .or_else(|| application.get("permission").map(str::to_string));
+  if component.kind == ComponentKind::Provider {
+    state.read_permission = attribute("readPermission");
+    state.write_permission = attribute("writePermission");
+  }
+  if state.exported {
+    for (name, permission) in [
+      ("permission", &state.permission),
+      ("readPermission", &state.read_permission),
+      ("writePermission", &state.write_permission),
+    ] {
+      if let Some(permission) = permission {
+        state
+          .reasoning
+          .push(format!("callers need {} {}", name, permission));
+      }
+    }
+  }
+  state
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::manifest::Manifest;
+  use anyhow::Result;
+
+  fn states(target_sdk: u32) -> Result<Vec<(String, bool, ExportedReason)>> {
+    let xml = format!(
+      r#"<manifest package="com.example">
+<uses-sdk minSdkVersion="16" targetSdkVersion="{}"></uses-sdk>
+<application permission="com.example.APP">
+  <activity name=".Explicit" exported="true"></activity>
+  <activity name=".Filters"><intent-filter><action name="a"></action></intent-filter></activity>
+  <activity name=".Private"></activity>
+  <activity name=".Disabled" exported="true" enabled="false"></activity>
+  <activity-alias name=".Alias" exported="@bool/exported"></activity-alias>
+  <service name=".Service" exported="true" permission="com.example.BIND"></service>
+  <receiver name=".Receiver"><intent-filter><action name="b"></action></intent-filter></receiver>
+  <provider name=".Provider" authorities="com.example"></provider>
+</application>
+</manifest>"#,
+      target_sdk
+    );
+    let manifest = Manifest::from_xml(xml.as_bytes())?;
+    Ok(
+      manifest
+        .components()
+        .map(|component| {
+          let state = &component.effective_exported;
+          (
+            component
+              .name
+              .trim_start_matches("com.example.")
+              .to_string(),
+            state.exported,
+            state.reason,
+          )
+        })
+        .collect(),
+    )
+  }
+
+  #[test]
+  fn test_effective_exported() -> Result<()> {
+    use ExportedReason::*;
+    let expected = |filters, provider| {
+      [
+        ("Explicit".to_string(), true, Explicit),
+        ("Filters".to_string(), filters == IntentFilters, filters),
+        ("Private".to_string(), false, NoIntentFilters),
+        ("Disabled".to_string(), false, Disabled),
+        ("Alias".to_string(), true, Unresolved),
+        ("Service".to_string(), true, Explicit),
+        ("Receiver".to_string(), filters == IntentFilters, filters),
+        ("Provider".to_string(), provider == LegacyProvider, provider),
+      ]
+    };
+    assert_eq!(states(16)?, expected(IntentFilters, LegacyProvider));
+    assert_eq!(states(30)?, expected(IntentFilters, Provider));
+    assert_eq!(states(31)?, expected(MissingExported, Provider));
+
+    let manifest = Manifest::from_xml(
+      br#"<manifest package="com.example"><application permission="com.example.APP">
+<activity name=".A" exported="true"></activity>
+<activity name=".B" exported="true" permission="com.example.B"></activity>
+<activity-alias name=".AliasB" targetActivity=".B" exported="true"></activity-alias>
+<activity-alias name=".AliasOwn" targetActivity="com.example.B" exported="true" permission="com.example.OWN"></activity-alias>
+<service name=".S" exported="true" permission="com.example.BIND"></service>
+<provider name=".P" exported="true" readPermission="com.example.READ"></provider>
+</application></manifest>"#,
+    )?;
+    let application = manifest.application.as_ref().unwrap();
+    let aliases = application
+      .activity_aliases
+      .iter()
+      .map(|alias| alias.effective_exported.permission.as_deref())
+      .collect::<Vec<_>>();
+    assert_eq!(aliases, [Some("com.example.B"), Some("com.example.OWN")]);
+    assert_eq!(ExportedState::default().reason, Undetermined);
+    let activity = &application.activities[0].effective_exported;
+    assert_eq!(activity.permission.as_deref(), Some("com.example.APP"));
+    assert!(!activity.is_unprotected());
+    assert_eq!(
+      application.services[0]
+        .effective_exported
+        .permission
+        .as_deref(),
+      Some("com.example.BIND")
+    );
+    let provider = &application.providers[0].effective_exported;
+    assert_eq!(
+      provider.read_permission.as_deref(),
+      Some("com.example.READ")
+    );
+    assert_eq!(
+      provider.reasoning,
+      [
+        "exported=\"true\"",
+        "callers need permission com.example.APP",
+        "callers need readPermission com.example.READ"
+      ]
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,362 @@
+use crate::arsc_parser::{Arsc, EntryValue, ResourceName, Value};
+use crate::parser::ApkArchive;
+use crate::res_config::ResConfig;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const DEX_HEADER_SIZE: usize = 112;
+
+/// Compression and placement of an archive entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryInfo {
+  pub name: String,
+  /// ZIP compression method, 0 for stored and 8 for deflated.
+  pub method: u16,
+  pub compressed_size: u64,
+  pub uncompressed_size: u64,
+  /// Offset of the data in the APK, `None` if the local file header is broken.
+  pub data_offset: Option<u64>,
+}
+
+impl EntryInfo {
+  pub fn is_compressed(&self) -> bool {
+    self.method != 0
+  }
+
+  /// Alignment `zipalign` gives stored entries, which Android maps straight from the APK:
+  /// native libraries are page aligned and everything else is 4 byte aligned.
+  pub fn required_alignment(&self) -> u64 {
+    if self.is_compressed() {
+      1
+    } else if self.name.starts_with("lib/") && self.name.ends_with(".so") {
+      4096
+    } else {
+      4
+    }
+  }
+
+  pub fn is_aligned(&self) -> bool {
+    self
+      .data_offset
+      .is_some_and(|offset| offset % self.required_alignment() == 0)
+  }
+}
+
+/// Sizes of the sections of a DEX file, from its header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DexFile {
+  pub name: String,
+  /// Format version, e.g. `035`.
+  pub version: String,
+  pub size: u64,
+  pub string_ids: u32,
+  pub type_ids: u32,
+  pub proto_ids: u32,
+  pub field_ids: u32,
+  pub method_ids: u32,
+  pub class_defs: u32,
+}
+
+impl DexFile {
+  /// Reads the header, `None` if the data isn't a DEX file.
+  pub fn parse(
+    name: &str,
+    dex: &[u8],
+  ) -> Option<Self> {
+    if dex.len() < DEX_HEADER_SIZE || !dex.starts_with(b"dex\n") {
+      return None;
+    }
+    let count = |offset: usize| u32::from_le_bytes(dex[offset..offset + 4].try_into().unwrap());
+    Some(Self {
+      name: name.to_string(),
+      version: String::from_utf8_lossy(&dex[4..7]).to_string(),
+      size: dex.len() as u64,
+      string_ids: count(56),
+      type_ids: count(64),
+      proto_ids: count(72),
+      field_ids: count(80),
+      method_ids: count(88),
+      class_defs: count(96),
+    })
+  }
+}
+
+/// A resource used by a file of the APK: the table entry whose value is the file path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileResource {
+  pub id: u32,
+  pub name: Option<ResourceName>,
+  pub config: ResConfig,
+}
+
+/// A file resource, e.g. a layout or drawable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceFile {
+  pub path: String,
+  /// Usually a single id in a single configuration. Empty for files the table doesn't
+  /// reference.
+  pub resources: Vec<FileResource>,
+}
+
+/// What an APK contains besides its manifest, each entry in exactly one group.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Inventory {
+  /// Every entry in central directory order, duplicates included.
+  pub entries: Vec<EntryInfo>,
+  /// `classes.dex`, `classes2.dex`, ... in loading order.
+  pub dex_files: Vec<DexFile>,
+  /// Entries of `lib/` keyed by ABI, e.g. `arm64-v8a`.
+  pub native_libraries: BTreeMap<String, Vec<String>>,
+  pub assets: Vec<String>,
+  /// Files of `res/`, and files elsewhere that the table references, e.g. after resource
+  /// path obfuscation.
+  pub resource_files: Vec<ResourceFile>,
+  pub meta_inf: Vec<String>,
+  /// Everything else, such as `AndroidManifest.xml`, `resources.arsc`, Java resources or
+  /// broken DEX files.
+  pub other: Vec<String>,
+}
+
+impl Inventory {
+  /// Lists the entries of an archive, mapping files to resources through its table.
+  pub fn from_archive(
+    archive: &ApkArchive,
+    arsc: Option<&Arsc>,
+  ) -> Self {
+    let file_resources = arsc.map(file_resources).unwrap_or_default();
+    let mut inventory = Self::default();
+    let mut names = HashSet::new();
+    for entry in archive.entries() {
+      inventory.entries.push(EntryInfo {
+        name: entry.name.clone(),
+        method: entry.method,
+        compressed_size: entry.compressed_size,
+        uncompressed_size: entry.uncompressed_size,
+        data_offset: archive.data_offset(entry).map(|offset| offset as u64),
+      });
+      // Android only sees the first of duplicate entries
+      if entry.name.ends_with('/') || !names.insert(entry.name.as_str()) {
+        continue;
+      }
+
+      let name = entry.name.clone();
+      let dex = match dex_index(&name) {
+        Some(_) => archive
+          .extract(entry)
+          .ok()
+          .and_then(|dex| DexFile::parse(&name, &dex)),
+        None => None,
+      };
+      if let Some(dex) = dex {
+        inventory.dex_files.push(dex);
+      } else if let Some((abi, _)) = name
+        .strip_prefix("lib/")
+        .and_then(|path| path.split_once('/'))
+      {
+        inventory
+          .native_libraries
+          .entry(abi.to_string())
+          .or_default()
+          .push(name);
+      } else if name.starts_with("assets/") {
+        inventory.assets.push(name);
+      } else if name.starts_with("res/") || file_resources.contains_key(name.as_str()) {
+        let resources = file_resources
+          .get(name.as_str())
+          .cloned()
+          .unwrap_or_default();
+        inventory.resource_files.push(ResourceFile {
+          path: name,
+          resources,
+        });
+      } else if name.starts_with("META-INF/") {
+        inventory.meta_inf.push(name);
+      } else {
+        inventory.other.push(name);
+      }
+    }
+    inventory.dex_files.sort_by_key(|dex| dex_index(&dex.name));
+    inventory
+  }
+
+  pub fn entry(
+    &self,
+    name: &str,
+  ) -> Option<&EntryInfo> {
+    self.entries.iter().find(|entry| entry.name == name)
+  }
+
+  /// Stored entries whose data isn't at the offset `zipalign` would put it.
+  pub fn misaligned_entries(&self) -> Vec<&EntryInfo> {
+    self
+      .entries
+      .iter()
+      .filter(|entry| !entry.is_aligned())
+      .collect()
+  }
+
+  pub fn abis(&self) -> Vec<&str> {
+    self.native_libraries.keys().map(String::as_str).collect()
+  }
+}
+
+impl std::fmt::Display for Inventory {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    writeln!(f, "dex")?;
+    for dex in &self.dex_files {
+      writeln!(
+        f,
+        "  {} (version {}, {} classes, {} methods, {} fields, {} strings)",
+        dex.name, dex.version, dex.class_defs, dex.method_ids, dex.field_ids, dex.string_ids
+      )?;
+    }
+    writeln!(f, "native libraries")?;
+    for (abi, libraries) in &self.native_libraries {
+      writeln!(f, "  {}: {}", abi, libraries.len())?;
+      for library in libraries {
+        writeln!(f, "    {}", library)?;
+      }
+    }
+    writeln!(f, "assets: {}", self.assets.len())?;
+    writeln!(f, "resource files: {}", self.resource_files.len())?;
+    writeln!(f, "META-INF")?;
+    for name in &self.meta_inf {
+      writeln!(f, "  {}", name)?;
+    }
+    writeln!(f, "other: {}", self.other.len())?;
+    for entry in self.misaligned_entries() {
+      writeln!(
+        f,
+        "misaligned: {} (needs {} byte alignment)",
+        entry.name,
+        entry.required_alignment()
+      )?;
+    }
+    Ok(())
+  }
+}
+
+// Loading order of classes.dex, classes2.dex, ... at the root of the APK.
+fn dex_index(name: &str) -> Option<u32> {
+  let number = name.strip_prefix("classes")?.strip_suffix(".dex")?;
+  match number {
+    "" => Some(1),
+    _ => number.parse().ok().filter(|number| *number > 1),
+  }
+}
+
+// Resources whose value is a file path, keyed by that path.
+fn file_resources(arsc: &Arsc) -> HashMap<String, Vec<FileResource>> {
+  let mut file_resources: HashMap<String, Vec<FileResource>> = HashMap::new();
+  for package_id in arsc.package_ids() {
+    let Some(package) = arsc.package(package_id) else {
+      continue;
+    };
+    for type_chunk in &package.type_chunks {
+      for (entry_id, entry) in type_chunk.entries.iter().enumerate() {
+        let Some(EntryValue::Simple(Value::String(path))) = entry.as_ref().map(|e| &e.value) else {
+          continue;
+        };
+        let id = (package_id << 24) | ((type_chunk.type_id as u32) << 16) | entry_id as u32;
+        file_resources
+          .entry(path.clone())
+          .or_default()
+          .push(FileResource {
+            id,
+            name: arsc.resource_name(id),
+            config: type_chunk.res_config(),
+          });
+      }
+    }
+  }
+  file_resources
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use crate::test_util::{stored, zip_with};
+  use anyhow::{Context, Result};
+
+  fn dex(class_defs: u32) -> Vec<u8> {
+    let mut dex = b"dex\n035\0".to_vec();
+    dex.resize(DEX_HEADER_SIZE, 0);
+    dex[96..100].copy_from_slice(&class_defs.to_le_bytes());
+    dex
+  }
+
+  #[test]
+  fn test_inventory() -> Result<()> {
+    let manifest = std::fs::read("../data/xml/AndroidManifest.xml")?;
+    let arsc_bytes = std::fs::read(
+      "../data/arsc/ab4cb5175fc0827860b9c3361d8bf2aec99a71f63b5a7666ae2403b0030ac56f.arsc",
+    )?;
+    let mut arsc = Arsc::new(&arsc_bytes);
+    arsc.parse()?;
+    let resources = file_resources(&arsc);
+    let layout = resources
+      .keys()
+      .filter(|path| path.starts_with("res/layout"))
+      .min()
+      .context("no layout file")?;
+
+    let apk = zip_with(
+      &[
+        ("AndroidManifest.xml", &manifest),
+        ("classes2.dex", &dex(7)),
+        ("classes.dex", &dex(3)),
+        ("resources.arsc", &arsc_bytes),
+        ("assets/config.json", b"{}"),
+        (layout, b"layout"),
+        ("res/raw/unreferenced.bin", b"data"),
+        ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\r\n"),
+        ("lib/arm64-v8a/libfoo.so", b"\x7fELF"),
+        ("lib/x86_64/libfoo.so", b"\x7fELF"),
+      ],
+      |name| match name {
+        "lib/arm64-v8a/libfoo.so" => (stored(), 4096),
+        "lib/x86_64/libfoo.so" => (stored(), 0),
+        _ => (zip::write::FileOptions::default(), 0),
+      },
+    )?;
+
+    let inventory = Parser::from_bytes(&apk)?.inventory()?;
+    let dex_files = inventory
+      .dex_files
+      .iter()
+      .map(|dex| (dex.name.as_str(), dex.class_defs))
+      .collect::<Vec<_>>();
+    assert_eq!(dex_files, [("classes.dex", 3), ("classes2.dex", 7)]);
+    assert_eq!(inventory.abis(), ["arm64-v8a", "x86_64"]);
+    assert_eq!(
+      inventory.native_libraries["arm64-v8a"],
+      ["lib/arm64-v8a/libfoo.so"]
+    );
+    assert_eq!(inventory.assets, ["assets/config.json"]);
+    assert_eq!(inventory.meta_inf, ["META-INF/MANIFEST.MF"]);
+    assert_eq!(inventory.other, ["AndroidManifest.xml", "resources.arsc"]);
+
+    assert_eq!(inventory.resource_files.len(), 2);
+    let layout_file = &inventory.resource_files[0];
+    assert_eq!(layout_file.path, *layout);
+    assert_eq!(layout_file.resources, resources[layout]);
+    let name = layout_file.resources[0].name.as_ref().unwrap();
+    assert_eq!(name.type_name, "layout");
+    assert!(inventory.resource_files[1].resources.is_empty());
+
+    let library = inventory.entry("lib/arm64-v8a/libfoo.so").unwrap();
+    assert!(!library.is_compressed());
+    assert_eq!(library.required_alignment(), 4096);
+    assert!(library.is_aligned());
+    assert!(inventory.entry("classes.dex").unwrap().is_compressed());
+    let unaligned = inventory.entry("lib/x86_64/libfoo.so").unwrap();
+    assert_eq!(
+      unaligned.is_aligned(),
+      unaligned.data_offset.unwrap() % 4096 == 0
+    );
+    Ok(())
+  }
+}
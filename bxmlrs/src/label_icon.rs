@@ -0,0 +1,373 @@
+use crate::arsc_parser::{Arsc, EntryValue, Value};
+use crate::manifest::{Element, Manifest};
+use crate::nom_parser::ResType;
+use crate::parser::ApkArchive;
+use crate::res_config::ResConfig;
+use crate::xml_parser::AndroidManifest;
+
+/// The label in one configuration of the resource table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalizedLabel {
+  pub config: ResConfig,
+  pub label: String,
+}
+
+/// A layer of an adaptive icon and what its drawable resolves to in each configuration.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IconLayer {
+  /// The `drawable` attribute, `None` for drawables aapt2 inlined into the element.
+  pub reference: Option<String>,
+  /// File paths, or `#aarrggbb` for color layers.
+  pub values: Vec<(ResConfig, String)>,
+}
+
+/// The layers of an `<adaptive-icon>`, usually in `mipmap-anydpi-v26`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AdaptiveIcon {
+  pub background: Option<IconLayer>,
+  pub foreground: Option<IconLayer>,
+  /// Themed icon layer, API 33 and later.
+  pub monochrome: Option<IconLayer>,
+}
+
+/// The icon file of one configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IconVariant {
+  pub config: ResConfig,
+  pub path: String,
+  /// The decoded layers if the file is an adaptive icon.
+  pub adaptive: Option<AdaptiveIcon>,
+}
+
+impl IconVariant {
+  /// A bitmap rather than an XML drawable.
+  pub fn is_bitmap(&self) -> bool {
+    !self.path.ends_with(".xml")
+  }
+}
+
+/// The application label in every configuration and the icons in every density.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabelAndIcon {
+  /// The `label` attribute as written, a literal or a `@res/0x...` reference.
+  pub label: Option<String>,
+  pub labels: Vec<LocalizedLabel>,
+  pub icon: Option<String>,
+  pub icons: Vec<IconVariant>,
+  pub round_icon: Option<String>,
+  pub round_icons: Vec<IconVariant>,
+}
+
+impl LabelAndIcon {
+  /// Resolves the `label`, `icon` and `roundIcon` of the application through `arsc`. The
+  /// manifest has to be decoded without a table so the attributes still hold the references.
+  /// Adaptive icons are decoded from `archive`.
+  pub fn from_manifest(
+    manifest: &Manifest,
+    archive: Option<&ApkArchive>,
+    arsc: Option<&Arsc>,
+  ) -> Self {
+    let Some(application) = &manifest.application else {
+      return Self::default();
+    };
+    let attribute = |name| application.attributes.get(name).map(str::to_string);
+    let label = attribute("label");
+    let icon = attribute("icon");
+    let round_icon = attribute("roundIcon");
+    let icons = |icon: &Option<String>| {
+      icon
+        .as_deref()
+        .map(|icon| icon_variants(icon, archive, arsc))
+        .unwrap_or_default()
+    };
+    Self {
+      labels: label
+        .as_deref()
+        .map(|label| resource_values(label, arsc))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(config, label)| LocalizedLabel { config, label })
+        .collect(),
+      icons: icons(&icon),
+      round_icons: icons(&round_icon),
+      label,
+      icon,
+      round_icon,
+    }
+  }
+
+  /// The label a device in `locale`, e.g. `pt-BR`, shows: an exact match, then the language,
+  /// then the default.
+  pub fn label_for(
+    &self,
+    locale: &str,
+  ) -> Option<&str> {
+    let language = locale.split('-').next().unwrap_or_default();
+    let find = |matches: &dyn Fn(&ResConfig) -> bool| {
+      self
+        .labels
+        .iter()
+        .find(|label| matches(&label.config))
+        .map(|label| label.label.as_str())
+    };
+    find(&|config| config.locale().as_deref() == Some(locale))
+      .or_else(|| find(&|config| config.locale().as_deref() == Some(language)))
+      .or_else(|| find(&|config| config.language().is_none()))
+  }
+
+  /// The icon bitmap Android picks for a screen of `density` dpi, preferring a close higher
+  /// density over a lower one as `ResTable_config::isBetterThan` does.
+  pub fn best_icon(
+    &self,
+    density: u16,
+  ) -> Option<&IconVariant> {
+    self
+      .icons
+      .iter()
+      .filter(|icon| icon.is_bitmap())
+      .reduce(|best, icon| {
+        match is_better_density(icon.config.density, best.config.density, density) {
+          true => icon,
+          false => best,
+        }
+      })
+  }
+}
+
+// Density matching of ResTable_config::isBetterThan: scaling down is preferred to scaling up,
+// unless the higher density is more than twice as far away.
+fn is_better_density(
+  this: u16,
+  other: u16,
+  requested: u16,
+) -> bool {
+  let density = |density: u16| match density {
+    ResConfig::DENSITY_DEFAULT => ResConfig::DENSITY_MEDIUM as i64,
+    // Never scaled, only a fallback
+    ResConfig::DENSITY_NONE | ResConfig::DENSITY_ANY => 0,
+    density => density as i64,
+  };
+  let (this, other, requested) = (density(this), density(other), requested as i64);
+  if this == other {
+    return false;
+  }
+  let (high, low, this_is_higher) = match this > other {
+    true => (this, other, true),
+    false => (other, this, false),
+  };
+  if requested >= high {
+    return this_is_higher;
+  }
+  if low >= requested {
+    return !this_is_higher;
+  }
+  match (2 * low - requested) * high > requested * requested {
+    true => !this_is_higher,
+    false => this_is_higher,
+  }
+}
+
+// Resource id of a `@res/0x7f...` or `@dyn/0x7F...` reference.
+fn reference_id(value: &str) -> Option<u32> {
+  let hex = value
+    .strip_prefix("@res/0x")
+    .or_else(|| value.strip_prefix("@dyn/0x"))?;
+  u32::from_str_radix(hex, 16).ok()
+}
+
+// The value of a reference in every configuration, or a literal as the default value.
+fn resource_values(
+  value: &str,
+  arsc: Option<&Arsc>,
+) -> Vec<(ResConfig, String)> {
+  let (Some(res_id), Some(arsc)) = (reference_id(value), arsc) else {
+    return match value.starts_with('@') {
+      true => vec![],
+      false => vec![(ResConfig::default(), value.to_string())],
+    };
+  };
+  arsc
+    .entry_values(res_id)
+    .into_iter()
+    .filter_map(|(config, entry)| match &entry.value {
+      EntryValue::Simple(value) => Some((config, value_string(value, arsc)?)),
+      EntryValue::Complex { .. } => None,
+    })
+    .collect()
+}
+
+fn value_string(
+  value: &Value,
+  arsc: &Arsc,
+) -> Option<String> {
+  match value {
+    Value::Data { data_type, data } => match *data_type {
+      // An alias, e.g. a label pointing at the app_name string
+      ResType::REFERENCE => arsc.get_res_value(*data),
+      ResType::INT_COLOR_ARGB8
+      | ResType::INT_COLOR_RGB8
+      | ResType::INT_COLOR_ARGB4
+      | ResType::INT_COLOR_RGB4 => Some(format!("#{:08x}", data)),
+      _ => value.as_string(),
+    },
+    _ => value.as_string(),
+  }
+}
+
+fn icon_variants(
+  icon: &str,
+  archive: Option<&ApkArchive>,
+  arsc: Option<&Arsc>,
+) -> Vec<IconVariant> {
+  resource_values(icon, arsc)
+    .into_iter()
+    .map(|(config, path)| IconVariant {
+      adaptive: match path.ends_with(".xml") {
+        true => archive.and_then(|archive| adaptive_icon(archive, &path, arsc)),
+        false => None,
+      },
+      config,
+      path,
+    })
+    .collect()
+}
+
+fn adaptive_icon(
+  archive: &ApkArchive,
+  path: &str,
+  arsc: Option<&Arsc>,
+) -> Option<AdaptiveIcon> {
+  let binary_xml = archive.extract(archive.by_name(path)?).ok()?;
+  // Decoded without the table, the layers are resolved in every configuration below
+  let xml = AndroidManifest::new(&binary_xml).parse(None).ok()?;
+  let root = Element::parse(&xml).ok()?;
+  if root.name != "adaptive-icon" {
+    return None;
+  }
+  let layer = |name| {
+    let element = root.children_named(name).next()?;
+    let reference = element.attribute("drawable").map(str::to_string);
+    Some(IconLayer {
+      values: reference
+        .as_deref()
+        .map(|reference| resource_values(reference, arsc))
+        .unwrap_or_default(),
+      reference,
+    })
+  };
+  Some(AdaptiveIcon {
+    background: layer("background"),
+    foreground: layer("foreground"),
+    monochrome: layer("monochrome"),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{binary_xml, zip, AttributeValue, XmlElement};
+  use anyhow::{Context, Result};
+
+  const DRAWABLE_ATTR: u32 = 0x01010199;
+
+  fn spec_id(
+    arsc: &Arsc,
+    type_name: &str,
+    name: &str,
+  ) -> Result<u32> {
+    Ok(
+      arsc
+        .spec_entries()
+        .into_iter()
+        .find(|spec_entry| spec_entry.type_name == type_name && spec_entry.name == name)
+        .context(format!("{}/{} not found", type_name, name))?
+        .id,
+    )
+  }
+
+  #[test]
+  fn test_label_and_icon() -> Result<()> {
+    let arsc_bytes = std::fs::read(
+      "../data/arsc/08553817f72693d0a68778cceec12fef1b223c4c1e1f182a07dd391e4023e8d4.arsc",
+    )?;
+    let mut arsc = Arsc::new(&arsc_bytes);
+    arsc.parse()?;
+    let manifest = Manifest::from_xml(
+      format!(
+        r#"<manifest package="com.example"><application label="@res/0x{:x}"></application></manifest>"#,
+        spec_id(&arsc, "string", "app_name")?
+      )
+      .as_bytes(),
+    )?;
+    let labels = LabelAndIcon::from_manifest(&manifest, None, Some(&arsc));
+    assert!(labels.labels.len() > 30);
+    assert_eq!(labels.label_for("en-US"), Some("Amaze File Manager"));
+    assert_eq!(labels.label_for("ja"), Some("Amaze ファイルマネージャー"));
+    assert_eq!(labels.label_for("zh-CN"), Some("文件管理器"));
+    assert_eq!(labels.label_for("sv-FI"), Some("Förvåna"));
+
+    let arsc_bytes = std::fs::read(
+      "../data/arsc/547638973a12aeae1029b1d6b2411faef06c4f403d9d42d4bb4ddbb5c500c37c.arsc",
+    )?;
+    let mut arsc = Arsc::new(&arsc_bytes);
+    arsc.parse()?;
+    let icon = spec_id(&arsc, "mipmap", "ic_launcher")?;
+    let background = spec_id(&arsc, "mipmap", "ic_launcher_round")?;
+    let foreground = spec_id(&arsc, "string", "app_name")?;
+    let manifest = Manifest::from_xml(
+      format!(
+        r#"<manifest package="com.example"><application label="Example" icon="@res/0x{:x}"></application></manifest>"#,
+        icon
+      )
+      .as_bytes(),
+    )?;
+    let layer = |name, drawable| {
+      XmlElement::new(name).attribute(
+        "drawable",
+        Some(DRAWABLE_ATTR),
+        AttributeValue::Data(ResType::REFERENCE, drawable),
+      )
+    };
+    let adaptive_icon_xml = binary_xml(
+      &XmlElement::new("adaptive-icon")
+        .child(layer("background", background))
+        .child(layer("foreground", foreground)),
+    )?;
+    let archive = ApkArchive::new(zip(&[(
+      "res/mipmap-anydpi-v26/ic_launcher.xml",
+      &adaptive_icon_xml,
+    )])?)?;
+
+    let icons = LabelAndIcon::from_manifest(&manifest, Some(&archive), Some(&arsc));
+    assert_eq!(icons.label_for("de"), Some("Example"));
+    assert_eq!(icons.icons.len(), 6);
+    let best = |density| icons.best_icon(density).map(|icon| icon.path.as_str());
+    assert_eq!(best(480), Some("res/mipmap-xxhdpi-v4/ic_launcher.png"));
+    assert_eq!(best(400), Some("res/mipmap-xxhdpi-v4/ic_launcher.png"));
+    assert_eq!(best(280), Some("res/mipmap-xhdpi-v4/ic_launcher.png"));
+    assert_eq!(best(100), Some("res/mipmap-mdpi-v4/ic_launcher.png"));
+    assert_eq!(best(1000), Some("res/mipmap-xxxhdpi-v4/ic_launcher.png"));
+
+    let adaptive = icons.icons[5]
+      .adaptive
+      .as_ref()
+      .context("adaptive icon not decoded")?;
+    let background_layer = adaptive.background.as_ref().context("no background")?;
+    assert_eq!(
+      background_layer.reference,
+      Some(format!("@res/0x{:x}", background))
+    );
+    assert_eq!(background_layer.values.len(), 6);
+    assert_eq!(
+      adaptive
+        .foreground
+        .as_ref()
+        .context("no foreground")?
+        .values[0]
+        .1,
+      "Example"
+    );
+    assert_eq!(adaptive.monochrome, None);
+    Ok(())
+  }
+}
@@ -1,5 +1,30 @@
+pub mod aab;
+pub mod aapt_container;
+pub mod abx;
+pub mod arsc_diff;
 pub mod arsc_parser;
+pub mod arsc_writer;
 mod attributes;
+pub mod bundle;
+pub mod deep_links;
+mod der;
+pub mod exported;
+pub mod inventory;
+pub mod label_icon;
+pub mod lint;
+pub mod manifest;
+pub mod manifest_diff;
+pub mod network_security_config;
 mod nom_parser;
 pub mod parser;
+mod permission_table;
+pub mod permissions;
+mod proto;
+pub mod res_config;
+pub mod scan;
+pub mod signature;
+#[cfg(test)]
+mod test_util;
+pub mod values_decoder;
 pub mod xml_parser;
+pub mod xml_resources;
@@ -0,0 +1,761 @@
+use crate::exported::ExportedReason;
+use crate::manifest::{Component, ComponentKind, Manifest};
+use crate::permissions::{PermissionInfo, Protection};
+
+// Android 12, from which dataExtractionRules replace allowBackup for device-to-device transfers
+const S: u32 = 31;
+
+/// How bad a finding is, ordered from least to most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+  Info,
+  Low,
+  Medium,
+  High,
+}
+
+impl std::fmt::Display for Severity {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    let name = match self {
+      Severity::Info => "info",
+      Severity::Low => "low",
+      Severity::Medium => "medium",
+      Severity::High => "high",
+    };
+    f.write_str(name)
+  }
+}
+
+/// A problem a rule found in the manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+  pub rule_id: String,
+  pub severity: Severity,
+  /// Path of the offending element, e.g. `manifest/application/activity[com.example.Main]`.
+  pub path: String,
+  pub message: String,
+}
+
+impl Finding {
+  pub fn new(
+    rule: &dyn Rule,
+    severity: Severity,
+    path: impl Into<String>,
+    message: impl Into<String>,
+  ) -> Self {
+    Self {
+      rule_id: rule.id().to_string(),
+      severity,
+      path: path.into(),
+      message: message.into(),
+    }
+  }
+}
+
+impl std::fmt::Display for Finding {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    write!(
+      f,
+      "[{}] {} {}: {}",
+      self.severity, self.rule_id, self.path, self.message
+    )
+  }
+}
+
+/// A check over the decoded manifest. Implement it to add rules to a `Linter`.
+pub trait Rule {
+  /// Stable identifier such as `debuggable`, for filtering and suppressing findings.
+  fn id(&self) -> &'static str;
+
+  fn description(&self) -> &'static str;
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding>;
+}
+
+/// Runs a set of rules over a manifest.
+pub struct Linter {
+  rules: Vec<Box<dyn Rule>>,
+}
+
+impl Default for Linter {
+  /// A linter with the built-in rules.
+  fn default() -> Self {
+    Self::new(vec![
+      Box::new(Debuggable),
+      Box::new(AllowBackup),
+      Box::new(CleartextTraffic),
+      Box::new(ExportedWithoutPermission),
+      Box::new(MissingExported),
+      Box::new(NormalProtectionLevel),
+      Box::new(TaskAffinity),
+      Box::new(SharedUserId),
+      Box::new(MissingNetworkSecurityConfig),
+      Box::new(OutdatedTargetSdk::default()),
+    ])
+  }
+}
+
+impl Linter {
+  pub fn new(rules: Vec<Box<dyn Rule>>) -> Self {
+    Self { rules }
+  }
+
+  pub fn add_rule(
+    &mut self,
+    rule: Box<dyn Rule>,
+  ) {
+    self.rules.push(rule);
+  }
+
+  pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+    self.rules.iter().map(|rule| rule.as_ref())
+  }
+
+  /// Findings of all rules, most severe first and otherwise in the order of the rules.
+  pub fn run(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    let mut findings = self
+      .rules
+      .iter()
+      .flat_map(|rule| rule.check(manifest))
+      .collect::<Vec<_>>();
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+    findings
+  }
+}
+
+const APPLICATION: &str = "manifest/application";
+
+fn component_path(component: &Component) -> String {
+  format!(
+    "{}/{}[{}]",
+    APPLICATION,
+    component.kind.element_name(),
+    component.name
+  )
+}
+
+fn application_attribute<'a>(
+  manifest: &'a Manifest,
+  name: &str,
+) -> Option<&'a str> {
+  manifest.application.as_ref()?.attributes.get(name)
+}
+
+fn application_flag(
+  manifest: &Manifest,
+  name: &str,
+) -> Option<bool> {
+  manifest.application.as_ref()?.attributes.get_bool(name)
+}
+
+/// `android:debuggable="true"`, anyone with adb can attach a debugger and run code as the app.
+pub struct Debuggable;
+
+impl Rule for Debuggable {
+  fn id(&self) -> &'static str {
+    "debuggable"
+  }
+
+  fn description(&self) -> &'static str {
+    "The application is debuggable"
+  }
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    match application_flag(manifest, "debuggable") {
+      Some(true) => vec![Finding::new(
+        self,
+        Severity::High,
+        APPLICATION,
+        "debuggable=\"true\" lets anyone with adb run code as the app",
+      )],
+      _ => vec![],
+    }
+  }
+}
+
+/// App data copied off the device by backups or device-to-device transfers without rules
+/// limiting what is copied. Apps targeting API 31 or higher only follow `dataExtractionRules`
+/// on Android 12 and later, where `allowBackup="false"` no longer stops transfers, older
+/// devices and apps follow `allowBackup` and `fullBackupContent`.
+pub struct AllowBackup;
+
+impl Rule for AllowBackup {
+  fn id(&self) -> &'static str {
+    "allow-backup"
+  }
+
+  fn description(&self) -> &'static str {
+    "App data can be backed up or transferred without rules limiting what is copied"
+  }
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    if manifest.application.is_none() {
+      return vec![];
+    }
+    let allow_backup = application_flag(manifest, "allowBackup");
+    let full_backup_content = application_attribute(manifest, "fullBackupContent").is_some();
+    let data_extraction_rules = application_attribute(manifest, "dataExtractionRules").is_some();
+    let allowed = match application_attribute(manifest, "allowBackup") {
+      Some(_) => "allowBackup=\"true\"",
+      None => "allowBackup defaults to true and",
+    };
+    let finding =
+      |severity, message: String| vec![Finding::new(self, severity, APPLICATION, message)];
+    if manifest.target_sdk() < S {
+      return match allow_backup == Some(false) || full_backup_content {
+        true => vec![],
+        false => finding(
+          Severity::Medium,
+          format!(
+            "{} there is no fullBackupContent, app data can be copied off the device",
+            allowed
+          ),
+        ),
+      };
+    }
+    match (allow_backup, data_extraction_rules) {
+      (Some(false), false) => finding(
+        Severity::Low,
+        format!(
+          "allowBackup=\"false\" doesn't stop device-to-device transfers when targeting API {} \
+           and there are no dataExtractionRules",
+          manifest.target_sdk()
+        ),
+      ),
+      (_, false) => finding(
+        Severity::Medium,
+        format!(
+          "{} there are no dataExtractionRules, app data can be copied off the device",
+          allowed
+        ),
+      ),
+      // dataExtractionRules are ignored below Android 12
+      (None | Some(true), true) if manifest.min_sdk() < S && !full_backup_content => finding(
+        Severity::Low,
+        format!(
+          "{} there is no fullBackupContent, app data can be copied off devices below Android 12",
+          allowed
+        ),
+      ),
+      _ => vec![],
+    }
+  }
+}
+
+/// Cleartext HTTP allowed, explicitly or by default for apps targeting API 27 or lower.
+pub struct CleartextTraffic;
+
+impl Rule for CleartextTraffic {
+  fn id(&self) -> &'static str {
+    "cleartext-traffic"
+  }
+
+  fn description(&self) -> &'static str {
+    "The application allows cleartext network traffic"
+  }
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    // The network security config overrides the attribute
+    if manifest.application.is_none()
+      || application_attribute(manifest, "networkSecurityConfig").is_some()
+    {
+      return vec![];
+    }
+    match application_flag(manifest, "usesCleartextTraffic") {
+      Some(true) => vec![Finding::new(
+        self,
+        Severity::Medium,
+        APPLICATION,
+        "usesCleartextTraffic=\"true\" allows plain HTTP",
+      )],
+      None if manifest.target_sdk() < 28 => vec![Finding::new(
+        self,
+        Severity::Low,
+        APPLICATION,
+        format!(
+          "cleartext traffic is allowed by default when targeting API {}",
+          manifest.target_sdk()
+        ),
+      )],
+      _ => vec![],
+    }
+  }
+}
+
+/// Exported components other apps can use without holding a permission.
+pub struct ExportedWithoutPermission;
+
+impl Rule for ExportedWithoutPermission {
+  fn id(&self) -> &'static str {
+    "exported-without-permission"
+  }
+
+  fn description(&self) -> &'static str {
+    "Components other apps can reach without a permission"
+  }
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for component in manifest.components() {
+      let exported = &component.effective_exported;
+      // A missing exported attribute is reported once, by `MissingExported`
+      if exported.reason == ExportedReason::MissingExported
+        || !exported.is_unprotected()
+        || is_launcher(component)
+      {
+        continue;
+      }
+      let severity = match component.kind {
+        ComponentKind::Provider => Severity::High,
+        _ => Severity::Medium,
+      };
+      findings.push(Finding::new(
+        self,
+        severity,
+        component_path(component),
+        format!(
+          "exported without a permission ({})",
+          exported.reasoning.join(", ")
+        ),
+      ));
+    }
+    findings
+  }
+}
+
+/// Components with intent filters but no `android:exported`, which apps targeting API 31 or
+/// higher have to set.
+pub struct MissingExported;
+
+impl Rule for MissingExported {
+  fn id(&self) -> &'static str {
+    "missing-exported"
+  }
+
+  fn description(&self) -> &'static str {
+    "Components with intent filters don't set exported"
+  }
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    manifest
+      .components()
+      .filter(|component| component.effective_exported.reason == ExportedReason::MissingExported)
+      .map(|component| {
+        Finding::new(
+          self,
+          Severity::High,
+          component_path(component),
+          "intent filters without an exported attribute, the APK can't be installed on Android 12+",
+        )
+      })
+      .collect()
+  }
+}
+
+// Launcher activities have to be exported
+fn is_launcher(component: &Component) -> bool {
+  matches!(
+    component.kind,
+    ComponentKind::Activity | ComponentKind::ActivityAlias
+  ) && component.intent_filters.iter().any(|filter| {
+    filter.has_action("android.intent.action.MAIN")
+      && filter.has_category("android.intent.category.LAUNCHER")
+  })
+}
+
+/// Custom permissions any app can request because their protection level is `normal`.
+pub struct NormalProtectionLevel;
+
+impl Rule for NormalProtectionLevel {
+  fn id(&self) -> &'static str {
+    "normal-protection-level"
+  }
+
+  fn description(&self) -> &'static str {
+    "Custom permissions any app is granted"
+  }
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    manifest
+      .permissions
+      .iter()
+      .filter(|permission| {
+        PermissionInfo::from_definition(permission)
+          .protection_level
+          .protection
+          == Protection::Normal
+      })
+      .map(|permission| {
+        Finding::new(
+          self,
+          Severity::Low,
+          format!("manifest/permission[{}]", permission.name),
+          "protection level normal, every app that asks for the permission is granted it",
+        )
+      })
+      .collect()
+  }
+}
+
+/// Task affinities that let activities be placed in, or moved into, another app's task as in
+/// StrandHogg style task hijacking.
+pub struct TaskAffinity;
+
+impl Rule for TaskAffinity {
+  fn id(&self) -> &'static str {
+    "task-affinity"
+  }
+
+  fn description(&self) -> &'static str {
+    "Task affinities and task reparenting open to task hijacking"
+  }
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    let Some(application) = &manifest.application else {
+      return vec![];
+    };
+    let package = manifest.package.as_deref().unwrap_or_default();
+    let mut findings = Vec::new();
+    let mut check = |path: String, attributes: &crate::manifest::Attributes| {
+      if let Some(affinity) = attributes.get("taskAffinity") {
+        if !affinity.is_empty() && affinity != package {
+          findings.push(Finding::new(
+            self,
+            Severity::Medium,
+            path.clone(),
+            format!(
+              "taskAffinity \"{}\" differs from the package, the activity can join another app's task",
+              affinity
+            ),
+          ));
+        }
+      }
+      if attributes.get_bool("allowTaskReparenting") == Some(true) {
+        findings.push(Finding::new(
+          self,
+          Severity::Low,
+          path,
+          "allowTaskReparenting=\"true\" lets the activity move into another app's task",
+        ));
+      }
+    };
+    check(APPLICATION.to_string(), &application.attributes);
+    for activity in application
+      .activities
+      .iter()
+      .chain(&application.activity_aliases)
+    {
+      check(component_path(activity), &activity.attributes);
+    }
+    findings
+  }
+}
+
+/// `sharedUserId`, deprecated and sharing the data and permissions of other apps.
+pub struct SharedUserId;
+
+impl Rule for SharedUserId {
+  fn id(&self) -> &'static str {
+    "shared-user-id"
+  }
+
+  fn description(&self) -> &'static str {
+    "The app shares a Linux user id with other apps"
+  }
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    match manifest.attributes.get("sharedUserId") {
+      Some(user_id) => vec![Finding::new(
+        self,
+        Severity::Low,
+        "manifest",
+        format!(
+          "sharedUserId \"{}\" shares data and permissions with other apps signed with the same key",
+          user_id
+        ),
+      )],
+      None => vec![],
+    }
+  }
+}
+
+/// No `networkSecurityConfig`, leaving trust anchors and cleartext policy to the defaults.
+pub struct MissingNetworkSecurityConfig;
+
+impl Rule for MissingNetworkSecurityConfig {
+  fn id(&self) -> &'static str {
+    "missing-network-security-config"
+  }
+
+  fn description(&self) -> &'static str {
+    "The application has no network security config"
+  }
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    if manifest.application.is_none()
+      || application_attribute(manifest, "networkSecurityConfig").is_some()
+    {
+      return vec![];
+    }
+    vec![Finding::new(
+      self,
+      Severity::Info,
+      APPLICATION,
+      "no networkSecurityConfig, the platform defaults apply",
+    )]
+  }
+}
+
+/// A target SDK below what app stores require, keeping legacy platform behaviour.
+pub struct OutdatedTargetSdk {
+  pub min_target_sdk: u32,
+}
+
+impl Default for OutdatedTargetSdk {
+  fn default() -> Self {
+    // Google Play's requirement for new apps and updates since August 2025
+    Self { min_target_sdk: 35 }
+  }
+}
+
+impl Rule for OutdatedTargetSdk {
+  fn id(&self) -> &'static str {
+    "outdated-target-sdk"
+  }
+
+  fn description(&self) -> &'static str {
+    "The app targets an outdated API level"
+  }
+
+  fn check(
+    &self,
+    manifest: &Manifest,
+  ) -> Vec<Finding> {
+    let target_sdk = manifest.target_sdk();
+    if target_sdk >= self.min_target_sdk {
+      return vec![];
+    }
+    vec![Finding::new(
+      self,
+      Severity::Medium,
+      "manifest/uses-sdk",
+      format!(
+        "targetSdkVersion {} is below {}, legacy platform behaviour applies",
+        target_sdk, self.min_target_sdk
+      ),
+    )]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::Result;
+
+  struct NoQueries;
+
+  impl Rule for NoQueries {
+    fn id(&self) -> &'static str {
+      "no-queries"
+    }
+
+    fn description(&self) -> &'static str {
+      "The app doesn't declare package visibility queries"
+    }
+
+    fn check(
+      &self,
+      manifest: &Manifest,
+    ) -> Vec<Finding> {
+      match manifest.queries.packages.is_empty() {
+        true => vec![Finding::new(self, Severity::Info, "manifest", "no queries")],
+        false => vec![],
+      }
+    }
+  }
+
+  #[test]
+  fn test_lint() -> Result<()> {
+    let manifest = Manifest::from_xml(
+      br#"<manifest package="com.example" sharedUserId="com.example.shared">
+<uses-sdk minSdkVersion="21" targetSdkVersion="26"></uses-sdk>
+<permission name="com.example.NORMAL" protectionLevel="0x0"></permission>
+<permission name="com.example.SIGNATURE" protectionLevel="0x2"></permission>
+<application debuggable="true" allowTaskReparenting="true">
+  <activity name=".Main" taskAffinity="com.bank">
+    <intent-filter>
+      <action name="android.intent.action.MAIN"></action>
+      <category name="android.intent.category.LAUNCHER"></category>
+    </intent-filter>
+  </activity>
+  <service name=".Open" exported="true"></service>
+  <receiver name=".Guarded" exported="true" permission="com.example.SIGNATURE"></receiver>
+  <provider name=".Provider" exported="true" authorities="com.example"></provider>
+</application>
+</manifest>"#,
+    )?;
+    let mut linter = Linter::default();
+    linter.add_rule(Box::new(NoQueries));
+    let findings = linter
+      .run(&manifest)
+      .into_iter()
+      .map(|finding| (finding.severity, finding.rule_id, finding.path))
+      .collect::<Vec<_>>();
+    let finding =
+      |severity, rule_id: &str, path: &str| (severity, rule_id.to_string(), path.to_string());
+    assert_eq!(
+      findings,
+      [
+        finding(Severity::High, "debuggable", "manifest/application"),
+        finding(
+          Severity::High,
+          "exported-without-permission",
+          "manifest/application/provider[com.example.Provider]"
+        ),
+        finding(Severity::Medium, "allow-backup", "manifest/application"),
+        finding(
+          Severity::Medium,
+          "exported-without-permission",
+          "manifest/application/service[com.example.Open]"
+        ),
+        finding(
+          Severity::Medium,
+          "task-affinity",
+          "manifest/application/activity[com.example.Main]"
+        ),
+        finding(Severity::Medium, "outdated-target-sdk", "manifest/uses-sdk"),
+        finding(Severity::Low, "cleartext-traffic", "manifest/application"),
+        finding(
+          Severity::Low,
+          "normal-protection-level",
+          "manifest/permission[com.example.NORMAL]"
+        ),
+        finding(Severity::Low, "task-affinity", "manifest/application"),
+        finding(Severity::Low, "shared-user-id", "manifest"),
+        finding(
+          Severity::Info,
+          "missing-network-security-config",
+          "manifest/application"
+        ),
+        finding(Severity::Info, "no-queries", "manifest"),
+      ]
+    );
+
+    let hardened = Manifest::from_xml(
+      br#"<manifest package="com.example">
+<uses-sdk minSdkVersion="26" targetSdkVersion="35"></uses-sdk>
+<application allowBackup="false" dataExtractionRules="@res/0x7f140001" networkSecurityConfig="@res/0x7f140000"></application>
+</manifest>"#,
+    )?;
+    assert_eq!(Linter::default().run(&hardened), []);
+    assert_eq!(
+      Linter::new(vec![Box::new(OutdatedTargetSdk { min_target_sdk: 36 })]).run(&hardened)[0]
+        .to_string(),
+      "[medium] outdated-target-sdk manifest/uses-sdk: targetSdkVersion 35 is below 36, legacy platform behaviour applies"
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_target_sdk_31() -> Result<()> {
+    let manifest = |uses_sdk: &str, application: &str| {
+      Manifest::from_xml(
+        format!(
+          r#"<manifest package="com.example"><uses-sdk {}></uses-sdk><application {}>
+<receiver name=".Boot"><intent-filter><action name="android.intent.action.BOOT_COMPLETED"></action></intent-filter></receiver>
+</application></manifest>"#,
+          uses_sdk, application
+        )
+        .as_bytes(),
+      )
+    };
+    let allow_backup = |uses_sdk: &str, application: &str| -> Result<Vec<String>> {
+      Ok(
+        AllowBackup
+          .check(&manifest(uses_sdk, application)?)
+          .into_iter()
+          .map(|finding| finding.message)
+          .collect(),
+      )
+    };
+    let old = r#"minSdkVersion="21" targetSdkVersion="30""#;
+    let new = r#"minSdkVersion="21" targetSdkVersion="31""#;
+    assert_eq!(
+      allow_backup(old, "")?,
+      ["allowBackup defaults to true and there is no fullBackupContent, app data can be copied off the device"]
+    );
+    assert!(allow_backup(old, r#"allowBackup="false""#)?.is_empty());
+    assert!(allow_backup(old, r#"fullBackupContent="@res/0x7f140000""#)?.is_empty());
+    // dataExtractionRules only apply to apps targeting API 31
+    assert_eq!(
+      allow_backup(old, r#"dataExtractionRules="@res/0x7f140001""#)?.len(),
+      1
+    );
+    assert_eq!(
+      allow_backup(new, r#"allowBackup="false""#)?,
+      ["allowBackup=\"false\" doesn't stop device-to-device transfers when targeting API 31 and there are no dataExtractionRules"]
+    );
+    assert_eq!(
+      allow_backup(new, r#"fullBackupContent="@res/0x7f140000""#)?,
+      ["allowBackup defaults to true and there are no dataExtractionRules, app data can be copied off the device"]
+    );
+    assert_eq!(
+      allow_backup(new, r#"dataExtractionRules="@res/0x7f140001""#)?,
+      ["allowBackup defaults to true and there is no fullBackupContent, app data can be copied off devices below Android 12"]
+    );
+    assert!(allow_backup(
+      r#"minSdkVersion="31""#,
+      r#"dataExtractionRules="@res/0x7f140001""#
+    )?
+    .is_empty());
+
+    // The receiver is reported once, as missing its exported attribute
+    let findings = Linter::default()
+      .run(&manifest(new, "")?)
+      .into_iter()
+      .filter(|finding| finding.path == "manifest/application/receiver[com.example.Boot]")
+      .map(|finding| finding.rule_id)
+      .collect::<Vec<_>>();
+    assert_eq!(findings, ["missing-exported"]);
+    Ok(())
+  }
+}
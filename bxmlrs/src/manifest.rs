@@ -0,0 +1,946 @@
+use crate::exported::{effective_exported, ExportedState};
+use crate::nom_parser::ParseError;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use serde::Serialize;
+
+/// SDK version Android uses for preview codenames such as `UpsideDownCake`.
+const CUR_DEVELOPMENT: u32 = 10000;
+
+/// An element of the decoded manifest, attributes named without their namespace prefix as
+/// `AndroidManifest::parse` writes them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Element {
+  pub name: String,
+  pub attributes: Attributes,
+  pub children: Vec<Element>,
+  /// Character data directly inside the element, e.g. the domain of a network security config
+  /// `<domain>`.
+  pub text: String,
+}
+
+impl Element {
+  /// Builds the tree of a decoded XML document and returns its root element.
+  pub fn parse(xml: &[u8]) -> Result<Self, ParseError> {
+    let mut reader = Reader::from_reader(xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    loop {
+      let event = reader
+        .read_event_into(&mut buf)
+        .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+      let is_empty = matches!(event, Event::Empty(_));
+      match event {
+        Event::Start(e) | Event::Empty(e) if root.is_none() => {
+          let mut element = Element {
+            name: String::from_utf8_lossy(e.local_name().as_ref()).to_string(),
+            ..Default::default()
+          };
+          for attr in e.attributes().flatten() {
+            let value = attr
+              .unescape_value()
+              .map_err(|e| ParseError::BuildXml(e.to_string()))?
+              .into_owned();
+            let name = String::from_utf8_lossy(attr.key.local_name().as_ref()).to_string();
+            element.attributes.0.push((name, value));
+          }
+          stack.push(element);
+          if is_empty {
+            close(&mut stack, &mut root);
+          }
+        }
+        Event::End(_) => close(&mut stack, &mut root),
+        Event::Text(text) => {
+          if let Some(element) = stack.last_mut() {
+            let text = text
+              .unescape()
+              .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+            element.text.push_str(&text);
+          }
+        }
+        Event::Eof => break,
+        _ => {}
+      }
+      buf.clear();
+    }
+    root.ok_or_else(|| ParseError::BuildXml("document has no root element".to_string()))
+  }
+
+  /// Direct children with the given name.
+  pub fn children_named<'a>(
+    &'a self,
+    name: &'a str,
+  ) -> impl Iterator<Item = &'a Element> + 'a {
+    self.children.iter().filter(move |child| child.name == name)
+  }
+
+  pub fn attribute(
+    &self,
+    name: &str,
+  ) -> Option<&str> {
+    self.attributes.get(name)
+  }
+}
+
+// Moves the innermost open element to its parent, or makes it the root.
+fn close(
+  stack: &mut Vec<Element>,
+  root: &mut Option<Element>,
+) {
+  let Some(element) = stack.pop() else {
+    return;
+  };
+  match stack.last_mut() {
+    Some(parent) => parent.children.push(element),
+    None => *root = Some(element),
+  }
+}
+
+/// Attributes of an element in document order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Attributes(pub Vec<(String, String)>);
+
+impl Attributes {
+  pub fn get(
+    &self,
+    name: &str,
+  ) -> Option<&str> {
+    self
+      .0
+      .iter()
+      .find(|(key, _)| key == name)
+      .map(|(_, value)| value.as_str())
+  }
+
+  /// A boolean attribute, `None` if it's missing or not a boolean, e.g. an unresolved
+  /// resource reference.
+  pub fn get_bool(
+    &self,
+    name: &str,
+  ) -> Option<bool> {
+    match self.get(name)? {
+      "true" => Some(true),
+      "false" => Some(false),
+      _ => None,
+    }
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+    self
+      .0
+      .iter()
+      .map(|(key, value)| (key.as_str(), value.as_str()))
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UsesSdk {
+  /// Raw values, either a level or a preview codename.
+  pub min_sdk_version: Option<String>,
+  pub target_sdk_version: Option<String>,
+  pub max_sdk_version: Option<String>,
+}
+
+/// `<uses-permission>` and `<uses-permission-sdk-23>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsesPermission {
+  pub name: String,
+  pub max_sdk_version: Option<u32>,
+  /// Only requested on API 23 and later.
+  pub sdk_23: bool,
+  pub attributes: Attributes,
+}
+
+/// A permission the app defines with `<permission>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Permission {
+  pub name: String,
+  /// E.g. `signature` or `dangerous|instant`, `normal` when missing.
+  pub protection_level: Option<String>,
+  pub permission_group: Option<String>,
+  pub attributes: Attributes,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsesFeature {
+  /// Missing for OpenGL ES requirements.
+  pub name: Option<String>,
+  pub required: bool,
+  pub gl_es_version: Option<String>,
+}
+
+/// `<uses-library>` or `<uses-native-library>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsesLibrary {
+  pub name: String,
+  pub required: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetaData {
+  pub name: String,
+  pub value: Option<String>,
+  pub resource: Option<String>,
+}
+
+/// A `<data>` element of an intent filter. Android merges the attributes of all `<data>`
+/// elements of a filter, see `IntentFilter::uri_patterns`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntentData {
+  pub scheme: Option<String>,
+  pub host: Option<String>,
+  pub port: Option<String>,
+  pub path: Option<String>,
+  pub path_prefix: Option<String>,
+  pub path_pattern: Option<String>,
+  pub path_advanced_pattern: Option<String>,
+  pub path_suffix: Option<String>,
+  pub mime_type: Option<String>,
+}
+
+impl IntentData {
+  fn from_element(element: &Element) -> Self {
+    let string = |name| element.attribute(name).map(str::to_string);
+    Self {
+      scheme: string("scheme"),
+      host: string("host"),
+      port: string("port"),
+      path: string("path"),
+      path_prefix: string("pathPrefix"),
+      path_pattern: string("pathPattern"),
+      path_advanced_pattern: string("pathAdvancedPattern"),
+      path_suffix: string("pathSuffix"),
+      mime_type: string("mimeType"),
+    }
+  }
+
+  fn path_matchers(&self) -> Vec<PathMatcher> {
+    [
+      (
+        &self.path,
+        PathMatcher::Literal as fn(String) -> PathMatcher,
+      ),
+      (&self.path_prefix, PathMatcher::Prefix),
+      (&self.path_pattern, PathMatcher::Pattern),
+      (&self.path_advanced_pattern, PathMatcher::AdvancedPattern),
+      (&self.path_suffix, PathMatcher::Suffix),
+    ]
+    .into_iter()
+    .filter_map(|(path, matcher)| path.clone().map(matcher))
+    .collect()
+  }
+}
+
+/// How a filter matches the path of a URI.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PathMatcher {
+  Literal(String),
+  Prefix(String),
+  /// `pathPattern`, a simple glob where `.*` matches anything.
+  Pattern(String),
+  /// `pathAdvancedPattern`, a regular expression subset.
+  AdvancedPattern(String),
+  Suffix(String),
+}
+
+impl std::fmt::Display for PathMatcher {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      PathMatcher::Literal(path)
+      | PathMatcher::Pattern(path)
+      | PathMatcher::AdvancedPattern(path) => write!(f, "{}", path),
+      PathMatcher::Prefix(prefix) => write!(f, "{}*", prefix),
+      PathMatcher::Suffix(suffix) => write!(f, "*{}", suffix),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Authority {
+  pub host: String,
+  pub port: Option<String>,
+}
+
+/// A URI an intent filter accepts, one combination of its schemes, authorities and paths.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UriPattern {
+  pub scheme: String,
+  pub authority: Option<Authority>,
+  pub path: Option<PathMatcher>,
+}
+
+impl std::fmt::Display for UriPattern {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    write!(f, "{}:", self.scheme)?;
+    if let Some(authority) = &self.authority {
+      write!(f, "//{}", authority.host)?;
+      if let Some(port) = &authority.port {
+        write!(f, ":{}", port)?;
+      }
+    }
+    if let Some(path) = &self.path {
+      write!(f, "{}", path)?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntentFilter {
+  pub actions: Vec<String>,
+  pub categories: Vec<String>,
+  pub data: Vec<IntentData>,
+  /// Priority among filters of different apps, 0 by default.
+  pub priority: i32,
+  /// Ordering among the filters of the same app, 0 by default.
+  pub order: i32,
+  /// Asks the system to verify the hosts as App Links.
+  pub auto_verify: bool,
+  pub attributes: Attributes,
+}
+
+impl IntentFilter {
+  fn from_element(element: &Element) -> Self {
+    let names = |child_name| {
+      element
+        .children_named(child_name)
+        .filter_map(|child| child.attribute("name"))
+        .map(str::to_string)
+        .collect()
+    };
+    let number = |name| {
+      element
+        .attribute(name)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+    };
+    Self {
+      actions: names("action"),
+      categories: names("category"),
+      data: element
+        .children_named("data")
+        .map(IntentData::from_element)
+        .collect(),
+      priority: number("priority"),
+      order: number("order"),
+      auto_verify: element.attributes.get_bool("autoVerify").unwrap_or(false),
+      attributes: element.attributes.clone(),
+    }
+  }
+
+  pub fn has_action(
+    &self,
+    action: &str,
+  ) -> bool {
+    self.actions.iter().any(|name| name == action)
+  }
+
+  pub fn has_category(
+    &self,
+    category: &str,
+  ) -> bool {
+    self.categories.iter().any(|name| name == category)
+  }
+
+  /// Schemes of all `<data>` elements, without duplicates.
+  pub fn schemes(&self) -> Vec<&str> {
+    unique(self.data.iter().filter_map(|data| data.scheme.as_deref()))
+  }
+
+  /// Hosts with their ports. A port only applies to the host of its own `<data>` element.
+  pub fn authorities(&self) -> Vec<Authority> {
+    let mut authorities: Vec<Authority> = Vec::new();
+    for data in &self.data {
+      let Some(host) = &data.host else {
+        continue;
+      };
+      let authority = Authority {
+        host: host.clone(),
+        port: data.port.clone(),
+      };
+      if !authorities.contains(&authority) {
+        authorities.push(authority);
+      }
+    }
+    authorities
+  }
+
+  pub fn paths(&self) -> Vec<PathMatcher> {
+    let mut paths: Vec<PathMatcher> = Vec::new();
+    for path in self.data.iter().flat_map(IntentData::path_matchers) {
+      if !paths.contains(&path) {
+        paths.push(path);
+      }
+    }
+    paths
+  }
+
+  pub fn mime_types(&self) -> Vec<&str> {
+    unique(
+      self
+        .data
+        .iter()
+        .filter_map(|data| data.mime_type.as_deref()),
+    )
+  }
+
+  /// The URIs the filter accepts, as the cross product of its schemes, authorities and
+  /// paths. Authorities are ignored without a scheme and paths without an authority, like
+  /// `IntentFilter.matchData` does. A filter with only MIME types implicitly accepts
+  /// `content:` and `file:` URIs.
+  pub fn uri_patterns(&self) -> Vec<UriPattern> {
+    let mut schemes = self.schemes();
+    let mime_only = schemes.is_empty() && !self.mime_types().is_empty();
+    if mime_only {
+      schemes = vec!["content", "file"];
+    }
+    let authorities = self.authorities();
+    let paths = self.paths();
+    let mut patterns = Vec::new();
+    for scheme in schemes.into_iter().map(str::to_string) {
+      if authorities.is_empty() || mime_only {
+        patterns.push(UriPattern {
+          scheme,
+          authority: None,
+          path: None,
+        });
+        continue;
+      }
+      for authority in &authorities {
+        let pattern = |path| UriPattern {
+          scheme: scheme.clone(),
+          authority: Some(authority.clone()),
+          path,
+        };
+        if paths.is_empty() {
+          patterns.push(pattern(None));
+        }
+        patterns.extend(paths.iter().map(|path| pattern(Some(path.clone()))));
+      }
+    }
+    patterns
+  }
+}
+
+fn unique<'a>(values: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+  let mut unique = Vec::new();
+  for value in values {
+    if !unique.contains(&value) {
+      unique.push(value);
+    }
+  }
+  unique
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ComponentKind {
+  Activity,
+  ActivityAlias,
+  Service,
+  Receiver,
+  Provider,
+}
+
+impl ComponentKind {
+  /// Name of the manifest element.
+  pub fn element_name(&self) -> &'static str {
+    match self {
+      ComponentKind::Activity => "activity",
+      ComponentKind::ActivityAlias => "activity-alias",
+      ComponentKind::Service => "service",
+      ComponentKind::Receiver => "receiver",
+      ComponentKind::Provider => "provider",
+    }
+  }
+}
+
+/// An activity, activity alias, service, receiver or provider.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Component {
+  pub kind: ComponentKind,
+  /// Fully qualified class name, names starting with `.` are resolved against the package.
+  pub name: String,
+  pub attributes: Attributes,
+  pub intent_filters: Vec<IntentFilter>,
+  pub meta_data: Vec<MetaData>,
+  /// Whether other apps can reach the component, worked out from the whole manifest.
+  pub effective_exported: ExportedState,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Application {
+  pub attributes: Attributes,
+  pub activities: Vec<Component>,
+  pub activity_aliases: Vec<Component>,
+  pub services: Vec<Component>,
+  pub receivers: Vec<Component>,
+  pub providers: Vec<Component>,
+  pub uses_libraries: Vec<UsesLibrary>,
+  pub uses_native_libraries: Vec<UsesLibrary>,
+  pub meta_data: Vec<MetaData>,
+}
+
+impl Application {
+  /// Every component in the order of the kinds, then of the manifest.
+  pub fn components(&self) -> impl Iterator<Item = &Component> {
+    self
+      .activities
+      .iter()
+      .chain(&self.activity_aliases)
+      .chain(&self.services)
+      .chain(&self.receivers)
+      .chain(&self.providers)
+  }
+
+  fn components_mut(&mut self) -> impl Iterator<Item = &mut Component> {
+    self
+      .activities
+      .iter_mut()
+      .chain(&mut self.activity_aliases)
+      .chain(&mut self.services)
+      .chain(&mut self.receivers)
+      .chain(&mut self.providers)
+  }
+}
+
+/// Packages, intents and providers the app wants to see under package visibility.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Queries {
+  pub packages: Vec<String>,
+  pub intents: Vec<IntentFilter>,
+  pub provider_authorities: Vec<String>,
+}
+
+/// The decoded manifest, built from the element tree `Parser::parse` writes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Manifest {
+  pub package: Option<String>,
+  /// `versionCodeMajor` in the upper 32 bits.
+  pub version_code: Option<u64>,
+  pub version_name: Option<String>,
+  /// Name of a split APK, an empty name is the same as none.
+  pub split: Option<String>,
+  pub attributes: Attributes,
+  pub uses_sdk: UsesSdk,
+  pub application: Option<Application>,
+  pub uses_permissions: Vec<UsesPermission>,
+  pub permissions: Vec<Permission>,
+  pub uses_features: Vec<UsesFeature>,
+  pub queries: Queries,
+  /// The whole tree, for elements the model doesn't cover.
+  pub root: Element,
+}
+
+impl Manifest {
+  pub fn from_xml(xml: &[u8]) -> Result<Self, ParseError> {
+    Self::from_element(Element::parse(xml)?)
+  }
+
+  pub fn from_element(root: Element) -> Result<Self, ParseError> {
+    if root.name != "manifest" {
+      return Err(ParseError::BuildXml(format!(
+        "expected a manifest element, found {}",
+        root.name
+      )));
+    }
+    let package = root.attribute("package").map(str::to_string);
+    let version_code = root
+      .attribute("versionCode")
+      .and_then(|code| code.parse::<u32>().ok())
+      .map(|code| {
+        let major = root
+          .attribute("versionCodeMajor")
+          .and_then(|major| major.parse::<u32>().ok())
+          .unwrap_or_default();
+        ((major as u64) << 32) | code as u64
+      });
+
+    let mut manifest = Self {
+      version_code,
+      version_name: root.attribute("versionName").map(str::to_string),
+      split: root
+        .attribute("split")
+        .filter(|split| !split.is_empty())
+        .map(str::to_string),
+      attributes: root.attributes.clone(),
+      ..Default::default()
+    };
+    let string = |element: &Element, name| element.attribute(name).map(str::to_string);
+    for child in &root.children {
+      match child.name.as_str() {
+        "uses-sdk" => {
+          manifest.uses_sdk = UsesSdk {
+            min_sdk_version: string(child, "minSdkVersion"),
+            target_sdk_version: string(child, "targetSdkVersion"),
+            max_sdk_version: string(child, "maxSdkVersion"),
+          }
+        }
+        "uses-permission" | "uses-permission-sdk-23" | "uses-permission-sdk-m" => {
+          let Some(name) = string(child, "name") else {
+            continue;
+          };
+          manifest.uses_permissions.push(UsesPermission {
+            name,
+            max_sdk_version: child
+              .attribute("maxSdkVersion")
+              .and_then(|version| version.parse().ok()),
+            sdk_23: child.name != "uses-permission",
+            attributes: child.attributes.clone(),
+          });
+        }
+        "permission" => {
+          let Some(name) = string(child, "name") else {
+            continue;
+          };
+          manifest.permissions.push(Permission {
+            name,
+            protection_level: string(child, "protectionLevel"),
+            permission_group: string(child, "permissionGroup"),
+            attributes: child.attributes.clone(),
+          });
+        }
+        "uses-feature" => manifest.uses_features.push(UsesFeature {
+          name: string(child, "name"),
+          required: child.attributes.get_bool("required").unwrap_or(true),
+          gl_es_version: string(child, "glEsVersion"),
+        }),
+        "queries" => {
+          for query in &child.children {
+            match query.name.as_str() {
+              "package" => manifest.queries.packages.extend(string(query, "name")),
+              "intent" => manifest
+                .queries
+                .intents
+                .push(IntentFilter::from_element(query)),
+              "provider" => manifest
+                .queries
+                .provider_authorities
+                .extend(string(query, "authorities")),
+              _ => {}
+            }
+          }
+        }
+        "application" => {
+          manifest.application = Some(application(child, package.as_deref()));
+        }
+        _ => {}
+      }
+    }
+    // uses-sdk can come after the application
+    let target_sdk = manifest.target_sdk();
+    if let Some(application) = &mut manifest.application {
+      let attributes = application.attributes.clone();
+      let activities = application.activities.clone();
+      for component in application.components_mut() {
+        let target = match component.kind {
+          ComponentKind::ActivityAlias => component
+            .attributes
+            .get("targetActivity")
+            .map(|name| resolve_class_name(package.as_deref(), name))
+            .and_then(|name| activities.iter().find(|activity| activity.name == name)),
+          _ => None,
+        };
+        component.effective_exported =
+          effective_exported(component, target, &attributes, target_sdk);
+      }
+    }
+    manifest.package = package;
+    manifest.root = root;
+    Ok(manifest)
+  }
+
+  /// `minSdkVersion`, 1 when missing as on Android.
+  pub fn min_sdk(&self) -> u32 {
+    sdk_version(self.uses_sdk.min_sdk_version.as_deref()).unwrap_or(1)
+  }
+
+  /// `targetSdkVersion`, defaulting to the minimum SDK version.
+  pub fn target_sdk(&self) -> u32 {
+    sdk_version(self.uses_sdk.target_sdk_version.as_deref()).unwrap_or_else(|| self.min_sdk())
+  }
+
+  /// Names of the requested permissions.
+  pub fn permission_names(&self) -> Vec<&str> {
+    self
+      .uses_permissions
+      .iter()
+      .map(|permission| permission.name.as_str())
+      .collect()
+  }
+
+  pub fn components(&self) -> impl Iterator<Item = &Component> {
+    self.application.iter().flat_map(Application::components)
+  }
+}
+
+fn sdk_version(version: Option<&str>) -> Option<u32> {
+  let version = version?;
+  match version.parse() {
+    Ok(version) => Some(version),
+    // Preview codename
+    Err(_) if !version.is_empty() => Some(CUR_DEVELOPMENT),
+    Err(_) => None,
+  }
+}
+
+/// Resolves a component class name against the package like `PackageParser` does. Empty
+/// names and unresolved references such as `@res/0x7f0e0001` are returned unchanged.
+pub fn resolve_class_name(
+  package: Option<&str>,
+  name: &str,
+) -> String {
+  if name.is_empty() || name.starts_with('@') {
+    return name.to_string();
+  }
+  match package {
+    Some(package) if name.starts_with('.') => format!("{}{}", package, name),
+    Some(package) if !name.contains('.') => format!("{}.{}", package, name),
+    _ => name.to_string(),
+  }
+}
+
+fn meta_data(element: &Element) -> Vec<MetaData> {
+  element
+    .children_named("meta-data")
+    .filter_map(|meta_data| {
+      Some(MetaData {
+        name: meta_data.attribute("name")?.to_string(),
+        value: meta_data.attribute("value").map(str::to_string),
+        resource: meta_data.attribute("resource").map(str::to_string),
+      })
+    })
+    .collect()
+}
+
+fn application(
+  element: &Element,
+  package: Option<&str>,
+) -> Application {
+  let mut application = Application {
+    attributes: element.attributes.clone(),
+    meta_data: meta_data(element),
+    ..Default::default()
+  };
+  for child in &element.children {
+    let (kind, components) = match child.name.as_str() {
+      "activity" => (ComponentKind::Activity, &mut application.activities),
+      "activity-alias" => (
+        ComponentKind::ActivityAlias,
+        &mut application.activity_aliases,
+      ),
+      "service" => (ComponentKind::Service, &mut application.services),
+      "receiver" => (ComponentKind::Receiver, &mut application.receivers),
+      "provider" => (ComponentKind::Provider, &mut application.providers),
+      "uses-library" | "uses-native-library" => {
+        let Some(name) = child.attribute("name") else {
+          continue;
+        };
+        let library = UsesLibrary {
+          name: name.to_string(),
+          required: child.attributes.get_bool("required").unwrap_or(true),
+        };
+        match child.name.as_str() {
+          "uses-library" => application.uses_libraries.push(library),
+          _ => application.uses_native_libraries.push(library),
+        }
+        continue;
+      }
+      _ => continue,
+    };
+    components.push(Component {
+      kind,
+      name: resolve_class_name(package, child.attribute("name").unwrap_or_default()),
+      attributes: child.attributes.clone(),
+      intent_filters: child
+        .children_named("intent-filter")
+        .map(IntentFilter::from_element)
+        .collect(),
+      meta_data: meta_data(child),
+      effective_exported: ExportedState::default(),
+    });
+  }
+  application
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::Result;
+
+  #[test]
+  fn test_manifest() -> Result<()> {
+    let xml = br#"<?xml encoding='utf-8' version='1.1'?>
+<manifest versionCode="7" versionCodeMajor="1" versionName="1.2" package="com.example">
+  <uses-sdk minSdkVersion="21" targetSdkVersion="33"></uses-sdk>
+  <uses-permission name="android.permission.INTERNET"></uses-permission>
+  <uses-permission-sdk-23 name="android.permission.CAMERA" maxSdkVersion="30"></uses-permission-sdk-23>
+  <permission name="com.example.READ" protectionLevel="signature"></permission>
+  <uses-feature name="android.hardware.camera" required="false"></uses-feature>
+  <uses-feature glEsVersion="0x00020000"></uses-feature>
+  <queries>
+    <package name="com.other"></package>
+    <intent><action name="android.intent.action.SEND"></action></intent>
+    <provider authorities="com.other.provider"></provider>
+  </queries>
+  <application label="Example" debuggable="true">
+    <activity name=".MainActivity" exported="true">
+      <intent-filter>
+        <action name="android.intent.action.MAIN"></action>
+        <action name="android.intent.action.VIEW"></action>
+        <category name="android.intent.category.LAUNCHER"></category>
+      </intent-filter>
+      <meta-data name="key" value="value"></meta-data>
+    </activity>
+    <activity-alias name="Alias" targetActivity=".MainActivity"></activity-alias>
+    <service name="org.lib.Service"></service>
+    <receiver name=".Receiver"></receiver>
+    <provider name=".Provider" authorities="com.example.provider"></provider>
+    <uses-library name="org.apache.http.legacy" required="false"></uses-library>
+    <meta-data name="app" resource="@xml/config"></meta-data>
+  </application>
+</manifest>"#;
+    let manifest = Manifest::from_xml(xml)?;
+    assert_eq!(manifest.package.as_deref(), Some("com.example"));
+    assert_eq!(manifest.version_code, Some((1 << 32) | 7));
+    assert_eq!(manifest.version_name.as_deref(), Some("1.2"));
+    assert_eq!((manifest.min_sdk(), manifest.target_sdk()), (21, 33));
+    assert_eq!(
+      manifest.permission_names(),
+      ["android.permission.INTERNET", "android.permission.CAMERA"]
+    );
+    assert!(manifest.uses_permissions[1].sdk_23);
+    assert_eq!(manifest.uses_permissions[1].max_sdk_version, Some(30));
+    assert_eq!(
+      manifest.permissions[0].protection_level.as_deref(),
+      Some("signature")
+    );
+    assert!(!manifest.uses_features[0].required);
+    assert_eq!(manifest.uses_features[1].name, None);
+    assert_eq!(manifest.queries.packages, ["com.other"]);
+    assert_eq!(
+      manifest.queries.intents[0].actions,
+      ["android.intent.action.SEND"]
+    );
+    assert_eq!(
+      manifest.queries.provider_authorities,
+      ["com.other.provider"]
+    );
+
+    let application = manifest.application.as_ref().unwrap();
+    assert_eq!(application.attributes.get_bool("debuggable"), Some(true));
+    assert_eq!(
+      application.meta_data[0].resource.as_deref(),
+      Some("@xml/config")
+    );
+    assert!(!application.uses_libraries[0].required);
+    let names = manifest
+      .components()
+      .map(|component| (component.kind, component.name.as_str()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      names,
+      [
+        (ComponentKind::Activity, "com.example.MainActivity"),
+        (ComponentKind::ActivityAlias, "com.example.Alias"),
+        (ComponentKind::Service, "org.lib.Service"),
+        (ComponentKind::Receiver, "com.example.Receiver"),
+        (ComponentKind::Provider, "com.example.Provider"),
+      ]
+    );
+    let activity = &application.activities[0];
+    assert_eq!(activity.attributes.get_bool("exported"), Some(true));
+    assert_eq!(
+      activity.intent_filters[0].actions,
+      ["android.intent.action.MAIN", "android.intent.action.VIEW"]
+    );
+    assert_eq!(activity.meta_data[0].value.as_deref(), Some("value"));
+
+    // The binary manifest of the test data goes through the same model
+    let binary = std::fs::read("../data/xml/AndroidManifest.xml")?;
+    let decoded = crate::parser::Parser::from_manifest(binary, None).manifest()?;
+    assert!(decoded.package.is_some());
+    assert_eq!(decoded.root.name, "manifest");
+    Ok(())
+  }
+
+  #[test]
+  fn test_resolve_class_name() {
+    let package = Some("com.example");
+    assert_eq!(resolve_class_name(package, ".Main"), "com.example.Main");
+    assert_eq!(resolve_class_name(package, "Main"), "com.example.Main");
+    assert_eq!(resolve_class_name(package, "org.lib.Main"), "org.lib.Main");
+    assert_eq!(resolve_class_name(None, ".Main"), ".Main");
+    assert_eq!(resolve_class_name(package, ""), "");
+    assert_eq!(
+      resolve_class_name(package, "@res/0x7f0e0001"),
+      "@res/0x7f0e0001"
+    );
+  }
+
+  #[test]
+  fn test_intent_filter() -> Result<()> {
+    let xml = br#"<manifest package="com.example"><application>
+<activity name=".Links">
+  <intent-filter autoVerify="true" priority="10" order="2">
+    <action name="android.intent.action.VIEW"></action>
+    <action name="android.intent.action.SEND"></action>
+    <category name="android.intent.category.BROWSABLE"></category>
+    <data scheme="https"></data>
+    <data scheme="http" host="example.com"></data>
+    <data host="*.example.org" port="8080"></data>
+    <data pathPrefix="/app"></data>
+    <data path="/home" pathSuffix=".pdf"></data>
+  </intent-filter>
+  <intent-filter>
+    <action name="android.intent.action.SEND"></action>
+    <data mimeType="image/*"></data>
+  </intent-filter>
+  <intent-filter>
+    <data scheme="myapp"></data>
+  </intent-filter>
+</activity>
+</application></manifest>"#;
+    let manifest = Manifest::from_xml(xml)?;
+    let filters = &manifest.application.as_ref().unwrap().activities[0].intent_filters;
+    let links = &filters[0];
+    assert_eq!(
+      links.actions,
+      ["android.intent.action.VIEW", "android.intent.action.SEND"]
+    );
+    assert!(links.has_category("android.intent.category.BROWSABLE"));
+    assert_eq!(
+      (links.priority, links.order, links.auto_verify),
+      (10, 2, true)
+    );
+    assert_eq!(links.data.len(), 5);
+    assert_eq!(links.schemes(), ["https", "http"]);
+    let patterns = links
+      .uri_patterns()
+      .iter()
+      .map(UriPattern::to_string)
+      .collect::<Vec<_>>();
+    assert_eq!(patterns.len(), 2 * 2 * 3);
+    assert_eq!(patterns[0], "https://example.com/app*");
+    assert!(patterns.contains(&"http://*.example.org:8080/home".to_string()));
+    assert!(patterns.contains(&"http://example.com*.pdf".to_string()));
+
+    let patterns = filters[1]
+      .uri_patterns()
+      .iter()
+      .map(UriPattern::to_string)
+      .collect::<Vec<_>>();
+    assert_eq!(patterns, ["content:", "file:"]);
+    assert_eq!(filters[1].mime_types(), ["image/*"]);
+    assert_eq!(filters[2].uri_patterns()[0].to_string(), "myapp:");
+    Ok(())
+  }
+}
@@ -0,0 +1,611 @@
+use crate::manifest::{Component, ComponentKind, IntentFilter, Manifest, MetaData, UsesPermission};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Differences between two manifests, e.g. of two releases of an app.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct ManifestDiff {
+  pub changes: Vec<ManifestChange>,
+}
+
+/// A single difference between two manifests. Elements are matched by identity rather than by
+/// position: permissions and meta-data by name, components by kind and class name, intent
+/// filters by their actions, categories and data, so reordering the manifest changes nothing.
+/// In JSON the kind of change is in `change`, e.g. `"change":"permission_added"`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ManifestChange {
+  /// `versionCode`, `versionName` or one of the SDK versions of `<uses-sdk>`.
+  AttributeChanged {
+    name: &'static str,
+    old: Option<String>,
+    new: Option<String>,
+  },
+  PermissionAdded {
+    name: String,
+  },
+  PermissionRemoved {
+    name: String,
+  },
+  /// The `maxSdkVersion` of a `<uses-permission>`, past which it isn't requested.
+  PermissionMaxSdkChanged {
+    name: String,
+    old: Option<u32>,
+    new: Option<u32>,
+  },
+  /// Moved between `<uses-permission>` and `<uses-permission-sdk-23>`.
+  PermissionSdk23Changed {
+    name: String,
+    old: bool,
+    new: bool,
+  },
+  ComponentAdded {
+    kind: ComponentKind,
+    name: String,
+    exported: bool,
+  },
+  ComponentRemoved {
+    kind: ComponentKind,
+    name: String,
+    exported: bool,
+  },
+  /// A component the new manifest declares `count` times, where the old one didn't. The first
+  /// declaration is the one compared.
+  ComponentDuplicated {
+    kind: ComponentKind,
+    name: String,
+    count: usize,
+  },
+  /// The effective exported state, which intent filters and the target SDK can change too.
+  ExportedChanged {
+    kind: ComponentKind,
+    name: String,
+    old: bool,
+    new: bool,
+  },
+  IntentFilterAdded {
+    kind: ComponentKind,
+    name: String,
+    filter: String,
+  },
+  IntentFilterRemoved {
+    kind: ComponentKind,
+    name: String,
+    filter: String,
+  },
+  /// `owner` is `application` or the name of a component both manifests have.
+  MetaDataAdded {
+    owner: String,
+    name: String,
+    value: Option<String>,
+  },
+  MetaDataRemoved {
+    owner: String,
+    name: String,
+    value: Option<String>,
+  },
+  MetaDataChanged {
+    owner: String,
+    name: String,
+    old: Option<String>,
+    new: Option<String>,
+  },
+}
+
+impl std::fmt::Display for ManifestChange {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "(none)".to_string());
+    let exported = |exported: &bool| match exported {
+      true => " (exported)",
+      false => "",
+    };
+    match self {
+      ManifestChange::AttributeChanged { name, old, new } => {
+        write!(f, "~ {} {} -> {}", name, value(old), value(new))
+      }
+      ManifestChange::PermissionAdded { name } => write!(f, "+ uses-permission {}", name),
+      ManifestChange::PermissionRemoved { name } => write!(f, "- uses-permission {}", name),
+      ManifestChange::PermissionMaxSdkChanged { name, old, new } => {
+        let sdk = |sdk: &Option<u32>| sdk.map(|sdk| sdk.to_string());
+        write!(
+          f,
+          "~ uses-permission {} maxSdkVersion {} -> {}",
+          name,
+          value(&sdk(old)),
+          value(&sdk(new))
+        )
+      }
+      ManifestChange::PermissionSdk23Changed { name, old, new } => {
+        write!(f, "~ uses-permission {} sdk-23 {} -> {}", name, old, new)
+      }
+      ManifestChange::ComponentAdded {
+        kind,
+        name,
+        exported: is_exported,
+      } => write!(
+        f,
+        "+ {} {}{}",
+        kind.element_name(),
+        name,
+        exported(is_exported)
+      ),
+      ManifestChange::ComponentRemoved {
+        kind,
+        name,
+        exported: is_exported,
+      } => write!(
+        f,
+        "- {} {}{}",
+        kind.element_name(),
+        name,
+        exported(is_exported)
+      ),
+      ManifestChange::ComponentDuplicated { kind, name, count } => write!(
+        f,
+        "! {} {} declared {} times",
+        kind.element_name(),
+        name,
+        count
+      ),
+      ManifestChange::ExportedChanged {
+        kind,
+        name,
+        old,
+        new,
+      } => write!(
+        f,
+        "~ {} {} exported {} -> {}",
+        kind.element_name(),
+        name,
+        old,
+        new
+      ),
+      ManifestChange::IntentFilterAdded { kind, name, filter } => write!(
+        f,
+        "+ {} {} intent-filter {}",
+        kind.element_name(),
+        name,
+        filter
+      ),
+      ManifestChange::IntentFilterRemoved { kind, name, filter } => write!(
+        f,
+        "- {} {} intent-filter {}",
+        kind.element_name(),
+        name,
+        filter
+      ),
+      ManifestChange::MetaDataAdded {
+        owner,
+        name,
+        value: meta_value,
+      } => write!(f, "+ meta-data {} {} = {}", owner, name, value(meta_value)),
+      ManifestChange::MetaDataRemoved {
+        owner,
+        name,
+        value: meta_value,
+      } => write!(f, "- meta-data {} {} = {}", owner, name, value(meta_value)),
+      ManifestChange::MetaDataChanged {
+        owner,
+        name,
+        old,
+        new,
+      } => write!(
+        f,
+        "~ meta-data {} {} {} -> {}",
+        owner,
+        name,
+        value(old),
+        value(new)
+      ),
+    }
+  }
+}
+
+impl std::fmt::Display for ManifestDiff {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    for change in &self.changes {
+      writeln!(f, "{}", change)?;
+    }
+    Ok(())
+  }
+}
+
+impl ManifestDiff {
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+
+  /// The changes as a JSON array of objects.
+  pub fn to_json(&self) -> String {
+    // Strings, booleans and options only, serializing them can't fail
+    serde_json::to_string(self).unwrap_or_default()
+  }
+}
+
+// An intent filter in a form that doesn't depend on the order of its elements, e.g.
+// `actions=[android.intent.action.VIEW] categories=[...] data=[https://example.com/*]`.
+fn filter_summary(filter: &IntentFilter) -> String {
+  let sorted = |values: Vec<String>| {
+    values
+      .into_iter()
+      .collect::<BTreeSet<_>>()
+      .into_iter()
+      .collect::<Vec<_>>()
+      .join(", ")
+  };
+  let data = filter
+    .uri_patterns()
+    .iter()
+    .map(|uri| uri.to_string())
+    .chain(filter.mime_types().into_iter().map(str::to_string))
+    .collect();
+  let mut parts = Vec::new();
+  for (name, values) in [
+    ("actions", sorted(filter.actions.clone())),
+    ("categories", sorted(filter.categories.clone())),
+    ("data", sorted(data)),
+  ] {
+    if !values.is_empty() {
+      parts.push(format!("{}=[{}]", name, values));
+    }
+  }
+  parts.join(" ")
+}
+
+fn meta_data_values(meta_data: &[MetaData]) -> BTreeMap<&str, Option<String>> {
+  meta_data
+    .iter()
+    .map(|meta_data| {
+      let value = meta_data
+        .value
+        .clone()
+        .or_else(|| meta_data.resource.clone());
+      (meta_data.name.as_str(), value)
+    })
+    .collect()
+}
+
+fn diff_meta_data(
+  owner: &str,
+  old: &[MetaData],
+  new: &[MetaData],
+  changes: &mut Vec<ManifestChange>,
+) {
+  let (old, new) = (meta_data_values(old), meta_data_values(new));
+  let names = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+  for name in names {
+    let change = match (old.get(name), new.get(name)) {
+      (Some(old), Some(new)) if old != new => ManifestChange::MetaDataChanged {
+        owner: owner.to_string(),
+        name: name.to_string(),
+        old: old.clone(),
+        new: new.clone(),
+      },
+      (Some(value), None) => ManifestChange::MetaDataRemoved {
+        owner: owner.to_string(),
+        name: name.to_string(),
+        value: value.clone(),
+      },
+      (None, Some(value)) => ManifestChange::MetaDataAdded {
+        owner: owner.to_string(),
+        name: name.to_string(),
+        value: value.clone(),
+      },
+      _ => continue,
+    };
+    changes.push(change);
+  }
+}
+
+// Components keyed by their identity, the first declaration with the number of declarations.
+fn components(manifest: &Manifest) -> BTreeMap<(ComponentKind, String), (&Component, usize)> {
+  let mut components = BTreeMap::new();
+  for component in manifest.components() {
+    let key = (component.kind, component.name.clone());
+    components.entry(key).or_insert((component, 0)).1 += 1;
+  }
+  components
+}
+
+// Requested permissions keyed by name, the first request wins as it does for the package manager.
+fn permissions(manifest: &Manifest) -> BTreeMap<&str, &UsesPermission> {
+  let mut permissions = BTreeMap::new();
+  for permission in &manifest.uses_permissions {
+    permissions
+      .entry(permission.name.as_str())
+      .or_insert(permission);
+  }
+  permissions
+}
+
+fn diff_component(
+  old: &Component,
+  new: &Component,
+  changes: &mut Vec<ManifestChange>,
+) {
+  let (old_exported, new_exported) = (
+    old.effective_exported.exported,
+    new.effective_exported.exported,
+  );
+  if old_exported != new_exported {
+    changes.push(ManifestChange::ExportedChanged {
+      kind: new.kind,
+      name: new.name.clone(),
+      old: old_exported,
+      new: new_exported,
+    });
+  }
+  let filters = |component: &Component| {
+    component
+      .intent_filters
+      .iter()
+      .map(filter_summary)
+      .collect::<BTreeSet<_>>()
+  };
+  let (old_filters, new_filters) = (filters(old), filters(new));
+  for filter in old_filters.difference(&new_filters) {
+    changes.push(ManifestChange::IntentFilterRemoved {
+      kind: old.kind,
+      name: old.name.clone(),
+      filter: filter.clone(),
+    });
+  }
+  for filter in new_filters.difference(&old_filters) {
+    changes.push(ManifestChange::IntentFilterAdded {
+      kind: new.kind,
+      name: new.name.clone(),
+      filter: filter.clone(),
+    });
+  }
+  diff_meta_data(&new.name, &old.meta_data, &new.meta_data, changes);
+}
+
+impl Manifest {
+  /// Compares this manifest (the old version) with `other` (the new version).
+  pub fn diff(
+    &self,
+    other: &Manifest,
+  ) -> ManifestDiff {
+    let mut changes = Vec::new();
+
+    let version_code = |manifest: &Manifest| manifest.version_code.map(|code| code.to_string());
+    for (name, old, new) in [
+      ("versionCode", version_code(self), version_code(other)),
+      (
+        "versionName",
+        self.version_name.clone(),
+        other.version_name.clone(),
+      ),
+      (
+        "minSdkVersion",
+        self.uses_sdk.min_sdk_version.clone(),
+        other.uses_sdk.min_sdk_version.clone(),
+      ),
+      (
+        "targetSdkVersion",
+        self.uses_sdk.target_sdk_version.clone(),
+        other.uses_sdk.target_sdk_version.clone(),
+      ),
+      (
+        "maxSdkVersion",
+        self.uses_sdk.max_sdk_version.clone(),
+        other.uses_sdk.max_sdk_version.clone(),
+      ),
+    ] {
+      if old != new {
+        changes.push(ManifestChange::AttributeChanged { name, old, new });
+      }
+    }
+
+    let (old_permissions, new_permissions) = (permissions(self), permissions(other));
+    let names = old_permissions
+      .keys()
+      .chain(new_permissions.keys())
+      .collect::<BTreeSet<_>>();
+    for name in names {
+      let name = name.to_string();
+      match (
+        old_permissions.get(name.as_str()),
+        new_permissions.get(name.as_str()),
+      ) {
+        (Some(old), Some(new)) => {
+          if old.max_sdk_version != new.max_sdk_version {
+            changes.push(ManifestChange::PermissionMaxSdkChanged {
+              name: name.clone(),
+              old: old.max_sdk_version,
+              new: new.max_sdk_version,
+            });
+          }
+          if old.sdk_23 != new.sdk_23 {
+            changes.push(ManifestChange::PermissionSdk23Changed {
+              name,
+              old: old.sdk_23,
+              new: new.sdk_23,
+            });
+          }
+        }
+        (Some(_), None) => changes.push(ManifestChange::PermissionRemoved { name }),
+        (None, Some(_)) => changes.push(ManifestChange::PermissionAdded { name }),
+        (None, None) => {}
+      }
+    }
+
+    let (old_components, new_components) = (components(self), components(other));
+    let keys = old_components
+      .keys()
+      .chain(new_components.keys())
+      .collect::<BTreeSet<_>>();
+    for key in keys {
+      let (old_count, new_count) = (
+        old_components.get(key).map_or(0, |(_, count)| *count),
+        new_components.get(key).map_or(0, |(_, count)| *count),
+      );
+      if new_count > 1 && new_count != old_count {
+        changes.push(ManifestChange::ComponentDuplicated {
+          kind: key.0,
+          name: key.1.clone(),
+          count: new_count,
+        });
+      }
+      let (old, new) = (
+        old_components.get(key).map(|(component, _)| *component),
+        new_components.get(key).map(|(component, _)| *component),
+      );
+      match (old, new) {
+        (Some(old), Some(new)) => diff_component(old, new, &mut changes),
+        (Some(old), None) => changes.push(ManifestChange::ComponentRemoved {
+          kind: old.kind,
+          name: old.name.clone(),
+          exported: old.effective_exported.exported,
+        }),
+        (None, Some(new)) => changes.push(ManifestChange::ComponentAdded {
+          kind: new.kind,
+          name: new.name.clone(),
+          exported: new.effective_exported.exported,
+        }),
+        (None, None) => {}
+      }
+    }
+
+    let meta_data = |manifest: &Manifest| {
+      manifest
+        .application
+        .as_ref()
+        .map(|application| application.meta_data.clone())
+        .unwrap_or_default()
+    };
+    diff_meta_data(
+      "application",
+      &meta_data(self),
+      &meta_data(other),
+      &mut changes,
+    );
+
+    ManifestDiff { changes }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::Result;
+
+  #[test]
+  fn test_manifest_diff() -> Result<()> {
+    let old = Manifest::from_xml(
+      br#"<manifest package="com.example" versionCode="1" versionName="1.0">
+<uses-sdk minSdkVersion="21" targetSdkVersion="30"></uses-sdk>
+<uses-permission name="android.permission.INTERNET"></uses-permission>
+<uses-permission name="android.permission.READ_CONTACTS"></uses-permission>
+<uses-permission name="android.permission.WRITE_EXTERNAL_STORAGE" maxSdkVersion="28"></uses-permission>
+<application>
+<meta-data name="com.example.key" value="old"></meta-data>
+<meta-data name="com.example.gone" value="1"></meta-data>
+<activity name=".Main" exported="true">
+<intent-filter><action name="android.intent.action.MAIN"></action><category name="android.intent.category.LAUNCHER"></category></intent-filter>
+</activity>
+<activity name=".Share">
+<intent-filter><action name="android.intent.action.SEND"></action><data mimeType="text/plain"></data></intent-filter>
+</activity>
+<service name=".Sync" exported="false"></service>
+<receiver name=".Boot" exported="false"></receiver>
+</application>
+</manifest>"#,
+    )?;
+    // Same elements in another order
+    let reordered = Manifest::from_xml(
+      br#"<manifest package="com.example" versionCode="1" versionName="1.0">
+<uses-permission name="android.permission.READ_CONTACTS"></uses-permission>
+<uses-permission name="android.permission.WRITE_EXTERNAL_STORAGE" maxSdkVersion="28"></uses-permission>
+<uses-permission name="android.permission.INTERNET"></uses-permission>
+<uses-sdk targetSdkVersion="30" minSdkVersion="21"></uses-sdk>
+<application>
+<receiver name="com.example.Boot" exported="false"></receiver>
+<service name=".Sync" exported="false"></service>
+<activity name=".Share">
+<intent-filter><data mimeType="text/plain"></data><action name="android.intent.action.SEND"></action></intent-filter>
+</activity>
+<activity name=".Main" exported="true">
+<intent-filter><category name="android.intent.category.LAUNCHER"></category><action name="android.intent.action.MAIN"></action></intent-filter>
+</activity>
+<meta-data name="com.example.gone" value="1"></meta-data>
+<meta-data name="com.example.key" value="old"></meta-data>
+</application>
+</manifest>"#,
+    )?;
+    assert!(old.diff(&old).is_empty());
+    assert!(old.diff(&reordered).is_empty());
+
+    let new = Manifest::from_xml(
+      br#"<manifest package="com.example" versionCode="2" versionName="1.1">
+<uses-sdk minSdkVersion="21" targetSdkVersion="34"></uses-sdk>
+<uses-permission name="android.permission.INTERNET"></uses-permission>
+<uses-permission name="android.permission.CAMERA"></uses-permission>
+<uses-permission-sdk-23 name="android.permission.WRITE_EXTERNAL_STORAGE"></uses-permission-sdk-23>
+<application>
+<meta-data name="com.example.key" value="new"></meta-data>
+<meta-data name="com.example.added" resource="@res/0x7f010000"></meta-data>
+<activity name=".Main" exported="true">
+<intent-filter><action name="android.intent.action.MAIN"></action><category name="android.intent.category.LAUNCHER"></category></intent-filter>
+</activity>
+<activity name=".Share" exported="true">
+<intent-filter><action name="android.intent.action.SEND"></action><data mimeType="image/*"></data></intent-filter>
+</activity>
+<service name=".Sync" exported="true"></service>
+<provider name=".Files" authorities="com.example.files" exported="false"></provider>
+<provider name=".Files" authorities="com.example.other" exported="true"></provider>
+</application>
+</manifest>"#,
+    )?;
+    let diff = old.diff(&new);
+    let lines = diff.to_string();
+    assert_eq!(
+      lines.lines().collect::<Vec<_>>(),
+      [
+        "~ versionCode 1 -> 2",
+        "~ versionName 1.0 -> 1.1",
+        "~ targetSdkVersion 30 -> 34",
+        "+ uses-permission android.permission.CAMERA",
+        "- uses-permission android.permission.READ_CONTACTS",
+        "~ uses-permission android.permission.WRITE_EXTERNAL_STORAGE maxSdkVersion 28 -> (none)",
+        "~ uses-permission android.permission.WRITE_EXTERNAL_STORAGE sdk-23 false -> true",
+        "- activity com.example.Share intent-filter actions=[android.intent.action.SEND] data=[content:, file:, text/plain]",
+        "+ activity com.example.Share intent-filter actions=[android.intent.action.SEND] data=[content:, file:, image/*]",
+        "~ service com.example.Sync exported false -> true",
+        "- receiver com.example.Boot",
+        "! provider com.example.Files declared 2 times",
+        "+ provider com.example.Files",
+        "+ meta-data application com.example.added = @res/0x7f010000",
+        "- meta-data application com.example.gone = 1",
+        "~ meta-data application com.example.key old -> new",
+      ]
+    );
+
+    let json = diff.to_json();
+    assert!(json
+      .starts_with(r#"[{"change":"attribute_changed","name":"versionCode","old":"1","new":"2"}"#));
+    assert!(json.contains(
+      r#"{"change":"exported_changed","kind":"service","name":"com.example.Sync","old":false,"new":true}"#
+    ));
+    assert!(json.contains(
+      r#"{"change":"component_duplicated","kind":"provider","name":"com.example.Files","count":2}"#
+    ));
+    let escaped = ManifestDiff {
+      changes: vec![ManifestChange::PermissionAdded {
+        name: "a\"b\\\n\u{1}".to_string(),
+      }],
+    };
+    assert_eq!(
+      escaped.to_json(),
+      r#"[{"change":"permission_added","name":"a\"b\\\n\u0001"}]"#
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,499 @@
+use crate::manifest::Element;
+use crate::nom_parser::ParseError;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Targets that changed the defaults of the base config
+const CLEARTEXT_DEFAULT_OFF: u32 = 28;
+const USER_CERTIFICATES_DEFAULT_OFF: u32 = 24;
+
+/// Where the certificates of a `<certificates>` trust anchor come from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CertificateSource {
+  /// The preinstalled system CAs.
+  System,
+  /// CAs the user added, which includes interception proxies.
+  User,
+  /// A raw resource with PEM or DER certificates, the path in the APK once the reference is
+  /// resolved.
+  Resource(String),
+}
+
+impl std::fmt::Display for CertificateSource {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      CertificateSource::System => f.write_str("system"),
+      CertificateSource::User => f.write_str("user"),
+      CertificateSource::Resource(path) => f.write_str(path),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Certificates {
+  pub source: CertificateSource,
+  /// Certificates from this source bypass pin sets.
+  pub override_pins: bool,
+}
+
+/// A `<pin>`, the base64 digest of a certificate's SubjectPublicKeyInfo.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pin {
+  /// Digest algorithm, `SHA-256` is the only one Android supports.
+  pub digest: String,
+  pub value: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PinSet {
+  /// `yyyy-MM-dd` after which the pins are no longer enforced.
+  pub expiration: Option<String>,
+  pub pins: Vec<Pin>,
+}
+
+impl PinSet {
+  /// Whether the pins stopped being enforced before `today`, a `yyyy-MM-dd` date.
+  pub fn is_expired(
+    &self,
+    today: &str,
+  ) -> bool {
+    self
+      .expiration
+      .as_deref()
+      .is_some_and(|expiration| expiration < today)
+  }
+}
+
+/// The UTC date of `time` as `yyyy-MM-dd`, the format of pin set expirations, using the days
+/// to civil date algorithm of http://howardhinnant.github.io/date_algorithms.html. Times
+/// before the epoch count as the epoch.
+pub fn utc_date(time: SystemTime) -> String {
+  let seconds = time
+    .duration_since(UNIX_EPOCH)
+    .map(|duration| duration.as_secs())
+    .unwrap_or_default();
+  let days = (seconds / 86400) as i64 + 719468;
+  let era = days.div_euclid(146097);
+  let day_of_era = days.rem_euclid(146097);
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let month = if month_index < 10 {
+    month_index + 3
+  } else {
+    month_index - 9
+  };
+  let year = year_of_era + era * 400 + i64::from(month <= 2);
+  format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Domain {
+  pub name: String,
+  pub include_subdomains: bool,
+}
+
+impl std::fmt::Display for Domain {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    if self.include_subdomains {
+      write!(f, "*.")?;
+    }
+    f.write_str(&self.name)
+  }
+}
+
+/// `<base-config>`, settings for domains no domain config covers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BaseConfig {
+  pub cleartext_traffic_permitted: Option<bool>,
+  /// `None` without `<trust-anchors>`, the platform default then applies.
+  pub trust_anchors: Option<Vec<Certificates>>,
+}
+
+/// `<domain-config>`, settings that are inherited by nested domain configs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DomainConfig {
+  pub domains: Vec<Domain>,
+  pub cleartext_traffic_permitted: Option<bool>,
+  pub trust_anchors: Option<Vec<Certificates>>,
+  pub pin_set: Option<PinSet>,
+  pub domain_configs: Vec<DomainConfig>,
+}
+
+/// A decoded `res/xml` network security config, the file `android:networkSecurityConfig`
+/// points at.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkSecurityConfig {
+  pub base_config: Option<BaseConfig>,
+  pub domain_configs: Vec<DomainConfig>,
+  /// Trust anchors added when the app is debuggable.
+  pub debug_overrides: Option<Vec<Certificates>>,
+}
+
+impl NetworkSecurityConfig {
+  pub fn from_xml(xml: &[u8]) -> Result<Self, ParseError> {
+    Self::from_element(&Element::parse(xml)?)
+  }
+
+  pub fn from_element(root: &Element) -> Result<Self, ParseError> {
+    if root.name != "network-security-config" {
+      return Err(ParseError::BuildXml(format!(
+        "expected a network-security-config element, found {}",
+        root.name
+      )));
+    }
+    let mut config = Self::default();
+    for child in &root.children {
+      match child.name.as_str() {
+        "base-config" => {
+          config.base_config = Some(BaseConfig {
+            cleartext_traffic_permitted: child.attributes.get_bool("cleartextTrafficPermitted"),
+            trust_anchors: trust_anchors(child),
+          })
+        }
+        "domain-config" => config.domain_configs.push(domain_config(child)),
+        "debug-overrides" => config.debug_overrides = trust_anchors(child),
+        _ => {}
+      }
+    }
+    Ok(config)
+  }
+
+  /// Every domain config, nested ones right after their parent.
+  pub fn all_domain_configs(&self) -> Vec<&DomainConfig> {
+    fn walk<'a>(
+      configs: &'a [DomainConfig],
+      out: &mut Vec<&'a DomainConfig>,
+    ) {
+      for config in configs {
+        out.push(config);
+        walk(&config.domain_configs, out);
+      }
+    }
+    let mut configs = Vec::new();
+    walk(&self.domain_configs, &mut configs);
+    configs
+  }
+
+  /// Whether the base config allows cleartext, by default only when targeting API 27 or lower.
+  pub fn base_cleartext_permitted(
+    &self,
+    target_sdk: u32,
+  ) -> bool {
+    self
+      .base_config
+      .as_ref()
+      .and_then(|base| base.cleartext_traffic_permitted)
+      .unwrap_or(target_sdk < CLEARTEXT_DEFAULT_OFF)
+  }
+
+  /// Domains that allow cleartext, with the setting inherited from enclosing domain configs
+  /// and then the base config.
+  pub fn cleartext_domains(
+    &self,
+    target_sdk: u32,
+  ) -> Vec<&Domain> {
+    fn walk<'a>(
+      configs: &'a [DomainConfig],
+      inherited: bool,
+      out: &mut Vec<&'a Domain>,
+    ) {
+      for config in configs {
+        let permitted = config.cleartext_traffic_permitted.unwrap_or(inherited);
+        if permitted {
+          out.extend(&config.domains);
+        }
+        walk(&config.domain_configs, permitted, out);
+      }
+    }
+    let mut domains = Vec::new();
+    walk(
+      &self.domain_configs,
+      self.base_cleartext_permitted(target_sdk),
+      &mut domains,
+    );
+    domains
+  }
+
+  /// Whether release builds trust user installed CAs, from the base config, by default when
+  /// targeting API 23 or lower, or from a domain config.
+  pub fn trusts_user_certificates(
+    &self,
+    target_sdk: u32,
+  ) -> bool {
+    let has_user = |anchors: &[Certificates]| {
+      anchors
+        .iter()
+        .any(|anchor| anchor.source == CertificateSource::User)
+    };
+    let base = match self
+      .base_config
+      .as_ref()
+      .and_then(|base| base.trust_anchors.as_deref())
+    {
+      Some(anchors) => has_user(anchors),
+      None => target_sdk < USER_CERTIFICATES_DEFAULT_OFF,
+    };
+    base
+      || self
+        .all_domain_configs()
+        .iter()
+        .any(|config| config.trust_anchors.as_deref().is_some_and(has_user))
+  }
+
+  /// Domain configs whose pins are no longer enforced on `today`, a `yyyy-MM-dd` date.
+  pub fn expired_pin_sets(
+    &self,
+    today: &str,
+  ) -> Vec<&DomainConfig> {
+    self
+      .all_domain_configs()
+      .into_iter()
+      .filter(|config| {
+        config
+          .pin_set
+          .as_ref()
+          .is_some_and(|pin_set| pin_set.is_expired(today))
+      })
+      .collect()
+  }
+}
+
+impl std::fmt::Display for NetworkSecurityConfig {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    if let Some(base) = &self.base_config {
+      writeln!(f, "base-config")?;
+      write_settings(
+        f,
+        1,
+        base.cleartext_traffic_permitted,
+        base.trust_anchors.as_deref(),
+      )?;
+    }
+    for config in &self.domain_configs {
+      write_domain_config(f, 0, config)?;
+    }
+    if let Some(anchors) = &self.debug_overrides {
+      writeln!(f, "debug-overrides")?;
+      write_settings(f, 1, None, Some(anchors))?;
+    }
+    Ok(())
+  }
+}
+
+fn write_settings(
+  f: &mut std::fmt::Formatter<'_>,
+  depth: usize,
+  cleartext_traffic_permitted: Option<bool>,
+  trust_anchors: Option<&[Certificates]>,
+) -> std::fmt::Result {
+  let indent = "  ".repeat(depth);
+  if let Some(permitted) = cleartext_traffic_permitted {
+    writeln!(f, "{}cleartext: {}", indent, permitted)?;
+  }
+  for anchor in trust_anchors.unwrap_or_default() {
+    let override_pins = if anchor.override_pins {
+      " (overrides pins)"
+    } else {
+      ""
+    };
+    writeln!(f, "{}trust: {}{}", indent, anchor.source, override_pins)?;
+  }
+  Ok(())
+}
+
+fn write_domain_config(
+  f: &mut std::fmt::Formatter<'_>,
+  depth: usize,
+  config: &DomainConfig,
+) -> std::fmt::Result {
+  let domains = config
+    .domains
+    .iter()
+    .map(|domain| domain.to_string())
+    .collect::<Vec<_>>();
+  writeln!(
+    f,
+    "{}domain-config {}",
+    "  ".repeat(depth),
+    domains.join(", ")
+  )?;
+  write_settings(
+    f,
+    depth + 1,
+    config.cleartext_traffic_permitted,
+    config.trust_anchors.as_deref(),
+  )?;
+  if let Some(pin_set) = &config.pin_set {
+    let indent = "  ".repeat(depth + 1);
+    match &pin_set.expiration {
+      Some(expiration) => writeln!(f, "{}pin-set (expires {})", indent, expiration)?,
+      None => writeln!(f, "{}pin-set", indent)?,
+    }
+    for pin in &pin_set.pins {
+      writeln!(f, "{}  {} {}", indent, pin.digest, pin.value)?;
+    }
+  }
+  for nested in &config.domain_configs {
+    write_domain_config(f, depth + 1, nested)?;
+  }
+  Ok(())
+}
+
+fn trust_anchors(element: &Element) -> Option<Vec<Certificates>> {
+  let anchors = element.children_named("trust-anchors").next()?;
+  Some(
+    anchors
+      .children_named("certificates")
+      .filter_map(|certificates| {
+        let source = match certificates.attribute("src")? {
+          "system" => CertificateSource::System,
+          "user" => CertificateSource::User,
+          path => CertificateSource::Resource(path.to_string()),
+        };
+        Some(Certificates {
+          source,
+          override_pins: certificates
+            .attributes
+            .get_bool("overridePins")
+            .unwrap_or_default(),
+        })
+      })
+      .collect(),
+  )
+}
+
+fn domain_config(element: &Element) -> DomainConfig {
+  DomainConfig {
+    domains: element
+      .children_named("domain")
+      .map(|domain| Domain {
+        name: domain.text.clone(),
+        include_subdomains: domain
+          .attributes
+          .get_bool("includeSubdomains")
+          .unwrap_or_default(),
+      })
+      .collect(),
+    cleartext_traffic_permitted: element.attributes.get_bool("cleartextTrafficPermitted"),
+    trust_anchors: trust_anchors(element),
+    pin_set: element
+      .children_named("pin-set")
+      .next()
+      .map(|pin_set| PinSet {
+        expiration: pin_set.attribute("expiration").map(str::to_string),
+        pins: pin_set
+          .children_named("pin")
+          .map(|pin| Pin {
+            digest: pin.attribute("digest").unwrap_or("SHA-256").to_string(),
+            value: pin.text.clone(),
+          })
+          .collect(),
+      }),
+    domain_configs: element
+      .children_named("domain-config")
+      .map(domain_config)
+      .collect(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::Result;
+  use std::time::Duration;
+
+  #[test]
+  fn test_network_security_config() -> Result<()> {
+    let config = NetworkSecurityConfig::from_xml(
+      br#"<network-security-config>
+  <base-config cleartextTrafficPermitted="false">
+    <trust-anchors><certificates src="system"></certificates></trust-anchors>
+  </base-config>
+  <domain-config cleartextTrafficPermitted="true">
+    <domain includeSubdomains="true">example.com</domain>
+    <trust-anchors>
+      <certificates src="res/raw/ca.pem" overridePins="true"></certificates>
+    </trust-anchors>
+    <pin-set expiration="2025-01-01">
+      <pin digest="SHA-256">7HIpactkIAq2Y49orFOOQKurWxmmSFZhBCoQYcRhJ3Y=</pin>
+    </pin-set>
+    <domain-config cleartextTrafficPermitted="false">
+      <domain>secure.example.com</domain>
+    </domain-config>
+    <domain-config>
+      <domain>api.example.com</domain>
+      <trust-anchors><certificates src="user"></certificates></trust-anchors>
+    </domain-config>
+  </domain-config>
+  <debug-overrides>
+    <trust-anchors><certificates src="user"></certificates></trust-anchors>
+  </debug-overrides>
+</network-security-config>"#,
+    )?;
+    assert!(!config.base_cleartext_permitted(21));
+    let names = |domains: Vec<&Domain>| {
+      domains
+        .iter()
+        .map(|domain| domain.to_string())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+      names(config.cleartext_domains(34)),
+      ["*.example.com", "api.example.com"]
+    );
+    assert!(config.trusts_user_certificates(34));
+    assert_eq!(config.all_domain_configs().len(), 3);
+    assert_eq!(config.expired_pin_sets("2024-12-31").len(), 0);
+    assert_eq!(config.expired_pin_sets("2026-10-18").len(), 1);
+    let date = |seconds| utc_date(UNIX_EPOCH + Duration::from_secs(seconds));
+    assert_eq!(date(0), "1970-01-01");
+    assert_eq!(date(951868799), "2000-02-29");
+    assert_eq!(date(951868800), "2000-03-01");
+    assert_eq!(date(1792367999), "2026-10-18");
+    assert_eq!(utc_date(UNIX_EPOCH - Duration::from_secs(1)), "1970-01-01");
+    assert_eq!(
+      config.debug_overrides,
+      Some(vec![Certificates {
+        source: CertificateSource::User,
+        override_pins: false,
+      }])
+    );
+    assert_eq!(
+      config.to_string(),
+      "base-config
+  cleartext: false
+  trust: system
+domain-config *.example.com
+  cleartext: true
+  trust: res/raw/ca.pem (overrides pins)
+  pin-set (expires 2025-01-01)
+    SHA-256 7HIpactkIAq2Y49orFOOQKurWxmmSFZhBCoQYcRhJ3Y=
+  domain-config secure.example.com
+    cleartext: false
+  domain-config api.example.com
+    trust: user
+debug-overrides
+  trust: user
+"
+    );
+
+    let defaults =
+      NetworkSecurityConfig::from_xml(b"<network-security-config></network-security-config>")?;
+    assert!(defaults.base_cleartext_permitted(27));
+    assert!(!defaults.base_cleartext_permitted(28));
+    assert!(defaults.trusts_user_certificates(23));
+    assert!(!defaults.trusts_user_certificates(24));
+    Ok(())
+  }
+}
@@ -69,6 +69,36 @@ pub enum ParseError {
 
   #[error("Failed to open file: {0}")]
   File(String),
+
+  #[error("Missing entry in APK: {0}")]
+  MissingEntry(String),
+
+  #[error("Failed to read split APK: {0}")]
+  Split(String),
+
+  #[error("Failed to parse protobuf: {0}")]
+  Protobuf(String),
+
+  #[error("Failed to read ABX: {0}")]
+  Abx(String),
+
+  #[error("Failed to read APK signature: {0}")]
+  Signature(String),
+
+  #[error("Failed to parse library chunk: {0}")]
+  TableLibrary(String),
+
+  #[error("Failed to parse staged alias chunk: {0}")]
+  TableStagedAlias(String),
+
+  #[error("Resource not found: {0}")]
+  ResourceNotFound(String),
+
+  #[error("Invalid locale: {0}")]
+  InvalidLocale(String),
+
+  #[error("Failed to write resource table: {0}")]
+  WriteTable(String),
   // TODO: Add more error variants as needed
 }
 
@@ -167,15 +197,14 @@ impl StringPoolChunk {
     string_buffer: &[u8],
   ) -> Result<String, ParseError> {
     if self.is_utf8 {
-      let (mut string_buffer, str_len) = le_u8::<_, nom::error::Error<&[u8]>>(string_buffer)
-        .map_err(|e| ParseError::String(e.to_string()))?;
-      string_buffer = &string_buffer[1..];
-      if string_buffer.len() < str_len as usize {
+      // UTF-8 strings are prefixed with their length in UTF-16 units and then in bytes,
+      // each stored in one byte, or two when the high bit of the first one is set
+      let (string_buffer, _utf16_len) = Self::utf8_length(string_buffer)?;
+      let (string_buffer, str_len) = Self::utf8_length(string_buffer)?;
+      if string_buffer.len() < str_len {
         return Err(ParseError::BufferNotEnough("Not enough bytes".to_string()));
       }
-      let (_, str_bytes) =
-        count(le_u8::<_, nom::error::Error<&[u8]>>, str_len as usize)(string_buffer)
-          .map_err(|e| ParseError::String(e.to_string()))?;
+      let str_bytes = &string_buffer[..str_len];
       let null_pos = str_bytes
         .iter()
         .position(|&x| x == 0)
@@ -183,14 +212,21 @@ impl StringPoolChunk {
       let str_bytes = &str_bytes[..null_pos];
       Ok(String::from_utf8_lossy(str_bytes).to_string())
     } else {
-      let (string_buffer, str_len) = le_u16::<_, nom::error::Error<&[u8]>>(string_buffer)
-        .map_err(|e| ParseError::String(e.to_string()))?;
-      if string_buffer.len() < str_len as usize * 2 {
+      let (mut string_buffer, mut str_len) = le_u16::<_, nom::error::Error<&[u8]>>(string_buffer)
+        .map_err(|e| ParseError::String(e.to_string()))
+        .map(|(buffer, len)| (buffer, len as usize))?;
+      if str_len & 0x8000 != 0 {
+        // lengths above 0x7fff continue in a second u16
+        let (buffer, low) = le_u16::<_, nom::error::Error<&[u8]>>(string_buffer)
+          .map_err(|e| ParseError::String(e.to_string()))?;
+        string_buffer = buffer;
+        str_len = ((str_len & 0x7fff) << 16) | low as usize;
+      }
+      if string_buffer.len() < str_len * 2 {
         return Err(ParseError::BufferNotEnough("Not enough bytes".to_string()));
       }
-      let (_, str_bytes) =
-        count(le_u16::<_, nom::error::Error<&[u8]>>, str_len as usize)(string_buffer)
-          .map_err(|e| ParseError::String(e.to_string()))?;
+      let (_, str_bytes) = count(le_u16::<_, nom::error::Error<&[u8]>>, str_len)(string_buffer)
+        .map_err(|e| ParseError::String(e.to_string()))?;
       let null_pos = str_bytes
         .iter()
         .position(|&x| x == 0)
@@ -199,6 +235,30 @@ impl StringPoolChunk {
       Ok(String::from_utf16_lossy(str_bytes))
     }
   }
+
+  fn utf8_length(buffer: &[u8]) -> Result<(&[u8], usize), ParseError> {
+    let (buffer, len) = le_u8::<_, nom::error::Error<&[u8]>>(buffer)
+      .map_err(|e| ParseError::String(e.to_string()))?;
+    if len & 0x80 == 0 {
+      return Ok((buffer, len as usize));
+    }
+    let (buffer, low) = le_u8::<_, nom::error::Error<&[u8]>>(buffer)
+      .map_err(|e| ParseError::String(e.to_string()))?;
+    Ok((buffer, (((len & 0x7f) as usize) << 8) | low as usize))
+  }
+}
+
+// Span of a styled string, ResStringPool_span. The name is the index of the tag in the same
+// string pool, e.g. "b" or "font;color=red". Characters are counted in UTF-16 units.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct StringPoolSpan {
+  pub name: u32,
+  pub first_char: u32,
+  pub last_char: u32,
+}
+
+impl StringPoolSpan {
+  pub const END: u32 = 0xFFFFFFFF;
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -221,7 +281,9 @@ pub struct PackageChunkHeader {
   pub last_public_type: u32,
   pub key_strings: u32,
   pub last_public_key: u32,
-  // pub type_id_offset: u32, // was added later, may not present
+  // Added later, headers of older tables end before this field. Type ids of the package start
+  // after this offset, so the type string at index 0 names type `type_id_offset + 1`.
+  pub type_id_offset: u32,
 }
 
 impl PackageChunkHeader {
@@ -239,27 +301,71 @@ impl PackageChunkHeader {
       le_u32,
     ))(input)?;
 
-    let null_pos = name_bytes
-      .iter()
-      .position(|&x| x == 0)
-      .unwrap_or(name_bytes.len());
-    let name_bytes = &name_bytes[..null_pos];
-    let name = String::from_utf16_lossy(name_bytes);
+    let (input, type_id_offset) = if header.header_size >= 288 {
+      le_u32(input)?
+    } else {
+      (input, 0)
+    };
 
     let header = PackageChunkHeader {
       header,
       id,
-      name,
+      name: utf16_name(&name_bytes),
       type_strings,
       last_public_type,
       key_strings,
       last_public_key,
+      type_id_offset,
     };
 
     Ok((input, header))
   }
 }
 
+// Null terminated UTF-16 name of a fixed size array, as used for package names.
+fn utf16_name(name: &[u16]) -> String {
+  let null_pos = name.iter().position(|&x| x == 0).unwrap_or(name.len());
+  String::from_utf16_lossy(&name[..null_pos])
+}
+
+// ResTable_lib_header, the dynamic reference table mapping the package ids used by shared
+// libraries at build time to their package names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LibraryChunk {
+  pub header: ChunkHeader,
+  // (package id, package name) pairs
+  pub entries: Vec<(u32, String)>,
+}
+
+impl LibraryChunk {
+  pub(crate) fn parse(input: &[u8]) -> IResult<&[u8], LibraryChunk> {
+    let (input, (header, entry_count)) = tuple((ChunkHeader::parse, le_u32))(input)?;
+    let (input, entries) = count(
+      map(tuple((le_u32, count(le_u16, 128))), |(package_id, name)| {
+        (package_id, utf16_name(&name))
+      }),
+      entry_count as usize,
+    )(input)?;
+    Ok((input, LibraryChunk { header, entries }))
+  }
+}
+
+// ResTable_staged_alias_header, mapping staged resource ids to their finalized ids.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StagedAliasChunk {
+  pub header: ChunkHeader,
+  // (staged resource id, finalized resource id) pairs
+  pub entries: Vec<(u32, u32)>,
+}
+
+impl StagedAliasChunk {
+  pub(crate) fn parse(input: &[u8]) -> IResult<&[u8], StagedAliasChunk> {
+    let (input, (header, entry_count)) = tuple((ChunkHeader::parse, le_u32))(input)?;
+    let (input, entries) = count(tuple((le_u32, le_u32)), entry_count as usize)(input)?;
+    Ok((input, StagedAliasChunk { header, entries }))
+  }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TypeSpecChunkHeader {
   pub header: ChunkHeader,
@@ -278,6 +384,24 @@ pub struct TypeSpecChunkHeader {
 }
 
 impl TypeSpecChunkHeader {
+  // Header of a TABLE_SPEC chunk built from a model rather than parsed.
+  pub(crate) fn new(
+    type_id: u8,
+    entry_count: u32,
+  ) -> Self {
+    Self {
+      header: ChunkHeader {
+        typ: ChunkType::TABLE_SPEC,
+        header_size: 16,
+        chunk_size: 16 + entry_count * 4,
+      },
+      type_id,
+      res0: 0,
+      res1: 0,
+      entry_count,
+    }
+  }
+
   pub(crate) fn parse(input: &[u8]) -> IResult<&[u8], (TypeSpecChunkHeader, Vec<u32>)> {
     let (input, (header, type_id, res0, res1, entry_count)) =
       tuple((ChunkHeader::parse, le_u8, le_u8, le_u16, le_u32))(input)?;
@@ -306,7 +430,7 @@ pub struct TypeChunkConfig {
   // TODO: add later if necessary
   pub data: Vec<u8>,
 }
-struct TypeChunkFlags;
+pub(crate) struct TypeChunkFlags;
 impl TypeChunkFlags {
   // If set, the entry is sparse, and encodes both the entry ID and offset into each entry,
   // and a binary search is used to find the key. Only available on platforms >= O.
@@ -370,6 +494,41 @@ impl TypeChunkHeader {
   }
 }
 
+pub(crate) struct TypeSpecFlag;
+impl TypeSpecFlag {
+  // Additional flag indicating an entry is public.
+  pub const SPEC_PUBLIC: u32 = 0x4000_0000;
+  // Additional flag indicating the resource id for this resource may change in a future
+  // build. If this flag is set, the SPEC_PUBLIC flag is also set since the resource must be
+  // public to be exposed as an API to other applications.
+  pub const SPEC_STAGED_API: u32 = 0x2000_0000;
+  // The lower bits hold the configuration dimensions an entry varies by.
+  pub const CONFIG_MASK: u32 = 0x00ff_ffff;
+}
+
+// Configuration change flags, as stored in the lower bits of each TABLE_SPEC entry.
+// These match the ACONFIGURATION_* constants from the NDK.
+pub(crate) const CONFIG_CHANGES: [(u32, &str); 18] = [
+  (0x0001, "mcc"),
+  (0x0002, "mnc"),
+  (0x0004, "locale"),
+  (0x0008, "touchscreen"),
+  (0x0010, "keyboard"),
+  (0x0020, "keyboardHidden"),
+  (0x0040, "navigation"),
+  (0x0080, "orientation"),
+  (0x0100, "density"),
+  (0x0200, "screenSize"),
+  (0x0400, "version"),
+  (0x0800, "screenLayout"),
+  (0x1000, "uiMode"),
+  (0x2000, "smallestScreenSize"),
+  (0x4000, "layoutDirection"),
+  (0x8000, "screenRound"),
+  (0x1_0000, "colorMode"),
+  (0x2_0000, "grammaticalGender"),
+];
+
 pub(crate) struct TableEntryFlag;
 impl TableEntryFlag {
   // If set, this is a complex entry, holding a set of name/value
@@ -480,7 +639,7 @@ impl ResValue {
       ResType::INT_BOOLEAN => Some((if self.data != 0 { "true" } else { "false" }).to_string()),
       ResType::INT_DEC => Some(format!("{}", self.data)),
       ResType::INT_HEX => Some(format!("0x{:X}", self.data)),
-      ResType::FLOAT => Some(format!("{:.2}", self.data as f32)),
+      ResType::FLOAT => Some(format!("{:.2}", f32::from_bits(self.data))),
       ResType::REFERENCE => Some(format!("@res/0x{:x}", self.data)),
       ResType::DYNAMIC_REFERENCE => Some(format!("@dyn/0x{:X}", self.data)),
       ResType::ATTRIBUTE => Some(format!("@attr/0x{:x}", self.data)),
@@ -511,15 +670,38 @@ impl ResType {
   // container.
   pub const FRACTION: u8 = 0x06;
 
+  // The 'data' holds a dynamic ResTable_ref, which needs to be
+  // resolved before it can be used like a TYPE_REFERENCE.
   pub const DYNAMIC_REFERENCE: u8 = 0x07;
+  // The 'data' holds an attribute resource identifier, which needs to be resolved
+  // before it can be used like a TYPE_ATTRIBUTE.
+  pub const DYNAMIC_ATTRIBUTE: u8 = 0x08;
+  // The 'data' is a raw integer value of the form n..n.
   pub const INT_DEC: u8 = 0x10;
+  // The 'data' is a raw integer value of the form 0xn..n.
   pub const INT_HEX: u8 = 0x11;
+  // The 'data' is either 0 or 1, for input "false" or "true" respectively.
   pub const INT_BOOLEAN: u8 = 0x12;
+  // The 'data' is a raw integer value of the form #aarrggbb.
+  pub const INT_COLOR_ARGB8: u8 = 0x1c;
+  // The 'data' is a raw integer value of the form #rrggbb.
+  pub const INT_COLOR_RGB8: u8 = 0x1d;
+  // The 'data' is a raw integer value of the form #argb.
+  pub const INT_COLOR_ARGB4: u8 = 0x1e;
+  // The 'data' is a raw integer value of the form #rgb.
+  pub const INT_COLOR_RGB4: u8 = 0x1f;
 }
 
 pub mod parser {
   use super::*;
 
+  pub(crate) fn take_u16s(
+    buffer: &[u8],
+    len: usize,
+  ) -> IResult<&[u8], Vec<u16>> {
+    count(le_u16::<_, nom::error::Error<&[u8]>>, len)(buffer)
+  }
+
   pub(crate) fn take_u32s(
     buffer: &[u8],
     len: usize,
@@ -566,6 +748,47 @@ pub mod parser {
     Ok(strings)
   }
 
+  // Styles of a string pool: the spans of the string at the same index.
+  // Only the first `style_count` strings of a pool can have styles.
+  pub(crate) fn string_styles(string_chunk: &[u8]) -> Result<Vec<Vec<StringPoolSpan>>, ParseError> {
+    let (string_chunk_next, string_pool_chunk) = parse_string_pool_header(string_chunk)
+      .map_err(|e| ParseError::StringPoolHeader(e.to_string()))?;
+    if string_pool_chunk.style_count == 0 {
+      return Ok(Vec::new());
+    }
+
+    let offsets_buffer = string_chunk_next
+      .get(string_pool_chunk.string_count as usize * 4..)
+      .ok_or_else(|| ParseError::BufferNotEnough("Not enough bytes".to_string()))?;
+    let (_, style_offsets) = take_u32s(offsets_buffer, string_pool_chunk.style_count as usize)
+      .map_err(|e| ParseError::StringPool(e.to_string()))?;
+
+    let styles_buffer = string_chunk
+      .get(string_pool_chunk.styles_start as usize..)
+      .unwrap_or_default();
+    let mut styles = Vec::new();
+    for offset in style_offsets {
+      let mut spans = Vec::new();
+      let mut span_buffer = styles_buffer.get(offset as usize..).unwrap_or_default();
+      // A span list is terminated by END, malformed lists simply stop at the end of the chunk
+      while let Ok((next, (name, first_char, last_char))) =
+        tuple((le_u32::<_, nom::error::Error<&[u8]>>, le_u32, le_u32))(span_buffer)
+      {
+        if name == StringPoolSpan::END {
+          break;
+        }
+        spans.push(StringPoolSpan {
+          name,
+          first_char,
+          last_char,
+        });
+        span_buffer = next;
+      }
+      styles.push(spans);
+    }
+    Ok(styles)
+  }
+
   fn read_strings(
     strings_buffer: &[u8],
     string_pool_chunk: &StringPoolChunk,
@@ -620,3 +843,35 @@ pub mod parser {
     )(input)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_value_as_string() {
+    let value = |data_type, data| ResValue {
+      size: 8,
+      res0: 0,
+      data_type,
+      data,
+    };
+    // FLOAT data holds the bits of an IEEE 754 float, 1.5 and not 1069547520.00
+    assert_eq!(
+      value(ResType::FLOAT, 1.5f32.to_bits()).as_string(&[]),
+      Some("1.50".to_string())
+    );
+    assert_eq!(
+      value(ResType::FLOAT, (-0.25f32).to_bits()).as_string(&[]),
+      Some("-0.25".to_string())
+    );
+    assert_eq!(
+      value(ResType::INT_HEX, 0xff).as_string(&[]),
+      Some("0xFF".to_string())
+    );
+    assert_eq!(
+      value(ResType::STRING, 1).as_string(&["a".to_string()]),
+      None
+    );
+  }
+}
@@ -1,65 +1,851 @@
 use crate::arsc_parser::Arsc;
+use crate::deep_links::DeepLinks;
+use crate::inventory::Inventory;
+use crate::label_icon::LabelAndIcon;
+use crate::manifest::Manifest;
+use crate::network_security_config::NetworkSecurityConfig;
 use crate::nom_parser::ParseError;
-use crate::xml_parser::AndroidManifest;
-use std::fs::File;
-use std::io::Read;
+use crate::scan::{ScanLimits, ScanNode, Scanner};
+use crate::signature::{ApkSignatures, V4Signature, V4Verification};
+use crate::values_decoder::ValuesDecoder;
+use crate::xml_parser::{resolve_references, AndroidManifest};
+use crate::xml_resources::XmlResources;
+use flate2::read::DeflateDecoder;
+use flate2::Crc;
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 use zip::ZipArchive;
 
 pub struct Parser {
-  arsc_raw: Vec<u8>,
+  arsc_raw: Option<Vec<u8>>,
   manifest_raw: Vec<u8>,
+  diagnostics: Vec<Diagnostic>,
+  // The APK the manifest was read from, if it was opened through `ApkArchive`
+  archive: Option<ApkArchive>,
+}
+
+/// Problems found while reading an APK that don't prevent decoding it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Diagnostic {
+  /// The APK has no `resources.arsc`, resource references stay unresolved.
+  MissingResourceTable,
+  /// A compression method other than stored or deflated, which Android inflates anyway.
+  UnsupportedCompression { entry: String, method: u16 },
+  /// The encryption flag is set, Android ignores it and reads the entry as usual.
+  EncryptionFlag { entry: String },
+  /// The local file header disagrees with the central directory, which is the one used.
+  LocalHeaderMismatch { entry: String },
+  /// Several entries have the same name, the first one of the central directory is used.
+  DuplicateEntry { entry: String },
+  /// The comment length of the end of central directory record doesn't match the file.
+  EocdCommentMismatch { declared: u16, actual: usize },
+  /// The CRC-32 of the extracted data differs from the one in the central directory.
+  CrcMismatch { entry: String },
+  /// A bundle of split APKs without a base APK.
+  MissingBaseApk,
+  /// A standalone or universal APK of an `.apks`, skipped in favour of the splits.
+  StandaloneApk { entry: String },
+  /// A resource table chunk of an unknown type, skipped like Android does.
+  UnknownChunk { chunk_type: u16 },
+  /// A binary XML document whose root chunk isn't of the XML type, decoded anyway.
+  XmlChunkType { chunk_type: u16 },
+  /// A binary XML chunk of an unknown type, skipped like Android does.
+  UnknownXmlChunk { chunk_type: u16 },
+}
+
+impl std::fmt::Display for Diagnostic {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      Diagnostic::MissingResourceTable => {
+        write!(f, "no resources.arsc, resource references are not resolved")
+      }
+      Diagnostic::UnsupportedCompression { entry, method } => write!(
+        f,
+        "{}: unsupported compression method {}, inflated like Android does",
+        entry, method
+      ),
+      Diagnostic::EncryptionFlag { entry } => {
+        write!(f, "{}: encryption flag set but ignored", entry)
+      }
+      Diagnostic::LocalHeaderMismatch { entry } => write!(
+        f,
+        "{}: local file header disagrees with the central directory",
+        entry
+      ),
+      Diagnostic::DuplicateEntry { entry } => {
+        write!(f, "{}: duplicate entry, using the first one", entry)
+      }
+      Diagnostic::EocdCommentMismatch { declared, actual } => write!(
+        f,
+        "end of central directory declares a {} byte comment, {} bytes follow it",
+        declared, actual
+      ),
+      Diagnostic::CrcMismatch { entry } => write!(f, "{}: CRC-32 mismatch", entry),
+      Diagnostic::MissingBaseApk => write!(f, "no base APK among the splits"),
+      Diagnostic::StandaloneApk { entry } => {
+        write!(f, "{}: standalone APK skipped, the splits are used", entry)
+      }
+      Diagnostic::UnknownChunk { chunk_type } => {
+        write!(
+          f,
+          "resources.arsc: unknown chunk type 0x{:04x} skipped",
+          chunk_type
+        )
+      }
+      Diagnostic::XmlChunkType { chunk_type } => write!(
+        f,
+        "binary XML: root chunk type 0x{:04x} instead of 0x0003, decoded anyway",
+        chunk_type
+      ),
+      Diagnostic::UnknownXmlChunk { chunk_type } => {
+        write!(
+          f,
+          "binary XML: unknown chunk type 0x{:04x} skipped",
+          chunk_type
+        )
+      }
+    }
+  }
 }
 
 impl Parser {
+  /// Reads the APK at `file_path`. The whole file is read into memory, like the other
+  /// constructors `ApkArchive` works on a buffer rather than on a file.
   pub fn from_file(file_path: &Path) -> Result<Self, ParseError> {
-    let file = File::open(file_path).map_err(|e| ParseError::File(e.to_string()))?;
-    let mut archive = ZipArchive::new(file).map_err(|e| ParseError::Zip(e.to_string()))?;
-    let mut manifest_raw: Vec<u8> = Vec::new();
-    let mut arsc_raw: Vec<u8> = Vec::new();
-
-    for i in 0..archive.len() {
-      let mut file = archive
-        .by_index(i)
-        .map_err(|e| ParseError::Zip(e.to_string()))?;
-
-      if file.name() == "AndroidManifest.xml" {
-        let mut data = Vec::new();
-        file
-          .read_to_end(&mut data)
-          .map_err(|e| ParseError::Zip(e.to_string()))?;
-        manifest_raw = data;
-      } else if file.name() == "resources.arsc" {
-        let mut data = Vec::new();
-        file
-          .read_to_end(&mut data)
-          .map_err(|e| ParseError::Zip(e.to_string()))?;
-        arsc_raw = data;
-      }
+    let apk = std::fs::read(file_path).map_err(|e| ParseError::File(e.to_string()))?;
+    Self::from_apk_archive(ApkArchive::new(apk)?)
+  }
+
+  /// Reads an APK that is already in memory.
+  pub fn from_bytes(apk: &[u8]) -> Result<Self, ParseError> {
+    Self::from_apk_archive(ApkArchive::new(apk.to_vec())?)
+  }
+
+  /// Reads an APK from any seekable source, e.g. an entry extracted from another archive.
+  pub fn from_reader<R: Read + Seek>(mut reader: R) -> Result<Self, ParseError> {
+    let mut apk = Vec::new();
+    reader
+      .seek(SeekFrom::Start(0))
+      .and_then(|_| reader.read_to_end(&mut apk))
+      .map_err(|e| ParseError::File(e.to_string()))?;
+    Self::from_apk_archive(ApkArchive::new(apk)?)
+  }
+
+  /// Reads the manifest and resource table with the same ZIP rules Android uses to install the
+  /// APK. Problems Android tolerates are reported as diagnostics.
+  pub fn from_apk_archive(mut archive: ApkArchive) -> Result<Self, ParseError> {
+    let manifest_raw = match archive.by_name("AndroidManifest.xml").cloned() {
+      Some(entry) => archive.read(&entry)?,
+      None => return Err(ParseError::MissingEntry("AndroidManifest.xml".to_string())),
+    };
+    let arsc_raw = match archive.by_name("resources.arsc").cloned() {
+      Some(entry) => Some(archive.read(&entry)?),
+      None => None,
+    };
+
+    let mut parser = Self::from_manifest(manifest_raw, arsc_raw);
+    parser
+      .diagnostics
+      .splice(0..0, archive.diagnostics.iter().cloned());
+    parser.archive = Some(archive);
+    Ok(parser)
+  }
+
+  /// Reads an APK opened with the `zip` crate. Its reader is read again from the start with the
+  /// ZIP rules of `ApkArchive`, so the parser has the whole archive and not just the manifest.
+  pub fn from_archive<R: Read + Seek>(archive: ZipArchive<R>) -> Result<Self, ParseError> {
+    Self::from_reader(archive.into_inner())
+  }
+
+  /// Uses a binary `AndroidManifest.xml` and `resources.arsc` that were extracted beforehand.
+  pub fn from_manifest(
+    manifest: Vec<u8>,
+    arsc: Option<Vec<u8>>,
+  ) -> Self {
+    let mut diagnostics = Vec::new();
+    if arsc.is_none() {
+      diagnostics.push(Diagnostic::MissingResourceTable);
+    }
+    Self {
+      arsc_raw: arsc,
+      manifest_raw: manifest,
+      diagnostics,
+      archive: None,
     }
+  }
 
-    Ok(Self {
-      arsc_raw: arsc_raw.clone(),
-      manifest_raw: manifest_raw.clone(),
-    })
+  /// Non fatal problems found while reading the APK.
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    &self.diagnostics
   }
 
-  pub fn parse(&mut self) -> Result<Vec<u8>, ParseError> {
-    let mut arsc_parser = Arsc::new(&self.arsc_raw);
+  /// The archive the APK was read from. Parsers built from an extracted manifest or a
+  /// `ZipArchive` have none.
+  pub fn archive(&self) -> Option<&ApkArchive> {
+    self.archive.as_ref()
+  }
+
+  // Operations that need the whole APK rather than the manifest and table.
+  fn required_archive(&self) -> Result<&ApkArchive, ParseError> {
+    self
+      .archive
+      .as_ref()
+      .ok_or_else(|| ParseError::MissingEntry("APK archive".to_string()))
+  }
+
+  /// Reads the v1, v2, v3 and v3.1 signatures of the APK and checks their digests against its
+  /// contents.
+  pub fn signatures(&self) -> Result<ApkSignatures, ParseError> {
+    ApkSignatures::from_archive(self.required_archive()?)
+  }
+
+  /// Checks the contents of a v4 `.idsig` file against the APK.
+  pub fn verify_v4(
+    &self,
+    idsig: &[u8],
+  ) -> Result<V4Verification, ParseError> {
+    V4Signature::parse(idsig)?.verify(self.required_archive()?)
+  }
+
+  /// Groups the entries of the APK into DEX files, native libraries, assets, resource files
+  /// and `META-INF`, with their compression and alignment.
+  pub fn inventory(&self) -> Result<Inventory, ParseError> {
+    let arsc = self.resource_table()?;
+    Ok(Inventory::from_archive(
+      self.required_archive()?,
+      arsc.as_ref(),
+    ))
+  }
+
+  /// Looks for APKs, ZIPs, DEX files and encrypted looking blobs embedded in the APK, and
+  /// parses nested APKs the same way as this one.
+  pub fn scan(
+    &self,
+    limits: ScanLimits,
+  ) -> Result<ScanNode, ParseError> {
+    Scanner::new(limits).scan(self)
+  }
+
+  /// Whether the APK has a `resources.arsc`.
+  pub fn has_resource_table(&self) -> bool {
+    self.arsc_raw.is_some()
+  }
+
+  /// Parses the resource table, if there is one.
+  pub fn resource_table(&self) -> Result<Option<Arsc<'_>>, ParseError> {
+    let Some(arsc_raw) = &self.arsc_raw else {
+      return Ok(None);
+    };
+    let mut arsc_parser = Arsc::new(arsc_raw);
     arsc_parser.parse()?;
+    Ok(Some(arsc_parser))
+  }
+
+  // Operations that only make sense with a resource table.
+  fn required_arsc(&self) -> Result<Arsc<'_>, ParseError> {
+    self
+      .resource_table()?
+      .ok_or_else(|| ParseError::MissingEntry("resources.arsc".to_string()))
+  }
 
+  pub fn parse(&mut self) -> Result<Vec<u8>, ParseError> {
+    let arsc_parser = self.resource_table()?;
+    self.parse_with(arsc_parser.as_ref())
+  }
+
+  /// Decodes the manifest resolving references through another table, e.g. the table merged
+  /// from all splits of an app.
+  pub fn parse_with(
+    &self,
+    arsc: Option<&Arsc>,
+  ) -> Result<Vec<u8>, ParseError> {
     let mut manifest_parser = AndroidManifest::new(&self.manifest_raw);
-    let parsed_manifest_bytes = manifest_parser.parse(Some(&arsc_parser))?;
+    manifest_parser.parse(arsc)
+  }
+
+  /// Decodes the manifest into the typed model, resolving references through the APK's table.
+  pub fn manifest(&self) -> Result<Manifest, ParseError> {
+    let arsc = self.resource_table()?;
+    Manifest::from_xml(&self.parse_with(arsc.as_ref())?)
+  }
+
+  /// The URIs the browsable activities of the manifest claim, with their App Link status.
+  pub fn deep_links(&self) -> Result<DeepLinks, ParseError> {
+    let arsc = self.resource_table()?;
+    let manifest = Manifest::from_xml(&self.parse_with(arsc.as_ref())?)?;
+    Ok(DeepLinks::from_manifest(&manifest, arsc.as_ref()))
+  }
+
+  /// The application label in every locale and its icons in every density, adaptive icons
+  /// decoded into their layers.
+  pub fn label_and_icon(&self) -> Result<LabelAndIcon, ParseError> {
+    let arsc = self.resource_table()?;
+    // Decoded without the table so the attributes keep their references
+    let manifest = Manifest::from_xml(&self.parse_with(None)?)?;
+    Ok(LabelAndIcon::from_manifest(
+      &manifest,
+      self.archive(),
+      arsc.as_ref(),
+    ))
+  }
+
+  /// The path and bytes of the icon bitmap that best fits a screen of `density` dpi, `None`
+  /// when the icon has no bitmap.
+  pub fn icon_bytes(
+    &self,
+    density: u16,
+  ) -> Result<Option<(String, Vec<u8>)>, ParseError> {
+    let label_and_icon = self.label_and_icon()?;
+    let Some(icon) = label_and_icon.best_icon(density) else {
+      return Ok(None);
+    };
+    let archive = self.required_archive()?;
+    let entry = archive
+      .by_name(&icon.path)
+      .ok_or_else(|| ParseError::MissingEntry(icon.path.clone()))?;
+    Ok(Some((icon.path.clone(), archive.extract(entry)?)))
+  }
+
+  /// Follows `android:networkSecurityConfig` to its `res/xml` file and decodes it, `None` when
+  /// the application doesn't set one.
+  pub fn network_security_config(&self) -> Result<Option<NetworkSecurityConfig>, ParseError> {
+    let arsc = self.resource_table()?;
+    let manifest = Manifest::from_xml(&self.parse_with(arsc.as_ref())?)?;
+    let Some(reference) = manifest
+      .application
+      .as_ref()
+      .and_then(|application| application.attributes.get("networkSecurityConfig"))
+    else {
+      return Ok(None);
+    };
+    // Decoding the manifest already resolved the reference if the table has it
+    let path = resolve_references(Some(reference.to_string()), arsc.as_ref()).unwrap_or_default();
+    if path.starts_with('@') {
+      return Err(ParseError::ResourceNotFound(path));
+    }
+    let archive = self.required_archive()?;
+    let entry = archive
+      .by_name(&path)
+      .ok_or_else(|| ParseError::MissingEntry(path.clone()))?;
+    let binary_xml = archive.extract(entry)?;
+    let xml = AndroidManifest::new(&binary_xml).parse(arsc.as_ref())?;
+    NetworkSecurityConfig::from_xml(&xml).map(Some)
+  }
+
+  /// Paths of every binary XML entry of the APK.
+  pub fn xml_paths(&self) -> Result<Vec<String>, ParseError> {
+    let arsc = self.resource_table()?;
+    Ok(XmlResources::new(self.required_archive()?, arsc.as_ref()).paths())
+  }
+
+  /// Decodes a binary XML entry of the APK, resolving references through its table.
+  pub fn decode_xml(
+    &self,
+    path: &str,
+  ) -> Result<Vec<u8>, ParseError> {
+    let arsc = self.resource_table()?;
+    XmlResources::new(self.required_archive()?, arsc.as_ref()).decode(path)
+  }
+
+  /// Decodes the file of an XML resource, such as a layout, in every configuration.
+  pub fn decode_xml_resource(
+    &self,
+    res_id: u32,
+  ) -> Result<Vec<(String, Vec<u8>)>, ParseError> {
+    let arsc = self.required_arsc()?;
+    let xml_resources = XmlResources::new(self.required_archive()?, Some(&arsc));
+    let paths = xml_resources.resource_paths(res_id);
+    if paths.is_empty() {
+      return Err(ParseError::ResourceNotFound(format!("0x{:08x}", res_id)));
+    }
+    paths
+      .into_iter()
+      .map(|(_, path)| {
+        let xml = xml_resources.decode(&path)?;
+        Ok((path, xml))
+      })
+      .collect()
+  }
+
+  /// Decodes every binary XML entry of the APK into `out_dir`, keeping its directory tree.
+  /// Returns the entries that couldn't be decoded.
+  pub fn write_xml(
+    &self,
+    out_dir: &Path,
+  ) -> Result<Vec<(String, ParseError)>, ParseError> {
+    let arsc = self.resource_table()?;
+    XmlResources::new(self.required_archive()?, arsc.as_ref()).write_to_dir(out_dir)
+  }
+
+  /// Generates a `public.xml` pinning the resource ids of the APK's `resources.arsc`.
+  pub fn public_xml(&self) -> Result<Vec<u8>, ParseError> {
+    self.required_arsc()?.public_xml()
+  }
+
+  /// Decodes the values resources of the APK's `resources.arsc` into `out_dir/res/values*/`.
+  pub fn write_values(
+    &self,
+    out_dir: &Path,
+  ) -> Result<(), ParseError> {
+    ValuesDecoder::new(&self.required_arsc()?).write_to_dir(out_dir)
+  }
+}
+
+// Signatures of the ZIP records read by `ApkArchive`.
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const ZIP64_EOCD_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+
+const LOCAL_HEADER_SIZE: usize = 30;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const EOCD_SIZE: usize = 22;
+const ZIP64_EOCD_LOCATOR_SIZE: usize = 20;
+const MAX_COMMENT_SIZE: usize = 0xffff;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+const COMPRESSION_STORED: u16 = 0;
+const COMPRESSION_DEFLATED: u16 = 8;
+const FLAG_ENCRYPTED: u16 = 0x0001;
+const FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+
+/// A ZIP entry as described by the central directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApkEntry {
+  pub name: String,
+  pub flags: u16,
+  pub method: u16,
+  pub crc32: u32,
+  pub compressed_size: u64,
+  pub uncompressed_size: u64,
+  pub local_header_offset: u64,
+}
+
+/// Reads an APK the way Android's libziparchive does when installing it, rather than the way
+/// ZIP tools do. Only the central directory is trusted: the local file header is just used to
+/// skip to the data, methods other than stored are inflated, the encryption flag is ignored,
+/// and the first of duplicate entries wins. Everything the `zip` crate would reject or
+/// misread is reported in `diagnostics()` instead.
+pub struct ApkArchive {
+  data: Vec<u8>,
+  entries: Vec<ApkEntry>,
+  diagnostics: Vec<Diagnostic>,
+  // Offset and size of the central directory, and offset of the end of central directory
+  // record that points at it
+  cd_offset: usize,
+  cd_size: usize,
+  eocd_offset: usize,
+}
+
+impl ApkArchive {
+  pub fn new(data: Vec<u8>) -> Result<Self, ParseError> {
+    let mut archive = Self {
+      data,
+      entries: Vec::new(),
+      diagnostics: Vec::new(),
+      cd_offset: 0,
+      cd_size: 0,
+      eocd_offset: 0,
+    };
+    let (cd_offset, cd_size, entry_count) = archive.find_central_directory()?;
+    archive.read_central_directory(cd_offset, cd_size, entry_count)?;
+    archive.cd_offset = cd_offset;
+    archive.cd_size = cd_size;
+    archive.check_entries();
+    Ok(archive)
+  }
+
+  /// Entries in central directory order, duplicates included.
+  pub fn entries(&self) -> &[ApkEntry] {
+    &self.entries
+  }
+
+  /// The entry Android uses for a name, the first one of the central directory.
+  pub fn by_name(
+    &self,
+    name: &str,
+  ) -> Option<&ApkEntry> {
+    self.entries.iter().find(|entry| entry.name == name)
+  }
+
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    &self.diagnostics
+  }
+
+  /// The whole APK.
+  pub fn data(&self) -> &[u8] {
+    &self.data
+  }
+
+  /// Offset and size of the central directory, and offset of the end of central directory
+  /// record.
+  pub fn central_directory(&self) -> (usize, usize, usize) {
+    (self.cd_offset, self.cd_size, self.eocd_offset)
+  }
+
+  /// Extracts an entry and reports a CRC-32 mismatch of a stored entry in `diagnostics()`.
+  pub fn read(
+    &mut self,
+    entry: &ApkEntry,
+  ) -> Result<Vec<u8>, ParseError> {
+    let data = self.extract(entry)?;
+    let mut crc = Crc::new();
+    crc.update(&data);
+    if crc.sum() != entry.crc32 {
+      self.diagnostics.push(Diagnostic::CrcMismatch {
+        entry: entry.name.clone(),
+      });
+    }
+    Ok(data)
+  }
+
+  /// Offset of the entry data, right after its local file header. Stored entries are read in
+  /// place from there, so it's what alignment is about.
+  pub fn data_offset(
+    &self,
+    entry: &ApkEntry,
+  ) -> Option<usize> {
+    let local_header = usize::try_from(entry.local_header_offset)
+      .ok()
+      .filter(|offset| u32_at(&self.data, *offset) == Some(LOCAL_HEADER_SIGNATURE))?;
+    let name_length = u16_at(&self.data, local_header + 26).unwrap_or_default() as usize;
+    let extra_length = u16_at(&self.data, local_header + 28).unwrap_or_default() as usize;
+    Some(local_header + LOCAL_HEADER_SIZE + name_length + extra_length)
+  }
+
+  /// Extracts an entry. Stored entries are copied unchecked and every other method is
+  /// inflated, which fails unless the output has the size and CRC-32 of the central directory.
+  pub fn extract(
+    &self,
+    entry: &ApkEntry,
+  ) -> Result<Vec<u8>, ParseError> {
+    let zip_error = |message: &str| ParseError::Zip(format!("{}: {}", entry.name, message));
+    let raw_data = self.raw_data(entry)?;
+    if entry.method == COMPRESSION_STORED {
+      return Ok(raw_data.to_vec());
+    }
+    let mut data = Vec::with_capacity((entry.uncompressed_size as usize).min(raw_data.len() * 4));
+    // One byte more than declared is enough to tell a stream that inflates to more
+    DeflateDecoder::new(raw_data)
+      .take(entry.uncompressed_size.saturating_add(1))
+      .read_to_end(&mut data)
+      .map_err(|e| zip_error(&e.to_string()))?;
+    if data.len() as u64 != entry.uncompressed_size {
+      return Err(zip_error(
+        "inflated size differs from the central directory",
+      ));
+    }
+    let mut crc = Crc::new();
+    crc.update(&data);
+    if crc.sum() != entry.crc32 {
+      return Err(zip_error(
+        "inflated CRC-32 differs from the central directory",
+      ));
+    }
+    Ok(data)
+  }
+
+  /// The first `length` bytes of an entry, or all of it when it's shorter, without inflating
+  /// the rest. Enough to recognize the format of an entry by its magic.
+  pub fn extract_prefix(
+    &self,
+    entry: &ApkEntry,
+    length: usize,
+  ) -> Result<Vec<u8>, ParseError> {
+    let raw_data = self.raw_data(entry)?;
+    if entry.method == COMPRESSION_STORED {
+      return Ok(raw_data[..length.min(raw_data.len())].to_vec());
+    }
+    let length = (length as u64).min(entry.uncompressed_size);
+    let mut data = Vec::with_capacity(length as usize);
+    DeflateDecoder::new(raw_data)
+      .take(length)
+      .read_to_end(&mut data)
+      .map_err(|e| ParseError::Zip(format!("{}: {}", entry.name, e)))?;
+    Ok(data)
+  }
+
+  // The data of a stored entry, or the compressed data of any other
+  fn raw_data(
+    &self,
+    entry: &ApkEntry,
+  ) -> Result<&[u8], ParseError> {
+    let zip_error = |message: &str| ParseError::Zip(format!("{}: {}", entry.name, message));
+    let data_start = self
+      .data_offset(entry)
+      .ok_or_else(|| zip_error("invalid local file header offset"))?;
+    if entry.method == COMPRESSION_STORED {
+      // The uncompressed size is what Android copies out of stored entries
+      data_start
+        .checked_add(entry.uncompressed_size as usize)
+        .and_then(|data_end| self.data.get(data_start..data_end))
+        .ok_or_else(|| zip_error("stored data out of bounds"))
+    } else {
+      let compressed_end = data_start
+        .saturating_add(entry.compressed_size as usize)
+        .min(self.data.len());
+      self
+        .data
+        .get(data_start..compressed_end)
+        .ok_or_else(|| zip_error("compressed data out of bounds"))
+    }
+  }
+
+  // Like libziparchive, the end of central directory record closest to the end of the file is
+  // used, and the archive is rejected when its central directory can't exist.
+  fn find_central_directory(&mut self) -> Result<(usize, usize, u64), ParseError> {
+    let data = &self.data;
+    if data.len() < EOCD_SIZE {
+      return Err(ParseError::Zip(
+        "file too small to be a ZIP archive".to_string(),
+      ));
+    }
+    let search_start = data.len().saturating_sub(EOCD_SIZE + MAX_COMMENT_SIZE);
+    let eocd = (search_start..=data.len() - EOCD_SIZE)
+      .rev()
+      .find(|&eocd| u32_at(data, eocd) == Some(EOCD_SIGNATURE))
+      .ok_or_else(|| ParseError::Zip("end of central directory record not found".to_string()))?;
+    let mut entry_count = u16_at(data, eocd + 10).unwrap_or_default() as u64;
+    let mut cd_size = u32_at(data, eocd + 12).unwrap_or_default() as u64;
+    let mut cd_offset = u32_at(data, eocd + 16).unwrap_or_default() as u64;
+    let comment_length = u16_at(data, eocd + 20).unwrap_or_default();
+
+    let mut cd_end = eocd as u64;
+    if cd_offset == 0xFFFFFFFF || cd_size == 0xFFFFFFFF || entry_count == 0xFFFF {
+      (entry_count, cd_size, cd_offset, cd_end) = zip64_end_of_central_directory(data, eocd)
+        .ok_or_else(|| {
+          ParseError::Zip("zip64 end of central directory record not found".to_string())
+        })?;
+    }
+    let cd_valid = cd_offset
+      .checked_add(cd_size)
+      .is_some_and(|end| end <= cd_end)
+      && (entry_count == 0 || u32_at(data, cd_offset as usize) == Some(CENTRAL_HEADER_SIGNATURE));
+    if !cd_valid {
+      return Err(ParseError::Zip(format!(
+        "invalid central directory offset {} and size {}",
+        cd_offset, cd_size
+      )));
+    }
+
+    self.eocd_offset = eocd;
+    let actual = data.len() - eocd - EOCD_SIZE;
+    if comment_length as usize != actual {
+      self.diagnostics.push(Diagnostic::EocdCommentMismatch {
+        declared: comment_length,
+        actual,
+      });
+    }
+    Ok((cd_offset as usize, cd_size as usize, entry_count))
+  }
+
+  fn read_central_directory(
+    &mut self,
+    cd_offset: usize,
+    cd_size: usize,
+    entry_count: u64,
+  ) -> Result<(), ParseError> {
+    let cd_end = cd_offset + cd_size;
+    let mut offset = cd_offset;
+    for _ in 0..entry_count {
+      if offset + CENTRAL_HEADER_SIZE > cd_end
+        || u32_at(&self.data, offset) != Some(CENTRAL_HEADER_SIGNATURE)
+      {
+        return Err(ParseError::Zip(format!(
+          "invalid central directory record at {}",
+          offset
+        )));
+      }
+      let data = &self.data;
+      let flags = u16_at(data, offset + 8).unwrap_or_default();
+      let method = u16_at(data, offset + 10).unwrap_or_default();
+      let crc32 = u32_at(data, offset + 16).unwrap_or_default();
+      let mut compressed_size = u32_at(data, offset + 20).unwrap_or_default() as u64;
+      let mut uncompressed_size = u32_at(data, offset + 24).unwrap_or_default() as u64;
+      let name_length = u16_at(data, offset + 28).unwrap_or_default() as usize;
+      let extra_length = u16_at(data, offset + 30).unwrap_or_default() as usize;
+      let comment_length = u16_at(data, offset + 32).unwrap_or_default() as usize;
+      let mut local_header_offset = u32_at(data, offset + 42).unwrap_or_default() as u64;
+
+      let name_start = offset + CENTRAL_HEADER_SIZE;
+      let extra_start = name_start + name_length;
+      let record_end = extra_start + extra_length + comment_length;
+      if record_end > cd_end {
+        return Err(ParseError::Zip(format!(
+          "central directory record at {} exceeds the central directory",
+          offset
+        )));
+      }
+      let name = String::from_utf8_lossy(&data[name_start..extra_start]).to_string();
 
-    Ok(parsed_manifest_bytes)
+      // Sizes and offsets that don't fit 32 bits are stored in the ZIP64 extra field
+      let extra = &data[extra_start..extra_start + extra_length];
+      if let Some(mut zip64) = extra_field(extra, ZIP64_EXTRA_ID) {
+        for value in [
+          &mut uncompressed_size,
+          &mut compressed_size,
+          &mut local_header_offset,
+        ] {
+          if *value == 0xFFFFFFFF {
+            if let Some(zip64_value) = u64_at(zip64, 0) {
+              *value = zip64_value;
+              zip64 = &zip64[8..];
+            }
+          }
+        }
+      }
+
+      self.entries.push(ApkEntry {
+        name,
+        flags,
+        method,
+        crc32,
+        compressed_size,
+        uncompressed_size,
+        local_header_offset,
+      });
+      offset = record_end;
+    }
+    Ok(())
   }
+
+  // Reports what Android silently tolerates.
+  fn check_entries(&mut self) {
+    let mut names = HashSet::new();
+    let mut duplicates = HashSet::new();
+    for entry in &self.entries {
+      if !names.insert(entry.name.as_str()) && duplicates.insert(entry.name.as_str()) {
+        self.diagnostics.push(Diagnostic::DuplicateEntry {
+          entry: entry.name.clone(),
+        });
+      }
+      if entry.method != COMPRESSION_STORED && entry.method != COMPRESSION_DEFLATED {
+        self.diagnostics.push(Diagnostic::UnsupportedCompression {
+          entry: entry.name.clone(),
+          method: entry.method,
+        });
+      }
+      if entry.flags & FLAG_ENCRYPTED != 0 {
+        self.diagnostics.push(Diagnostic::EncryptionFlag {
+          entry: entry.name.clone(),
+        });
+      }
+      if !self.local_header_matches(entry) {
+        self.diagnostics.push(Diagnostic::LocalHeaderMismatch {
+          entry: entry.name.clone(),
+        });
+      }
+    }
+  }
+
+  fn local_header_matches(
+    &self,
+    entry: &ApkEntry,
+  ) -> bool {
+    let Ok(offset) = usize::try_from(entry.local_header_offset) else {
+      return false;
+    };
+    let data = &self.data;
+    if u32_at(data, offset) != Some(LOCAL_HEADER_SIGNATURE) {
+      return false;
+    }
+    let flags = u16_at(data, offset + 6).unwrap_or_default();
+    let method = u16_at(data, offset + 8).unwrap_or_default();
+    let crc32 = u32_at(data, offset + 14).unwrap_or_default();
+    let compressed_size = u32_at(data, offset + 18).unwrap_or_default() as u64;
+    let uncompressed_size = u32_at(data, offset + 22).unwrap_or_default() as u64;
+    let name_length = u16_at(data, offset + 26).unwrap_or_default() as usize;
+    let name_start = offset + LOCAL_HEADER_SIZE;
+    let name = data.get(name_start..name_start + name_length);
+
+    // Without a data descriptor, sizes and checksum have to be in the local header as well
+    let sizes_match = flags & FLAG_DATA_DESCRIPTOR != 0
+      || (crc32 == entry.crc32
+        && (compressed_size == entry.compressed_size || compressed_size == 0xFFFFFFFF)
+        && (uncompressed_size == entry.uncompressed_size || uncompressed_size == 0xFFFFFFFF));
+    method == entry.method && name == Some(entry.name.as_bytes()) && sizes_match
+  }
+}
+
+// Entry count, central directory size and offset, and the end of the central directory from
+// the ZIP64 end of central directory record.
+fn zip64_end_of_central_directory(
+  data: &[u8],
+  eocd: usize,
+) -> Option<(u64, u64, u64, u64)> {
+  let locator = eocd.checked_sub(ZIP64_EOCD_LOCATOR_SIZE)?;
+  if u32_at(data, locator)? != ZIP64_EOCD_LOCATOR_SIGNATURE {
+    return None;
+  }
+  let zip64_eocd = u64_at(data, locator + 8)?;
+  let zip64_eocd = usize::try_from(zip64_eocd).ok()?;
+  if u32_at(data, zip64_eocd)? != ZIP64_EOCD_SIGNATURE {
+    return None;
+  }
+  Some((
+    u64_at(data, zip64_eocd + 32)?,
+    u64_at(data, zip64_eocd + 40)?,
+    u64_at(data, zip64_eocd + 48)?,
+    zip64_eocd as u64,
+  ))
+}
+
+// Data of the extra field with the given header id.
+fn extra_field(
+  mut extra: &[u8],
+  id: u16,
+) -> Option<&[u8]> {
+  while extra.len() >= 4 {
+    let field_id = u16_at(extra, 0)?;
+    let size = u16_at(extra, 2)? as usize;
+    let field = extra.get(4..4 + size)?;
+    if field_id == id {
+      return Some(field);
+    }
+    extra = &extra[4 + size..];
+  }
+  None
+}
+
+fn u16_at(
+  data: &[u8],
+  offset: usize,
+) -> Option<u16> {
+  Some(u16::from_le_bytes(
+    data.get(offset..offset.checked_add(2)?)?.try_into().ok()?,
+  ))
+}
+
+fn u32_at(
+  data: &[u8],
+  offset: usize,
+) -> Option<u32> {
+  Some(u32::from_le_bytes(
+    data.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+  ))
+}
+
+fn u64_at(
+  data: &[u8],
+  offset: usize,
+) -> Option<u64> {
+  Some(u64::from_le_bytes(
+    data.get(offset..offset.checked_add(8)?)?.try_into().ok()?,
+  ))
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::test_util::zip;
   use anyhow::Result;
   use quick_xml::reader::Reader;
+  use std::io::Cursor;
 
   #[test]
   fn test_parser() -> Result<()> {
@@ -81,4 +867,177 @@ mod tests {
     println!("{}", std::str::from_utf8(&parsed_manifest_bytes)?);
     Ok(())
   }
+
+  #[test]
+  fn test_parser_constructors() -> Result<()> {
+    let manifest = std::fs::read("../data/xml/AndroidManifest.xml")?;
+    let arsc = std::fs::read(
+      "../data/arsc/ab4cb5175fc0827860b9c3361d8bf2aec99a71f63b5a7666ae2403b0030ac56f.arsc",
+    )?;
+    let apk = zip(&[
+      ("AndroidManifest.xml", &manifest),
+      ("resources.arsc", &arsc),
+    ])?;
+
+    let expected = Parser::from_manifest(manifest, Some(arsc)).parse()?;
+    assert!(!expected.is_empty());
+    assert_eq!(Parser::from_bytes(&apk)?.parse()?, expected);
+    assert_eq!(Parser::from_reader(Cursor::new(&apk))?.parse()?, expected);
+    let mut from_archive = Parser::from_archive(ZipArchive::new(Cursor::new(&apk))?)?;
+    assert!(from_archive.archive().is_some());
+    assert_eq!(from_archive.parse()?, expected);
+    Ok(())
+  }
+
+  #[test]
+  fn test_optional_resource_table() -> Result<()> {
+    let manifest = std::fs::read("../data/xml/AndroidManifest.xml")?;
+    let apk = zip(&[("AndroidManifest.xml", &manifest)])?;
+    let mut parser = Parser::from_bytes(&apk)?;
+    assert_eq!(parser.diagnostics(), [Diagnostic::MissingResourceTable]);
+    assert!(!parser.parse()?.is_empty());
+    assert!(matches!(
+      parser.public_xml(),
+      Err(ParseError::MissingEntry(_))
+    ));
+
+    let apk = zip(&[("classes.dex", b"dex\n035\0")])?;
+    assert!(matches!(
+      Parser::from_bytes(&apk),
+      Err(ParseError::MissingEntry(entry)) if entry == "AndroidManifest.xml"
+    ));
+    Ok(())
+  }
+
+  // Offset of the central directory record of an entry.
+  fn central_header(
+    apk: &[u8],
+    name: &str,
+  ) -> usize {
+    (0..apk.len() - CENTRAL_HEADER_SIZE)
+      .find(|offset| {
+        u32_at(apk, *offset) == Some(CENTRAL_HEADER_SIGNATURE)
+          && apk[offset + CENTRAL_HEADER_SIZE..].starts_with(name.as_bytes())
+      })
+      .unwrap()
+  }
+
+  #[test]
+  fn test_android_zip_quirks() -> Result<()> {
+    let manifest = std::fs::read("../data/xml/AndroidManifest.xml")?;
+    let expected = Parser::from_manifest(manifest.clone(), None).parse()?;
+    let apk = zip(&[
+      ("AndroidManifest.xml", &manifest),
+      ("AndroidManifest.xmX", b"not a manifest"),
+    ])?;
+    let archive = ApkArchive::new(apk.clone())?;
+    let local_header = archive
+      .by_name("AndroidManifest.xml")
+      .unwrap()
+      .local_header_offset as usize;
+    let cd = central_header(&apk, "AndroidManifest.xml");
+    let entry = archive.by_name("AndroidManifest.xml").unwrap();
+    assert_eq!(archive.extract_prefix(entry, 4)?, manifest[..4]);
+    assert_eq!(archive.extract_prefix(entry, usize::MAX)?, manifest);
+    let manifest_diagnostics = |apk: &[u8]| -> Result<Vec<Diagnostic>> {
+      let mut parser = Parser::from_bytes(apk)?;
+      assert_eq!(parser.parse()?, expected);
+      Ok(
+        parser
+          .diagnostics()
+          .iter()
+          .filter(|diagnostic| **diagnostic != Diagnostic::MissingResourceTable)
+          .cloned()
+          .collect(),
+      )
+    };
+    assert!(manifest_diagnostics(&apk)?.is_empty());
+
+    // Unknown compression methods are inflated
+    let mut unknown_method = apk.clone();
+    unknown_method[local_header + 8..local_header + 10].copy_from_slice(&99u16.to_le_bytes());
+    unknown_method[cd + 10..cd + 12].copy_from_slice(&99u16.to_le_bytes());
+    assert_eq!(
+      manifest_diagnostics(&unknown_method)?,
+      [Diagnostic::UnsupportedCompression {
+        entry: "AndroidManifest.xml".to_string(),
+        method: 99
+      }]
+    );
+
+    // Inflated entries must match the size and CRC-32 of the central directory
+    let mut short_size = apk.clone();
+    let size = u32_at(&apk, cd + 24).unwrap();
+    short_size[cd + 24..cd + 28].copy_from_slice(&(size - 1).to_le_bytes());
+    let mut bad_crc = apk.clone();
+    bad_crc[cd + 16] ^= 0xff;
+    for corrupted in [short_size, bad_crc] {
+      assert!(matches!(
+        Parser::from_bytes(&corrupted),
+        Err(ParseError::Zip(_))
+      ));
+    }
+
+    // The encryption flag is ignored
+    let mut encrypted = apk.clone();
+    encrypted[local_header + 6] |= FLAG_ENCRYPTED as u8;
+    encrypted[cd + 8] |= FLAG_ENCRYPTED as u8;
+    assert_eq!(
+      manifest_diagnostics(&encrypted)?,
+      [Diagnostic::EncryptionFlag {
+        entry: "AndroidManifest.xml".to_string()
+      }]
+    );
+
+    // Only the central directory is trusted
+    let mut local_mismatch = apk.clone();
+    local_mismatch[local_header + 8..local_header + 10].fill(0);
+    local_mismatch[local_header + 18..local_header + 26].fill(0);
+    assert_eq!(
+      manifest_diagnostics(&local_mismatch)?,
+      [Diagnostic::LocalHeaderMismatch {
+        entry: "AndroidManifest.xml".to_string()
+      }]
+    );
+
+    // The first of duplicate entries wins
+    let mut duplicate = apk.clone();
+    let second_local_header = archive
+      .by_name("AndroidManifest.xmX")
+      .unwrap()
+      .local_header_offset as usize;
+    let second_cd = central_header(&apk, "AndroidManifest.xmX");
+    let name_end = "AndroidManifest.xml".len() - 1;
+    duplicate[second_local_header + LOCAL_HEADER_SIZE + name_end] = b'l';
+    duplicate[second_cd + CENTRAL_HEADER_SIZE + name_end] = b'l';
+    assert_eq!(
+      manifest_diagnostics(&duplicate)?,
+      [Diagnostic::DuplicateEntry {
+        entry: "AndroidManifest.xml".to_string()
+      }]
+    );
+
+    // Trailing data after the end of central directory record
+    let mut trailing_data = apk.clone();
+    trailing_data.extend_from_slice(&[0xff; 22]);
+    assert_eq!(
+      manifest_diagnostics(&trailing_data)?,
+      [Diagnostic::EocdCommentMismatch {
+        declared: 0,
+        actual: 22
+      }]
+    );
+
+    // A fake end of central directory record after the real one is the one Android reads
+    let mut fake_eocd = apk.clone();
+    fake_eocd.extend_from_slice(b"PK\x05\x06\0\0\0\0\x01\0\x01\0");
+    fake_eocd.extend_from_slice(&16u32.to_le_bytes());
+    fake_eocd.extend_from_slice(&0xfffffff0u32.to_le_bytes());
+    fake_eocd.extend_from_slice(&[0, 0]);
+    assert!(matches!(
+      Parser::from_bytes(&fake_eocd),
+      Err(ParseError::Zip(_))
+    ));
+    Ok(())
+  }
 }
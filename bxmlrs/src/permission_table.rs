@@ -0,0 +1,199 @@
+// Platform permissions from frameworks/base/core/res/AndroidManifest.xml and the
+// Manifest.permission reference. Names without a dot are in android.permission, groups in
+// android.permission-group.
+
+// Name, protection level, permission group, API level that added it, API level from which it is
+// deprecated or has no effect
+pub(crate) type PlatformPermission = (
+  &'static str,
+  &'static str,
+  Option<&'static str>,
+  u32,
+  Option<u32>,
+);
+
+#[rustfmt::skip]
+pub(crate) const PLATFORM_PERMISSIONS: &[PlatformPermission] = &[
+  // Runtime permissions
+  ("ACCEPT_HANDOVER", "dangerous", Some("PHONE"), 28, None),
+  ("ACCESS_BACKGROUND_LOCATION", "dangerous", Some("LOCATION"), 29, None),
+  ("ACCESS_COARSE_LOCATION", "dangerous", Some("LOCATION"), 1, None),
+  ("ACCESS_FINE_LOCATION", "dangerous", Some("LOCATION"), 1, None),
+  ("ACCESS_MEDIA_LOCATION", "dangerous", None, 29, None),
+  ("ACTIVITY_RECOGNITION", "dangerous", Some("ACTIVITY_RECOGNITION"), 29, None),
+  ("ANSWER_PHONE_CALLS", "dangerous", Some("PHONE"), 26, None),
+  ("BLUETOOTH_ADVERTISE", "dangerous", Some("NEARBY_DEVICES"), 31, None),
+  ("BLUETOOTH_CONNECT", "dangerous", Some("NEARBY_DEVICES"), 31, None),
+  ("BLUETOOTH_SCAN", "dangerous", Some("NEARBY_DEVICES"), 31, None),
+  ("BODY_SENSORS", "dangerous", Some("SENSORS"), 20, None),
+  ("BODY_SENSORS_BACKGROUND", "dangerous", Some("SENSORS"), 33, None),
+  ("CALL_PHONE", "dangerous", Some("PHONE"), 1, None),
+  ("CAMERA", "dangerous", Some("CAMERA"), 1, None),
+  ("GET_ACCOUNTS", "dangerous", Some("CONTACTS"), 1, None),
+  ("NEARBY_WIFI_DEVICES", "dangerous", Some("NEARBY_DEVICES"), 33, None),
+  ("POST_NOTIFICATIONS", "dangerous", Some("NOTIFICATIONS"), 33, None),
+  ("PROCESS_OUTGOING_CALLS", "dangerous", Some("CALL_LOG"), 1, Some(29)),
+  ("READ_CALENDAR", "dangerous", Some("CALENDAR"), 1, None),
+  ("READ_CALL_LOG", "dangerous", Some("CALL_LOG"), 16, None),
+  ("READ_CONTACTS", "dangerous", Some("CONTACTS"), 1, None),
+  ("READ_EXTERNAL_STORAGE", "dangerous", Some("STORAGE"), 16, Some(33)),
+  ("READ_MEDIA_AUDIO", "dangerous", Some("READ_MEDIA_AURAL"), 33, None),
+  ("READ_MEDIA_IMAGES", "dangerous", Some("READ_MEDIA_VISUAL"), 33, None),
+  ("READ_MEDIA_VIDEO", "dangerous", Some("READ_MEDIA_VISUAL"), 33, None),
+  ("READ_MEDIA_VISUAL_USER_SELECTED", "dangerous", Some("READ_MEDIA_VISUAL"), 34, None),
+  ("READ_PHONE_NUMBERS", "dangerous", Some("PHONE"), 26, None),
+  ("READ_PHONE_STATE", "dangerous", Some("PHONE"), 1, None),
+  ("READ_SMS", "dangerous", Some("SMS"), 1, None),
+  ("RECEIVE_MMS", "dangerous", Some("SMS"), 1, None),
+  ("RECEIVE_SMS", "dangerous", Some("SMS"), 1, None),
+  ("RECEIVE_WAP_PUSH", "dangerous", Some("SMS"), 1, None),
+  ("RECORD_AUDIO", "dangerous", Some("MICROPHONE"), 1, None),
+  ("SEND_SMS", "dangerous", Some("SMS"), 1, None),
+  ("USE_SIP", "dangerous", Some("PHONE"), 9, None),
+  ("UWB_RANGING", "dangerous", Some("NEARBY_DEVICES"), 31, None),
+  ("WRITE_CALENDAR", "dangerous", Some("CALENDAR"), 1, None),
+  ("WRITE_CALL_LOG", "dangerous", Some("CALL_LOG"), 16, None),
+  ("WRITE_CONTACTS", "dangerous", Some("CONTACTS"), 1, None),
+  ("WRITE_EXTERNAL_STORAGE", "dangerous", Some("STORAGE"), 4, Some(30)),
+  ("com.android.voicemail.permission.ADD_VOICEMAIL", "dangerous", Some("PHONE"), 14, None),
+  // Removed from the runtime model, granted at install time before API 23
+  ("AUTHENTICATE_ACCOUNTS", "dangerous", None, 5, Some(23)),
+  ("MANAGE_ACCOUNTS", "dangerous", None, 5, Some(23)),
+  ("USE_CREDENTIALS", "dangerous", None, 5, Some(23)),
+  // Install time permissions
+  ("ACCESS_LOCATION_EXTRA_COMMANDS", "normal", None, 1, None),
+  ("ACCESS_NETWORK_STATE", "normal", None, 1, None),
+  ("ACCESS_NOTIFICATION_POLICY", "normal", None, 23, None),
+  ("ACCESS_WIFI_STATE", "normal", None, 1, None),
+  ("BLUETOOTH", "normal", None, 1, Some(31)),
+  ("BLUETOOTH_ADMIN", "normal", None, 1, Some(31)),
+  ("BROADCAST_STICKY", "normal", None, 1, None),
+  ("CALL_COMPANION_APP", "normal", None, 29, None),
+  ("CHANGE_NETWORK_STATE", "normal", None, 1, None),
+  ("CHANGE_WIFI_MULTICAST_STATE", "normal", None, 4, None),
+  ("CHANGE_WIFI_STATE", "normal", None, 1, None),
+  ("DELIVER_COMPANION_MESSAGES", "normal", None, 33, None),
+  ("DETECT_SCREEN_CAPTURE", "normal", None, 34, None),
+  ("DISABLE_KEYGUARD", "normal", None, 1, None),
+  ("ENFORCE_UPDATE_OWNERSHIP", "normal", None, 34, None),
+  ("EXPAND_STATUS_BAR", "normal", None, 1, None),
+  ("FOREGROUND_SERVICE", "normal", None, 28, None),
+  ("FOREGROUND_SERVICE_CAMERA", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_CONNECTED_DEVICE", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_DATA_SYNC", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_HEALTH", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_LOCATION", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_MEDIA_PLAYBACK", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_MEDIA_PROCESSING", "normal", None, 35, None),
+  ("FOREGROUND_SERVICE_MEDIA_PROJECTION", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_MICROPHONE", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_PHONE_CALL", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_REMOTE_MESSAGING", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_SPECIAL_USE", "normal", None, 34, None),
+  ("FOREGROUND_SERVICE_SYSTEM_EXEMPTED", "normal", None, 34, None),
+  ("GET_PACKAGE_SIZE", "normal", None, 1, None),
+  ("GET_TASKS", "normal", None, 1, Some(21)),
+  ("HIDE_OVERLAY_WINDOWS", "normal", None, 31, None),
+  ("HIGH_SAMPLING_RATE_SENSORS", "normal", None, 31, None),
+  ("INTERNET", "normal", None, 1, None),
+  ("KILL_BACKGROUND_PROCESSES", "normal", None, 8, None),
+  ("MANAGE_OWN_CALLS", "normal", None, 26, None),
+  ("MODIFY_AUDIO_SETTINGS", "normal", None, 1, None),
+  ("NFC", "normal", None, 9, None),
+  ("NFC_PREFERRED_PAYMENT_INFO", "normal", None, 30, None),
+  ("NFC_TRANSACTION_EVENT", "normal", None, 28, None),
+  ("QUERY_ALL_PACKAGES", "normal", None, 30, None),
+  ("READ_BASIC_PHONE_STATE", "normal", None, 33, None),
+  ("READ_SYNC_SETTINGS", "normal", None, 1, None),
+  ("READ_SYNC_STATS", "normal", None, 1, None),
+  ("RECEIVE_BOOT_COMPLETED", "normal", None, 1, None),
+  ("REORDER_TASKS", "normal", None, 1, None),
+  ("REQUEST_COMPANION_RUN_IN_BACKGROUND", "normal", None, 26, None),
+  ("REQUEST_COMPANION_USE_DATA_IN_BACKGROUND", "normal", None, 26, None),
+  ("REQUEST_DELETE_PACKAGES", "normal", None, 26, None),
+  ("REQUEST_IGNORE_BATTERY_OPTIMIZATIONS", "normal", None, 23, None),
+  ("REQUEST_OBSERVE_COMPANION_DEVICE_PRESENCE", "normal", None, 31, None),
+  ("RUN_USER_INITIATED_JOBS", "normal", None, 34, None),
+  ("SET_WALLPAPER", "normal", None, 1, None),
+  ("SET_WALLPAPER_HINTS", "normal", None, 1, None),
+  ("TRANSMIT_IR", "normal", None, 19, None),
+  ("UPDATE_PACKAGES_WITHOUT_USER_ACTION", "normal", None, 31, None),
+  ("USE_BIOMETRIC", "normal", None, 28, None),
+  ("USE_EXACT_ALARM", "normal", None, 33, None),
+  ("USE_FINGERPRINT", "normal", None, 23, Some(28)),
+  ("USE_FULL_SCREEN_INTENT", "normal", None, 29, None),
+  ("VIBRATE", "normal", None, 1, None),
+  ("WAKE_LOCK", "normal", None, 1, None),
+  ("WRITE_SYNC_SETTINGS", "normal", None, 1, None),
+  ("com.android.alarm.permission.SET_ALARM", "normal", None, 9, None),
+  ("com.android.launcher.permission.INSTALL_SHORTCUT", "normal", None, 19, None),
+  ("com.android.launcher.permission.UNINSTALL_SHORTCUT", "normal", None, 19, None),
+  // Special access the user grants in Settings
+  ("LOADER_USAGE_STATS", "signature|privileged|appop", None, 30, None),
+  ("MANAGE_EXTERNAL_STORAGE", "signature|appop|preinstalled", None, 30, None),
+  ("MANAGE_MEDIA", "signature|appop|preinstalled", None, 31, None),
+  ("MANAGE_ONGOING_CALLS", "signature|appop", None, 31, None),
+  ("PACKAGE_USAGE_STATS", "signature|privileged|development|appop|retailDemo", None, 23, None),
+  ("REQUEST_INSTALL_PACKAGES", "signature|appop", None, 23, None),
+  ("SCHEDULE_EXACT_ALARM", "signature|privileged|appop", None, 31, None),
+  ("SMS_FINANCIAL_TRANSACTIONS", "signature|appop", None, 29, None),
+  ("SYSTEM_ALERT_WINDOW", "signature|setup|appop|installer|pre23|development", None, 1, None),
+  ("WRITE_SETTINGS", "signature|setup|appop|pre23|preinstalled", None, 1, None),
+  // Only granted to the system and apps signed with the platform key
+  ("ACCESS_CHECKIN_PROPERTIES", "signature|privileged", None, 1, None),
+  ("ACCOUNT_MANAGER", "signature", None, 5, None),
+  ("BATTERY_STATS", "signature|privileged|development", None, 1, None),
+  ("BIND_ACCESSIBILITY_SERVICE", "signature", None, 16, None),
+  ("BIND_APPWIDGET", "signature|privileged", None, 3, None),
+  ("BIND_AUTOFILL_SERVICE", "signature", None, 26, None),
+  ("BIND_CARRIER_SERVICES", "signature|privileged", None, 23, None),
+  ("BIND_CHOOSER_TARGET_SERVICE", "signature", None, 23, Some(30)),
+  ("BIND_COMPANION_DEVICE_SERVICE", "signature", None, 31, None),
+  ("BIND_CONDITION_PROVIDER_SERVICE", "signature", None, 24, None),
+  ("BIND_CREDENTIAL_PROVIDER_SERVICE", "signature", None, 34, None),
+  ("BIND_DEVICE_ADMIN", "signature", None, 8, None),
+  ("BIND_DREAM_SERVICE", "signature", None, 21, None),
+  ("BIND_INCALL_SERVICE", "signature|privileged", None, 23, None),
+  ("BIND_INPUT_METHOD", "signature", None, 3, None),
+  ("BIND_JOB_SERVICE", "signature", None, 21, None),
+  ("BIND_NFC_SERVICE", "signature", None, 19, None),
+  ("BIND_NOTIFICATION_LISTENER_SERVICE", "signature", None, 18, None),
+  ("BIND_PRINT_SERVICE", "signature", None, 19, None),
+  ("BIND_QUICK_SETTINGS_TILE", "signature", None, 24, None),
+  ("BIND_REMOTEVIEWS", "signature|privileged", None, 11, None),
+  ("BIND_SCREENING_SERVICE", "signature|privileged", None, 24, None),
+  ("BIND_TELECOM_CONNECTION_SERVICE", "signature|privileged", None, 23, None),
+  ("BIND_TEXT_SERVICE", "signature", None, 14, None),
+  ("BIND_VOICE_INTERACTION", "signature", None, 21, None),
+  ("BIND_VPN_SERVICE", "signature", None, 14, None),
+  ("BIND_WALLPAPER", "signature|privileged", None, 8, None),
+  ("BROADCAST_PACKAGE_REMOVED", "signature", None, 1, None),
+  ("BROADCAST_SMS", "signature", None, 2, None),
+  ("BROADCAST_WAP_PUSH", "signature", None, 2, None),
+  ("CAPTURE_AUDIO_OUTPUT", "signature|privileged", None, 19, None),
+  ("CHANGE_CONFIGURATION", "signature|privileged|development", None, 1, None),
+  ("CLEAR_APP_CACHE", "signature|privileged", None, 1, None),
+  ("DELETE_PACKAGES", "signature|privileged", None, 1, None),
+  ("DUMP", "signature|privileged|development", None, 1, None),
+  ("FACTORY_TEST", "signature", None, 1, None),
+  ("INSTALL_LOCATION_PROVIDER", "signature|privileged", None, 4, None),
+  ("INSTALL_PACKAGES", "signature|privileged", None, 1, None),
+  ("LOCATION_HARDWARE", "signature|privileged", None, 18, None),
+  ("MANAGE_DOCUMENTS", "signature", None, 19, None),
+  ("MASTER_CLEAR", "signature|privileged", None, 1, None),
+  ("MEDIA_CONTENT_CONTROL", "signature|privileged", None, 19, None),
+  ("MODIFY_PHONE_STATE", "signature|privileged", None, 1, None),
+  ("MOUNT_UNMOUNT_FILESYSTEMS", "signature|privileged", None, 1, None),
+  ("READ_LOGS", "signature|privileged|development", None, 1, None),
+  ("READ_PRIVILEGED_PHONE_STATE", "signature|privileged", None, 29, None),
+  ("REBOOT", "signature|privileged", None, 1, None),
+  ("SET_TIME", "signature|privileged", None, 8, None),
+  ("SET_TIME_ZONE", "signature|privileged", None, 1, None),
+  ("START_VIEW_PERMISSION_USAGE", "signature|installer", None, 29, None),
+  ("STATUS_BAR", "signature|privileged", None, 1, None),
+  ("UPDATE_DEVICE_STATS", "signature|privileged", None, 3, None),
+  ("WRITE_APN_SETTINGS", "signature|privileged", None, 1, None),
+  ("WRITE_SECURE_SETTINGS", "signature|privileged|development", None, 3, None),
+  ("com.android.voicemail.permission.READ_VOICEMAIL", "signature|privileged|role", None, 21, None),
+  ("com.android.voicemail.permission.WRITE_VOICEMAIL", "signature|privileged|role", None, 21, None),
+];
@@ -0,0 +1,364 @@
+use crate::manifest::{Manifest, Permission};
+use crate::permission_table::PLATFORM_PERMISSIONS;
+
+const ANDROID_PERMISSION: &str = "android.permission.";
+const ANDROID_PERMISSION_GROUP: &str = "android.permission-group.";
+// Runtime permissions replaced install time grants of dangerous permissions
+const RUNTIME_PERMISSIONS: u32 = 23;
+
+// PermissionInfo.PROTECTION_FLAG_*, the bits above the base level
+const PROTECTION_FLAGS: &[(u32, &str)] = &[
+  (0x10, "privileged"),
+  (0x20, "development"),
+  (0x40, "appop"),
+  (0x80, "pre23"),
+  (0x100, "installer"),
+  (0x200, "verifier"),
+  (0x400, "preinstalled"),
+  (0x800, "setup"),
+  (0x1000, "instant"),
+  (0x2000, "runtime"),
+  (0x4000, "oem"),
+  (0x8000, "vendorPrivileged"),
+  (0x10000, "textClassifier"),
+  (0x100000, "incidentReportApprover"),
+  (0x200000, "appPredictor"),
+  (0x400000, "module"),
+  (0x800000, "companion"),
+  (0x1000000, "retailDemo"),
+  (0x2000000, "recents"),
+  (0x4000000, "role"),
+  (0x8000000, "knownSigner"),
+];
+
+/// Base protection level of a permission.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Protection {
+  #[default]
+  Normal,
+  Dangerous,
+  Signature,
+  /// `signatureOrSystem`, the deprecated spelling of `signature|privileged`.
+  SignatureOrSystem,
+  Internal,
+}
+
+impl Protection {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Protection::Normal => "normal",
+      Protection::Dangerous => "dangerous",
+      Protection::Signature => "signature",
+      Protection::SignatureOrSystem => "signatureOrSystem",
+      Protection::Internal => "internal",
+    }
+  }
+}
+
+/// A protection level such as `signature|privileged`, the base level and its flags.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProtectionLevel {
+  pub protection: Protection,
+  pub flags: Vec<String>,
+}
+
+impl ProtectionLevel {
+  /// Parses a level as written in source manifests, `dangerous|instant`, or as compiled into
+  /// binary ones, `0x12`. Unknown names are kept as flags.
+  pub fn parse(level: &str) -> Self {
+    let value = match level.strip_prefix("0x") {
+      Some(hex) => u32::from_str_radix(hex, 16).ok(),
+      None => level.parse().ok(),
+    };
+    if let Some(value) = value {
+      return Self::from_value(value);
+    }
+    let mut protection_level = Self::default();
+    for name in level.split('|').map(str::trim) {
+      match name {
+        "normal" => protection_level.protection = Protection::Normal,
+        "dangerous" => protection_level.protection = Protection::Dangerous,
+        "signature" => protection_level.protection = Protection::Signature,
+        "signatureOrSystem" => protection_level.protection = Protection::SignatureOrSystem,
+        "internal" => protection_level.protection = Protection::Internal,
+        // Old name of privileged
+        "system" => protection_level.flags.push("privileged".to_string()),
+        "" => {}
+        flag => protection_level.flags.push(flag.to_string()),
+      }
+    }
+    protection_level
+  }
+
+  pub fn from_value(value: u32) -> Self {
+    let protection = match value & 0xf {
+      1 => Protection::Dangerous,
+      2 => Protection::Signature,
+      3 => Protection::SignatureOrSystem,
+      4 => Protection::Internal,
+      _ => Protection::Normal,
+    };
+    let flags = PROTECTION_FLAGS
+      .iter()
+      .filter(|(bit, _)| value & bit != 0)
+      .map(|(_, name)| name.to_string())
+      .collect();
+    Self { protection, flags }
+  }
+
+  pub fn has_flag(
+    &self,
+    flag: &str,
+  ) -> bool {
+    self.flags.iter().any(|name| name == flag)
+  }
+}
+
+impl std::fmt::Display for ProtectionLevel {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    f.write_str(self.protection.name())?;
+    for flag in &self.flags {
+      write!(f, "|{}", flag)?;
+    }
+    Ok(())
+  }
+}
+
+/// How an app gets a permission it requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Grant {
+  /// Granted when the app is installed.
+  Install,
+  /// Asked for at runtime, for dangerous permissions of apps targeting API 23 or higher.
+  Runtime,
+  /// The user grants it on a Settings screen, appop permissions such as
+  /// `SYSTEM_ALERT_WINDOW`.
+  SpecialAccess,
+  /// Only granted to apps signed with the same key as the definer, or to system apps.
+  Signature,
+}
+
+impl Grant {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Grant::Install => "install",
+      Grant::Runtime => "runtime",
+      Grant::SpecialAccess => "special access",
+      Grant::Signature => "signature",
+    }
+  }
+}
+
+/// Where the definition of a permission comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PermissionSource {
+  /// The bundled platform knowledge base.
+  Platform,
+  /// A `<permission>` element of the app.
+  App,
+}
+
+/// What is known about a permission.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PermissionInfo {
+  pub name: String,
+  pub protection_level: ProtectionLevel,
+  /// Full name, e.g. `android.permission-group.CAMERA`.
+  pub group: Option<String>,
+  pub added_in: Option<u32>,
+  /// API level from which the permission is deprecated or no longer has an effect.
+  pub deprecated_in: Option<u32>,
+  pub source: PermissionSource,
+}
+
+impl PermissionInfo {
+  /// Looks a permission up in the bundled platform permissions.
+  pub fn platform(name: &str) -> Option<Self> {
+    PLATFORM_PERMISSIONS
+      .iter()
+      .find(|(short_name, ..)| full_name(short_name) == name)
+      .map(|(_, level, group, added_in, deprecated_in)| Self {
+        name: name.to_string(),
+        protection_level: ProtectionLevel::parse(level),
+        group: group.map(|group| format!("{}{}", ANDROID_PERMISSION_GROUP, group)),
+        added_in: Some(*added_in),
+        deprecated_in: *deprecated_in,
+        source: PermissionSource::Platform,
+      })
+  }
+
+  /// A permission an app defines itself, `normal` without a protection level.
+  pub fn from_definition(permission: &Permission) -> Self {
+    Self {
+      name: permission.name.clone(),
+      protection_level: permission
+        .protection_level
+        .as_deref()
+        .map(ProtectionLevel::parse)
+        .unwrap_or_default(),
+      group: permission.permission_group.clone(),
+      added_in: None,
+      deprecated_in: None,
+      source: PermissionSource::App,
+    }
+  }
+
+  /// How an app targeting `target_sdk` is granted the permission.
+  pub fn grant(
+    &self,
+    target_sdk: u32,
+  ) -> Grant {
+    let level = &self.protection_level;
+    match level.protection {
+      Protection::Dangerous if target_sdk >= RUNTIME_PERMISSIONS => Grant::Runtime,
+      Protection::Dangerous | Protection::Normal => Grant::Install,
+      _ if level.has_flag("appop") => Grant::SpecialAccess,
+      _ => Grant::Signature,
+    }
+  }
+}
+
+fn full_name(name: &str) -> String {
+  match name.contains('.') {
+    true => name.to_string(),
+    false => format!("{}{}", ANDROID_PERMISSION, name),
+  }
+}
+
+/// A `uses-permission` with what is known about the permission.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestedPermission {
+  pub name: String,
+  /// Not requested on devices above this API level.
+  pub max_sdk_version: Option<u32>,
+  /// `uses-permission-sdk-23`, only requested on API 23 and later.
+  pub sdk_23: bool,
+  /// `None` for permissions neither the platform nor the app defines, such as those of other
+  /// apps.
+  pub info: Option<PermissionInfo>,
+  /// How the app gets the permission given its target SDK.
+  pub grant: Option<Grant>,
+}
+
+impl RequestedPermission {
+  /// The requested permissions of a manifest, looked up in the platform knowledge base and
+  /// then in the permissions the app defines.
+  pub fn from_manifest(manifest: &Manifest) -> Vec<Self> {
+    let target_sdk = manifest.target_sdk();
+    manifest
+      .uses_permissions
+      .iter()
+      .map(|uses_permission| {
+        let info = PermissionInfo::platform(&uses_permission.name).or_else(|| {
+          manifest
+            .permissions
+            .iter()
+            .find(|permission| permission.name == uses_permission.name)
+            .map(PermissionInfo::from_definition)
+        });
+        Self {
+          name: uses_permission.name.clone(),
+          max_sdk_version: uses_permission.max_sdk_version,
+          sdk_23: uses_permission.sdk_23,
+          grant: info.as_ref().map(|info| info.grant(target_sdk)),
+          info,
+        }
+      })
+      .collect()
+  }
+
+  /// Whether the app requests the permission on a device running `sdk`.
+  pub fn requested_on(
+    &self,
+    sdk: u32,
+  ) -> bool {
+    (!self.sdk_23 || sdk >= RUNTIME_PERMISSIONS)
+      && self.max_sdk_version.is_none_or(|max_sdk| sdk <= max_sdk)
+  }
+
+  /// Whether the platform deprecated the permission, or stopped giving it any effect, at or
+  /// before `sdk`.
+  pub fn is_deprecated_on(
+    &self,
+    sdk: u32,
+  ) -> bool {
+    self
+      .info
+      .as_ref()
+      .and_then(|info| info.deprecated_in)
+      .is_some_and(|deprecated_in| deprecated_in <= sdk)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::Result;
+
+  #[test]
+  fn test_permissions() -> Result<()> {
+    assert_eq!(
+      ProtectionLevel::parse("0x12").to_string(),
+      "signature|privileged"
+    );
+    assert_eq!(
+      ProtectionLevel::parse("signatureOrSystem"),
+      ProtectionLevel::from_value(3)
+    );
+    assert_eq!(
+      ProtectionLevel::parse("signature|system").flags,
+      ["privileged"]
+    );
+
+    let manifest = Manifest::from_xml(
+      br#"<manifest package="com.example">
+<uses-sdk minSdkVersion="21" targetSdkVersion="34"></uses-sdk>
+<uses-permission name="android.permission.CAMERA"></uses-permission>
+<uses-permission name="android.permission.READ_EXTERNAL_STORAGE" maxSdkVersion="32"></uses-permission>
+<uses-permission-sdk-23 name="android.permission.SYSTEM_ALERT_WINDOW"></uses-permission-sdk-23>
+<uses-permission name="android.permission.INTERNET"></uses-permission>
+<uses-permission name="android.permission.READ_LOGS"></uses-permission>
+<uses-permission name="com.example.permission.SYNC"></uses-permission>
+<uses-permission name="com.other.permission.READ"></uses-permission>
+<permission name="com.example.permission.SYNC" protectionLevel="0x2" permissionGroup="com.example.group"></permission>
+</manifest>"#,
+    )?;
+    let permissions = RequestedPermission::from_manifest(&manifest);
+    let grants = permissions
+      .iter()
+      .map(|permission| permission.grant)
+      .collect::<Vec<_>>();
+    assert_eq!(
+      grants,
+      [
+        Some(Grant::Runtime),
+        Some(Grant::Runtime),
+        Some(Grant::SpecialAccess),
+        Some(Grant::Install),
+        Some(Grant::Signature),
+        Some(Grant::Signature),
+        None,
+      ]
+    );
+
+    let camera = permissions[0].info.as_ref().unwrap();
+    assert_eq!(
+      camera.group.as_deref(),
+      Some("android.permission-group.CAMERA")
+    );
+    assert_eq!(camera.source, PermissionSource::Platform);
+    assert!(permissions[1].requested_on(32) && !permissions[1].requested_on(33));
+    assert!(permissions[1].is_deprecated_on(33));
+    assert!(!permissions[2].requested_on(22) && permissions[2].requested_on(23));
+    let sync = permissions[5].info.as_ref().unwrap();
+    assert_eq!(sync.source, PermissionSource::App);
+    assert_eq!(sync.group.as_deref(), Some("com.example.group"));
+
+    // Dangerous permissions are granted at install time under the legacy model
+    assert_eq!(camera.grant(22), Grant::Install);
+    Ok(())
+  }
+}
@@ -0,0 +1,155 @@
+// Minimal reader for the protobuf wire format, enough to walk the aapt2 messages without
+// generated code. Unknown fields are skipped by the callers.
+
+use crate::nom_parser::ParseError;
+
+/// Value of a single field as encoded on the wire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Field<'a> {
+  Varint(u64),
+  Fixed64(u64),
+  Bytes(&'a [u8]),
+  Fixed32(u32),
+}
+
+impl<'a> Field<'a> {
+  pub(crate) fn as_u32(&self) -> u32 {
+    match *self {
+      Field::Varint(value) | Field::Fixed64(value) => value as u32,
+      Field::Fixed32(value) => value,
+      Field::Bytes(_) => 0,
+    }
+  }
+
+  pub(crate) fn as_bool(&self) -> bool {
+    self.as_u32() != 0
+  }
+
+  /// Embedded messages, strings and bytes. Scalars yield an empty message.
+  pub(crate) fn as_bytes(&self) -> &'a [u8] {
+    match *self {
+      Field::Bytes(bytes) => bytes,
+      _ => &[],
+    }
+  }
+
+  pub(crate) fn as_string(&self) -> String {
+    String::from_utf8_lossy(self.as_bytes()).to_string()
+  }
+}
+
+/// Decodes the fields of a message in wire order as (field number, value) pairs.
+pub(crate) fn fields(mut input: &[u8]) -> Result<Vec<(u32, Field<'_>)>, ParseError> {
+  let mut fields = Vec::new();
+  while !input.is_empty() {
+    let key = varint(&mut input)?;
+    let number = (key >> 3) as u32;
+    let field = match key & 0x7 {
+      0 => Field::Varint(varint(&mut input)?),
+      1 => Field::Fixed64(u64::from_le_bytes(take(&mut input, 8)?.try_into().unwrap())),
+      2 => {
+        let len = varint(&mut input)?;
+        let len = usize::try_from(len)
+          .map_err(|_| ParseError::Protobuf(format!("length {} out of range", len)))?;
+        Field::Bytes(take(&mut input, len)?)
+      }
+      5 => Field::Fixed32(u32::from_le_bytes(take(&mut input, 4)?.try_into().unwrap())),
+      wire_type => {
+        return Err(ParseError::Protobuf(format!(
+          "unsupported wire type {} of field {}",
+          wire_type, number
+        )))
+      }
+    };
+    fields.push((number, field));
+  }
+  Ok(fields)
+}
+
+/// The last value of a field, which wins for singular fields.
+pub(crate) fn field<'a>(
+  fields: &[(u32, Field<'a>)],
+  number: u32,
+) -> Option<Field<'a>> {
+  fields
+    .iter()
+    .rev()
+    .find(|(field_number, _)| *field_number == number)
+    .map(|(_, field)| *field)
+}
+
+/// Every value of a repeated field.
+pub(crate) fn repeated<'a, 'f>(
+  fields: &'f [(u32, Field<'a>)],
+  number: u32,
+) -> impl Iterator<Item = Field<'a>> + 'f {
+  fields
+    .iter()
+    .filter(move |(field_number, _)| *field_number == number)
+    .map(|(_, field)| *field)
+}
+
+fn varint(input: &mut &[u8]) -> Result<u64, ParseError> {
+  let mut value = 0u64;
+  for shift in (0..64).step_by(7) {
+    let (&byte, rest) = input
+      .split_first()
+      .ok_or_else(|| ParseError::Protobuf("truncated varint".to_string()))?;
+    *input = rest;
+    value |= ((byte & 0x7f) as u64) << shift;
+    if byte & 0x80 == 0 {
+      return Ok(value);
+    }
+  }
+  Err(ParseError::Protobuf("varint too long".to_string()))
+}
+
+fn take<'a>(
+  input: &mut &'a [u8],
+  len: usize,
+) -> Result<&'a [u8], ParseError> {
+  if input.len() < len {
+    return Err(ParseError::Protobuf(format!(
+      "field of {} bytes exceeds the {} remaining",
+      len,
+      input.len()
+    )));
+  }
+  let (bytes, rest) = input.split_at(len);
+  *input = rest;
+  Ok(bytes)
+}
+
+// Encoding, for building messages in tests.
+#[cfg(test)]
+pub(crate) mod encode {
+  pub(crate) fn varint(
+    out: &mut Vec<u8>,
+    number: u32,
+    value: u64,
+  ) {
+    raw_varint(out, (number as u64) << 3);
+    raw_varint(out, value);
+  }
+
+  pub(crate) fn bytes(
+    out: &mut Vec<u8>,
+    number: u32,
+    value: &[u8],
+  ) {
+    raw_varint(out, ((number as u64) << 3) | 2);
+    raw_varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+  }
+
+  fn raw_varint(
+    out: &mut Vec<u8>,
+    mut value: u64,
+  ) {
+    while value >= 0x80 {
+      out.push((value as u8) | 0x80);
+      value >>= 7;
+    }
+    out.push(value as u8);
+  }
+}
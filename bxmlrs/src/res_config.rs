@@ -0,0 +1,579 @@
+use crate::nom_parser::TypeChunkConfig;
+
+/// Decoded `ResTable_config`, describing which device configuration a TABLE_TYPE chunk targets.
+///
+/// Fields that were added in later platform versions are left at 0 when the config structure
+/// in the table is too short to contain them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResConfig {
+  pub mcc: u16,
+  pub mnc: u16,
+  // Two bytes per field, either plain ASCII or the packed three letter encoding.
+  pub language: [u8; 2],
+  pub country: [u8; 2],
+  pub orientation: u8,
+  pub touchscreen: u8,
+  pub density: u16,
+  pub keyboard: u8,
+  pub navigation: u8,
+  pub input_flags: u8,
+  pub screen_width: u16,
+  pub screen_height: u16,
+  pub sdk_version: u16,
+  pub minor_version: u16,
+  pub screen_layout: u8,
+  pub ui_mode: u8,
+  pub smallest_screen_width_dp: u16,
+  pub screen_width_dp: u16,
+  pub screen_height_dp: u16,
+  pub locale_script: [u8; 4],
+  pub locale_variant: [u8; 8],
+  pub screen_layout2: u8,
+  pub color_mode: u8,
+  pub locale_numbering_system: [u8; 8],
+}
+
+// Full size of the config structure we know about, not counting the leading size field.
+const CONFIG_DATA_SIZE: usize = 60;
+
+impl ResConfig {
+  pub const DENSITY_DEFAULT: u16 = 0;
+  pub const DENSITY_LOW: u16 = 120;
+  pub const DENSITY_MEDIUM: u16 = 160;
+  pub const DENSITY_TV: u16 = 213;
+  pub const DENSITY_HIGH: u16 = 240;
+  pub const DENSITY_XHIGH: u16 = 320;
+  pub const DENSITY_XXHIGH: u16 = 480;
+  pub const DENSITY_XXXHIGH: u16 = 640;
+  pub const DENSITY_ANY: u16 = 0xfffe;
+  pub const DENSITY_NONE: u16 = 0xffff;
+
+  pub fn parse(config: &TypeChunkConfig) -> Self {
+    let mut data = [0u8; CONFIG_DATA_SIZE];
+    let len = config.data.len().min(CONFIG_DATA_SIZE);
+    data[..len].copy_from_slice(&config.data[..len]);
+
+    let u16_at = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+    let mut locale_script = [0u8; 4];
+    locale_script.copy_from_slice(&data[32..36]);
+    let mut locale_variant = [0u8; 8];
+    locale_variant.copy_from_slice(&data[36..44]);
+    let mut locale_numbering_system = [0u8; 8];
+    locale_numbering_system.copy_from_slice(&data[49..57]);
+
+    Self {
+      mcc: u16_at(0),
+      mnc: u16_at(2),
+      language: [data[4], data[5]],
+      country: [data[6], data[7]],
+      orientation: data[8],
+      touchscreen: data[9],
+      density: u16_at(10),
+      keyboard: data[12],
+      navigation: data[13],
+      input_flags: data[14],
+      screen_width: u16_at(16),
+      screen_height: u16_at(18),
+      sdk_version: u16_at(20),
+      minor_version: u16_at(22),
+      screen_layout: data[24],
+      ui_mode: data[25],
+      smallest_screen_width_dp: u16_at(26),
+      screen_width_dp: u16_at(28),
+      screen_height_dp: u16_at(30),
+      locale_script,
+      locale_variant,
+      screen_layout2: data[44],
+      color_mode: data[45],
+      locale_numbering_system,
+    }
+  }
+
+  /// Encodes the config back into the raw structure of a TABLE_TYPE chunk.
+  pub fn to_config(&self) -> TypeChunkConfig {
+    let mut data = vec![0u8; CONFIG_DATA_SIZE];
+    let put_u16 = |data: &mut Vec<u8>, offset: usize, value: u16| {
+      data[offset..offset + 2].copy_from_slice(&value.to_le_bytes())
+    };
+    put_u16(&mut data, 0, self.mcc);
+    put_u16(&mut data, 2, self.mnc);
+    data[4..6].copy_from_slice(&self.language);
+    data[6..8].copy_from_slice(&self.country);
+    data[8] = self.orientation;
+    data[9] = self.touchscreen;
+    put_u16(&mut data, 10, self.density);
+    data[12] = self.keyboard;
+    data[13] = self.navigation;
+    data[14] = self.input_flags;
+    put_u16(&mut data, 16, self.screen_width);
+    put_u16(&mut data, 18, self.screen_height);
+    put_u16(&mut data, 20, self.sdk_version);
+    put_u16(&mut data, 22, self.minor_version);
+    data[24] = self.screen_layout;
+    data[25] = self.ui_mode;
+    put_u16(&mut data, 26, self.smallest_screen_width_dp);
+    put_u16(&mut data, 28, self.screen_width_dp);
+    put_u16(&mut data, 30, self.screen_height_dp);
+    data[32..36].copy_from_slice(&self.locale_script);
+    data[36..44].copy_from_slice(&self.locale_variant);
+    data[44] = self.screen_layout2;
+    data[45] = self.color_mode;
+    data[49..57].copy_from_slice(&self.locale_numbering_system);
+    TypeChunkConfig {
+      structure_size: CONFIG_DATA_SIZE as u32 + 4,
+      data,
+    }
+  }
+
+  /// Sets the locale from a BCP-47 tag such as `en-US`, `sr-Latn-RS` or `es-419`, the form
+  /// aapt2 uses in its protobuf tables. Returns `None` if the tag can't be represented.
+  pub fn set_bcp47_locale(
+    &mut self,
+    tag: &str,
+  ) -> Option<()> {
+    let mut parts = tag.split(['-', '_']);
+    let language = parts.next().filter(|language| {
+      (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic())
+    })?;
+    let mut locale = Self {
+      language: pack_locale_part(&language.to_ascii_lowercase(), b'a')?,
+      ..Self::default()
+    };
+    for part in parts {
+      let is_alphabetic = part.chars().all(|c| c.is_ascii_alphabetic());
+      let is_numeric = part.chars().all(|c| c.is_ascii_digit());
+      if part.len() == 4 && is_alphabetic && locale.locale_script == [0; 4] {
+        let mut script = part.to_ascii_lowercase().into_bytes();
+        script[0] = script[0].to_ascii_uppercase();
+        locale.locale_script.copy_from_slice(&script);
+      } else if (part.len() == 2 && is_alphabetic || part.len() == 3 && is_numeric)
+        && locale.country == [0; 2]
+      {
+        locale.country = pack_locale_part(&part.to_ascii_uppercase(), b'0')?;
+      } else if (5..=8).contains(&part.len()) && locale.locale_variant == [0; 8] {
+        locale.locale_variant[..part.len()].copy_from_slice(part.as_bytes());
+      } else {
+        return None;
+      }
+    }
+    self.language = locale.language;
+    self.country = locale.country;
+    self.locale_script = locale.locale_script;
+    self.locale_variant = locale.locale_variant;
+    Some(())
+  }
+
+  /// Configuration change flags of the dimensions in which this config differs from `other`,
+  /// as stored in the TABLE_SPEC entry flags.
+  pub fn config_changes(
+    &self,
+    other: &ResConfig,
+  ) -> u32 {
+    let dimensions = [
+      (self.mcc != other.mcc, 0x0001),
+      (self.mnc != other.mnc, 0x0002),
+      (
+        (
+          self.language,
+          self.country,
+          self.locale_script,
+          self.locale_variant,
+        ) != (
+          other.language,
+          other.country,
+          other.locale_script,
+          other.locale_variant,
+        ),
+        0x0004,
+      ),
+      (self.touchscreen != other.touchscreen, 0x0008),
+      (self.keyboard != other.keyboard, 0x0010),
+      // keysHidden and navHidden both count as a keyboard hidden change
+      ((self.input_flags ^ other.input_flags) & 0x0f != 0, 0x0020),
+      (self.navigation != other.navigation, 0x0040),
+      (self.orientation != other.orientation, 0x0080),
+      (self.density != other.density, 0x0100),
+      (
+        (
+          self.screen_width,
+          self.screen_height,
+          self.screen_width_dp,
+          self.screen_height_dp,
+        ) != (
+          other.screen_width,
+          other.screen_height,
+          other.screen_width_dp,
+          other.screen_height_dp,
+        ),
+        0x0200,
+      ),
+      (
+        (self.sdk_version, self.minor_version) != (other.sdk_version, other.minor_version),
+        0x0400,
+      ),
+      (
+        (self.screen_layout ^ other.screen_layout) & 0x3f != 0,
+        0x0800,
+      ),
+      (self.ui_mode != other.ui_mode, 0x1000),
+      (
+        self.smallest_screen_width_dp != other.smallest_screen_width_dp,
+        0x2000,
+      ),
+      (
+        (self.screen_layout ^ other.screen_layout) & 0xc0 != 0,
+        0x4000,
+      ),
+      (self.screen_layout2 != other.screen_layout2, 0x8000),
+      (self.color_mode != other.color_mode, 0x1_0000),
+    ];
+    dimensions
+      .into_iter()
+      .filter(|(changed, _)| *changed)
+      .fold(0, |flags, (_, flag)| flags | flag)
+  }
+
+  /// True for the default configuration, which has no qualifiers.
+  pub fn is_default(&self) -> bool {
+    *self == Self::default()
+  }
+
+  /// Language code such as `en` or `fil`, if the config is locale specific.
+  pub fn language(&self) -> Option<String> {
+    unpack_locale_part(self.language, b'a')
+  }
+
+  /// Region code such as `US` or `419`, if the config is region specific.
+  pub fn region(&self) -> Option<String> {
+    unpack_locale_part(self.country, b'0')
+  }
+
+  pub fn script(&self) -> Option<String> {
+    c_string(&self.locale_script)
+  }
+
+  pub fn variant(&self) -> Option<String> {
+    c_string(&self.locale_variant)
+  }
+
+  /// BCP-47 style locale tag, e.g. `pt-BR` or `sr-Latn`.
+  pub fn locale(&self) -> Option<String> {
+    let mut locale = self.language()?;
+    for part in [self.script(), self.region(), self.variant()]
+      .into_iter()
+      .flatten()
+    {
+      locale.push('-');
+      locale.push_str(&part);
+    }
+    Some(locale)
+  }
+
+  /// Resource directory qualifiers in aapt order, e.g. `de-rAT-land-xhdpi-v21`.
+  /// The default configuration yields an empty string.
+  pub fn qualifier(&self) -> String {
+    let mut parts: Vec<String> = Vec::new();
+
+    if self.mcc != 0 {
+      parts.push(format!("mcc{:03}", self.mcc));
+      if self.mnc != 0 {
+        // 0xffff means "mnc00"
+        let mnc = if self.mnc == 0xffff { 0 } else { self.mnc };
+        parts.push(format!("mnc{:02}", mnc));
+      }
+    }
+
+    if let Some(language) = self.language() {
+      let script = self.script();
+      let variant = self.variant();
+      if script.is_some() || variant.is_some() || language.len() > 2 {
+        // BCP-47 form is required for anything beyond language and region
+        let mut locale = format!("b+{}", language);
+        for part in [script, self.region(), variant].into_iter().flatten() {
+          locale.push('+');
+          locale.push_str(&part);
+        }
+        parts.push(locale);
+      } else {
+        parts.push(language);
+        if let Some(region) = self.region() {
+          parts.push(format!("r{}", region));
+        }
+      }
+    }
+
+    match self.screen_layout & 0xc0 {
+      0x40 => parts.push("ldltr".to_string()),
+      0x80 => parts.push("ldrtl".to_string()),
+      _ => {}
+    }
+    if self.smallest_screen_width_dp != 0 {
+      parts.push(format!("sw{}dp", self.smallest_screen_width_dp));
+    }
+    if self.screen_width_dp != 0 {
+      parts.push(format!("w{}dp", self.screen_width_dp));
+    }
+    if self.screen_height_dp != 0 {
+      parts.push(format!("h{}dp", self.screen_height_dp));
+    }
+    match self.screen_layout & 0x0f {
+      0x01 => parts.push("small".to_string()),
+      0x02 => parts.push("normal".to_string()),
+      0x03 => parts.push("large".to_string()),
+      0x04 => parts.push("xlarge".to_string()),
+      _ => {}
+    }
+    match self.screen_layout & 0x30 {
+      0x10 => parts.push("notlong".to_string()),
+      0x20 => parts.push("long".to_string()),
+      _ => {}
+    }
+    match self.screen_layout2 & 0x03 {
+      0x01 => parts.push("notround".to_string()),
+      0x02 => parts.push("round".to_string()),
+      _ => {}
+    }
+    match self.color_mode & 0x03 {
+      0x01 => parts.push("nowidecg".to_string()),
+      0x02 => parts.push("widecg".to_string()),
+      _ => {}
+    }
+    match self.color_mode & 0x0c {
+      0x04 => parts.push("lowdr".to_string()),
+      0x08 => parts.push("highdr".to_string()),
+      _ => {}
+    }
+    match self.orientation {
+      0x01 => parts.push("port".to_string()),
+      0x02 => parts.push("land".to_string()),
+      0x03 => parts.push("square".to_string()),
+      _ => {}
+    }
+    match self.ui_mode & 0x0f {
+      0x02 => parts.push("desk".to_string()),
+      0x03 => parts.push("car".to_string()),
+      0x04 => parts.push("television".to_string()),
+      0x05 => parts.push("appliance".to_string()),
+      0x06 => parts.push("watch".to_string()),
+      0x07 => parts.push("vrheadset".to_string()),
+      _ => {}
+    }
+    match self.ui_mode & 0x30 {
+      0x10 => parts.push("notnight".to_string()),
+      0x20 => parts.push("night".to_string()),
+      _ => {}
+    }
+    match self.density {
+      Self::DENSITY_DEFAULT => {}
+      Self::DENSITY_LOW => parts.push("ldpi".to_string()),
+      Self::DENSITY_MEDIUM => parts.push("mdpi".to_string()),
+      Self::DENSITY_TV => parts.push("tvdpi".to_string()),
+      Self::DENSITY_HIGH => parts.push("hdpi".to_string()),
+      Self::DENSITY_XHIGH => parts.push("xhdpi".to_string()),
+      Self::DENSITY_XXHIGH => parts.push("xxhdpi".to_string()),
+      Self::DENSITY_XXXHIGH => parts.push("xxxhdpi".to_string()),
+      Self::DENSITY_ANY => parts.push("anydpi".to_string()),
+      Self::DENSITY_NONE => parts.push("nodpi".to_string()),
+      density => parts.push(format!("{}dpi", density)),
+    }
+    match self.touchscreen {
+      0x01 => parts.push("notouch".to_string()),
+      0x02 => parts.push("stylus".to_string()),
+      0x03 => parts.push("finger".to_string()),
+      _ => {}
+    }
+    match self.input_flags & 0x03 {
+      0x01 => parts.push("keysexposed".to_string()),
+      0x02 => parts.push("keyshidden".to_string()),
+      0x03 => parts.push("keyssoft".to_string()),
+      _ => {}
+    }
+    match self.keyboard {
+      0x01 => parts.push("nokeys".to_string()),
+      0x02 => parts.push("qwerty".to_string()),
+      0x03 => parts.push("12key".to_string()),
+      _ => {}
+    }
+    match self.input_flags & 0x0c {
+      0x04 => parts.push("navexposed".to_string()),
+      0x08 => parts.push("navhidden".to_string()),
+      _ => {}
+    }
+    match self.navigation {
+      0x01 => parts.push("nonav".to_string()),
+      0x02 => parts.push("dpad".to_string()),
+      0x03 => parts.push("trackball".to_string()),
+      0x04 => parts.push("wheel".to_string()),
+      _ => {}
+    }
+    if self.screen_width != 0 && self.screen_height != 0 {
+      parts.push(format!("{}x{}", self.screen_width, self.screen_height));
+    }
+    if self.sdk_version != 0 {
+      parts.push(format!("v{}", self.sdk_version));
+    }
+
+    parts.join("-")
+  }
+}
+
+impl std::fmt::Display for ResConfig {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    if self.is_default() {
+      write!(f, "default")
+    } else {
+      write!(f, "{}", self.qualifier())
+    }
+  }
+}
+
+/// Packs a locale such as `de`, `pt-rBR`, `es-419` or `b+tlh+QO` into the raw language and
+/// country bytes of a ResTable_config. Scripts and variants are not supported.
+pub fn pack_locale(locale: &str) -> Option<([u8; 2], [u8; 2])> {
+  let parts = match locale.strip_prefix("b+") {
+    Some(bcp47) => bcp47.split('+').collect::<Vec<_>>(),
+    None => locale.split(['-', '_']).collect::<Vec<_>>(),
+  };
+  let (language, region) = match parts.as_slice() {
+    [language] => (*language, None),
+    [language, region] => (*language, Some(region.strip_prefix('r').unwrap_or(region))),
+    _ => return None,
+  };
+  if !language.chars().all(|c| c.is_ascii_alphabetic()) {
+    return None;
+  }
+  let language = pack_locale_part(&language.to_ascii_lowercase(), b'a')?;
+  let region = match region {
+    Some(region) if region.chars().all(|c| c.is_ascii_digit()) => pack_locale_part(region, b'0')?,
+    Some(region) if region.chars().all(|c| c.is_ascii_alphabetic()) => {
+      pack_locale_part(&region.to_ascii_uppercase(), b'0')?
+    }
+    Some(_) => return None,
+    None => [0, 0],
+  };
+  Some((language, region))
+}
+
+// Inverse of `unpack_locale_part`.
+fn pack_locale_part(
+  part: &str,
+  base: u8,
+) -> Option<[u8; 2]> {
+  let bytes = part.as_bytes();
+  match bytes.len() {
+    2 => Some([bytes[0], bytes[1]]),
+    3 => {
+      let [first, second, third] = [
+        bytes[0].checked_sub(base)?,
+        bytes[1].checked_sub(base)?,
+        bytes[2].checked_sub(base)?,
+      ];
+      if first > 0x1f || second > 0x1f || third > 0x1f {
+        return None;
+      }
+      Some([0x80 | (third << 2) | (second >> 3), (second << 5) | first])
+    }
+    _ => None,
+  }
+}
+
+// Language and region codes are either two ASCII characters, or three characters packed into
+// 5 bits each when the high bit of the first byte is set.
+fn unpack_locale_part(
+  packed: [u8; 2],
+  base: u8,
+) -> Option<String> {
+  if packed[0] == 0 {
+    return None;
+  }
+  if packed[0] & 0x80 != 0 {
+    let first = packed[1] & 0x1f;
+    let second = ((packed[1] & 0xe0) >> 5) | ((packed[0] & 0x03) << 3);
+    let third = (packed[0] & 0x7c) >> 2;
+    return Some(
+      [first, second, third]
+        .iter()
+        .map(|c| (base + c) as char)
+        .collect(),
+    );
+  }
+  c_string(&packed)
+}
+
+fn c_string(bytes: &[u8]) -> Option<String> {
+  let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+  if end == 0 {
+    return None;
+  }
+  Some(String::from_utf8_lossy(&bytes[..end]).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn config(data: &[(usize, u8)]) -> ResConfig {
+    let mut raw = vec![0u8; 60];
+    for (offset, value) in data {
+      raw[*offset] = *value;
+    }
+    ResConfig::parse(&TypeChunkConfig {
+      structure_size: 64,
+      data: raw,
+    })
+  }
+
+  #[test]
+  fn test_qualifier() {
+    assert_eq!(config(&[]).qualifier(), "");
+    assert!(config(&[]).is_default());
+
+    // de-rAT, landscape, xhdpi, v21
+    let de_at = config(&[
+      (4, b'd'),
+      (5, b'e'),
+      (6, b'A'),
+      (7, b'T'),
+      (8, 0x02),
+      (10, 0x40),
+      (11, 0x01),
+      (20, 21),
+    ]);
+    assert_eq!(de_at.qualifier(), "de-rAT-land-xhdpi-v21");
+    assert_eq!(de_at.locale(), Some("de-AT".to_string()));
+
+    // packed three letter language "fil"
+    let fil = config(&[(4, 0xad), (5, 0x05)]);
+    assert_eq!(fil.language(), Some("fil".to_string()));
+    assert_eq!(fil.qualifier(), "b+fil");
+
+    assert_eq!(pack_locale("fil"), Some(([0xad, 0x05], [0, 0])));
+    assert_eq!(pack_locale("de-rAT"), Some((*b"de", *b"AT")));
+    assert_eq!(pack_locale("b+de+AT"), pack_locale("de_AT"));
+    assert_eq!(pack_locale("de-AT-x"), None);
+
+    let night_sw600 = config(&[(25, 0x20), (26, 0x58), (27, 0x02)]);
+    assert_eq!(night_sw600.qualifier(), "sw600dp-night");
+    assert_eq!(ResConfig::parse(&night_sw600.to_config()), night_sw600);
+    assert_eq!(night_sw600.config_changes(&ResConfig::default()), 0x3000);
+
+    let mut sr_latn = ResConfig::default();
+    sr_latn.set_bcp47_locale("sr-Latn-RS").unwrap();
+    assert_eq!(sr_latn.qualifier(), "b+sr+Latn+RS");
+    assert_eq!(
+      sr_latn.config_changes(&de_at),
+      0x0004 | 0x0080 | 0x0100 | 0x0400
+    );
+    assert!(ResConfig::default().set_bcp47_locale("not a tag").is_none());
+
+    // navhidden, navexposed and dpad
+    let nav_hidden = config(&[(14, 0x08)]);
+    let nav_exposed = config(&[(14, 0x04)]);
+    let dpad = config(&[(13, 0x02)]);
+    assert_eq!(nav_hidden.config_changes(&nav_exposed), 0x0020);
+    assert_eq!(dpad.config_changes(&ResConfig::default()), 0x0040);
+  }
+}
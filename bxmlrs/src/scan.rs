@@ -0,0 +1,461 @@
+use crate::manifest::Manifest;
+use crate::nom_parser::ParseError;
+use crate::parser::{ApkArchive, Diagnostic, Parser};
+use std::collections::HashSet;
+
+/// Bounds of a recursive scan, so that ZIP bombs and deeply nested archives stay cheap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScanLimits {
+  /// Nesting level whose archives are reported but not opened, the APK's entries are level 1.
+  pub max_depth: usize,
+  /// Entries larger than this once extracted are reported but not read.
+  pub max_entry_size: u64,
+  /// Bytes extracted over the whole scan.
+  pub max_total_size: u64,
+}
+
+impl Default for ScanLimits {
+  fn default() -> Self {
+    Self {
+      max_depth: 4,
+      max_entry_size: 64 * 1024 * 1024,
+      max_total_size: 512 * 1024 * 1024,
+    }
+  }
+}
+
+/// What an embedded entry was detected as, from its content rather than its name.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EmbeddedKind {
+  /// A ZIP with an `AndroidManifest.xml`.
+  Apk,
+  /// Any other ZIP, e.g. a JAR.
+  Zip,
+  Dex {
+    version: String,
+  },
+  /// Data of `assets/` or `res/raw/` that looks random and isn't a known media format, e.g.
+  /// an encrypted payload. Entropy is in bits per byte.
+  HighEntropy {
+    entropy: f64,
+  },
+  /// An entry that wasn't read because of a limit or an extraction error.
+  Unknown,
+}
+
+impl std::fmt::Display for EmbeddedKind {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      EmbeddedKind::Apk => write!(f, "apk"),
+      EmbeddedKind::Zip => write!(f, "zip"),
+      EmbeddedKind::Dex { version } => write!(f, "dex {}", version),
+      EmbeddedKind::HighEntropy { entropy } => write!(f, "high entropy {:.2}", entropy),
+      EmbeddedKind::Unknown => write!(f, "unknown"),
+    }
+  }
+}
+
+/// Why an entry or archive wasn't looked into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+  DepthLimit,
+  EntrySizeLimit,
+  TotalSizeLimit,
+}
+
+impl std::fmt::Display for SkipReason {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    match self {
+      SkipReason::DepthLimit => write!(f, "depth limit"),
+      SkipReason::EntrySizeLimit => write!(f, "entry size limit"),
+      SkipReason::TotalSizeLimit => write!(f, "total size limit"),
+    }
+  }
+}
+
+/// A finding of a recursive scan: the scanned APK at the root, and below it every embedded
+/// APK, ZIP, DEX or high entropy blob.
+pub struct ScanNode {
+  /// Entry names from the root joined by `!/`, e.g. `assets/a.zip!/b.apk`. Empty for the root.
+  pub path: String,
+  pub kind: EmbeddedKind,
+  /// Extracted size.
+  pub size: u64,
+  pub depth: usize,
+  /// Package of nested APKs.
+  pub package: Option<String>,
+  /// ZIP problems of nested archives.
+  pub diagnostics: Vec<Diagnostic>,
+  /// Why a nested archive couldn't be read.
+  pub error: Option<String>,
+  pub skipped: Option<SkipReason>,
+  pub children: Vec<ScanNode>,
+  parser: Option<Parser>,
+}
+
+impl ScanNode {
+  fn new(
+    path: String,
+    kind: EmbeddedKind,
+    size: u64,
+    depth: usize,
+  ) -> Self {
+    Self {
+      path,
+      kind,
+      size,
+      depth,
+      package: None,
+      diagnostics: Vec::new(),
+      error: None,
+      skipped: None,
+      children: Vec::new(),
+      parser: None,
+    }
+  }
+
+  /// Parser of a nested APK, to decode its manifest and resources like those of any APK.
+  pub fn parser(&self) -> Option<&Parser> {
+    self.parser.as_ref()
+  }
+
+  /// This node and all nodes below it, depth first.
+  pub fn nodes(&self) -> Vec<&ScanNode> {
+    let mut nodes = vec![self];
+    for child in &self.children {
+      nodes.extend(child.nodes());
+    }
+    nodes
+  }
+}
+
+impl std::fmt::Display for ScanNode {
+  fn fmt(
+    &self,
+    f: &mut std::fmt::Formatter<'_>,
+  ) -> std::fmt::Result {
+    for node in self.nodes() {
+      let name = if node.path.is_empty() {
+        "."
+      } else {
+        node.path.rsplit("!/").next().unwrap_or_default()
+      };
+      write!(
+        f,
+        "{:indent$}{}: {}, {} bytes",
+        "",
+        name,
+        node.kind,
+        node.size,
+        indent = node.depth * 2
+      )?;
+      if let Some(package) = &node.package {
+        write!(f, ", package {}", package)?;
+      }
+      if let Some(skipped) = node.skipped {
+        write!(f, ", skipped ({})", skipped)?;
+      }
+      if let Some(error) = &node.error {
+        write!(f, ", error: {}", error)?;
+      }
+      writeln!(f)?;
+      for diagnostic in &node.diagnostics {
+        writeln!(
+          f,
+          "{:indent$}warning: {}",
+          "",
+          diagnostic,
+          indent = node.depth * 2 + 2
+        )?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Walks an APK and the archives embedded in it.
+pub struct Scanner {
+  limits: ScanLimits,
+  // Bytes extracted so far
+  total_size: u64,
+}
+
+impl Scanner {
+  pub fn new(limits: ScanLimits) -> Self {
+    Self {
+      limits,
+      total_size: 0,
+    }
+  }
+
+  /// Scans the APK a parser was read from.
+  pub fn scan(
+    &mut self,
+    parser: &Parser,
+  ) -> Result<ScanNode, ParseError> {
+    let archive = parser
+      .archive()
+      .ok_or_else(|| ParseError::MissingEntry("APK archive".to_string()))?;
+    let mut root = ScanNode::new(
+      String::new(),
+      EmbeddedKind::Apk,
+      archive.data().len() as u64,
+      0,
+    );
+    root.package = manifest_package(parser);
+    root.children = self.scan_archive(archive, "", 1);
+    Ok(root)
+  }
+
+  fn scan_archive(
+    &mut self,
+    archive: &ApkArchive,
+    prefix: &str,
+    depth: usize,
+  ) -> Vec<ScanNode> {
+    let mut nodes = Vec::new();
+    let mut names = HashSet::new();
+    for entry in archive.entries() {
+      // Android only sees the first of duplicate entries
+      if entry.name.ends_with('/') || !names.insert(entry.name.as_str()) {
+        continue;
+      }
+      let path = format!("{}{}", prefix, entry.name);
+      let size = entry.uncompressed_size;
+      let skipped = if size > self.limits.max_entry_size {
+        Some(SkipReason::EntrySizeLimit)
+      } else if self.total_size.saturating_add(size) > self.limits.max_total_size {
+        Some(SkipReason::TotalSizeLimit)
+      } else {
+        None
+      };
+      if let Some(skipped) = skipped {
+        let mut node = ScanNode::new(path, EmbeddedKind::Unknown, size, depth);
+        node.skipped = Some(skipped);
+        nodes.push(node);
+        continue;
+      }
+
+      self.total_size += size;
+      let data = match archive.extract(entry) {
+        Ok(data) => data,
+        Err(e) => {
+          let mut node = ScanNode::new(path, EmbeddedKind::Unknown, size, depth);
+          node.error = Some(e.to_string());
+          nodes.push(node);
+          continue;
+        }
+      };
+      let Some(kind) = detect(&entry.name, &data) else {
+        continue;
+      };
+      let mut node = ScanNode::new(path, kind, data.len() as u64, depth);
+      if node.kind == EmbeddedKind::Zip {
+        self.scan_nested(&mut node, data);
+      }
+      nodes.push(node);
+    }
+    nodes
+  }
+
+  // Opens a nested ZIP, reading it as an APK if it has a manifest.
+  fn scan_nested(
+    &mut self,
+    node: &mut ScanNode,
+    data: Vec<u8>,
+  ) {
+    let archive = match ApkArchive::new(data) {
+      Ok(archive) => archive,
+      Err(e) => {
+        node.error = Some(e.to_string());
+        return;
+      }
+    };
+    node.diagnostics = archive.diagnostics().to_vec();
+    if archive.by_name("AndroidManifest.xml").is_some() {
+      node.kind = EmbeddedKind::Apk;
+    }
+    if node.depth >= self.limits.max_depth {
+      node.skipped = Some(SkipReason::DepthLimit);
+      return;
+    }
+    let prefix = format!("{}!/", node.path);
+    if node.kind == EmbeddedKind::Zip {
+      node.children = self.scan_archive(&archive, &prefix, node.depth + 1);
+      return;
+    }
+    match Parser::from_apk_archive(archive) {
+      Ok(parser) => {
+        node.package = manifest_package(&parser);
+        node.diagnostics = parser.diagnostics().to_vec();
+        if let Some(archive) = parser.archive() {
+          node.children = self.scan_archive(archive, &prefix, node.depth + 1);
+        }
+        node.parser = Some(parser);
+      }
+      Err(e) => node.error = Some(e.to_string()),
+    }
+  }
+}
+
+fn detect(
+  name: &str,
+  data: &[u8],
+) -> Option<EmbeddedKind> {
+  if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+    return Some(EmbeddedKind::Zip);
+  }
+  if data.len() >= 8 && data.starts_with(b"dex\n") {
+    let version = String::from_utf8_lossy(&data[4..7]).to_string();
+    return Some(EmbeddedKind::Dex { version });
+  }
+  let hidden = name.starts_with("assets/") || name.starts_with("res/raw/");
+  if hidden && data.len() >= 1024 && !is_media(data) {
+    let entropy = entropy(data);
+    if entropy >= 7.9 {
+      return Some(EmbeddedKind::HighEntropy { entropy });
+    }
+  }
+  None
+}
+
+// Compressed formats that are expected to look random.
+fn is_media(data: &[u8]) -> bool {
+  const MAGICS: [&[u8]; 9] = [
+    b"\x89PNG",
+    b"\xff\xd8\xff",
+    b"GIF8",
+    b"RIFF",
+    b"OggS",
+    b"ID3",
+    b"fLaC",
+    b"wOF2",
+    b"wOFF",
+  ];
+  MAGICS.iter().any(|magic| data.starts_with(magic))
+    // MPEG audio frames and ISO media files (mp4, heif, ...)
+    || (data.len() >= 2 && data[0] == 0xff && data[1] & 0xe0 == 0xe0)
+    || data.get(4..8) == Some(b"ftyp")
+}
+
+// Shannon entropy in bits per byte.
+fn entropy(data: &[u8]) -> f64 {
+  let mut counts = [0usize; 256];
+  for byte in data {
+    counts[*byte as usize] += 1;
+  }
+  let len = data.len() as f64;
+  counts
+    .iter()
+    .filter(|count| **count > 0)
+    .map(|count| {
+      let p = *count as f64 / len;
+      -p * p.log2()
+    })
+    .sum()
+}
+
+// The package attribute is a plain string, no resource table is needed to read it.
+fn manifest_package(parser: &Parser) -> Option<String> {
+  let manifest = Manifest::from_xml(&parser.parse_with(None).ok()?).ok()?;
+  manifest.package
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::zip;
+  use anyhow::Result;
+  use sha2::{Digest, Sha256};
+
+  #[test]
+  fn test_scan() -> Result<()> {
+    let manifest = std::fs::read("../data/xml/AndroidManifest.xml")?;
+    let dex = [b"dex\n039\0".as_slice(), &[0; 104]].concat();
+    // Looks like an encrypted payload
+    let blob = (0u32..256)
+      .flat_map(|i| Sha256::digest(i.to_le_bytes()))
+      .collect::<Vec<_>>();
+    let jar = zip(&[
+      ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\r\n"),
+      ("a.dex", &dex),
+    ])?;
+    let payload = zip(&[
+      ("AndroidManifest.xml", &manifest),
+      ("classes.dex", &dex),
+      ("assets/lib.jar", &jar),
+    ])?;
+    let apk = zip(&[
+      ("AndroidManifest.xml", &manifest),
+      ("classes.dex", &dex),
+      ("assets/payload.apk", &payload),
+      ("res/raw/blob.bin", &blob),
+      ("res/raw/text.txt", &b"hello ".repeat(500)),
+    ])?;
+    let parser = Parser::from_bytes(&apk)?;
+
+    let root = parser.scan(ScanLimits::default())?;
+    let package = root.package.clone();
+    assert!(package.is_some());
+    let found = root
+      .nodes()
+      .iter()
+      .map(|node| (node.path.as_str(), node.depth))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      found,
+      [
+        ("", 0),
+        ("classes.dex", 1),
+        ("assets/payload.apk", 1),
+        ("assets/payload.apk!/classes.dex", 2),
+        ("assets/payload.apk!/assets/lib.jar", 2),
+        ("assets/payload.apk!/assets/lib.jar!/a.dex", 3),
+        ("res/raw/blob.bin", 1),
+      ]
+    );
+    let nested = &root.children[1];
+    assert_eq!(nested.kind, EmbeddedKind::Apk);
+    assert_eq!(nested.package, package);
+    assert!(!nested.parser().unwrap().parse_with(None)?.is_empty());
+    assert_eq!(nested.children[1].kind, EmbeddedKind::Zip);
+    assert_eq!(
+      root.children[0].kind,
+      EmbeddedKind::Dex {
+        version: "039".to_string()
+      }
+    );
+    assert!(matches!(
+      root.children[2].kind,
+      EmbeddedKind::HighEntropy { .. }
+    ));
+    assert!(root.to_string().contains("  assets/payload.apk: apk"));
+
+    // The nested APK is reported but not opened
+    let root = parser.scan(ScanLimits {
+      max_depth: 1,
+      ..Default::default()
+    })?;
+    assert_eq!(root.children[1].skipped, Some(SkipReason::DepthLimit));
+    assert!(root.children[1].children.is_empty());
+
+    let root = parser.scan(ScanLimits {
+      max_entry_size: 1024,
+      ..Default::default()
+    })?;
+    let nodes = root.nodes();
+    let payload = nodes
+      .iter()
+      .find(|node| node.path == "assets/payload.apk")
+      .unwrap();
+    assert_eq!(payload.kind, EmbeddedKind::Unknown);
+    assert_eq!(payload.skipped, Some(SkipReason::EntrySizeLimit));
+    Ok(())
+  }
+}
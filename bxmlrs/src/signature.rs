@@ -0,0 +1,1397 @@
+use crate::der::{
+  Der, TAG_BIT_STRING, TAG_CONTEXT_0, TAG_GENERALIZED_TIME, TAG_INTEGER, TAG_OID, TAG_SEQUENCE,
+  TAG_SET, TAG_UTC_TIME,
+};
+use crate::nom_parser::ParseError;
+use crate::parser::ApkArchive;
+use base64::Engine;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::collections::HashMap;
+
+// Ids of the APK Signing Block pairs
+pub const V2_BLOCK_ID: u32 = 0x7109871a;
+pub const V3_BLOCK_ID: u32 = 0xf05368c0;
+pub const V31_BLOCK_ID: u32 = 0x1b93ad61;
+// Additional attribute of v3 signers holding the signing certificate lineage
+pub const PROOF_OF_ROTATION_ATTR_ID: u32 = 0x3ba06f8c;
+
+const SIGNING_BLOCK_MAGIC: &[u8; 16] = b"APK Sig Block 42";
+const CHUNK_SIZE: usize = 1024 * 1024;
+const VERITY_BLOCK_SIZE: usize = 4096;
+
+/// An X.509 certificate of a signer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Certificate {
+  pub der: Vec<u8>,
+  /// Distinguished names in RFC 4514 form, e.g. `CN=Android Debug,O=Android,C=US`.
+  pub subject: String,
+  pub issuer: String,
+  /// Serial number in hex.
+  pub serial: String,
+  pub not_before: String,
+  pub not_after: String,
+  pub signature_algorithm: String,
+  pub public_key_algorithm: String,
+  /// DER encoded SubjectPublicKeyInfo.
+  pub public_key: Vec<u8>,
+}
+
+impl Certificate {
+  pub fn from_der(der: &[u8]) -> Result<Self, ParseError> {
+    let certificate = Der::parse_all(der)?.expect(TAG_SEQUENCE)?.children()?;
+    let tbs = certificate
+      .first()
+      .ok_or_else(|| signature_error("empty certificate"))?
+      .expect(TAG_SEQUENCE)?
+      .children()?;
+    // The version is optional and defaults to v1
+    let fields = match tbs.first() {
+      Some(version) if version.tag == TAG_CONTEXT_0 => &tbs[1..],
+      _ => &tbs[..],
+    };
+    let [serial, signature, issuer, validity, subject, public_key, ..] = fields else {
+      return Err(signature_error("truncated certificate"));
+    };
+    let validity = validity.expect(TAG_SEQUENCE)?.children()?;
+    let time = |index: usize| {
+      validity
+        .get(index)
+        .filter(|time| time.tag == TAG_UTC_TIME || time.tag == TAG_GENERALIZED_TIME)
+        .map(|time| time.time())
+        .unwrap_or_default()
+    };
+    let public_key_info = public_key.expect(TAG_SEQUENCE)?.children()?;
+    let [public_key_algorithm, key] = public_key_info.as_slice() else {
+      return Err(signature_error("invalid public key"));
+    };
+    key.expect(TAG_BIT_STRING)?;
+
+    Ok(Self {
+      der: der.to_vec(),
+      subject: distinguished_name(subject)?,
+      issuer: distinguished_name(issuer)?,
+      serial: serial_number(serial.expect(TAG_INTEGER)?.content),
+      not_before: time(0),
+      not_after: time(1),
+      signature_algorithm: algorithm_name(signature)?,
+      public_key_algorithm: algorithm_name(public_key_algorithm)?,
+      public_key: public_key.raw.to_vec(),
+    })
+  }
+
+  pub fn sha1_fingerprint(&self) -> String {
+    hex::encode(Sha1::digest(&self.der))
+  }
+
+  pub fn sha256_fingerprint(&self) -> String {
+    hex::encode(Sha256::digest(&self.der))
+  }
+}
+
+fn signature_error(message: &str) -> ParseError {
+  ParseError::Signature(message.to_string())
+}
+
+// RDNs are written last to first, as RFC 4514 requires.
+fn distinguished_name(name: &Der) -> Result<String, ParseError> {
+  let mut rdns = Vec::new();
+  for rdn in name.expect(TAG_SEQUENCE)?.children()? {
+    let mut attributes = Vec::new();
+    for attribute in rdn.expect(TAG_SET)?.children()? {
+      let attribute = attribute.expect(TAG_SEQUENCE)?.children()?;
+      let [oid, value, ..] = attribute.as_slice() else {
+        return Err(signature_error("truncated name attribute"));
+      };
+      let oid = oid.expect(TAG_OID)?.oid();
+      let key = match oid.as_str() {
+        "2.5.4.3" => "CN",
+        "2.5.4.6" => "C",
+        "2.5.4.7" => "L",
+        "2.5.4.8" => "ST",
+        "2.5.4.9" => "STREET",
+        "2.5.4.10" => "O",
+        "2.5.4.11" => "OU",
+        "0.9.2342.19200300.100.1.1" => "UID",
+        "0.9.2342.19200300.100.1.25" => "DC",
+        "1.2.840.113549.1.9.1" => "EMAILADDRESS",
+        _ => oid.as_str(),
+      };
+      attributes.push(format!("{}={}", key, escape_name_value(&value.string())));
+    }
+    rdns.push(attributes.join("+"));
+  }
+  rdns.reverse();
+  Ok(rdns.join(","))
+}
+
+fn escape_name_value(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  let last = value.chars().count().saturating_sub(1);
+  for (index, c) in value.chars().enumerate() {
+    let special = matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';')
+      || (index == 0 && (c == '#' || c == ' '))
+      || (index == last && c == ' ');
+    if special {
+      escaped.push('\\');
+    }
+    escaped.push(c);
+  }
+  escaped
+}
+
+fn serial_number(serial: &[u8]) -> String {
+  // Drop the sign padding of positive serials
+  let serial = match serial {
+    [0, rest @ ..] if !rest.is_empty() => rest,
+    _ => serial,
+  };
+  hex::encode(serial)
+}
+
+// Name of the algorithm of an AlgorithmIdentifier.
+fn algorithm_name(algorithm: &Der) -> Result<String, ParseError> {
+  let children = algorithm.expect(TAG_SEQUENCE)?.children()?;
+  let oid = children
+    .first()
+    .ok_or_else(|| signature_error("empty algorithm identifier"))?
+    .expect(TAG_OID)?
+    .oid();
+  let name = match oid.as_str() {
+    "1.2.840.113549.1.1.1" => "RSA",
+    "1.2.840.10045.2.1" => "EC",
+    "1.2.840.10040.4.1" => "DSA",
+    "1.3.101.112" => "Ed25519",
+    "1.2.840.113549.1.1.4" => "MD5withRSA",
+    "1.2.840.113549.1.1.5" => "SHA1withRSA",
+    "1.2.840.113549.1.1.10" => "RSASSA-PSS",
+    "1.2.840.113549.1.1.11" => "SHA256withRSA",
+    "1.2.840.113549.1.1.12" => "SHA384withRSA",
+    "1.2.840.113549.1.1.13" => "SHA512withRSA",
+    "1.2.840.10045.4.1" => "SHA1withECDSA",
+    "1.2.840.10045.4.3.2" => "SHA256withECDSA",
+    "1.2.840.10045.4.3.3" => "SHA384withECDSA",
+    "1.2.840.10045.4.3.4" => "SHA512withECDSA",
+    "1.2.840.10040.4.3" => "SHA1withDSA",
+    "2.16.840.1.101.3.4.3.2" => "SHA256withDSA",
+    _ => return Ok(oid),
+  };
+  Ok(name.to_string())
+}
+
+// Certificates of a PKCS#7 SignedData block such as META-INF/CERT.RSA.
+fn pkcs7_certificates(block: &[u8]) -> Result<Vec<Certificate>, ParseError> {
+  let content_info = Der::parse_all(block)?.expect(TAG_SEQUENCE)?.children()?;
+  let content = content_info
+    .get(1)
+    .ok_or_else(|| signature_error("PKCS#7 block without content"))?
+    .expect(TAG_CONTEXT_0)?;
+  let signed_data = Der::parse_all(content.content)?.expect(TAG_SEQUENCE)?;
+  let mut certificates = Vec::new();
+  // certificates [0] IMPLICIT SET OF Certificate
+  for field in signed_data.children()? {
+    if field.tag == TAG_CONTEXT_0 {
+      for certificate in field.children()? {
+        certificates.push(Certificate::from_der(certificate.raw)?);
+      }
+    }
+  }
+  Ok(certificates)
+}
+
+/// Name of an APK Signature Scheme v2+ signature algorithm id.
+pub fn signature_algorithm_name(id: u32) -> &'static str {
+  match id {
+    0x0101 => "RSASSA-PSS with SHA2-256",
+    0x0102 => "RSASSA-PSS with SHA2-512",
+    0x0103 => "RSASSA-PKCS1-v1_5 with SHA2-256",
+    0x0104 => "RSASSA-PKCS1-v1_5 with SHA2-512",
+    0x0201 => "ECDSA with SHA2-256",
+    0x0202 => "ECDSA with SHA2-512",
+    0x0301 => "DSA with SHA2-256",
+    0x0421 => "RSASSA-PKCS1-v1_5 with SHA2-256 verity",
+    0x0423 => "ECDSA with SHA2-256 verity",
+    0x0425 => "DSA with SHA2-256 verity",
+    _ => "unknown",
+  }
+}
+
+/// A digest of the APK contents claimed by a v2+ signer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentDigest {
+  pub algorithm: u32,
+  pub expected: Vec<u8>,
+  /// Digest computed from the archive, `None` for unknown algorithms.
+  pub actual: Option<Vec<u8>>,
+}
+
+impl ContentDigest {
+  pub fn verified(&self) -> bool {
+    self.actual.as_ref() == Some(&self.expected)
+  }
+}
+
+/// A previous signing certificate of a v3 signer, oldest first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineageNode {
+  pub certificate: Certificate,
+  /// Capabilities granted to this certificate, e.g. 1 for installed data and 2 for shared UID.
+  pub flags: u32,
+  /// Algorithm of the signature the previous certificate made over this node.
+  pub signature_algorithm: u32,
+}
+
+/// A signer of the APK Signing Block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signer {
+  /// Signing certificate first, then its chain.
+  pub certificates: Vec<Certificate>,
+  pub digests: Vec<ContentDigest>,
+  pub signature_algorithms: Vec<u32>,
+  /// DER encoded SubjectPublicKeyInfo.
+  pub public_key: Vec<u8>,
+  /// SDK range the signer applies to, v3 and v3.1 only.
+  pub min_sdk: Option<u32>,
+  pub max_sdk: Option<u32>,
+  /// Key rotation history of v3 signers.
+  pub lineage: Vec<LineageNode>,
+  pub additional_attributes: Vec<(u32, Vec<u8>)>,
+}
+
+impl Signer {
+  pub fn certificate(&self) -> Option<&Certificate> {
+    self.certificates.first()
+  }
+
+  /// Whether every digest matches the archive, every signature has a digest and the
+  /// certificate is the one of the public key. The signatures over the digests are not
+  /// checked, anyone can digest a modified APK again.
+  pub fn digests_match(&self) -> bool {
+    let digest_algorithms = self
+      .digests
+      .iter()
+      .map(|digest| digest.algorithm)
+      .collect::<Vec<_>>();
+    !self.digests.is_empty()
+      && self.digests.iter().all(ContentDigest::verified)
+      && digest_algorithms == self.signature_algorithms
+      && self
+        .certificate()
+        .is_some_and(|certificate| certificate.public_key == self.public_key)
+  }
+}
+
+/// A signer of the v1 JAR signature, one `.SF` file with its signature block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct V1Signer {
+  /// Base name of the files, e.g. `CERT` for `META-INF/CERT.SF`.
+  pub name: String,
+  pub certificates: Vec<Certificate>,
+  /// Schemes of the `X-Android-APK-Signed` attribute, which protects them from being stripped.
+  pub apk_signed_versions: Vec<u32>,
+  /// Whether the `.SF` digests match `MANIFEST.MF`.
+  pub manifest_verified: bool,
+}
+
+/// The v1 JAR signature of `META-INF/`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct V1Signature {
+  pub signers: Vec<V1Signer>,
+  /// Entries whose digest differs from the one of `MANIFEST.MF`.
+  pub mismatched_entries: Vec<String>,
+  /// Entries of `MANIFEST.MF` missing from the archive.
+  pub missing_entries: Vec<String>,
+  /// Entries of the archive not covered by `MANIFEST.MF`.
+  pub unlisted_entries: Vec<String>,
+}
+
+impl V1Signature {
+  /// Whether the entries match `MANIFEST.MF` and it matches every `.SF` file. The signature
+  /// blocks over the `.SF` files are not checked.
+  pub fn digests_match(&self) -> bool {
+    !self.signers.is_empty()
+      && self
+        .signers
+        .iter()
+        .all(|signer| signer.manifest_verified && !signer.certificates.is_empty())
+      && self.mismatched_entries.is_empty()
+      && self.missing_entries.is_empty()
+      && self.unlisted_entries.is_empty()
+  }
+}
+
+/// Every signature of an APK, with its digests checked against the archive.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApkSignatures {
+  pub v1: Option<V1Signature>,
+  pub v2: Vec<Signer>,
+  pub v3: Vec<Signer>,
+  pub v31: Vec<Signer>,
+  /// Ids of all pairs of the APK Signing Block, including unknown ones.
+  pub signing_block_ids: Vec<u32>,
+}
+
+impl ApkSignatures {
+  pub fn from_archive(archive: &ApkArchive) -> Result<Self, ParseError> {
+    let mut signatures = Self {
+      v1: v1_signature(archive)?,
+      ..Default::default()
+    };
+    let Some((block_offset, pairs)) = signing_block(archive)? else {
+      return Ok(signatures);
+    };
+    let content = SignedContent::new(archive, block_offset);
+    for (id, value) in pairs {
+      signatures.signing_block_ids.push(id);
+      let signers = match id {
+        V2_BLOCK_ID => &mut signatures.v2,
+        V3_BLOCK_ID => &mut signatures.v3,
+        V31_BLOCK_ID => &mut signatures.v31,
+        _ => continue,
+      };
+      *signers = parse_signers(value, id != V2_BLOCK_ID, &content)?;
+    }
+    Ok(signatures)
+  }
+
+  /// Schemes the v1 signature says the APK was signed with but whose block is missing.
+  pub fn stripped_schemes(&self) -> Vec<u32> {
+    let Some(v1) = &self.v1 else {
+      return Vec::new();
+    };
+    let mut stripped = Vec::new();
+    for version in v1
+      .signers
+      .iter()
+      .flat_map(|signer| &signer.apk_signed_versions)
+    {
+      let missing = match version {
+        2 => self.v2.is_empty(),
+        3 => self.v3.is_empty(),
+        _ => false,
+      };
+      if missing && !stripped.contains(version) {
+        stripped.push(*version);
+      }
+    }
+    stripped
+  }
+
+  /// Whether the APK is signed and the digests of every scheme match its contents. The
+  /// signatures are not checked, so this doesn't tell who signed the APK.
+  pub fn digests_match(&self) -> bool {
+    let schemes = [&self.v2, &self.v3, &self.v31];
+    let signed = self.v1.is_some() || schemes.iter().any(|signers| !signers.is_empty());
+    signed
+      && self.v1.as_ref().is_none_or(V1Signature::digests_match)
+      && schemes
+        .iter()
+        .all(|signers| signers.iter().all(Signer::digests_match))
+      && self.stripped_schemes().is_empty()
+  }
+}
+
+// Little endian reader of the length prefixed structures of the signing block and idsig files.
+struct Input<'a> {
+  data: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+  fn new(data: &'a [u8]) -> Self {
+    Self { data }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+
+  fn take(
+    &mut self,
+    len: usize,
+  ) -> Result<&'a [u8], ParseError> {
+    if self.data.len() < len {
+      return Err(signature_error("length prefixed value out of bounds"));
+    }
+    let (value, rest) = self.data.split_at(len);
+    self.data = rest;
+    Ok(value)
+  }
+
+  fn u8(&mut self) -> Result<u8, ParseError> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn u32(&mut self) -> Result<u32, ParseError> {
+    Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+  }
+
+  fn u64(&mut self) -> Result<u64, ParseError> {
+    Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+  }
+
+  fn prefixed(&mut self) -> Result<&'a [u8], ParseError> {
+    let len = self.u32()? as usize;
+    self.take(len)
+  }
+
+  // Items of a length prefixed sequence of length prefixed values.
+  fn sequence(&mut self) -> Result<Vec<&'a [u8]>, ParseError> {
+    let mut sequence = Input::new(self.prefixed()?);
+    let mut items = Vec::new();
+    while !sequence.is_empty() {
+      items.push(sequence.prefixed()?);
+    }
+    Ok(items)
+  }
+}
+
+type SigningBlock<'a> = (usize, Vec<(u32, &'a [u8])>);
+
+// Offset of the APK Signing Block and its (id, value) pairs. The block sits right before the
+// central directory and ends with its size and magic.
+fn signing_block(archive: &ApkArchive) -> Result<Option<SigningBlock<'_>>, ParseError> {
+  let data = archive.data();
+  let (cd_offset, _, _) = archive.central_directory();
+  let Some(footer) = cd_offset
+    .checked_sub(24)
+    .map(|start| &data[start..cd_offset])
+  else {
+    return Ok(None);
+  };
+  if &footer[8..] != SIGNING_BLOCK_MAGIC {
+    return Ok(None);
+  }
+  let size = Input::new(footer).u64()?;
+  // Like Android, the size counts at least the footer itself
+  if size < 24 {
+    return Err(signature_error("APK Signing Block size out of range"));
+  }
+  let block_offset = usize::try_from(size)
+    .ok()
+    .and_then(|size| size.checked_add(8))
+    .and_then(|size| cd_offset.checked_sub(size))
+    .ok_or_else(|| signature_error("APK Signing Block size out of bounds"))?;
+  let mut block = Input::new(&data[block_offset..cd_offset - 24]);
+  if block.u64()? != size {
+    return Err(signature_error(
+      "APK Signing Block header and footer sizes differ",
+    ));
+  }
+  let mut pairs = Vec::new();
+  while !block.is_empty() {
+    let len = block.u64()?;
+    let mut pair = Input::new(block.take(usize::try_from(len).unwrap_or(usize::MAX))?);
+    pairs.push((pair.u32()?, pair.data));
+  }
+  Ok(Some((block_offset, pairs)))
+}
+
+fn parse_signers(
+  value: &[u8],
+  is_v3: bool,
+  content: &SignedContent,
+) -> Result<Vec<Signer>, ParseError> {
+  let mut signers = Vec::new();
+  for signer in Input::new(value).sequence()? {
+    let mut signer = Input::new(signer);
+    let mut signed_data = Input::new(signer.prefixed()?);
+    let digests = signed_data.sequence()?;
+    let certificates = signed_data.sequence()?;
+    if is_v3 {
+      // The SDK range is repeated outside of the signed data
+      signed_data.u32()?;
+      signed_data.u32()?;
+    }
+    let attributes = signed_data.sequence()?;
+    let (min_sdk, max_sdk) = if is_v3 {
+      (Some(signer.u32()?), Some(signer.u32()?))
+    } else {
+      (None, None)
+    };
+    let signatures = signer.sequence()?;
+    let public_key = signer.prefixed()?.to_vec();
+
+    let mut parsed = Signer {
+      certificates: certificates
+        .into_iter()
+        .map(Certificate::from_der)
+        .collect::<Result<_, _>>()?,
+      digests: Vec::new(),
+      signature_algorithms: Vec::new(),
+      public_key,
+      min_sdk,
+      max_sdk,
+      lineage: Vec::new(),
+      additional_attributes: Vec::new(),
+    };
+    for digest in digests {
+      let mut digest = Input::new(digest);
+      let algorithm = digest.u32()?;
+      parsed.digests.push(ContentDigest {
+        algorithm,
+        expected: digest.prefixed()?.to_vec(),
+        actual: content.digest(algorithm),
+      });
+    }
+    for signature in signatures {
+      parsed
+        .signature_algorithms
+        .push(Input::new(signature).u32()?);
+    }
+    for attribute in attributes {
+      let mut attribute = Input::new(attribute);
+      let id = attribute.u32()?;
+      if id == PROOF_OF_ROTATION_ATTR_ID {
+        parsed.lineage = parse_lineage(attribute.data)?;
+      }
+      parsed
+        .additional_attributes
+        .push((id, attribute.data.to_vec()));
+    }
+    signers.push(parsed);
+  }
+  Ok(signers)
+}
+
+fn parse_lineage(value: &[u8]) -> Result<Vec<LineageNode>, ParseError> {
+  let mut lineage = Input::new(value);
+  let _version = lineage.u32()?;
+  let mut nodes = Vec::new();
+  while !lineage.is_empty() {
+    let mut node = Input::new(lineage.prefixed()?);
+    let mut signed_data = Input::new(node.prefixed()?);
+    let certificate = Certificate::from_der(signed_data.prefixed()?)?;
+    let flags = node.u32()?;
+    let signature_algorithm = node.u32()?;
+    nodes.push(LineageNode {
+      certificate,
+      flags,
+      signature_algorithm,
+    });
+  }
+  Ok(nodes)
+}
+
+// What v2+ signers digest: the entries before the signing block, the central directory and the
+// end of central directory record pointing at the signing block instead.
+struct SignedContent<'a> {
+  entries: &'a [u8],
+  central_directory: &'a [u8],
+  eocd: Vec<u8>,
+  // Digests already computed, by algorithm id
+  digests: std::cell::RefCell<HashMap<u32, Option<Vec<u8>>>>,
+}
+
+impl<'a> SignedContent<'a> {
+  fn new(
+    archive: &'a ApkArchive,
+    block_offset: usize,
+  ) -> Self {
+    let data = archive.data();
+    let (cd_offset, _, eocd_offset) = archive.central_directory();
+    let mut eocd = data[eocd_offset..].to_vec();
+    eocd[16..20].copy_from_slice(&(block_offset as u32).to_le_bytes());
+    Self {
+      entries: &data[..block_offset],
+      central_directory: &data[cd_offset..eocd_offset],
+      eocd,
+      digests: Default::default(),
+    }
+  }
+
+  fn digest(
+    &self,
+    algorithm: u32,
+  ) -> Option<Vec<u8>> {
+    let mut digests = self.digests.borrow_mut();
+    digests
+      .entry(algorithm)
+      .or_insert_with(|| {
+        let sections = [self.entries, self.central_directory, &self.eocd];
+        match algorithm {
+          0x0101 | 0x0103 | 0x0201 | 0x0301 => Some(chunked_digest::<Sha256>(&sections)),
+          0x0102 | 0x0104 | 0x0202 => Some(chunked_digest::<Sha512>(&sections)),
+          0x0421 | 0x0423 | 0x0425 => {
+            let data = sections.concat();
+            let mut digest = merkle_root(&data, VERITY_BLOCK_SIZE, &[]);
+            digest.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            Some(digest)
+          }
+          _ => None,
+        }
+      })
+      .clone()
+  }
+}
+
+// Digest of the 1 MiB chunks of the sections, each chunk hashed with its length.
+fn chunked_digest<D: Digest>(sections: &[&[u8]]) -> Vec<u8> {
+  let chunks = sections
+    .iter()
+    .flat_map(|section| section.chunks(CHUNK_SIZE))
+    .collect::<Vec<_>>();
+  let mut digest = D::new();
+  digest.update([0x5a]);
+  digest.update((chunks.len() as u32).to_le_bytes());
+  for chunk in chunks {
+    let mut chunk_digest = D::new();
+    chunk_digest.update([0xa5]);
+    chunk_digest.update((chunk.len() as u32).to_le_bytes());
+    chunk_digest.update(chunk);
+    digest.update(chunk_digest.finalize());
+  }
+  digest.finalize().to_vec()
+}
+
+// Root hash of the fs-verity style SHA-256 merkle tree of the data.
+fn merkle_root(
+  data: &[u8],
+  block_size: usize,
+  salt: &[u8],
+) -> Vec<u8> {
+  let hash_blocks = |level: &[u8]| {
+    let mut hashes = Vec::with_capacity(level.len() / block_size * 32 + 32);
+    let mut block = vec![0u8; block_size];
+    for chunk in level.chunks(block_size) {
+      block[..chunk.len()].copy_from_slice(chunk);
+      block[chunk.len()..].fill(0);
+      hashes.extend_from_slice(
+        &Sha256::new()
+          .chain_update(salt)
+          .chain_update(&block)
+          .finalize(),
+      );
+    }
+    hashes
+  };
+  let mut level = hash_blocks(data);
+  while level.len() > block_size {
+    level = hash_blocks(&level);
+  }
+  level.resize(block_size, 0);
+  hash_blocks(&level)
+}
+
+// A section of MANIFEST.MF or of a .SF file.
+struct ManifestSection<'a> {
+  raw: &'a [u8],
+  attributes: Vec<(String, String)>,
+}
+
+impl ManifestSection<'_> {
+  fn get(
+    &self,
+    name: &str,
+  ) -> Option<&str> {
+    self
+      .attributes
+      .iter()
+      .find(|(key, _)| key.eq_ignore_ascii_case(name))
+      .map(|(_, value)| value.as_str())
+  }
+
+  // (algorithm, digest) of the attributes named `<algorithm><suffix>`.
+  fn digests(
+    &self,
+    suffix: &str,
+  ) -> Vec<(&str, Vec<u8>)> {
+    self
+      .attributes
+      .iter()
+      .filter_map(|(key, value)| {
+        let algorithm = key.strip_suffix(suffix)?;
+        let digest = base64::engine::general_purpose::STANDARD
+          .decode(value)
+          .ok()?;
+        Some((algorithm, digest))
+      })
+      .collect()
+  }
+}
+
+// Sections end with an empty line, long values continue on lines starting with a space.
+fn manifest_sections(data: &[u8]) -> Vec<ManifestSection<'_>> {
+  let mut sections = Vec::new();
+  let mut section_start = 0;
+  let mut attributes: Vec<(String, String)> = Vec::new();
+  let mut offset = 0;
+  while offset < data.len() {
+    let line_end = data[offset..]
+      .iter()
+      .position(|&byte| byte == b'\n')
+      .map_or(data.len(), |position| offset + position + 1);
+    let line = String::from_utf8_lossy(&data[offset..line_end]);
+    let line = line.trim_end_matches(['\r', '\n']);
+    if line.is_empty() {
+      sections.push(ManifestSection {
+        raw: &data[section_start..line_end],
+        attributes: std::mem::take(&mut attributes),
+      });
+      section_start = line_end;
+    } else if let Some(continuation) = line.strip_prefix(' ') {
+      if let Some((_, value)) = attributes.last_mut() {
+        value.push_str(continuation);
+      }
+    } else if let Some((key, value)) = line.split_once(':') {
+      attributes.push((key.to_string(), value.trim_start().to_string()));
+    }
+    offset = line_end;
+  }
+  if section_start < data.len() {
+    sections.push(ManifestSection {
+      raw: &data[section_start..],
+      attributes,
+    });
+  }
+  sections
+}
+
+fn jar_digest(
+  algorithm: &str,
+  data: &[u8],
+) -> Option<Vec<u8>> {
+  let digest = match algorithm.to_ascii_uppercase().as_str() {
+    "SHA1" | "SHA-1" => Sha1::digest(data).to_vec(),
+    "SHA-256" => Sha256::digest(data).to_vec(),
+    "SHA-384" => Sha384::digest(data).to_vec(),
+    "SHA-512" => Sha512::digest(data).to_vec(),
+    _ => return None,
+  };
+  Some(digest)
+}
+
+// Whether the known digests match and there is at least one.
+fn jar_digests_match(
+  digests: &[(&str, Vec<u8>)],
+  data: &[u8],
+) -> bool {
+  let mut matched = false;
+  for (algorithm, expected) in digests {
+    match jar_digest(algorithm, data) {
+      Some(actual) if actual == *expected => matched = true,
+      Some(_) => return false,
+      None => {}
+    }
+  }
+  matched
+}
+
+fn is_signature_file(name: &str) -> bool {
+  let Some(file) = name.strip_prefix("META-INF/") else {
+    return false;
+  };
+  let upper = file.to_ascii_uppercase();
+  !file.contains('/')
+    && (upper == "MANIFEST.MF"
+      || upper.starts_with("SIG-")
+      || [".SF", ".RSA", ".DSA", ".EC"]
+        .iter()
+        .any(|extension| upper.ends_with(extension)))
+}
+
+fn v1_signature(archive: &ApkArchive) -> Result<Option<V1Signature>, ParseError> {
+  let Some(manifest_entry) = archive.by_name("META-INF/MANIFEST.MF") else {
+    return Ok(None);
+  };
+  let manifest = archive.extract(manifest_entry)?;
+  let sections = manifest_sections(&manifest);
+  let mut signature = V1Signature::default();
+
+  for entry in archive.entries() {
+    let Some(name) = entry
+      .name
+      .strip_prefix("META-INF/")
+      .filter(|file| !file.contains('/'))
+      .and_then(|file| file.strip_suffix(".SF"))
+    else {
+      continue;
+    };
+    let sf = archive.extract(entry)?;
+    let sf_sections = manifest_sections(&sf);
+    let Some(main) = sf_sections.first() else {
+      continue;
+    };
+    let apk_signed_versions = main
+      .get("X-Android-APK-Signed")
+      .map(|versions| {
+        versions
+          .split(',')
+          .filter_map(|version| version.trim().parse().ok())
+          .collect()
+      })
+      .unwrap_or_default();
+
+    // Without a matching digest of the whole manifest every entry section has to match
+    let manifest_verified = jar_digests_match(&main.digests("-Digest-Manifest"), &manifest)
+      || sections.iter().skip(1).all(|section| {
+        section.get("Name").is_some_and(|entry_name| {
+          sf_sections
+            .iter()
+            .find(|sf_section| sf_section.get("Name") == Some(entry_name))
+            .is_some_and(|sf_section| {
+              jar_digests_match(&sf_section.digests("-Digest"), section.raw)
+            })
+        })
+      });
+
+    let mut certificates = Vec::new();
+    for extension in ["RSA", "DSA", "EC"] {
+      if let Some(block) = archive.by_name(&format!("META-INF/{}.{}", name, extension)) {
+        certificates = pkcs7_certificates(&archive.extract(block)?)?;
+        break;
+      }
+    }
+    signature.signers.push(V1Signer {
+      name: name.to_string(),
+      certificates,
+      apk_signed_versions,
+      manifest_verified,
+    });
+  }
+
+  let mut listed = Vec::new();
+  for section in sections.iter().skip(1) {
+    let Some(name) = section.get("Name") else {
+      continue;
+    };
+    listed.push(name);
+    let Some(entry) = archive.by_name(name) else {
+      signature.missing_entries.push(name.to_string());
+      continue;
+    };
+    if !jar_digests_match(&section.digests("-Digest"), &archive.extract(entry)?) {
+      signature.mismatched_entries.push(name.to_string());
+    }
+  }
+  for entry in archive.entries() {
+    let unlisted = !entry.name.ends_with('/')
+      && !is_signature_file(&entry.name)
+      && !listed.contains(&entry.name.as_str())
+      && !signature.unlisted_entries.contains(&entry.name);
+    if unlisted {
+      signature.unlisted_entries.push(entry.name.clone());
+    }
+  }
+  Ok(Some(signature))
+}
+
+/// An APK Signature Scheme v4 `.idsig` file, which signs the merkle tree of the whole APK and
+/// the content digest of its v2 or v3 signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct V4Signature {
+  pub version: u32,
+  /// 1 for SHA-256.
+  pub hash_algorithm: u32,
+  pub log2_block_size: u8,
+  pub salt: Vec<u8>,
+  pub root_hash: Vec<u8>,
+  pub apk_digest: Vec<u8>,
+  pub certificate: Certificate,
+  pub additional_data: Vec<u8>,
+  pub public_key: Vec<u8>,
+  pub signature_algorithm: u32,
+  /// Ids of the extra signing info blocks of version 3 files.
+  pub signing_info_block_ids: Vec<u32>,
+}
+
+/// Outcome of checking a v4 signature against an APK.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct V4Verification {
+  /// The merkle tree root hash matches the APK.
+  pub root_hash_verified: bool,
+  /// The APK digest is one of the v2 or v3 content digests of the APK, and they match.
+  pub apk_digest_verified: bool,
+}
+
+impl V4Verification {
+  /// Whether both match, the signature over them is not checked.
+  pub fn digests_match(&self) -> bool {
+    self.root_hash_verified && self.apk_digest_verified
+  }
+}
+
+impl V4Signature {
+  pub fn parse(idsig: &[u8]) -> Result<Self, ParseError> {
+    let mut input = Input::new(idsig);
+    let version = input.u32()?;
+    if version != 2 && version != 3 {
+      return Err(ParseError::Signature(format!(
+        "unsupported v4 signature version {}",
+        version
+      )));
+    }
+    let mut hashing_info = Input::new(input.prefixed()?);
+    let hash_algorithm = hashing_info.u32()?;
+    let log2_block_size = hashing_info.u8()?;
+    let salt = hashing_info.prefixed()?.to_vec();
+    let root_hash = hashing_info.prefixed()?.to_vec();
+
+    let mut signing_infos = Input::new(input.prefixed()?);
+    let apk_digest = signing_infos.prefixed()?.to_vec();
+    let certificate = Certificate::from_der(signing_infos.prefixed()?)?;
+    let additional_data = signing_infos.prefixed()?.to_vec();
+    let public_key = signing_infos.prefixed()?.to_vec();
+    let signature_algorithm = signing_infos.u32()?;
+    signing_infos.prefixed()?;
+    let mut signing_info_block_ids = Vec::new();
+    while !signing_infos.is_empty() {
+      let mut block = Input::new(signing_infos.prefixed()?);
+      signing_info_block_ids.push(block.u32()?);
+    }
+
+    Ok(Self {
+      version,
+      hash_algorithm,
+      log2_block_size,
+      salt,
+      root_hash,
+      apk_digest,
+      certificate,
+      additional_data,
+      public_key,
+      signature_algorithm,
+      signing_info_block_ids,
+    })
+  }
+
+  pub fn verify(
+    &self,
+    archive: &ApkArchive,
+  ) -> Result<V4Verification, ParseError> {
+    let root_hash_verified = self.hash_algorithm == 1
+      && (10..=16).contains(&self.log2_block_size)
+      && merkle_root(archive.data(), 1 << self.log2_block_size, &self.salt) == self.root_hash;
+    let signatures = ApkSignatures::from_archive(archive)?;
+    let apk_digest_verified = signatures
+      .v3
+      .iter()
+      .chain(&signatures.v2)
+      .flat_map(|signer| &signer.digests)
+      .any(|digest| digest.verified() && digest.expected == self.apk_digest);
+    Ok(V4Verification {
+      root_hash_verified,
+      apk_digest_verified,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{stored, zip_with};
+  use anyhow::Result;
+
+  fn der(
+    tag: u8,
+    content: &[u8],
+  ) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    match content.len() {
+      len @ 0..=0x7f => encoded.push(len as u8),
+      len @ 0x80..=0xff => encoded.extend([0x81, len as u8]),
+      len => encoded.extend([0x82, (len >> 8) as u8, len as u8]),
+    }
+    encoded.extend_from_slice(content);
+    encoded
+  }
+
+  fn oid(encoded: &[u8]) -> Vec<u8> {
+    der(TAG_OID, encoded)
+  }
+
+  fn name(common_name: &str) -> Vec<u8> {
+    let attribute = |oid_bytes: &[u8], value: &str| {
+      der(
+        TAG_SET,
+        &der(
+          TAG_SEQUENCE,
+          &[oid(oid_bytes), der(0x13, value.as_bytes())].concat(),
+        ),
+      )
+    };
+    der(
+      TAG_SEQUENCE,
+      &[
+        attribute(&[0x55, 0x04, 0x06], "US"),
+        attribute(&[0x55, 0x04, 0x0a], "Android"),
+        attribute(&[0x55, 0x04, 0x03], common_name),
+      ]
+      .concat(),
+    )
+  }
+
+  // A self signed certificate whose "public key" is just the given bytes.
+  fn test_certificate(
+    common_name: &str,
+    key: &[u8],
+  ) -> Vec<u8> {
+    // sha256WithRSAEncryption and rsaEncryption
+    let sha256_rsa = der(
+      TAG_SEQUENCE,
+      &oid(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b]),
+    );
+    let rsa = der(
+      TAG_SEQUENCE,
+      &[
+        oid(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01]),
+        vec![0x05, 0x00],
+      ]
+      .concat(),
+    );
+    let tbs = der(
+      TAG_SEQUENCE,
+      &[
+        der(TAG_CONTEXT_0, &der(TAG_INTEGER, &[2])),
+        der(TAG_INTEGER, &[0x00, 0x9c, 0x01]),
+        sha256_rsa.clone(),
+        name(common_name),
+        der(
+          TAG_SEQUENCE,
+          &[
+            der(TAG_UTC_TIME, b"080229013347Z"),
+            der(TAG_GENERALIZED_TIME, b"20500717013347Z"),
+          ]
+          .concat(),
+        ),
+        name(common_name),
+        der(
+          TAG_SEQUENCE,
+          &[rsa, der(TAG_BIT_STRING, &[&[0], key].concat())].concat(),
+        ),
+      ]
+      .concat(),
+    );
+    der(
+      TAG_SEQUENCE,
+      &[tbs, sha256_rsa, der(TAG_BIT_STRING, &[0, 1, 2, 3])].concat(),
+    )
+  }
+
+  fn public_key(certificate: &[u8]) -> Vec<u8> {
+    Certificate::from_der(certificate).unwrap().public_key
+  }
+
+  fn prefixed(value: &[u8]) -> Vec<u8> {
+    [&(value.len() as u32).to_le_bytes(), value].concat()
+  }
+
+  fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    prefixed(
+      &items
+        .iter()
+        .map(|item| prefixed(item))
+        .collect::<Vec<_>>()
+        .concat(),
+    )
+  }
+
+  fn b64(digest: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(digest)
+  }
+
+  // Inserts a signing block with one v2 and one v3 signer, the latter with a lineage.
+  fn sign(
+    unsigned: &[u8],
+    certificate: &[u8],
+    old_certificate: &[u8],
+  ) -> Result<Vec<u8>> {
+    let archive = ApkArchive::new(unsigned.to_vec())?;
+    let (cd_offset, _, eocd_offset) = archive.central_directory();
+    let content = SignedContent::new(&archive, cd_offset);
+    let digests = |algorithms: &[u32]| {
+      algorithms
+        .iter()
+        .map(|algorithm| {
+          [
+            algorithm.to_le_bytes().to_vec(),
+            prefixed(&content.digest(*algorithm).unwrap()),
+          ]
+          .concat()
+        })
+        .collect::<Vec<_>>()
+    };
+    let signatures = |algorithms: &[u32]| {
+      algorithms
+        .iter()
+        .map(|algorithm| [algorithm.to_le_bytes().to_vec(), prefixed(b"sig")].concat())
+        .collect::<Vec<_>>()
+    };
+
+    let v2_signed_data = [
+      sequence(&digests(&[0x0103])),
+      sequence(&[certificate.to_vec()]),
+      sequence(&[]),
+    ]
+    .concat();
+    let v2_signer = [
+      prefixed(&v2_signed_data),
+      sequence(&signatures(&[0x0103])),
+      prefixed(&public_key(certificate)),
+    ]
+    .concat();
+
+    let lineage_node = |node_certificate: &[u8], flags: u32| {
+      [
+        prefixed(&[prefixed(node_certificate), 0x0103u32.to_le_bytes().to_vec()].concat()),
+        flags.to_le_bytes().to_vec(),
+        0x0103u32.to_le_bytes().to_vec(),
+        prefixed(b"sig"),
+      ]
+      .concat()
+    };
+    let lineage = [
+      1u32.to_le_bytes().to_vec(),
+      prefixed(&lineage_node(old_certificate, 0x1f)),
+      prefixed(&lineage_node(certificate, 0x1f)),
+    ]
+    .concat();
+    let v3_algorithms = [0x0104, 0x0421];
+    let v3_signed_data = [
+      sequence(&digests(&v3_algorithms)),
+      sequence(&[certificate.to_vec()]),
+      28u32.to_le_bytes().to_vec(),
+      0x7fffffffu32.to_le_bytes().to_vec(),
+      sequence(&[[PROOF_OF_ROTATION_ATTR_ID.to_le_bytes().to_vec(), lineage].concat()]),
+    ]
+    .concat();
+    let v3_signer = [
+      prefixed(&v3_signed_data),
+      28u32.to_le_bytes().to_vec(),
+      0x7fffffffu32.to_le_bytes().to_vec(),
+      sequence(&signatures(&v3_algorithms)),
+      prefixed(&public_key(certificate)),
+    ]
+    .concat();
+
+    let pair = |id: u32, value: &[u8]| {
+      [
+        ((value.len() + 4) as u64).to_le_bytes().to_vec(),
+        id.to_le_bytes().to_vec(),
+        value.to_vec(),
+      ]
+      .concat()
+    };
+    let pairs = [
+      pair(V2_BLOCK_ID, &sequence(&[v2_signer])),
+      pair(V3_BLOCK_ID, &sequence(&[v3_signer])),
+    ]
+    .concat();
+    let size = (pairs.len() + 8 + 16) as u64;
+    let block = [
+      size.to_le_bytes().to_vec(),
+      pairs,
+      size.to_le_bytes().to_vec(),
+      SIGNING_BLOCK_MAGIC.to_vec(),
+    ]
+    .concat();
+
+    let mut signed = [&unsigned[..cd_offset], &block, &unsigned[cd_offset..]].concat();
+    let eocd = eocd_offset + block.len();
+    signed[eocd + 16..eocd + 20].copy_from_slice(&((cd_offset + block.len()) as u32).to_le_bytes());
+    Ok(signed)
+  }
+
+  fn idsig(
+    apk: &[u8],
+    apk_digest: &[u8],
+    certificate: &[u8],
+  ) -> Vec<u8> {
+    let salt = b"salt";
+    let hashing_info = [
+      1u32.to_le_bytes().to_vec(),
+      vec![12],
+      prefixed(salt),
+      prefixed(&merkle_root(apk, 4096, salt)),
+    ]
+    .concat();
+    let signing_info = [
+      prefixed(apk_digest),
+      prefixed(certificate),
+      prefixed(b""),
+      prefixed(&public_key(certificate)),
+      0x0103u32.to_le_bytes().to_vec(),
+      prefixed(b"sig"),
+    ]
+    .concat();
+    [
+      2u32.to_le_bytes().to_vec(),
+      prefixed(&hashing_info),
+      prefixed(&signing_info),
+    ]
+    .concat()
+  }
+
+  // known-answer.apk was signed by JDK jarsigner (v1) and by sign_v2.py, written from the v2
+  // specification, with the 2048 bit RSA key of ks.p12. sign_v2.py lists the commands, its
+  // inputs are next to it. The expected values come from those tools, not from this module.
+  #[test]
+  fn test_known_answer() -> Result<()> {
+    let parser = crate::parser::Parser::from_file("../data/signed/known-answer.apk".as_ref())?;
+    let signatures = parser.signatures()?;
+    assert!(signatures.digests_match());
+    assert_eq!(signatures.signing_block_ids, [V2_BLOCK_ID]);
+
+    let v1 = signatures.v1.as_ref().unwrap();
+    assert!(v1.digests_match());
+    assert_eq!(v1.signers[0].name, "CERT");
+    let certificate = &v1.signers[0].certificates[0];
+    assert_eq!(certificate.subject, "CN=Known Answer,O=bxmlrs,C=US");
+    assert_eq!(certificate.serial, "3ff45416589170b5");
+    assert_eq!(certificate.not_before, "2024-01-01 14:20:17 UTC");
+    assert_eq!(certificate.not_after, "2051-05-19 14:20:17 UTC");
+    assert_eq!(certificate.signature_algorithm, "SHA256withRSA");
+    // keytool -list -v
+    assert_eq!(
+      certificate.sha256_fingerprint(),
+      "64d09624e319e37e611466b341572cadb45e333ab8893d004f748f56926ab2b4"
+    );
+
+    let v2 = &signatures.v2[0];
+    assert_eq!(v2.certificate(), Some(certificate));
+    assert_eq!(v2.digests.len(), 1);
+    assert_eq!(v2.digests[0].algorithm, 0x0103);
+    assert_eq!(
+      hex::encode(v2.digests[0].actual.as_ref().unwrap()),
+      "b0b7b903e4c199f7b3785255491f9599a707e968ea54faf4390bdf781dc29b68"
+    );
+    assert!(v2.digests[0].verified());
+
+    // A footer size smaller than the footer itself is rejected instead of slicing backwards
+    let mut apk = std::fs::read("../data/signed/known-answer.apk")?;
+    let (cd_offset, _, _) = ApkArchive::new(apk.clone())?.central_directory();
+    apk[cd_offset - 24..cd_offset - 16].copy_from_slice(&16u64.to_le_bytes());
+    assert!(matches!(
+      ApkSignatures::from_archive(&ApkArchive::new(apk)?),
+      Err(ParseError::Signature(_))
+    ));
+    Ok(())
+  }
+
+  #[test]
+  fn test_signatures() -> Result<()> {
+    let certificate = test_certificate("Test, Inc.", b"new key");
+    let old_certificate = test_certificate("Old", b"old key");
+    let parsed = Certificate::from_der(&certificate)?;
+    assert_eq!(parsed.subject, "CN=Test\\, Inc.,O=Android,C=US");
+    assert_eq!(parsed.issuer, parsed.subject);
+    assert_eq!(parsed.serial, "9c01");
+    assert_eq!(parsed.not_before, "2008-02-29 01:33:47 UTC");
+    assert_eq!(parsed.not_after, "2050-07-17 01:33:47 UTC");
+    assert_eq!(parsed.signature_algorithm, "SHA256withRSA");
+    assert_eq!(parsed.public_key_algorithm, "RSA");
+    assert_eq!(
+      parsed.sha256_fingerprint(),
+      hex::encode(Sha256::digest(&certificate))
+    );
+
+    // v1 signature over two entries
+    let manifest = std::fs::read("../data/xml/AndroidManifest.xml")?;
+    let classes = b"dex\n035\0".repeat(1000);
+    let manifest_mf = format!(
+      "Manifest-Version: 1.0\r\nCreated-By: test\r\n\r\n\
+       Name: AndroidManifest.xml\r\nSHA-256-Digest: {}\r\n\r\n\
+       Name: classes.dex\r\nSHA-256-Digest: {}\r\n\r\n",
+      b64(&Sha256::digest(&manifest)),
+      b64(&Sha256::digest(&classes)),
+    );
+    let cert_sf = format!(
+      "Signature-Version: 1.0\r\nSHA-256-Digest-Manifest: {}\r\n\
+       X-Android-APK-Signed: 2, 3\r\n\r\n",
+      b64(&Sha256::digest(manifest_mf.as_bytes())),
+    );
+    // ContentInfo { signedData, [0] SignedData { version, algorithms, content, [0] certs } }
+    let cert_rsa = der(
+      TAG_SEQUENCE,
+      &[
+        oid(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02]),
+        der(
+          TAG_CONTEXT_0,
+          &der(
+            TAG_SEQUENCE,
+            &[
+              der(TAG_INTEGER, &[1]),
+              der(TAG_SET, &[]),
+              der(
+                TAG_SEQUENCE,
+                &oid(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01]),
+              ),
+              der(TAG_CONTEXT_0, &certificate),
+              der(TAG_SET, &[]),
+            ]
+            .concat(),
+          ),
+        ),
+      ]
+      .concat(),
+    );
+    let unsigned = zip_with(
+      &[
+        ("AndroidManifest.xml", &manifest),
+        ("classes.dex", &classes),
+        ("META-INF/MANIFEST.MF", manifest_mf.as_bytes()),
+        ("META-INF/CERT.SF", cert_sf.as_bytes()),
+        ("META-INF/CERT.RSA", &cert_rsa),
+      ],
+      |_| (stored(), 0),
+    )?;
+
+    let v1_only = ApkSignatures::from_archive(&ApkArchive::new(unsigned.clone())?)?;
+    let v1 = v1_only.v1.as_ref().unwrap();
+    assert!(v1.digests_match());
+    assert_eq!(v1.signers[0].name, "CERT");
+    assert_eq!(v1.signers[0].certificates, std::slice::from_ref(&parsed));
+    assert_eq!(v1.signers[0].apk_signed_versions, [2, 3]);
+    // The v2 and v3 blocks were stripped
+    assert_eq!(v1_only.stripped_schemes(), [2, 3]);
+    assert!(!v1_only.digests_match());
+
+    let apk = sign(&unsigned, &certificate, &old_certificate)?;
+    let parser = crate::parser::Parser::from_bytes(&apk)?;
+    let signatures = parser.signatures()?;
+    assert!(signatures.digests_match());
+    assert_eq!(signatures.signing_block_ids, [V2_BLOCK_ID, V3_BLOCK_ID]);
+    assert_eq!(signatures.v2.len(), 1);
+    assert_eq!(signatures.v2[0].certificate(), Some(&parsed));
+    assert_eq!(signatures.v2[0].min_sdk, None);
+    let v3 = &signatures.v3[0];
+    assert_eq!((v3.min_sdk, v3.max_sdk), (Some(28), Some(0x7fffffff)));
+    assert_eq!(v3.digests.len(), 2);
+    assert!(v3.digests.iter().all(ContentDigest::verified));
+    let lineage = v3
+      .lineage
+      .iter()
+      .map(|node| node.certificate.subject.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      lineage,
+      ["CN=Old,O=Android,C=US", "CN=Test\\, Inc.,O=Android,C=US"]
+    );
+
+    // v4 signs the merkle tree of the whole APK and the strongest v3 digest
+    let v4 = V4Signature::parse(&idsig(&apk, &v3.digests[0].expected, &certificate))?;
+    assert_eq!(v4.certificate, parsed);
+    assert!(parser
+      .verify_v4(&idsig(&apk, &v3.digests[0].expected, &certificate))?
+      .digests_match());
+    let wrong_digest = idsig(&apk, &[0; 64], &certificate);
+    let verification = parser.verify_v4(&wrong_digest)?;
+    assert!(verification.root_hash_verified && !verification.apk_digest_verified);
+
+    // Changing an entry breaks every scheme
+    let mut tampered = apk.clone();
+    let offset = tampered
+      .windows(8)
+      .position(|window| window == b"dex\n035\0")
+      .unwrap();
+    tampered[offset + 8] = b'X';
+    let tampered = ApkSignatures::from_archive(&ApkArchive::new(tampered)?)?;
+    assert_eq!(
+      tampered.v1.as_ref().unwrap().mismatched_entries,
+      ["classes.dex"]
+    );
+    assert!(!tampered.v2[0].digests_match());
+    assert!(!tampered.v3[0].digests_match());
+    assert!(!tampered.digests_match());
+    Ok(())
+  }
+}
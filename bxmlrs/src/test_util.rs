@@ -0,0 +1,233 @@
+// Builders of the APKs, binary XML documents and protobuf messages the tests are made of.
+
+use crate::arsc_writer::{begin_chunk, end_chunk, push_u16, push_u32, write_string_pool};
+use crate::nom_parser::{ChunkType, ParseError};
+use std::io::{Cursor, Write};
+use zip::result::ZipResult;
+use zip::write::FileOptions;
+
+/// A ZIP of the given (name, data) entries, deflated.
+pub(crate) fn zip(entries: &[(&str, &[u8])]) -> ZipResult<Vec<u8>> {
+  zip_with(entries, |_| (FileOptions::default(), 0))
+}
+
+/// A ZIP whose entries get the options and data alignment `options` returns for their name.
+pub(crate) fn zip_with(
+  entries: &[(&str, &[u8])],
+  options: impl Fn(&str) -> (FileOptions, u16),
+) -> ZipResult<Vec<u8>> {
+  let mut zip_writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+  for (name, data) in entries {
+    let (file_options, alignment) = options(name);
+    zip_writer.start_file_aligned(*name, file_options, alignment)?;
+    zip_writer.write_all(data)?;
+  }
+  Ok(zip_writer.finish()?.into_inner())
+}
+
+pub(crate) fn stored() -> FileOptions {
+  FileOptions::default().compression_method(zip::CompressionMethod::Stored)
+}
+
+/// Collects the fields a protobuf message is encoded from.
+pub(crate) fn message(build: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+  let mut out = Vec::new();
+  build(&mut out);
+  out
+}
+
+/// The value of an attribute of a binary XML element.
+pub(crate) enum AttributeValue<'a> {
+  String(&'a str),
+  /// A typed value, `ResType` and data.
+  Data(u8, u32),
+}
+
+/// An attribute of a binary XML element, with the framework resource id aapt2 links it to.
+pub(crate) struct XmlAttribute<'a> {
+  pub(crate) name: &'a str,
+  pub(crate) res_id: Option<u32>,
+  pub(crate) value: AttributeValue<'a>,
+}
+
+/// An element of a binary XML document built by `binary_xml`.
+pub(crate) struct XmlElement<'a> {
+  pub(crate) name: &'a str,
+  pub(crate) attributes: Vec<XmlAttribute<'a>>,
+  pub(crate) children: Vec<XmlElement<'a>>,
+  /// Character data ahead of the children, written as a CDATA chunk.
+  pub(crate) text: Option<&'a str>,
+}
+
+impl<'a> XmlElement<'a> {
+  pub(crate) fn new(name: &'a str) -> Self {
+    Self {
+      name,
+      attributes: Vec::new(),
+      children: Vec::new(),
+      text: None,
+    }
+  }
+
+  pub(crate) fn attribute(
+    mut self,
+    name: &'a str,
+    res_id: Option<u32>,
+    value: AttributeValue<'a>,
+  ) -> Self {
+    self.attributes.push(XmlAttribute {
+      name,
+      res_id,
+      value,
+    });
+    self
+  }
+
+  pub(crate) fn child(
+    mut self,
+    child: XmlElement<'a>,
+  ) -> Self {
+    self.children.push(child);
+    self
+  }
+
+  pub(crate) fn text(
+    mut self,
+    text: &'a str,
+  ) -> Self {
+    self.text = Some(text);
+    self
+  }
+}
+
+const ANDROID_NS: &str = "http://schemas.android.com/apk/res/android";
+const NONE: u32 = 0xFFFFFFFF;
+
+/// The binary XML document aapt2 would compile `root` into, declaring the android namespace.
+pub(crate) fn binary_xml(root: &XmlElement) -> Result<Vec<u8>, ParseError> {
+  // Attribute names linked to a resource id come first, in the order of the resource map
+  let mut strings = Vec::new();
+  let mut res_ids = Vec::new();
+  walk(root, &mut |element| {
+    for attribute in &element.attributes {
+      if let Some(res_id) = attribute.res_id {
+        if !strings.iter().any(|string| string == attribute.name) {
+          strings.push(attribute.name.to_string());
+          res_ids.push(res_id);
+        }
+      }
+    }
+  });
+  for string in ["android", ANDROID_NS] {
+    intern(&mut strings, string);
+  }
+  walk(root, &mut |element| {
+    intern(&mut strings, element.name);
+    for attribute in &element.attributes {
+      intern(&mut strings, attribute.name);
+      if let AttributeValue::String(value) = attribute.value {
+        intern(&mut strings, value);
+      }
+    }
+    if let Some(text) = element.text {
+      intern(&mut strings, text);
+    }
+  });
+
+  let mut out = Vec::new();
+  let xml_start = begin_chunk(&mut out, ChunkType::XML, 8);
+  write_string_pool(&mut out, &strings, &[])?;
+  if !res_ids.is_empty() {
+    let map_start = begin_chunk(&mut out, ChunkType::XML_RESOURCE_MAP, 8);
+    for res_id in res_ids {
+      push_u32(&mut out, res_id);
+    }
+    end_chunk(&mut out, map_start);
+  }
+  let index = |string: &str| strings.iter().position(|s| s == string).unwrap() as u32;
+  let namespace = |out: &mut Vec<u8>, typ| {
+    let start = begin_chunk(out, typ, 16);
+    out.extend_from_slice(&[0; 4]);
+    push_u32(out, NONE);
+    push_u32(out, index("android"));
+    push_u32(out, index(ANDROID_NS));
+    end_chunk(out, start);
+  };
+  namespace(&mut out, ChunkType::XML_START_NAMESPACE);
+  write_element(&mut out, root, &index);
+  namespace(&mut out, ChunkType::XML_END_NAMESPACE);
+  end_chunk(&mut out, xml_start);
+  Ok(out)
+}
+
+fn walk<'a>(
+  element: &XmlElement<'a>,
+  visit: &mut impl FnMut(&XmlElement<'a>),
+) {
+  visit(element);
+  for child in &element.children {
+    walk(child, visit);
+  }
+}
+
+fn intern(
+  strings: &mut Vec<String>,
+  string: &str,
+) {
+  if !strings.iter().any(|s| s == string) {
+    strings.push(string.to_string());
+  }
+}
+
+fn write_element(
+  out: &mut Vec<u8>,
+  element: &XmlElement,
+  index: &impl Fn(&str) -> u32,
+) {
+  let start = begin_chunk(out, ChunkType::XML_START_ELEMENT, 16);
+  out.extend_from_slice(&[0; 4]);
+  push_u32(out, NONE);
+  push_u32(out, NONE);
+  push_u32(out, index(element.name));
+  push_u16(out, 20);
+  push_u16(out, 20);
+  push_u16(out, element.attributes.len() as u16);
+  out.extend_from_slice(&[0; 6]);
+  for attribute in &element.attributes {
+    let (data_type, data) = match attribute.value {
+      AttributeValue::String(value) => (crate::nom_parser::ResType::STRING, index(value)),
+      AttributeValue::Data(data_type, data) => (data_type, data),
+    };
+    push_u32(out, NONE);
+    push_u32(out, index(attribute.name));
+    push_u32(out, NONE);
+    push_u16(out, 8);
+    out.push(0);
+    out.push(data_type);
+    push_u32(out, data);
+  }
+  end_chunk(out, start);
+
+  if let Some(text) = element.text {
+    let cdata = begin_chunk(out, ChunkType::XML_CDATA, 16);
+    out.extend_from_slice(&[0; 4]);
+    push_u32(out, NONE);
+    push_u32(out, index(text));
+    push_u16(out, 8);
+    out.push(0);
+    out.push(crate::nom_parser::ResType::STRING);
+    push_u32(out, index(text));
+    end_chunk(out, cdata);
+  }
+
+  for child in &element.children {
+    write_element(out, child, index);
+  }
+
+  let end = begin_chunk(out, ChunkType::XML_END_ELEMENT, 16);
+  out.extend_from_slice(&[0; 4]);
+  push_u32(out, NONE);
+  push_u32(out, NONE);
+  push_u32(out, index(element.name));
+  end_chunk(out, end);
+}
@@ -0,0 +1,519 @@
+use crate::arsc_parser::{Arsc, EntryValue, Package, TypeEntry, Value};
+use crate::attributes;
+use crate::nom_parser::{ParseError, ResType};
+use quick_xml::escape::partial_escape;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::path::Path;
+
+// Special names of the items of an `attr` bag.
+pub(crate) const ATTR_TYPE: u32 = 0x0100_0000;
+pub(crate) const ATTR_MIN: u32 = 0x0100_0001;
+pub(crate) const ATTR_MAX: u32 = 0x0100_0002;
+const ATTR_L10N: u32 = 0x0100_0003;
+// Plural quantities, stored as the item names of a `plurals` bag.
+pub(crate) const PLURALS: [(u32, &str); 6] = [
+  (0x0100_0004, "other"),
+  (0x0100_0005, "zero"),
+  (0x0100_0006, "one"),
+  (0x0100_0007, "two"),
+  (0x0100_0008, "few"),
+  (0x0100_0009, "many"),
+];
+
+// Bits of the ATTR_TYPE value describing which formats an attribute accepts.
+const ATTR_FORMATS: [(u32, &str); 8] = [
+  (0x0001, "reference"),
+  (0x0002, "string"),
+  (0x0004, "integer"),
+  (0x0008, "boolean"),
+  (0x0010, "color"),
+  (0x0020, "float"),
+  (0x0040, "dimension"),
+  (0x0080, "fraction"),
+];
+const ATTR_TYPE_ANY: u32 = 0xFFFF;
+const ATTR_TYPE_FLAGS: u32 = 0x2_0000;
+
+const DIMENSION_UNITS: [&str; 6] = ["px", "dp", "sp", "pt", "in", "mm"];
+const FRACTION_UNITS: [&str; 2] = ["%", "%p"];
+const RADIX_MULTIPLIERS: [f32; 4] = [
+  1.0 / (1u32 << 8) as f32,
+  1.0 / (1u32 << 15) as f32,
+  1.0 / (1u32 << 23) as f32,
+  1.0 / (1u32 << 31) as f32,
+];
+
+/// Decodes the values resources of a resource table back into `res/values*/*.xml` files,
+/// the way apktool lays them out.
+pub struct ValuesDecoder<'a, 'barsc> {
+  arsc: &'a Arsc<'barsc>,
+}
+
+impl<'a, 'barsc> ValuesDecoder<'a, 'barsc> {
+  pub fn new(arsc: &'a Arsc<'barsc>) -> Self {
+    Self { arsc }
+  }
+
+  /// Decodes every values resource of the table. The result maps relative paths such as
+  /// `res/values-de/strings.xml` to the XML content of the file.
+  pub fn decode(&self) -> Result<BTreeMap<String, Vec<u8>>, ParseError> {
+    let mut writers: BTreeMap<String, Writer<Cursor<Vec<u8>>>> = BTreeMap::new();
+
+    let mut package_ids = self.arsc.packages().keys().copied().collect::<Vec<_>>();
+    package_ids.sort_unstable();
+    for package_id in package_ids {
+      let package = &self.arsc.packages()[&package_id];
+      for type_chunk in &package.type_chunks {
+        let Some(type_name) = package.type_name(type_chunk.type_id) else {
+          continue;
+        };
+        let qualifier = type_chunk.res_config().qualifier();
+        let values_dir = if qualifier.is_empty() {
+          "values".to_string()
+        } else {
+          format!("values-{}", qualifier)
+        };
+
+        for entry in type_chunk.entries.iter().flatten() {
+          let Some(file_name) = self.values_file(type_name, entry) else {
+            continue;
+          };
+          let path = format!("res/{}/{}", values_dir, file_name);
+          if !writers.contains_key(&path) {
+            writers.insert(path.clone(), Self::start_resources()?);
+          }
+          let xml_writer = writers.get_mut(&path).unwrap();
+          self.write_entry(xml_writer, package, type_name, entry)?;
+        }
+      }
+    }
+
+    writers
+      .into_iter()
+      .map(|(path, mut xml_writer)| {
+        xml_writer
+          .write_event(Event::End(BytesEnd::new("resources")))
+          .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+        Ok((path, xml_writer.into_inner().into_inner()))
+      })
+      .collect()
+  }
+
+  /// Decodes the table and writes the files below `out_dir`, creating directories as needed.
+  pub fn write_to_dir(
+    &self,
+    out_dir: &Path,
+  ) -> Result<(), ParseError> {
+    for (path, content) in self.decode()? {
+      let file_path = out_dir.join(path);
+      if let Some(parent) = file_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| ParseError::File(e.to_string()))?;
+      }
+      std::fs::write(&file_path, content).map_err(|e| ParseError::File(e.to_string()))?;
+    }
+    Ok(())
+  }
+
+  fn start_resources() -> Result<Writer<Cursor<Vec<u8>>>, ParseError> {
+    let mut xml_writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 4);
+    xml_writer
+      .write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))
+      .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+    xml_writer
+      .write_event(Event::Start(BytesStart::new("resources")))
+      .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+    Ok(xml_writer)
+  }
+
+  // File a values entry belongs to, or None for file based resources such as layouts.
+  fn values_file(
+    &self,
+    type_name: &str,
+    entry: &TypeEntry,
+  ) -> Option<String> {
+    match (type_name, &entry.value) {
+      ("style" | "array" | "plurals" | "attr", EntryValue::Complex { .. }) => {
+        Some(format!("{}s.xml", type_name))
+      }
+      ("string", EntryValue::Simple(_)) => Some("strings.xml".to_string()),
+      // Anything else stored as a path below res/ is a file, not a value
+      (_, EntryValue::Simple(Value::String(path))) if path.starts_with("res/") => None,
+      (_, EntryValue::Simple(_)) => Some(format!("{}s.xml", type_name)),
+      _ => None,
+    }
+  }
+
+  fn write_entry(
+    &self,
+    xml_writer: &mut Writer<Cursor<Vec<u8>>>,
+    package: &Package,
+    type_name: &str,
+    entry: &TypeEntry,
+  ) -> Result<(), ParseError> {
+    match &entry.value {
+      EntryValue::Simple(value) => {
+        let elem_name = match type_name {
+          "string" | "color" | "dimen" | "bool" | "integer" => type_name,
+          _ => "item",
+        };
+        let mut elem = BytesStart::new(elem_name);
+        if elem_name == "item" {
+          elem.push_attribute(("type", type_name));
+        }
+        elem.push_attribute(("name", entry.key.as_str()));
+        let text = self.format_value(package, value);
+        // ids only carry a value when they alias another resource
+        if type_name == "id"
+          && !matches!(
+            value,
+            Value::Data {
+              data_type: ResType::REFERENCE,
+              ..
+            }
+          )
+        {
+          return write(xml_writer, Event::Empty(elem));
+        }
+        write_text_element(xml_writer, elem, &text)
+      }
+      EntryValue::Complex { parent, items } => match type_name {
+        "style" => {
+          let mut elem = BytesStart::new("style");
+          elem.push_attribute(("name", entry.key.as_str()));
+          if *parent != 0 {
+            elem.push_attribute((
+              "parent",
+              self.format_reference(package, "@", *parent).as_str(),
+            ));
+          }
+          if items.is_empty() {
+            return write(xml_writer, Event::Empty(elem));
+          }
+          write(xml_writer, Event::Start(elem))?;
+          for (name, value) in items {
+            let mut item = BytesStart::new("item");
+            item.push_attribute(("name", self.attribute_name(package, *name).as_str()));
+            write_text_element(xml_writer, item, &self.format_value(package, value))?;
+          }
+          write(xml_writer, Event::End(BytesEnd::new("style")))
+        }
+        "array" => {
+          let is_string =
+            |value: &Value| matches!(value, Value::String(_) | Value::StyledString(_));
+          let is_integer = |value: &Value| {
+            matches!(value, Value::Data { data_type, .. }
+              if *data_type == ResType::INT_DEC || *data_type == ResType::INT_HEX)
+          };
+          let array_type = if items.iter().all(|(_, value)| is_string(value)) {
+            "string-array"
+          } else if items.iter().all(|(_, value)| is_integer(value)) {
+            "integer-array"
+          } else {
+            "array"
+          };
+          let mut elem = BytesStart::new(array_type);
+          elem.push_attribute(("name", entry.key.as_str()));
+          write(xml_writer, Event::Start(elem))?;
+          for (_, value) in items {
+            write_text_element(
+              xml_writer,
+              BytesStart::new("item"),
+              &self.format_value(package, value),
+            )?;
+          }
+          write(xml_writer, Event::End(BytesEnd::new(array_type)))
+        }
+        "plurals" => {
+          let mut elem = BytesStart::new("plurals");
+          elem.push_attribute(("name", entry.key.as_str()));
+          write(xml_writer, Event::Start(elem))?;
+          for (name, value) in items {
+            let Some((_, quantity)) = PLURALS.iter().find(|(id, _)| id == name) else {
+              continue;
+            };
+            let mut item = BytesStart::new("item");
+            item.push_attribute(("quantity", *quantity));
+            write_text_element(xml_writer, item, &self.format_value(package, value))?;
+          }
+          write(xml_writer, Event::End(BytesEnd::new("plurals")))
+        }
+        "attr" => self.write_attr(xml_writer, entry, items),
+        _ => Ok(()),
+      },
+    }
+  }
+
+  fn write_attr(
+    &self,
+    xml_writer: &mut Writer<Cursor<Vec<u8>>>,
+    entry: &TypeEntry,
+    items: &[(u32, Value)],
+  ) -> Result<(), ParseError> {
+    let data_of = |name: u32| {
+      items.iter().find_map(|(item_name, value)| match value {
+        Value::Data { data, .. } if *item_name == name => Some(*data),
+        _ => None,
+      })
+    };
+    let attr_type = data_of(ATTR_TYPE).unwrap_or(ATTR_TYPE_ANY);
+
+    let mut elem = BytesStart::new("attr");
+    elem.push_attribute(("name", entry.key.as_str()));
+    let formats = ATTR_FORMATS
+      .iter()
+      .filter(|(flag, _)| attr_type & flag != 0)
+      .map(|(_, format)| *format)
+      .collect::<Vec<_>>();
+    if attr_type & ATTR_TYPE_ANY != ATTR_TYPE_ANY && !formats.is_empty() {
+      elem.push_attribute(("format", formats.join("|").as_str()));
+    }
+    if let Some(min) = data_of(ATTR_MIN) {
+      elem.push_attribute(("min", (min as i32).to_string().as_str()));
+    }
+    if let Some(max) = data_of(ATTR_MAX) {
+      elem.push_attribute(("max", (max as i32).to_string().as_str()));
+    }
+
+    let symbols = items
+      .iter()
+      .filter(|(name, _)| ![ATTR_TYPE, ATTR_MIN, ATTR_MAX, ATTR_L10N].contains(name))
+      .collect::<Vec<_>>();
+    if symbols.is_empty() {
+      return write(xml_writer, Event::Empty(elem));
+    }
+
+    write(xml_writer, Event::Start(elem))?;
+    let symbol_type = if attr_type & ATTR_TYPE_FLAGS != 0 {
+      "flag"
+    } else {
+      "enum"
+    };
+    for (name, value) in symbols {
+      let symbol_name = self
+        .arsc
+        .resource_name(*name)
+        .map(|res_name| res_name.name)
+        .unwrap_or_else(|| format!("0x{:08x}", name));
+      let data = match value {
+        Value::Data { data, .. } => *data,
+        Value::String(_) | Value::StyledString(_) => continue,
+      };
+      let symbol_value = if symbol_type == "enum" {
+        (data as i32).to_string()
+      } else {
+        format!("0x{:08x}", data)
+      };
+      let mut symbol = BytesStart::new(symbol_type);
+      symbol.push_attribute(("name", symbol_name.as_str()));
+      symbol.push_attribute(("value", symbol_value.as_str()));
+      write(xml_writer, Event::Empty(symbol))?;
+    }
+    write(xml_writer, Event::End(BytesEnd::new("attr")))
+  }
+
+  // Name of the attribute an item of a style refers to, e.g. `android:textColor` or `colorPrimary`.
+  fn attribute_name(
+    &self,
+    package: &Package,
+    attr_id: u32,
+  ) -> String {
+    if attr_id >> 24 == 0x01 {
+      if let Some(name) = attributes::get_attribute_name(attr_id) {
+        return format!("android:{}", name);
+      }
+    }
+    match self.arsc.resource_name(attr_id) {
+      Some(res_name) if res_name.package == package.name => res_name.name,
+      Some(res_name) => format!("{}:{}", res_name.package, res_name.name),
+      None => format!("0x{:08x}", attr_id),
+    }
+  }
+
+  // `@type/name` style reference, qualified with the package when it's not the current one.
+  fn format_reference(
+    &self,
+    package: &Package,
+    prefix: &str,
+    res_id: u32,
+  ) -> String {
+    if res_id >> 24 == 0x01 {
+      if let Some(name) = attributes::get_attribute_name(res_id) {
+        return format!("{}android:attr/{}", prefix, name);
+      }
+    }
+    match self.arsc.resource_name(res_id) {
+      Some(res_name) if res_name.package == package.name => {
+        format!("{}{}/{}", prefix, res_name.type_name, res_name.name)
+      }
+      Some(res_name) => format!("{}{}", prefix, res_name),
+      None if prefix == "?" => format!("@attr/0x{:x}", res_id),
+      None => format!("@res/0x{:x}", res_id),
+    }
+  }
+
+  fn format_value(
+    &self,
+    package: &Package,
+    value: &Value,
+  ) -> String {
+    let (data_type, data) = match value {
+      Value::String(string) => return escape_android_string(string),
+      Value::StyledString(styled_string) => return escape_android_string(&styled_string.text),
+      Value::Data { data_type, data } => (*data_type, *data),
+    };
+    match data_type {
+      ResType::NULL if data == 1 => "@empty".to_string(),
+      ResType::NULL => "@null".to_string(),
+      ResType::REFERENCE | ResType::DYNAMIC_REFERENCE if data == 0 => "@null".to_string(),
+      ResType::REFERENCE | ResType::DYNAMIC_REFERENCE => self.format_reference(package, "@", data),
+      ResType::ATTRIBUTE | ResType::DYNAMIC_ATTRIBUTE => self.format_reference(package, "?", data),
+      ResType::FLOAT => format!("{}", f32::from_bits(data)),
+      ResType::DIMENSION => format_complex(data, false),
+      ResType::FRACTION => format_complex(data, true),
+      ResType::INT_DEC => (data as i32).to_string(),
+      ResType::INT_HEX => format!("0x{:x}", data),
+      ResType::INT_BOOLEAN => (data != 0).to_string(),
+      ResType::INT_COLOR_ARGB8 => format!("#{:08x}", data),
+      ResType::INT_COLOR_RGB8 => format!("#{:06x}", data & 0xFF_FFFF),
+      ResType::INT_COLOR_ARGB4 => format!(
+        "#{:x}{:x}{:x}{:x}",
+        (data >> 28) & 0xF,
+        (data >> 20) & 0xF,
+        (data >> 12) & 0xF,
+        (data >> 4) & 0xF
+      ),
+      ResType::INT_COLOR_RGB4 => format!(
+        "#{:x}{:x}{:x}",
+        (data >> 20) & 0xF,
+        (data >> 12) & 0xF,
+        (data >> 4) & 0xF
+      ),
+      _ => value.as_string().unwrap_or_default(),
+    }
+  }
+}
+
+fn write(
+  xml_writer: &mut Writer<Cursor<Vec<u8>>>,
+  event: Event,
+) -> Result<(), ParseError> {
+  xml_writer
+    .write_event(event)
+    .map_err(|e| ParseError::BuildXml(e.to_string()))
+}
+
+fn write_text_element(
+  xml_writer: &mut Writer<Cursor<Vec<u8>>>,
+  elem: BytesStart,
+  text: &str,
+) -> Result<(), ParseError> {
+  let end = BytesEnd::new(String::from_utf8_lossy(elem.name().as_ref()).to_string());
+  if text.is_empty() {
+    return write(xml_writer, Event::Empty(elem));
+  }
+  write(xml_writer, Event::Start(elem))?;
+  write(
+    xml_writer,
+    Event::Text(BytesText::from_escaped(partial_escape(text))),
+  )?;
+  write(xml_writer, Event::End(end))
+}
+
+// Dimensions and fractions are stored as a 24 bit mantissa, a radix and a unit.
+fn format_complex(
+  data: u32,
+  fraction: bool,
+) -> String {
+  let mantissa = (data & 0xFFFF_FF00) as i32;
+  let radix = ((data >> 4) & 0x3) as usize;
+  let value = mantissa as f32 * RADIX_MULTIPLIERS[radix];
+  let unit = (data & 0xF) as usize;
+  if fraction {
+    format!(
+      "{}{}",
+      value * 100.0,
+      FRACTION_UNITS.get(unit).unwrap_or(&"")
+    )
+  } else {
+    format!("{}{}", value, DIMENSION_UNITS.get(unit).unwrap_or(&""))
+  }
+}
+
+// Escapes a string the way aapt expects it in values XML.
+fn escape_android_string(string: &str) -> String {
+  let mut escaped = String::with_capacity(string.len());
+  if string.starts_with('@') || string.starts_with('?') {
+    escaped.push('\\');
+  }
+  for c in string.chars() {
+    match c {
+      '\\' => escaped.push_str("\\\\"),
+      '\'' => escaped.push_str("\\'"),
+      '"' => escaped.push_str("\\\""),
+      '\n' => escaped.push_str("\\n"),
+      '\t' => escaped.push_str("\\t"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anyhow::{Context, Result};
+
+  #[test]
+  fn test_decode_values() -> Result<()> {
+    let arsc_path = std::path::Path::new(
+      "../data/arsc/ab4cb5175fc0827860b9c3361d8bf2aec99a71f63b5a7666ae2403b0030ac56f.arsc",
+    );
+    let arsc_bytes: Vec<u8> = std::fs::read(arsc_path)?;
+    let mut parser = Arsc::new(arsc_bytes.as_slice());
+    parser
+      .parse()
+      .context(format!("Failed to parse arsc: {}", arsc_path.display()))?;
+
+    let files = ValuesDecoder::new(&parser).decode()?;
+    for file_name in ["strings", "colors", "dimens", "styles", "attrs", "ids"] {
+      assert!(files.contains_key(&format!("res/values/{}.xml", file_name)));
+    }
+    assert!(files.keys().any(|path| path.starts_with("res/values-de")));
+
+    // every file has to be well formed xml
+    for (path, content) in &files {
+      let mut reader = quick_xml::reader::Reader::from_reader(content.as_slice());
+      let mut buf = Vec::new();
+      loop {
+        match reader.read_event_into(&mut buf) {
+          Ok(Event::Eof) => break,
+          Err(e) => panic!(
+            "{}: error at position {}: {:?}",
+            path,
+            reader.buffer_position(),
+            e
+          ),
+          _ => buf.clear(),
+        }
+      }
+    }
+
+    let strings = std::str::from_utf8(&files["res/values/strings.xml"])?;
+    assert!(strings.contains("<string name=\"app_name\">"));
+    Ok(())
+  }
+
+  #[test]
+  fn test_format_complex() {
+    // mantissa 16, radix 23p0, unit dp
+    assert_eq!(format_complex(0x1001, false), "16dp");
+    // mantissa 192, radix 16p7, unit sp
+    assert_eq!(format_complex(0xc012, false), "1.5sp");
+    // mantissa 64, radix 16p7, unit %p
+    assert_eq!(format_complex(0x4011, true), "50%p");
+    assert_eq!(escape_android_string("@don't"), "\\@don\\'t");
+  }
+}
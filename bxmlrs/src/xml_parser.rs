@@ -3,13 +3,14 @@
 use nom::multi::count;
 use nom::number::complete::{le_u16, le_u32};
 use nom::{combinator::map, sequence::tuple, IResult};
-use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, Event};
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Writer;
 use std::io::Cursor;
 
 use crate::arsc_parser::Arsc;
 use crate::attributes;
 use crate::nom_parser::{parser, ChunkHeader, ChunkType, ParseError, ResValue};
+use crate::parser::Diagnostic;
 
 // Struct to represent parsed androidmanifest.xml file
 #[derive(Clone, Debug)]
@@ -18,6 +19,7 @@ pub struct AndroidManifest<'bxml> {
   strings: Vec<String>,
   resource_ids: Vec<u32>,
   xml_namespace: XmlNamespace,
+  diagnostics: Vec<Diagnostic>,
 }
 
 impl<'bxml> AndroidManifest<'bxml> {
@@ -30,9 +32,15 @@ impl<'bxml> AndroidManifest<'bxml> {
         prefix: "android".to_string(),
         uri: "http://schemas.android.com/apk/res/android".to_string(),
       },
+      diagnostics: vec![],
     }
   }
 
+  /// Chunks that were read anyway or skipped while decoding the document.
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    &self.diagnostics
+  }
+
   pub fn parse(
     &mut self,
     arsc: Option<&Arsc>,
@@ -56,10 +64,9 @@ impl<'bxml> AndroidManifest<'bxml> {
 
     if xml_chunk_header.typ != ChunkType::XML {
       // Android doesn't seem to care about the xml type identifier
-      println!(
-        "[warning] Invalid xml chunk type: 0x{:x}",
-        xml_chunk_header.typ
-      );
+      self.diagnostics.push(Diagnostic::XmlChunkType {
+        chunk_type: xml_chunk_header.typ,
+      });
     }
     // println!("xml chunk header: {}", xml_chunk_header);
 
@@ -75,6 +82,13 @@ impl<'bxml> AndroidManifest<'bxml> {
       let (mut input, chunk_header) =
         ChunkHeader::parse(input).map_err(|e| ParseError::ChunkHeader(e.to_string()))?;
       // println!("chunk header: {}", chunk_header);
+      if chunk_header.chunk_size < 8 {
+        // A chunk can't be smaller than its header, nothing after it can be trusted
+        return Err(ParseError::ChunkHeader(format!(
+          "invalid chunk size {} at offset {}",
+          chunk_header.chunk_size, chunk_start_offset
+        )));
+      }
 
       // todo: what if strings_pool is not the first chunk?
       // to others will refer it
@@ -145,28 +159,7 @@ impl<'bxml> AndroidManifest<'bxml> {
               .or_else(|| self.strings.get(attr.name as usize).cloned());
 
             // attribute value
-            let mut attr_value: Option<String> = attr.typed_value.as_string(&self.strings);
-            if let Some(arsc) = arsc {
-              let mut rec_count = 0;
-              while let Some(curr_attr_value) = &attr_value {
-                if curr_attr_value.starts_with("@res/0x") && rec_count < 5 {
-                  let res_id = u32::from_str_radix(&curr_attr_value[7..], 16).ok();
-                  if let Some(res_id) = res_id {
-                    let curr_arsc_value = arsc.get_res_value(res_id);
-                    if let Some(curr_arsc_value) = curr_arsc_value {
-                      // println!(
-                      //     "old: {:?} curr_arsc_value: {:?} counter: {}",
-                      //     attr_value, curr_arsc_value, rec_count
-                      // );
-                      attr_value = Some(curr_arsc_value);
-                      rec_count += 1;
-                      continue;
-                    }
-                  }
-                }
-                break;
-              }
-            }
+            let attr_value = resolve_references(attr.typed_value.as_string(&self.strings), arsc);
 
             // println!("attribute: {:?}", attr);
             // println!("name: {:?}", attr_name);
@@ -207,15 +200,29 @@ impl<'bxml> AndroidManifest<'bxml> {
         }
         // CDATA chunk
         // https://justanapplication.wordpress.com/2011/09/27/android-internals-binary-xml-part-eight-the-cdata-chunk
-        ChunkType::XML_CDATA => { /* skip it */ }
+        ChunkType::XML_CDATA => {
+          // skip lineNumber and comment fields
+          input = input
+            .get(8..)
+            .ok_or_else(|| ParseError::BufferNotEnough("CDATA chunk".to_string()))?;
+
+          let (_, data) = le_u32::<&[u8], nom::error::Error<&[u8]>>(input)
+            .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+          // aapt2 drops whitespace only text, what is left is content such as pin digests
+          if let Some(text) = self.strings.get(data as usize) {
+            xml_writer
+              .write_event(Event::Text(BytesText::new(text)))
+              .map_err(|e| ParseError::BuildXml(e.to_string()))?;
+          }
+        }
 
         ChunkType::XML_END_NAMESPACE => {
           break;
         }
-        _ => {
-          println!("unknown chunk type: 0x{:x}", chunk_header.typ);
-          break;
-        }
+        // Like ResXMLParser, unknown chunks are skipped
+        chunk_type => self
+          .diagnostics
+          .push(Diagnostic::UnknownXmlChunk { chunk_type }),
       }
       chunk_start_offset += chunk_header.chunk_size as usize;
     }
@@ -286,6 +293,39 @@ impl<'bxml> AndroidManifest<'bxml> {
   }
 }
 
+// Follows `@res/0x...` and `@dyn/0x...` references through the resource table, up to 5 levels
+// deep.
+pub(crate) fn resolve_references(
+  attr_value: Option<String>,
+  arsc: Option<&Arsc>,
+) -> Option<String> {
+  let Some(arsc) = arsc else {
+    return attr_value;
+  };
+  // References to shared libraries are made from the package of the document
+  let mut attr_value = attr_value.map(|value| arsc.resolve_dynamic_value(None, value));
+  let mut rec_count = 0;
+  while let Some(curr_attr_value) = &attr_value {
+    if curr_attr_value.starts_with("@res/0x") && rec_count < 5 {
+      let res_id = u32::from_str_radix(&curr_attr_value[7..], 16).ok();
+      if let Some(res_id) = res_id {
+        let curr_arsc_value = arsc.get_res_value(res_id);
+        if let Some(curr_arsc_value) = curr_arsc_value {
+          // println!(
+          //     "old: {:?} curr_arsc_value: {:?} counter: {}",
+          //     attr_value, curr_arsc_value, rec_count
+          // );
+          attr_value = Some(curr_arsc_value);
+          rec_count += 1;
+          continue;
+        }
+      }
+    }
+    break;
+  }
+  attr_value
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct XmlNamespace {
   pub prefix: String,
@@ -343,6 +383,7 @@ pub struct ResourceMapChunk {}
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::test_util::{binary_xml, AttributeValue, XmlElement};
   use anyhow::{Context, Result};
 
   #[test]
@@ -371,7 +412,7 @@ mod tests {
     let files = dir_path.read_dir()?;
     for file in files {
       let file = file?.path().canonicalize()?;
-      if file.extension().map_or(false, |ext| ext != "xml") {
+      if file.extension().is_some_and(|ext| ext != "xml") {
         continue;
       }
       println!("\n---------- TESTING {:?} ----------", file);
@@ -385,4 +426,64 @@ mod tests {
 
     Ok(())
   }
+
+  #[test]
+  fn test_cdata() -> Result<()> {
+    let digest = "7HIpactkIAq2Y49orFOOQKurWxmmSFZhBCoQYcRhJ3Y=";
+    let xml = binary_xml(
+      &XmlElement::new("pin")
+        .attribute("digest", None, AttributeValue::String("SHA-256"))
+        .text(digest),
+    )?;
+    let decoded = String::from_utf8(AndroidManifest::new(&xml).parse(None)?)?;
+    assert!(decoded.ends_with(&format!("<pin digest=\"SHA-256\">{}</pin>", digest)));
+
+    // A CDATA chunk cut off after its header
+    let cdata = xml
+      .windows(4)
+      .position(|bytes| bytes == [0x04, 0x01, 0x10, 0x00])
+      .context("no CDATA chunk")?;
+    assert!(matches!(
+      AndroidManifest::new(&xml[..cdata + 12]).parse(None),
+      Err(ParseError::BufferNotEnough(_))
+    ));
+    Ok(())
+  }
+
+  #[test]
+  fn test_unexpected_chunks() -> Result<()> {
+    let mut xml = binary_xml(&XmlElement::new("manifest").child(XmlElement::new("application")))?;
+    // An unknown chunk ahead of the first element, and a root chunk that isn't RES_XML_TYPE
+    let start_element = xml
+      .windows(4)
+      .position(|bytes| bytes == [0x02, 0x01, 0x10, 0x00])
+      .context("no start element chunk")?;
+    let unknown = [0x99, 0x01, 0x08, 0x00, 0x10, 0x00, 0x00, 0x00];
+    xml.splice(
+      start_element..start_element,
+      unknown.into_iter().chain([0; 8]),
+    );
+    xml[0] = 0x00;
+    let size = xml.len() as u32;
+    xml[4..8].copy_from_slice(&size.to_le_bytes());
+
+    let mut parser = AndroidManifest::new(&xml);
+    let decoded = String::from_utf8(parser.parse(None)?)?;
+    assert!(decoded.ends_with("<manifest><application></application></manifest>"));
+    assert_eq!(
+      parser.diagnostics(),
+      [
+        Diagnostic::XmlChunkType { chunk_type: 0x0000 },
+        Diagnostic::UnknownXmlChunk { chunk_type: 0x0199 }
+      ]
+    );
+
+    // A chunk smaller than its header would never move on to the next one
+    xml[start_element + 4..start_element + 8].copy_from_slice(&0u32.to_le_bytes());
+    assert!(matches!(
+      AndroidManifest::new(&xml).parse(None),
+      Err(ParseError::ChunkHeader(_))
+    ));
+    Ok(())
+  }
 }
@@ -0,0 +1,234 @@
+use crate::arsc_parser::{Arsc, EntryValue};
+use crate::nom_parser::{ChunkType, ParseError};
+use crate::parser::{ApkArchive, ApkEntry};
+use crate::res_config::ResConfig;
+use crate::xml_parser::AndroidManifest;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+// Binary XML files are small, larger entries aren't decoded rather than inflated into memory
+const MAX_XML_SIZE: u64 = 16 << 20;
+// The chunk type and header size `is_binary_xml` checks
+const MAGIC_SIZE: usize = 4;
+
+/// Decodes the binary XML files of an APK, layouts, menus, drawables, `res/xml` configs and
+/// the manifest alike, resolving references through the same table.
+pub struct XmlResources<'a> {
+  archive: &'a ApkArchive,
+  arsc: Option<&'a Arsc<'a>>,
+}
+
+impl<'a> XmlResources<'a> {
+  pub fn new(
+    archive: &'a ApkArchive,
+    arsc: Option<&'a Arsc<'a>>,
+  ) -> Self {
+    Self { archive, arsc }
+  }
+
+  /// Whether `data` starts like a binary XML document as aapt and aapt2 write it.
+  pub fn is_binary_xml(data: &[u8]) -> bool {
+    let u16_at = |offset: usize| {
+      data
+        .get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    u16_at(0) == Some(ChunkType::XML) && u16_at(2) == Some(8)
+  }
+
+  /// Paths of the entries holding binary XML, in central directory order. Entries are
+  /// recognized by their content, obfuscators rename `res/` and drop extensions. Only the
+  /// start of each entry is inflated, entries above 16 MiB are skipped.
+  pub fn paths(&self) -> Vec<String> {
+    self
+      .binary_xml_entries()
+      .map(|entry| entry.name.clone())
+      .collect()
+  }
+
+  fn binary_xml_entries(&self) -> impl Iterator<Item = &ApkEntry> {
+    self
+      .archive
+      .entries()
+      .iter()
+      .filter(|entry| !entry.name.ends_with('/') && entry.uncompressed_size <= MAX_XML_SIZE)
+      .filter(|entry| {
+        self
+          .archive
+          .extract_prefix(entry, MAGIC_SIZE)
+          .is_ok_and(|magic| Self::is_binary_xml(&magic))
+      })
+  }
+
+  /// The files of a resource in every configuration, e.g. `res/layout/main.xml` and
+  /// `res/layout-land/main.xml` for a layout id.
+  pub fn resource_paths(
+    &self,
+    res_id: u32,
+  ) -> Vec<(ResConfig, String)> {
+    let Some(arsc) = self.arsc else {
+      return vec![];
+    };
+    arsc
+      .entry_values(res_id)
+      .into_iter()
+      .filter_map(|(config, entry)| match &entry.value {
+        EntryValue::Simple(value) => Some((config, value.as_string()?)),
+        EntryValue::Complex { .. } => None,
+      })
+      .filter(|(_, path)| self.archive.by_name(path).is_some())
+      .collect()
+  }
+
+  /// Decodes the entry at `path`.
+  pub fn decode(
+    &self,
+    path: &str,
+  ) -> Result<Vec<u8>, ParseError> {
+    let entry = self
+      .archive
+      .by_name(path)
+      .ok_or_else(|| ParseError::MissingEntry(path.to_string()))?;
+    let binary_xml = self.archive.extract(entry)?;
+    AndroidManifest::new(&binary_xml).parse(self.arsc)
+  }
+
+  /// Decodes every binary XML entry, with the error of those that fail.
+  pub fn decode_all(&self) -> Vec<(String, Result<Vec<u8>, ParseError>)> {
+    self
+      .binary_xml_entries()
+      .map(|entry| {
+        let xml = self
+          .archive
+          .extract(entry)
+          .and_then(|binary_xml| AndroidManifest::new(&binary_xml).parse(self.arsc));
+        (entry.name.clone(), xml)
+      })
+      .collect()
+  }
+
+  /// Writes every binary XML entry decoded into `out_dir`, keeping the directory tree of the
+  /// APK. Entries that fail to decode, or whose sanitized path is taken by an earlier entry,
+  /// are skipped and returned with their error.
+  pub fn write_to_dir(
+    &self,
+    out_dir: &Path,
+  ) -> Result<Vec<(String, ParseError)>, ParseError> {
+    let mut failures = Vec::new();
+    let mut written = HashMap::new();
+    for (path, xml) in self.decode_all() {
+      let xml = match xml {
+        Ok(xml) => xml,
+        Err(e) => {
+          failures.push((path, e));
+          continue;
+        }
+      };
+      let sanitized = sanitized_path(&path);
+      if let Some(first) = written.get(&sanitized) {
+        let e = ParseError::File(format!(
+          "{} is already written from {}",
+          sanitized.display(),
+          first
+        ));
+        failures.push((path, e));
+        continue;
+      }
+      let file_path = out_dir.join(&sanitized);
+      written.insert(sanitized, path);
+      if let Some(parent) = file_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| ParseError::File(e.to_string()))?;
+      }
+      std::fs::write(&file_path, xml).map_err(|e| ParseError::File(e.to_string()))?;
+    }
+    Ok(failures)
+  }
+}
+
+// Entry names are attacker controlled, only their normal components are kept so nothing is
+// written outside of the output directory.
+fn sanitized_path(name: &str) -> PathBuf {
+  Path::new(name)
+    .components()
+    .filter_map(|component| match component {
+      Component::Normal(part) => Some(part),
+      _ => None,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::zip;
+  use anyhow::Result;
+
+  #[test]
+  fn test_xml_resources() -> Result<()> {
+    let binary_xml = std::fs::read("../data/xml/AndroidManifest.xml")?;
+    let mut oversized = binary_xml[..8].to_vec();
+    oversized.resize(MAX_XML_SIZE as usize + 1, 0);
+    let archive = ApkArchive::new(zip(&[
+      ("AndroidManifest.xml", &binary_xml[..]),
+      ("res/layout/main.xml", &binary_xml[..]),
+      ("res/mipmap-anydpi-v26/ic_launcher.xml", &binary_xml[..]),
+      ("r/a.bin", &binary_xml[..]),
+      ("../../escape.xml", &binary_xml[..]),
+      ("escape.xml", &binary_xml[..]),
+      ("res/raw/huge.xml", &oversized[..]),
+      ("res/raw/data.xml", b"<?xml version=\"1.0\"?><data/>"),
+      ("res/xml/broken.xml", &binary_xml[..64]),
+      ("classes.dex", b"dex\n035\0"),
+    ])?)?;
+    let xml_resources = XmlResources::new(&archive, None);
+
+    assert_eq!(
+      xml_resources.paths(),
+      [
+        "AndroidManifest.xml",
+        "res/layout/main.xml",
+        "res/mipmap-anydpi-v26/ic_launcher.xml",
+        "r/a.bin",
+        "../../escape.xml",
+        "escape.xml",
+        "res/xml/broken.xml",
+      ]
+    );
+    let decoded = xml_resources.decode_all();
+    assert_eq!(decoded.len(), 7);
+    assert!(decoded[6].1.is_err());
+    let manifest = xml_resources.decode("AndroidManifest.xml")?;
+    assert!(manifest.starts_with(b"<?xml"));
+    assert_eq!(xml_resources.decode("r/a.bin")?, manifest);
+    assert!(matches!(
+      xml_resources.decode("res/layout/missing.xml"),
+      Err(ParseError::MissingEntry(_))
+    ));
+
+    // mipmap/ic_launcher, the PNGs of the other densities aren't in the archive
+    let arsc_bytes = std::fs::read(
+      "../data/arsc/547638973a12aeae1029b1d6b2411faef06c4f403d9d42d4bb4ddbb5c500c37c.arsc",
+    )?;
+    let mut arsc = Arsc::new(&arsc_bytes);
+    arsc.parse()?;
+    let paths = XmlResources::new(&archive, Some(&arsc)).resource_paths(0x7f0d0000);
+    assert_eq!(paths.len(), 1);
+    assert_eq!(paths[0].0.qualifier(), "anydpi-v26");
+    assert_eq!(paths[0].1, "res/mipmap-anydpi-v26/ic_launcher.xml");
+
+    let out_dir = std::env::temp_dir().join(format!("bxmlrs-xml-{}", std::process::id()));
+    let failures = xml_resources.write_to_dir(&out_dir)?;
+    let written = std::fs::read(out_dir.join("res/layout/main.xml"));
+    let escaped = out_dir.join("escape.xml").exists();
+    std::fs::remove_dir_all(&out_dir)?;
+    assert_eq!(written?, manifest);
+    assert!(escaped);
+    // The second escape.xml would overwrite the sanitized ../../escape.xml
+    let failures = failures
+      .iter()
+      .map(|(path, _)| path.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(failures, ["escape.xml", "res/xml/broken.xml"]);
+    Ok(())
+  }
+}
@@ -0,0 +1,52 @@
+# Adds an APK Signature Scheme v2 block, following
+# https://source.android.com/docs/security/features/apksigning/v2
+#
+# known-answer.apk is made from the files next to this script:
+#   keytool -genkeypair -keystore ks.p12 -storetype PKCS12 -storepass password \
+#     -keypass password -alias kat -keyalg RSA -keysize 2048 -sigalg SHA256withRSA \
+#     -dname "CN=Known Answer,O=bxmlrs,C=US"
+#   jarsigner -keystore ks.p12 -storepass password -digestalg SHA-256 -sigalg SHA256withRSA \
+#     -sigfile CERT -signedjar v1.apk unsigned.apk kat
+#   python3 sign_v2.py && mv v1-v2.apk known-answer.apk
+import hashlib, struct
+from cryptography.hazmat.primitives.serialization import pkcs12, Encoding, PublicFormat
+from cryptography.hazmat.primitives import hashes
+from cryptography.hazmat.primitives.asymmetric import padding
+
+key, cert, _ = pkcs12.load_key_and_certificates(open('ks.p12','rb').read(), b'password')
+apk = open('v1.apk','rb').read()
+eocd = apk.rfind(b'PK\x05\x06')
+cd_size, cd_offset = struct.unpack('<II', apk[eocd+12:eocd+20])
+sections = [apk[:cd_offset], apk[cd_offset:cd_offset+cd_size], apk[eocd:]]
+
+def chunked_sha256(sections):
+  digests = []
+  for section in sections:
+    for i in range(0, len(section), 1 << 20):
+      chunk = section[i:i + (1 << 20)]
+      digests.append(hashlib.sha256(b'\xa5' + struct.pack('<I', len(chunk)) + chunk).digest())
+  return hashlib.sha256(b'\x5a' + struct.pack('<I', len(digests)) + b''.join(digests)).digest()
+
+# The EOCD is digested with the central directory offset of the unsigned APK, which is where the
+# signing block starts
+digest = chunked_sha256(sections)
+lp = lambda b: struct.pack('<I', len(b)) + b
+seq = lambda items: lp(b''.join(lp(i) for i in items))
+RSA_PKCS1_SHA256 = 0x0103
+signed_data = seq([struct.pack('<I', RSA_PKCS1_SHA256) + lp(digest)]) \
+  + seq([cert.public_bytes(Encoding.DER)]) + seq([])
+signature = key.sign(signed_data, padding.PKCS1v15(), hashes.SHA256())
+signer = lp(signed_data) + seq([struct.pack('<I', RSA_PKCS1_SHA256) + lp(signature)]) \
+  + lp(key.public_key().public_bytes(Encoding.DER, PublicFormat.SubjectPublicKeyInfo))
+value = seq([signer])
+pair = struct.pack('<Q', len(value) + 4) + struct.pack('<I', 0x7109871a) + value
+size = len(pair) + 8 + 16
+block = struct.pack('<Q', size) + pair + struct.pack('<Q', size) + b'APK Sig Block 42'
+eocd_record = bytearray(apk[eocd:])
+eocd_record[16:20] = struct.pack('<I', cd_offset + len(block))
+signed = apk[:cd_offset] + block + apk[cd_offset:eocd] + bytes(eocd_record)
+open('v1-v2.apk','wb').write(signed)
+key.public_key().verify(signature, signed_data, padding.PKCS1v15(), hashes.SHA256())
+print('v2 digest', digest.hex())
+print('cert sha256', hashlib.sha256(cert.public_bytes(Encoding.DER)).hexdigest())
+print('size', len(signed))